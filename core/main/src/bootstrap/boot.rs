@@ -32,7 +32,9 @@ use super::{
     setup_extn_client_step::SetupExtnClientStep,
     start_app_manager_step::StartAppManagerStep,
     start_communication_broker::{StartCommunicationBroker, StartOtherBrokers},
+    start_dial_step::StartDialStep,
     start_fbgateway_step::FireboltGatewayStep,
+    start_metrics_exporter_step::StartMetricsExporterStep,
     start_ws_step::StartWsStep,
 };
 /// Starts up Ripple uses `PlatformState` to manage State
@@ -49,6 +51,8 @@ use super::{
 /// 2. [SetupExtnClientStep] - Initializes the extn client to start the Inter process communication backbone
 /// 4. [LoadExtensionsStep] - Loads the Extensions in to [crate::state::extn_state::ExtnState]
 /// 6. [StartAppManagerStep] - Starts the App Manager and other supporting services
+/// 7. [StartDialStep] - Starts the DIAL server for second screen launches if it is enabled
+/// 7. [StartMetricsExporterStep] - Starts the `/metrics` endpoint if it is enabled
 /// 7. [StartOtherBrokers] - Start Other brokers if they are setup in endpoints for rules
 /// 8. [LoadDistributorValuesStep] - Loads the values from distributor like Session
 /// 10. [StartWsStep] - Starts the Websocket to accept external and internal connections
@@ -79,6 +83,10 @@ pub async fn boot(state: BootstrapState) -> RippleResponse {
     log_memory_usage("After-LoadExtensionsStep");
     execute_step(StartAppManagerStep, &bootstrap).await?;
     log_memory_usage("After-StartAppManagerStep");
+    execute_step(StartDialStep, &bootstrap).await?;
+    log_memory_usage("After-StartDialStep");
+    execute_step(StartMetricsExporterStep, &bootstrap).await?;
+    log_memory_usage("After-StartMetricsExporterStep");
     execute_step(StartOtherBrokers, &bootstrap).await?;
     log_memory_usage("After-StartOtherBrokers");
     execute_step(LoadDistributorValuesStep, &bootstrap).await?;
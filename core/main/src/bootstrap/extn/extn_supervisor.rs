@@ -0,0 +1,518 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ripple_sdk::{
+    api::{manifest::extn_manifest::ExtnManifestEntry, status_update::ExtnStatus},
+    extn::{
+        extn_client_message::{ExtnMessage, ExtnPayloadProvider},
+        extn_id::ExtnId,
+        ffi::ffi_channel::load_channel_builder,
+    },
+    framework::ripple_contract::RippleContract,
+    log::{error, info, warn},
+    tokio::{
+        self,
+        sync::oneshot::{self, error::TryRecvError},
+    },
+    uuid::Uuid,
+};
+
+use crate::state::platform_state::PlatformState;
+
+use super::load_extn_step::LoadedLibrary;
+
+// how often the extension is probed when there is no liveness timeout, also the time a probe
+// waits for its answer
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+// entry point of a run of the extension, called on its own thread
+type ExtnChannelStart = Box<dyn FnOnce() + Send>;
+
+/// Why a run of an extension ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtnExit {
+    /// the channel could not be created from the library, it is not restarted
+    NotStarted,
+    Exited,
+    Panicked(String),
+    /// the extension left the liveness probes unanswered for longer than the liveness timeout
+    Stalled,
+}
+
+/// Runs the channel of an extension library on its own thread and restarts it according to the
+/// restart policy of its manifest entry when it exits, panics or stalls.
+///
+/// A stalled thread can not be stopped, it is left behind when the extension is restarted. The
+/// connection of the restarted extension replaces the one of the stalled thread.
+pub struct ExtnSupervisor {
+    state: PlatformState,
+    entry: ExtnManifestEntry,
+    // creates the channel for every run, None when the library does not provide one
+    channel: Box<dyn Fn() -> Option<ExtnChannelStart> + Send + Sync>,
+}
+
+impl ExtnSupervisor {
+    pub fn start(state: PlatformState, library: LoadedLibrary) {
+        let entry = library.entry.clone();
+        let library = Arc::new(library);
+        let channel = move || {
+            let builder = unsafe { load_channel_builder(&library.library) }.ok()?;
+            // the library stays loaded while a thread runs its code, stalled ones included
+            let library = library.clone();
+            let start: ExtnChannelStart = Box::new(move || {
+                let _library = library;
+                (builder.start)()
+            });
+            Some(start)
+        };
+        let supervisor = ExtnSupervisor {
+            state,
+            entry,
+            channel: Box::new(channel),
+        };
+        tokio::spawn(async move { supervisor.supervise().await });
+    }
+
+    async fn supervise(self) {
+        let path = self.entry.path.clone();
+        let policy = self.entry.restart.clone();
+        let stable_uptime = Duration::from_millis(policy.stable_uptime);
+        let mut attempt = 0;
+        loop {
+            let (exit, connected_at) = self.run(attempt > 0).await;
+            match &exit {
+                ExtnExit::Panicked(message) => {
+                    error!("extension {} panicked: {}", path, message)
+                }
+                exit => warn!("extension {} ended: {:?}", path, exit),
+            }
+            self.remove_contracts();
+            if exit == ExtnExit::NotStarted {
+                self.emit_status(ExtnStatus::Error);
+                return;
+            }
+            self.emit_status(ExtnStatus::Interrupted);
+
+            // a run which stayed connected for the stable uptime starts the backoff over
+            attempt = match connected_at {
+                Some(at) if at.elapsed() >= stable_uptime => 1,
+                _ => attempt + 1,
+            };
+            match policy.get_restart_delay(attempt) {
+                Some(delay) => {
+                    info!(
+                        "restarting extension {} in {:?}, attempt {}",
+                        path, delay, attempt
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    error!("extension {} is not restarted", path);
+                    self.emit_status(ExtnStatus::Error);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Runs the channel until it ends, returns when it first answered a liveness probe.
+    async fn run(&self, restarted: bool) -> (ExtnExit, Option<Instant>) {
+        let path = self.entry.path.clone();
+        let Some(start) = (self.channel)() else {
+            return (ExtnExit::NotStarted, None);
+        };
+        info!("Starting library at  path {}", path);
+        let (exit_tx, mut exit_rx) = oneshot::channel();
+        let spawned = thread::Builder::new().name(path).spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(start));
+            let _ = exit_tx.send(result.err().map(panic_message));
+        });
+        if let Err(e) = spawned {
+            error!("could not spawn extension thread {:?}", e);
+            return (ExtnExit::NotStarted, None);
+        }
+
+        let liveness_timeout = self
+            .entry
+            .restart
+            .liveness_timeout
+            .map(Duration::from_millis);
+        let probe_interval = liveness_timeout
+            .map(|t| (t / 2).min(PROBE_INTERVAL))
+            .unwrap_or(PROBE_INTERVAL);
+        let mut probe = tokio::time::interval(probe_interval);
+        let mut connected_at = None;
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                result = &mut exit_rx => return (thread_exit(result), connected_at),
+                _ = probe.tick() => {
+                    if self.is_alive(probe_interval).await {
+                        if connected_at.is_none() {
+                            if restarted {
+                                info!("extension {} restarted", self.entry.path);
+                                self.emit_status(ExtnStatus::Ready);
+                            }
+                            connected_at = Some(Instant::now());
+                        }
+                        last_seen = Instant::now();
+                    } else if liveness_timeout.is_some_and(|t| last_seen.elapsed() > t) {
+                        // the thread may have ended while the probe was pending
+                        return match exit_rx.try_recv() {
+                            Err(TryRecvError::Empty) => (ExtnExit::Stalled, connected_at),
+                            result => (thread_exit(result), connected_at),
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    // liveness probe, any symbol of the library answering a request within the timeout
+    async fn is_alive(&self, timeout: Duration) -> bool {
+        let extn_client = self.state.get_client().get_extn_client();
+        for symbol in &self.entry.symbols {
+            if extn_client.probe(&symbol.id, timeout).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    // requests for the contracts fail right away instead of going to the dead channel, the
+    // restarted extension registers them again when it connects
+    fn remove_contracts(&self) {
+        let mut extn_client = self.state.get_client().get_extn_client();
+        for symbol in &self.entry.symbols {
+            extn_client.remove_sender(symbol.id.clone(), symbol.clone());
+        }
+    }
+
+    fn emit_status(&self, status: ExtnStatus) {
+        Self::emit_entry_status(&self.state, &self.entry, status)
+    }
+
+    /// Emits the status event for every symbol of the extension entry.
+    pub fn emit_entry_status(state: &PlatformState, entry: &ExtnManifestEntry, status: ExtnStatus) {
+        let extn_client = state.get_client().get_extn_client();
+        for symbol in &entry.symbols {
+            if let Ok(requestor) = ExtnId::try_from(symbol.id.clone()) {
+                let message = ExtnMessage {
+                    id: Uuid::new_v4().to_string(),
+                    requestor,
+                    target: RippleContract::ExtnStatus,
+                    target_id: None,
+                    payload: status.get_extn_payload(),
+                    ts: None,
+                };
+                let _ = extn_client.handle_message(message);
+            }
+        }
+    }
+}
+
+// exit of the extension thread from the result it sent, a thread ending without one panicked
+fn thread_exit<E>(result: Result<Option<String>, E>) -> ExtnExit {
+    match result {
+        Ok(None) => ExtnExit::Exited,
+        Ok(Some(message)) => ExtnExit::Panicked(message),
+        Err(_) => ExtnExit::Panicked("thread ended without a result".into()),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ripple_sdk::{
+        api::manifest::extn_manifest::{ExtnRestartMode, ExtnRestartPolicy, ExtnSymbol},
+        async_trait::async_trait,
+        extn::client::{
+            extn_client::ExtnClient,
+            extn_processor::{
+                DefaultExtnStreamer, ExtnEventProcessor, ExtnStreamProcessor, ExtnStreamer,
+            },
+        },
+        tokio::sync::mpsc::{self, Receiver, Sender},
+    };
+    use ripple_tdk::utils::test_utils::Mockable;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const EXTN_ID: &str = "ripple:channel:device:supervised";
+
+    #[derive(Debug)]
+    pub(crate) struct StatusRecorder {
+        sender: Sender<ExtnStatus>,
+        streamer: DefaultExtnStreamer,
+    }
+
+    impl ExtnStreamProcessor for StatusRecorder {
+        type STATE = Sender<ExtnStatus>;
+        type VALUE = ExtnStatus;
+        fn get_state(&self) -> Self::STATE {
+            self.sender.clone()
+        }
+
+        fn sender(&self) -> Sender<ExtnMessage> {
+            self.streamer.sender()
+        }
+
+        fn receiver(&mut self) -> Receiver<ExtnMessage> {
+            self.streamer.receiver()
+        }
+    }
+
+    #[async_trait]
+    impl ExtnEventProcessor for StatusRecorder {
+        async fn process_event(
+            state: Self::STATE,
+            _msg: ExtnMessage,
+            extracted_message: Self::VALUE,
+        ) -> Option<bool> {
+            let _ = state.send(extracted_message).await;
+            None
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum Run {
+        Exit,
+        Panic,
+        // connects and answers the probes for the given time before it exits
+        Serve(Duration),
+        // connects without ever answering a probe
+        Stall,
+    }
+
+    fn symbol() -> ExtnSymbol {
+        ExtnSymbol {
+            id: EXTN_ID.to_owned(),
+            uses: Vec::new(),
+            fulfills: Vec::new(),
+            config: None,
+            secret: None,
+        }
+    }
+
+    // the service connection of an extension, relays its messages to and from main
+    fn connect(main: ExtnClient, answer: bool, duration: Duration) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let (extn, mut extn_rx) = ExtnClient::new_extn(symbol());
+            let (tx, mut rx) = mpsc::channel(32);
+            main.clone().add_sender(EXTN_ID.to_owned(), symbol(), tx);
+            let deadline = tokio::time::sleep(duration);
+            tokio::pin!(deadline);
+            loop {
+                tokio::select! {
+                    Some(message) = rx.recv(), if answer => {
+                        extn.handle_message(ExtnMessage::try_from(message.jsonrpc_msg).unwrap());
+                    }
+                    Some(message) = extn_rx.recv() => {
+                        main.handle_message(ExtnMessage::try_from(message.jsonrpc_msg).unwrap());
+                    }
+                    _ = &mut deadline => break,
+                }
+            }
+        });
+    }
+
+    /// Records the extension statuses emitted on the state.
+    pub(crate) fn record_statuses(state: &PlatformState) -> Receiver<ExtnStatus> {
+        let (sender, statuses) = mpsc::channel(16);
+        state
+            .get_client()
+            .get_extn_client()
+            .add_event_processor(StatusRecorder {
+                sender,
+                streamer: DefaultExtnStreamer::new(),
+            });
+        statuses
+    }
+
+    /// Supervisor whose runs follow the given list, the last one repeats. Returns the number of
+    /// started runs and the emitted statuses.
+    fn test_supervisor(
+        policy: ExtnRestartPolicy,
+        runs: Vec<Run>,
+    ) -> (ExtnSupervisor, Arc<AtomicU32>, Receiver<ExtnStatus>) {
+        let state = PlatformState::mock();
+        let statuses = record_statuses(&state);
+        let started = Arc::new(AtomicU32::new(0));
+        let main = state.get_client().get_extn_client();
+        let count = started.clone();
+        let channel = move || {
+            let run = count.fetch_add(1, Ordering::SeqCst) as usize;
+            let run = runs[run.min(runs.len() - 1)];
+            let main = main.clone();
+            let start: ExtnChannelStart = Box::new(move || match run {
+                Run::Exit => {}
+                Run::Panic => panic!("extension failed"),
+                Run::Serve(duration) => connect(main, true, duration),
+                Run::Stall => connect(main, false, Duration::from_secs(5)),
+            });
+            Some(start)
+        };
+        let entry = ExtnManifestEntry {
+            path: "libsupervised".to_owned(),
+            symbols: vec![symbol()],
+            resolution: None,
+            sha256: None,
+            restart: policy,
+        };
+        let supervisor = ExtnSupervisor {
+            state,
+            entry,
+            channel: Box::new(channel),
+        };
+        (supervisor, started, statuses)
+    }
+
+    fn on_failure(max_attempts: u32) -> ExtnRestartPolicy {
+        ExtnRestartPolicy {
+            mode: ExtnRestartMode::OnFailure,
+            backoff: 10,
+            max_attempts: Some(max_attempts),
+            // probes often enough for the short runs of the tests
+            liveness_timeout: Some(200),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) async fn next_status(statuses: &mut Receiver<ExtnStatus>) -> ExtnStatus {
+        tokio::time::timeout(Duration::from_secs(5), statuses.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_exits() {
+        let (supervisor, _, _) = test_supervisor(Default::default(), vec![Run::Exit]);
+        assert_eq!(supervisor.run(false).await, (ExtnExit::Exited, None));
+
+        let (supervisor, _, _) = test_supervisor(Default::default(), vec![Run::Panic]);
+        let (exit, _) = supervisor.run(false).await;
+        assert_eq!(exit, ExtnExit::Panicked("extension failed".to_owned()));
+
+        let (mut supervisor, _, _) = test_supervisor(Default::default(), vec![Run::Exit]);
+        supervisor.channel = Box::new(|| None);
+        assert_eq!(supervisor.run(false).await, (ExtnExit::NotStarted, None));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_stalls() {
+        let policy = ExtnRestartPolicy {
+            liveness_timeout: Some(200),
+            ..Default::default()
+        };
+        let (supervisor, _, _) = test_supervisor(policy.clone(), vec![Run::Stall]);
+        assert_eq!(supervisor.run(false).await, (ExtnExit::Stalled, None));
+
+        // an extension answering the probes is not stalled
+        let (supervisor, _, _) =
+            test_supervisor(policy, vec![Run::Serve(Duration::from_millis(600))]);
+        let (exit, connected_at) = supervisor.run(false).await;
+        assert_eq!(exit, ExtnExit::Exited);
+        assert!(connected_at.is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_supervise_gives_up() {
+        let (supervisor, started, mut statuses) = test_supervisor(on_failure(2), vec![Run::Exit]);
+        supervisor.supervise().await;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        for _ in 0..3 {
+            assert_eq!(next_status(&mut statuses).await, ExtnStatus::Interrupted);
+        }
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Error);
+
+        // the default policy never restarts
+        let (supervisor, started, mut statuses) =
+            test_supervisor(Default::default(), vec![Run::Panic]);
+        supervisor.supervise().await;
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Interrupted);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_supervise_restarts() {
+        let runs = vec![
+            Run::Panic,
+            Run::Serve(Duration::from_millis(400)),
+            Run::Exit,
+        ];
+        let (supervisor, started, mut statuses) = test_supervisor(on_failure(2), runs);
+        supervisor.supervise().await;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Interrupted);
+        // the restarted extension reports ready once it answers a probe
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Ready);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Interrupted);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Interrupted);
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Error);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_supervise_resets_backoff_after_stable_uptime() {
+        let runs = vec![
+            Run::Serve(Duration::from_millis(400)),
+            Run::Serve(Duration::from_millis(400)),
+            Run::Exit,
+        ];
+        let (supervisor, started, _) = test_supervisor(on_failure(1), runs.clone());
+        supervisor.supervise().await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+
+        // the connected runs start the attempts over, the exit without a connection gives up
+        let policy = ExtnRestartPolicy {
+            stable_uptime: 0,
+            ..on_failure(1)
+        };
+        let (supervisor, started, _) = test_supervisor(policy, runs);
+        supervisor.supervise().await;
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = panic::catch_unwind(|| panic!("extension failed")).unwrap_err();
+        assert_eq!(panic_message(payload), "extension failed");
+        let payload = panic::catch_unwind(|| panic!("{} failed", "extension")).unwrap_err();
+        assert_eq!(panic_message(payload), "extension failed");
+    }
+}
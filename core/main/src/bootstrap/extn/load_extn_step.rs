@@ -15,20 +15,22 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::thread;
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::os::fd::AsRawFd;
 
 use ripple_sdk::{
-    api::manifest::extn_manifest::ExtnManifestEntry,
+    api::{manifest::extn_manifest::ExtnManifestEntry, status_update::ExtnStatus},
     async_trait::async_trait,
-    extn::ffi::ffi_channel::load_channel_builder,
+    extn::ffi::ffi_channel::{get_abi_version, ExtnAbiVersion},
     framework::bootstrap::Bootstep,
-    log::{debug, info, warn},
-    utils::error::RippleError,
+    log::{debug, error, info, warn},
+    utils::{digest_utils::sha256_reader, error::RippleError},
 };
 
+use super::extn_supervisor::ExtnSupervisor;
 use crate::state::bootstrap_state::BootstrapState;
 use ripple_sdk::libloading::Library;
-use std::ffi::OsStr;
 
 #[derive(Debug)]
 pub struct LoadedLibrary {
@@ -42,6 +44,24 @@ impl LoadedLibrary {
     }
 }
 
+/// Result of loading an extension library.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtnLoadStatus {
+    Loaded,
+    /// the library does not exist or could not be opened
+    NotFound,
+    /// the SHA-256 digest of the library differs from the one in the manifest
+    DigestMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// the library was built against an incompatible sdk, or without an abi version
+    AbiMismatch {
+        expected: ExtnAbiVersion,
+        actual: Option<ExtnAbiVersion>,
+    },
+}
+
 /// Actual bootstep which loads the extensions into the ExtnState.
 /// Currently this step loads
 /// 1. Device Channel
@@ -49,17 +69,53 @@ impl LoadedLibrary {
 pub struct LoadExtensionsStep;
 
 impl LoadExtensionsStep {
-    unsafe fn load_extension_library<P: AsRef<OsStr>>(
-        filename: P,
+    fn verify_digest(file: &File, entry: &ExtnManifestEntry) -> Result<(), ExtnLoadStatus> {
+        let Some(expected) = &entry.sha256 else {
+            return Ok(());
+        };
+        let actual = sha256_reader(file).map_err(|_| ExtnLoadStatus::NotFound)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(ExtnLoadStatus::DigestMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Path which opens the same file as the given open file, so a library swapped in at the
+    /// original path after it was verified is not the one loaded.
+    #[cfg(target_os = "linux")]
+    fn open_file_path(file: &File, _path: &str) -> String {
+        format!("/proc/self/fd/{}", file.as_raw_fd())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_file_path(_file: &File, path: &str) -> String {
+        path.to_owned()
+    }
+
+    unsafe fn load_extension_library(
+        path: &str,
         entry: ExtnManifestEntry,
-    ) -> Option<LoadedLibrary> {
-        match Library::new(&filename) {
-            Ok(library) => Some(LoadedLibrary::new(library, entry)),
-            Err(err) => {
-                debug!("Extn not found: {:?}", err);
-                None
-            }
+    ) -> Result<LoadedLibrary, ExtnLoadStatus> {
+        // the digest is verified before the library gets a chance to run any code, the library
+        // is hashed and loaded from the same open file
+        let file = File::open(path).map_err(|err| {
+            debug!("Extn not found: {:?}", err);
+            ExtnLoadStatus::NotFound
+        })?;
+        Self::verify_digest(&file, &entry)?;
+        let library = Library::new(Self::open_file_path(&file, path)).map_err(|err| {
+            debug!("Extn not found: {:?}", err);
+            ExtnLoadStatus::NotFound
+        })?;
+        let expected = ExtnAbiVersion::current();
+        let actual = get_abi_version(&library);
+        if !actual.is_some_and(|v| v.is_compatible(&expected)) {
+            return Err(ExtnLoadStatus::AbiMismatch { expected, actual });
         }
+        Ok(LoadedLibrary::new(library, entry))
     }
 
     async fn pre_setup(&self, state: BootstrapState) -> Result<Vec<LoadedLibrary>, RippleError> {
@@ -84,16 +140,29 @@ impl LoadExtensionsStep {
                     "******************Loading {}************************",
                     extn_path
                 );
-                let r = Self::load_extension_library(extn_path.clone(), entry);
+                let r = Self::load_extension_library(&extn_path, entry.clone());
                 match r {
-                    Some(loaded_extn) => {
-                        info!("Adding {}", loaded_extn.entry.path);
+                    Ok(loaded_extn) => {
+                        info!(
+                            "Adding {} status={:?}",
+                            loaded_extn.entry.path,
+                            ExtnLoadStatus::Loaded
+                        );
                         loaded_extns.push(loaded_extn);
                     }
-                    None => warn!(
+                    Err(ExtnLoadStatus::NotFound) => warn!(
                         "file={} doesnt contain a valid extension library",
                         extn_path
                     ),
+                    Err(status) => {
+                        error!("file={} not loaded status={:?}", extn_path, status);
+                        // a rejected library is reported like an extension which failed to start
+                        ExtnSupervisor::emit_entry_status(
+                            &state.platform_state,
+                            &entry,
+                            ExtnStatus::Error,
+                        );
+                    }
                 }
                 debug!("-------------------------------------------------");
                 debug!("");
@@ -116,19 +185,123 @@ impl Bootstep<BootstrapState> for LoadExtensionsStep {
     }
     async fn setup(&self, state: BootstrapState) -> Result<(), RippleError> {
         let loaded_extensions = self.pre_setup(state.clone()).await?;
-        for extn in loaded_extensions.iter() {
-            unsafe {
-                let path = &extn.entry.path;
-                let library = &extn.library;
-                info!("Starting library at  path {}", path);
-                if let Ok(builder) = load_channel_builder(library) {
-                    thread::spawn(move || {
-                        (builder.start)();
-                    });
-                }
-            }
+        for extn in loaded_extensions {
+            ExtnSupervisor::start(state.platform_state.clone(), extn);
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bootstrap::extn::extn_supervisor::tests::{next_status, record_statuses},
+        service::extn::ripple_client::RippleClient,
+        state::{bootstrap_state::ChannelsState, platform_state::PlatformState},
+    };
+    use ripple_sdk::{
+        api::manifest::{
+            device_manifest::DeviceManifest,
+            extn_manifest::{ExtnManifest, ExtnSymbol},
+        },
+        tokio,
+    };
+
+    const DIGEST: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn entry(sha256: Option<&str>) -> ExtnManifestEntry {
+        ExtnManifestEntry {
+            path: "libtest".to_owned(),
+            symbols: Vec::new(),
+            resolution: None,
+            sha256: sha256.map(|s| s.to_owned()),
+            restart: Default::default(),
+        }
+    }
+
+    fn temp_library(name: &str, content: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "ripple_load_extn_{}_{}.so",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_load_extension_library_status() {
+        let path = temp_library("status", b"abc");
+        let file = File::open(&path).unwrap();
+
+        assert_eq!(
+            unsafe { LoadExtensionsStep::load_extension_library(&path, entry(Some("00"))) }
+                .unwrap_err(),
+            ExtnLoadStatus::DigestMismatch {
+                expected: "00".to_owned(),
+                actual: DIGEST.to_owned()
+            }
+        );
+        assert!(LoadExtensionsStep::verify_digest(&file, &entry(Some(DIGEST))).is_ok());
+        assert!(LoadExtensionsStep::verify_digest(&file, &entry(None)).is_ok());
+        // the digest matches but the file is not a library
+        assert_eq!(
+            unsafe { LoadExtensionsStep::load_extension_library(&path, entry(Some(DIGEST))) }
+                .unwrap_err(),
+            ExtnLoadStatus::NotFound
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_file_path_ignores_swapped_file() {
+        let path = temp_library("swapped", b"abc");
+        let file = File::open(&path).unwrap();
+        assert!(LoadExtensionsStep::verify_digest(&file, &entry(Some(DIGEST))).is_ok());
+        // a different library replaces the verified one before it is loaded
+        let other = temp_library("other", b"evil");
+        std::fs::rename(&other, &path).unwrap();
+        let loaded = std::fs::read(LoadExtensionsStep::open_file_path(&file, &path)).unwrap();
+        assert_eq!(loaded, b"abc");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_library_emits_error_status() {
+        let path = temp_library("rejected", b"abc");
+        let mut rejected = entry(Some("00"));
+        rejected.path = path.clone();
+        rejected.symbols = vec![ExtnSymbol {
+            id: "ripple:channel:device:rejected".to_owned(),
+            uses: Vec::new(),
+            fulfills: Vec::new(),
+            config: None,
+            secret: None,
+        }];
+        let channels_state = ChannelsState::new();
+        let platform_state = PlatformState::new(
+            ExtnManifest {
+                extns: vec![rejected],
+                ..Default::default()
+            },
+            DeviceManifest::default(),
+            RippleClient::new(channels_state.clone()),
+            vec![],
+            None,
+        );
+        let mut statuses = record_statuses(&platform_state);
+        let state = BootstrapState {
+            start_time: std::time::Instant::now(),
+            platform_state,
+            channels_state,
+        };
+
+        let loaded = LoadExtensionsStep.pre_setup(state).await.unwrap();
+        assert!(loaded.is_empty());
+        assert_eq!(next_status(&mut statuses).await, ExtnStatus::Error);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -15,5 +15,6 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+pub mod extn_supervisor;
 pub mod load_extn_step;
 pub mod load_session_step;
@@ -22,5 +22,7 @@ pub mod manifest;
 pub mod setup_extn_client_step;
 pub mod start_app_manager_step;
 pub mod start_communication_broker;
+pub mod start_dial_step;
 pub mod start_fbgateway_step;
+pub mod start_metrics_exporter_step;
 pub mod start_ws_step;
@@ -20,7 +20,9 @@ use ripple_sdk::{framework::bootstrap::Bootstep, tokio, utils::error::RippleErro
 
 use crate::processor::lifecycle_management_processor::LifecycleManagementProcessor;
 use crate::{
-    service::apps::delegated_launcher_handler::DelegatedLauncherHandler,
+    service::apps::{
+        app_library::DynamicAppLibrary, delegated_launcher_handler::DelegatedLauncherHandler,
+    },
     state::bootstrap_state::BootstrapState,
 };
 
@@ -40,6 +42,9 @@ impl Bootstep<BootstrapState> for StartAppManagerStep {
             .add_request_processor(LifecycleManagementProcessor::new(
                 state.platform_state.get_client(),
             ));
+        // keeps the app library in sync with the remote catalog, if one is configured
+        DynamicAppLibrary::start_sync(&state.platform_state);
+        DynamicAppLibrary::start_manifest_refresh(&state.platform_state);
         let mut app_manager =
             DelegatedLauncherHandler::new(state.channels_state, state.platform_state);
         tokio::spawn(async move {
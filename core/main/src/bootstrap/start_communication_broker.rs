@@ -20,6 +20,7 @@ use ripple_sdk::{
 };
 
 use crate::broker::endpoint_broker::BrokerOutputForwarder;
+use crate::broker::rules::rules_watcher::RulesWatcher;
 use crate::processor::rpc_gateway_processor::RpcGatewayProcessor;
 use crate::state::bootstrap_state::BootstrapState;
 
@@ -68,6 +69,7 @@ impl Bootstep<BootstrapState> for StartOtherBrokers {
         // Setup the endpoints from the manifests
         let mut endpoint_state = ps.clone().endpoint_state;
         endpoint_state.build_other_endpoints(ps.clone(), ps.session_state.get_account_session());
+        RulesWatcher::start(ps);
         Ok(())
     }
 }
@@ -0,0 +1,46 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use ripple_sdk::{
+    async_trait::async_trait, framework::bootstrap::Bootstep, log::error, utils::error::RippleError,
+};
+
+use crate::{service::dial::DialServer, state::bootstrap_state::BootstrapState};
+
+/// Starts the DIAL server if it is enabled in the device manifest
+pub struct StartDialStep;
+
+#[async_trait]
+impl Bootstep<BootstrapState> for StartDialStep {
+    fn get_name(&self) -> String {
+        "StartDialStep".into()
+    }
+
+    async fn setup(&self, state: BootstrapState) -> Result<(), RippleError> {
+        let config = state
+            .platform_state
+            .get_device_manifest()
+            .get_dial_configuration();
+        if config.enabled {
+            // second screen launches are not essential, Ripple starts without them
+            if let Err(e) = DialServer::start(state.platform_state, config).await {
+                error!("Unable to start the DIAL server {:?}", e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -38,8 +38,11 @@ use crate::{
     state::{bootstrap_state::BootstrapState, platform_state::PlatformState},
 };
 use jsonrpsee::core::{async_trait, server::rpc_module::Methods};
-use ripple_sdk::log::{debug, info};
-use ripple_sdk::{framework::bootstrap::Bootstep, utils::error::RippleError};
+use ripple_sdk::log::{debug, info, warn};
+use ripple_sdk::{
+    api::manifest::device_manifest::ResponseValidation, framework::bootstrap::Bootstep,
+    utils::error::RippleError,
+};
 pub struct FireboltGatewayStep;
 
 impl FireboltGatewayStep {
@@ -91,6 +94,11 @@ impl Bootstep<BootstrapState> for FireboltGatewayStep {
     }
 
     async fn setup(&self, state: BootstrapState) -> Result<(), RippleError> {
+        if !cfg!(feature = "openrpc_validation")
+            && state.platform_state.get_response_validation() != ResponseValidation::Off
+        {
+            warn!("Response validation is configured but Ripple is built without the openrpc_validation feature, responses are not validated");
+        }
         let methods = self.init_handlers(state.platform_state.clone()).await;
         let gateway = FireboltGateway::new(state.clone(), methods);
         debug!("Handlers initialized");
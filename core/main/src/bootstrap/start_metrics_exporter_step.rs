@@ -0,0 +1,51 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use ripple_sdk::{
+    async_trait::async_trait,
+    framework::bootstrap::Bootstep,
+    log::{error, info},
+    utils::error::RippleError,
+};
+
+use crate::{service::metrics_exporter::MetricsExporter, state::bootstrap_state::BootstrapState};
+
+/// Starts the metrics exporter if it is enabled in the device manifest
+pub struct StartMetricsExporterStep;
+
+#[async_trait]
+impl Bootstep<BootstrapState> for StartMetricsExporterStep {
+    fn get_name(&self) -> String {
+        "StartMetricsExporterStep".into()
+    }
+
+    async fn setup(&self, state: BootstrapState) -> Result<(), RippleError> {
+        let config = state
+            .platform_state
+            .get_device_manifest()
+            .get_metrics_exporter_configuration();
+        if config.enabled {
+            // metrics are not essential, Ripple starts without them
+            match MetricsExporter::new(state.platform_state.metrics.clone()).start(&config.address)
+            {
+                Ok(addr) => info!("Metrics exporter listening on {}", addr),
+                Err(e) => error!("Unable to start the metrics exporter {:?}", e),
+            }
+        }
+        Ok(())
+    }
+}
@@ -16,10 +16,17 @@
 //
 
 use ripple_sdk::{
-    async_trait::async_trait, framework::bootstrap::Bootstep, tokio, utils::error::RippleError,
+    async_trait::async_trait,
+    framework::bootstrap::Bootstep,
+    log::{error, info, warn},
+    tokio,
+    utils::error::RippleError,
 };
 
-use crate::state::bootstrap_state::BootstrapState;
+use crate::{
+    service::{session_recorder::SessionRecorder, tracer::Tracer},
+    state::bootstrap_state::BootstrapState,
+};
 
 use crate::firebolt::firebolt_ws::FireboltWs;
 
@@ -37,19 +44,35 @@ impl Bootstep<BootstrapState> for StartWsStep {
         let ws_enabled = manifest.get_web_socket_enabled();
         let internal_ws_enabled = manifest.get_internal_ws_enabled();
         let iai_c = iai.clone();
+        // started before any connection so recordings contain whole sessions
+        if let Some(path) = manifest.get_session_recording_path() {
+            match SessionRecorder::start(&path) {
+                Ok(_) => warn!("Recording Firebolt sessions to {}", path),
+                Err(e) => error!("Session recording to {} not started {:?}", path, e),
+            }
+        }
+        let trace_export = manifest.get_trace_export_configuration();
+        if trace_export.is_enabled() {
+            match Tracer::start(&trace_export) {
+                Ok(_) => info!("Exporting traces {:?}", trace_export),
+                Err(e) => error!("Trace export {:?} not started {:?}", trace_export, e),
+            }
+        }
         if ws_enabled {
             let ws_addr = manifest.get_ws_gateway_host();
+            let tls = manifest.get_ws_tls_configuration();
             let state_for_ws = state.platform_state.clone();
             tokio::spawn(async move {
-                FireboltWs::start(ws_addr.as_str(), state_for_ws, true, iai.clone()).await;
+                FireboltWs::start(ws_addr.as_str(), state_for_ws, true, iai.clone(), tls).await;
             });
         }
 
         if internal_ws_enabled {
             let ws_addr = manifest.get_internal_gateway_host();
+            let tls = manifest.get_internal_ws_tls_configuration();
             let state_for_ws = state.platform_state;
             tokio::spawn(async move {
-                FireboltWs::start(ws_addr.as_str(), state_for_ws, false, iai_c).await;
+                FireboltWs::start(ws_addr.as_str(), state_for_ws, false, iai_c, tls).await;
             });
         }
 
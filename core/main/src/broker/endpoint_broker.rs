@@ -21,15 +21,16 @@ use ripple_sdk::{
             FireboltPermission, CAPABILITY_NOT_AVAILABLE, JSON_RPC_STANDARD_ERROR_INVALID_PARAMS,
         },
         gateway::rpc_gateway_api::{
-            ApiMessage, ApiProtocol, CallContext, JsonRpcApiRequest, JsonRpcApiResponse,
-            RpcRequest, RPC_V2,
+            ApiMessage, ApiProtocol, CallContext, JsonRpcApiError, JsonRpcApiRequest,
+            JsonRpcApiResponse, RpcRequest, RPC_V2,
         },
+        manifest::extn_manifest::ExtnManifest,
         observability::log_signal::LogSignal,
         session::AccountSession,
     },
     extn::extn_client_message::{ExtnEvent, ExtnMessage},
     framework::RippleResponse,
-    log::{debug, error, info, trace},
+    log::{debug, error, info, trace, warn},
     service::service_message::{
         Id as ServiceMessageId, JsonRpcMessage as ServiceJsonRpcMessage,
         JsonRpcSuccess as ServiceJsonRpcSuccess, ServiceMessage,
@@ -47,14 +48,14 @@ use std::{
     collections::HashMap,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
 };
 
 use crate::{
     broker::broker_utils::BrokerUtils,
-    firebolt::firebolt_gateway::JsonRpcError,
-    service::extn::ripple_client::RippleClient,
+    firebolt::firebolt_gateway::{validate_response, JsonRpcError},
+    service::{extn::ripple_client::RippleClient, tracer::Tracer},
     state::{
         ops_metrics_state::OpMetricState, platform_state::PlatformState, session_state::Session,
     },
@@ -377,8 +378,11 @@ pub struct EndpointBrokerState {
     request_map: Arc<RwLock<HashMap<u64, BrokerRequest>>>,
     extension_request_map: Arc<RwLock<HashMap<u64, ExtnMessage>>>,
     rule_engine: Arc<RwLock<RuleEngine>>,
-    cleaner_list: Arc<RwLock<Vec<BrokerCleaner>>>,
+    // cleaners of the endpoints by their key
+    cleaner_list: Arc<RwLock<HashMap<String, BrokerCleaner>>>,
     reconnect_tx: Sender<BrokerConnectRequest>,
+    // serializes rule reloads, which can be triggered by the rules watcher and over rpc
+    reload_lock: Arc<Mutex<()>>,
     provider_broker_state: ProvideBrokerState,
     metrics_state: OpMetricState,
 }
@@ -468,8 +472,9 @@ impl Default for EndpointBrokerState {
             request_map: Arc::new(RwLock::new(HashMap::new())),
             extension_request_map: Arc::new(RwLock::new(HashMap::new())),
             rule_engine: Arc::new(RwLock::new(RuleEngine::default())),
-            cleaner_list: Arc::new(RwLock::new(Vec::new())),
+            cleaner_list: Arc::new(RwLock::new(HashMap::new())),
             reconnect_tx: mpsc::channel(2).0,
+            reload_lock: Arc::new(Mutex::new(())),
             provider_broker_state: ProvideBrokerState::default(),
             metrics_state: OpMetricState::default(),
         }
@@ -490,8 +495,9 @@ impl EndpointBrokerState {
             request_map: Arc::new(RwLock::new(HashMap::new())),
             extension_request_map: Arc::new(RwLock::new(HashMap::new())),
             rule_engine: Arc::new(RwLock::new(rule_engine)),
-            cleaner_list: Arc::new(RwLock::new(Vec::new())),
+            cleaner_list: Arc::new(RwLock::new(HashMap::new())),
             reconnect_tx,
+            reload_lock: Arc::new(Mutex::new(())),
             provider_broker_state: ProvideBrokerState::default(),
             metrics_state,
         };
@@ -511,6 +517,83 @@ impl EndpointBrokerState {
     pub fn has_rule(&self, rule: &str) -> bool {
         self.rule_engine.read().unwrap().has_rule(rule)
     }
+
+    /// Methods which are brokered to the service with the given id.
+    pub fn get_service_methods(&self, service_id: &str) -> Vec<String> {
+        let rule_engine = self.rule_engine.read().unwrap();
+        let rule_set = &rule_engine.rules;
+        let mut methods: Vec<String> = rule_set
+            .rules
+            .iter()
+            .filter(|(_, rule)| {
+                rule.alias == service_id
+                    && rule
+                        .endpoint
+                        .as_ref()
+                        .and_then(|endpoint| rule_set.endpoints.get(endpoint))
+                        .is_some_and(|endpoint| {
+                            matches!(endpoint.protocol, RuleEndpointProtocol::Service)
+                        })
+            })
+            .map(|(method, _)| method.clone())
+            .collect();
+        methods.sort();
+        methods
+    }
+
+    pub fn get_rule_source_paths(&self, extn_manifest: &ExtnManifest) -> Vec<String> {
+        self.rule_engine.read().unwrap().source_paths(extn_manifest)
+    }
+
+    /// Reloads the rules from the extension manifest and swaps them in atomically.
+    /// Requests which are already brokered keep the [Rule] they were dispatched with. If the new
+    /// rules fail to load or validate the current rules stay active and the error is returned.
+    /// Endpoints which are new in the reloaded rules are started and endpoints whose config
+    /// changed are restarted. Endpoints which are no longer in the rules are removed, their
+    /// brokers stop once the requests in flight are done. Reloads run one at a time.
+    pub fn reload_rules(&self, ps: PlatformState) -> Result<usize, RippleError> {
+        let _reload = self.reload_lock.lock().unwrap();
+        let rule_engine = RuleEngine::try_build(&ps.extn_manifest)?;
+        let rule_count = rule_engine.rules.rules.len();
+        let new_endpoints = rule_engine.rules.endpoints.clone();
+        let old_endpoints = {
+            let mut current = self.rule_engine.write().unwrap();
+            std::mem::replace(&mut *current, rule_engine)
+                .rules
+                .endpoints
+        };
+        let mut state = self.clone();
+        for key in old_endpoints.keys() {
+            if !new_endpoints.contains_key(key) {
+                info!("reload_rules: stopping removed endpoint {}", key);
+                state.remove_endpoint(key);
+            }
+        }
+        for (key, endpoint) in new_endpoints {
+            let old_endpoint = old_endpoints.get(&key);
+            if old_endpoint.is_some_and(|old| *old == endpoint) && self.get_sender(&key).is_some() {
+                continue;
+            }
+            // thunder is only started during bootstrap as its reconnect stops the gateway
+            if let RuleEndpointProtocol::Thunder = endpoint.protocol {
+                if old_endpoint.is_some_and(|old| *old != endpoint) {
+                    warn!("reload_rules: endpoint {} changed, restart to apply", key);
+                }
+                continue;
+            }
+            info!("reload_rules: starting endpoint {}", key);
+            let request = BrokerConnectRequest::new_with_sesssion(
+                key,
+                endpoint,
+                self.reconnect_tx.clone(),
+                ps.session_state.get_account_session(),
+            );
+            state.build_endpoint(Some(ps.clone()), request);
+        }
+        info!("reload_rules: loaded {} rules", rule_count);
+        Ok(rule_count)
+    }
+
     #[cfg(not(test))]
     fn reconnect_thread(&self, mut rx: Receiver<BrokerConnectRequest>, client: RippleClient) {
         use crate::firebolt::firebolt_gateway::FireboltGatewayCommand;
@@ -709,6 +792,11 @@ impl EndpointBrokerState {
         }
         self
     }
+    // the broker of the endpoint stops once the last sender of its requests is dropped
+    fn remove_endpoint(&mut self, key: &str) {
+        self.endpoint_map.write().unwrap().remove(key);
+        self.cleaner_list.write().unwrap().remove(key);
+    }
     pub fn get_endpoints(&self) -> HashMap<String, BrokerSender> {
         self.endpoint_map.read().unwrap().clone()
     }
@@ -718,7 +806,7 @@ impl EndpointBrokerState {
         let key = request.key.clone();
         let (broker, cleaner) = match endpoint.protocol {
             RuleEndpointProtocol::Http => (
-                HttpBroker::get_broker(None, request, self.callback.clone(), self).get_sender(),
+                HttpBroker::get_broker(ps, request, self.callback.clone(), self).get_sender(),
                 None,
             ),
             RuleEndpointProtocol::Websocket => {
@@ -747,12 +835,13 @@ impl EndpointBrokerState {
                 None,
             ),
         };
-        self.add_endpoint(key, broker);
+        self.add_endpoint(key.clone(), broker);
 
-        if let Some(cleaner) = cleaner {
-            let mut cleaner_list = self.cleaner_list.write().unwrap();
-            cleaner_list.push(cleaner);
-        }
+        let mut cleaner_list = self.cleaner_list.write().unwrap();
+        match cleaner {
+            Some(cleaner) => cleaner_list.insert(key, cleaner),
+            None => cleaner_list.remove(&key),
+        };
     }
 
     fn handle_static_request(&self, rpc_request: RpcRequest) -> JsonRpcApiResponse {
@@ -824,6 +913,25 @@ impl EndpointBrokerState {
     fn get_sender(&self, hash: &str) -> Option<BrokerSender> {
         self.endpoint_map.read().unwrap().get(hash).cloned()
     }
+
+    /// Returns the name and protocol of the endpoint of a rule, which label its metrics.
+    fn get_endpoint_labels(&self, rule: &Rule) -> (String, String) {
+        let name = rule
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| "thunder".to_owned());
+        let protocol = self
+            .rule_engine
+            .read()
+            .unwrap()
+            .rules
+            .endpoints
+            .get(&name)
+            .map(|endpoint| endpoint.protocol.clone())
+            .unwrap_or(RuleEndpointProtocol::Thunder);
+        let protocol = format!("{:?}", protocol).to_lowercase();
+        (name, protocol)
+    }
     fn get_broker_rule(
         &self,
         rpc_request: &RpcRequest,
@@ -1057,7 +1165,18 @@ impl EndpointBrokerState {
                         method: Some(request.rpc.method.clone()),
                         params: request.rpc.get_params(),
                     };
-                    let request_for_spawn = request.clone();
+                    let (endpoint_name, protocol) = self.get_endpoint_labels(&rule);
+                    self.metrics_state.start_broker_timing(
+                        request.rpc.ctx.call_id,
+                        &endpoint_name,
+                        &protocol,
+                    );
+                    let mut request_for_spawn = request.clone();
+                    Tracer::start_broker_span(
+                        &mut request_for_spawn.rpc.ctx,
+                        &endpoint_name,
+                        &protocol,
+                    );
                     tokio::spawn(async move { endpoint.send_request(request_for_spawn).await });
 
                     Ok(RenderedRequest::ProviderJsonRpc(data))
@@ -1098,7 +1217,14 @@ impl EndpointBrokerState {
 
     // Method to cleanup all subscription on App termination
     pub async fn cleanup_for_app(&self, app_id: &str) {
-        let cleaners = { self.cleaner_list.read().unwrap().clone() };
+        let cleaners: Vec<BrokerCleaner> = {
+            self.cleaner_list
+                .read()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect()
+        };
 
         for cleaner in cleaners {
             /*
@@ -1312,6 +1438,25 @@ pub trait EndpointBroker {
     fn send_broker_failure_response(callback: &BrokerCallback, error_message: JsonRpcApiResponse) {
         BrokerOutputForwarder::send_json_rpc_response_to_broker(error_message, callback.clone());
     }
+
+    /// Answers the requests still queued for a broker which could not start with an error.
+    /// The receiver is closed, so later requests already fail when they are sent.
+    fn reject_queued_requests(
+        rx: &mut Receiver<BrokerRequest>,
+        callback: &BrokerCallback,
+        message: &str,
+    ) {
+        rx.close();
+        while let Ok(request) = rx.try_recv() {
+            Self::send_broker_failure_response(
+                callback,
+                JsonRpcApiError::default()
+                    .with_id(request.rpc.ctx.call_id)
+                    .with_message(message.to_owned())
+                    .into(),
+            );
+        }
+    }
 }
 
 /// Forwarder gets the BrokerOutput and forwards the response to the gateway.
@@ -1337,6 +1482,10 @@ impl BrokerOutputForwarder {
                 };
 
                 if let Some(id) = id {
+                    if !is_event {
+                        platform_state.metrics.finish_broker_timing(id);
+                        Tracer::end_broker_span(id, response.error.is_some());
+                    }
                     if let Ok(broker_request) = platform_state.endpoint_state.get_request(id) {
                         LogSignal::new(
                             "start_forwarder".to_string(),
@@ -1468,6 +1617,13 @@ impl BrokerOutputForwarder {
             let mut response = response.clone();
             if is_event {
                 response.update_event_message(rpc_request);
+            } else if let Some(error) = response
+                .result
+                .as_ref()
+                .and_then(|result| validate_response(platform_state, rpc_request, result))
+            {
+                response.result = None;
+                response.error = Some(error);
             }
             let mut message = ApiMessage::new(
                 rpc_request.ctx.protocol.clone(),
@@ -2037,6 +2193,8 @@ mod endpoint_broker_tests {
                         filter: None,
                         event_handler: None,
                         sources: None,
+                        workflow: None,
+                        http: None,
                     },
                     subscription_processed: None,
                     workflow_callback: None,
@@ -2742,6 +2900,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             state.update_request(&rpc_request, &rule, None, None, vec![]);
             apply_response(filter, &rpc_request.ctx.method, &mut output.data);
@@ -2787,6 +2947,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             state.update_request(&rpc_request, &rule, None, None, vec![]);
             apply_response(filter, &rpc_request.ctx.method, &mut output.data);
@@ -2834,6 +2996,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             state.update_request(&rpc_request, &rule, None, None, vec![]);
             apply_response(filter, &rpc_request.ctx.method, &mut output.data);
@@ -2880,6 +3044,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             state.update_request(&rpc_request, &rule, None, None, vec![]);
             apply_response(filter, &rpc_request.ctx.method, &mut output.data);
@@ -2927,6 +3093,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             state.update_request(&rpc_request, &rule, None, None, vec![]);
             apply_response(filter, &rpc_request.ctx.method, &mut output.data);
@@ -2965,6 +3133,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             engine.add_rule(r);
 
@@ -3109,6 +3279,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             engine.add_rule(rule);
             let mut under_test =
@@ -3162,6 +3334,8 @@ mod endpoint_broker_tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             };
             engine.add_rule(rule);
             let under_test = EndpointBrokerState::new(OpMetricState::default(), tx, engine, client);
@@ -3214,6 +3388,8 @@ mod endpoint_broker_tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    workflow: None,
+                    http: None,
                 };
 
                 let broker_request = state.update_request(&rpc_request, &rule, None, None, vec![]);
@@ -3243,6 +3419,8 @@ mod endpoint_broker_tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    workflow: None,
+                    http: None,
                 };
                 let extn_message = Some(ExtnMessage::default());
 
@@ -3274,6 +3452,8 @@ mod endpoint_broker_tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    workflow: None,
+                    http: None,
                 };
                 let workflow_callback = Some(BrokerCallback::default());
 
@@ -3311,6 +3491,8 @@ mod endpoint_broker_tests {
                     filter: None,
                     event_handler: None,
                     sources: None,
+                    workflow: None,
+                    http: None,
                 };
                 let telemetry_response_listeners = vec![channel(2).0];
 
@@ -3355,6 +3537,101 @@ mod endpoint_broker_tests {
         }
     }
     #[cfg(test)]
+    mod reload_rules {
+        use std::{fs, sync::Arc};
+
+        use ripple_sdk::{
+            api::manifest::extn_manifest::ExtnManifest, tokio, utils::error::RippleError,
+        };
+        use ripple_tdk::utils::test_utils::Mockable;
+
+        use crate::state::platform_state::PlatformState;
+
+        fn platform_state_with_rules(name: &str, contents: &str) -> PlatformState {
+            let dir = std::env::temp_dir().join(format!(
+                "ripple_reload_rules_{}_{}",
+                name,
+                std::process::id()
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("rules.json"), contents).unwrap();
+            let mut ps = PlatformState::mock();
+            ps.extn_manifest = Arc::new(ExtnManifest {
+                default_path: format!("{}/", dir.display()),
+                rules_path: vec!["rules.json".to_string()],
+                ..Default::default()
+            });
+            ps
+        }
+
+        #[tokio::test]
+        async fn test_reload_rules_swaps_rules() {
+            let ps = platform_state_with_rules(
+                "swap",
+                r#"{ "endpoints": {}, "rules": { "device.make": { "alias": "getDeviceInfo" } } }"#,
+            );
+            assert!(!ps.endpoint_state.has_rule("device.make"));
+            assert_eq!(ps.endpoint_state.reload_rules(ps.clone()), Ok(1));
+            assert!(ps.endpoint_state.has_rule("device.make"));
+        }
+
+        #[tokio::test]
+        async fn test_reload_rules_keeps_rules_on_error() {
+            let ps = platform_state_with_rules(
+                "keep",
+                r#"{ "endpoints": {}, "rules": { "device.make": { "alias": "getDeviceInfo" } } }"#,
+            );
+            assert!(ps.endpoint_state.reload_rules(ps.clone()).is_ok());
+            fs::write(
+                format!("{}rules.json", ps.extn_manifest.default_path),
+                r#"{ "endpoints": {}, "rules": { "device.model": { "alias": "x", "transform": { "response": "if" } } } }"#,
+            )
+            .unwrap();
+            assert_eq!(
+                ps.endpoint_state.reload_rules(ps.clone()),
+                Err(RippleError::RuleError)
+            );
+            assert!(ps.endpoint_state.has_rule("device.make"));
+            assert!(!ps.endpoint_state.has_rule("device.model"));
+        }
+
+        #[tokio::test]
+        async fn test_reload_rules_restarts_changed_endpoints() {
+            let ps = platform_state_with_rules(
+                "endpoints",
+                r#"{ "endpoints": {
+                    "changed": { "protocol": "http", "url": "http://127.0.0.1:1" },
+                    "kept": { "protocol": "http", "url": "http://127.0.0.1:2" },
+                    "removed": { "protocol": "http", "url": "http://127.0.0.1:3" }
+                }, "rules": {} }"#,
+            );
+            assert!(ps.endpoint_state.reload_rules(ps.clone()).is_ok());
+            let endpoints = ps.endpoint_state.get_endpoints();
+            let changed = endpoints.get("changed").unwrap().sender.downgrade();
+            let kept = endpoints.get("kept").unwrap().sender.clone();
+            let removed = endpoints.get("removed").unwrap().sender.downgrade();
+            drop(endpoints);
+
+            fs::write(
+                format!("{}rules.json", ps.extn_manifest.default_path),
+                r#"{ "endpoints": {
+                    "changed": { "protocol": "http", "url": "http://127.0.0.1:4" },
+                    "kept": { "protocol": "http", "url": "http://127.0.0.1:2" }
+                }, "rules": {} }"#,
+            )
+            .unwrap();
+            assert!(ps.endpoint_state.reload_rules(ps.clone()).is_ok());
+            let endpoints = ps.endpoint_state.get_endpoints();
+            assert_eq!(endpoints.len(), 2);
+            assert!(!endpoints.contains_key("removed"));
+            assert!(endpoints.get("kept").unwrap().sender.same_channel(&kept));
+            assert!(endpoints.contains_key("changed"));
+            // the brokers of the replaced and removed endpoints stop with their last sender
+            assert!(changed.upgrade().is_none());
+            assert!(removed.upgrade().is_none());
+        }
+    }
+    #[cfg(test)]
     mod workflow {
         // fn test_workflow() {
         //     let (tx, _) = channel(2);
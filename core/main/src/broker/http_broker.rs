@@ -17,14 +17,28 @@
 
 use std::vec;
 
-use hyper::{client::HttpConnector, Body, Client, Method, Request, Response, Uri};
+use hyper::{
+    client::{connect::Connect, HttpConnector},
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    http::response::Parts,
+    Body, Client, Method, Request, Response, Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use ripple_sdk::{
-    api::{gateway::rpc_gateway_api::JsonRpcApiError, observability::log_signal::LogSignal},
+    api::{
+        gateway::rpc_gateway_api::JsonRpcApiError,
+        observability::{
+            log_signal::LogSignal,
+            session_recording::{RecordChannel, RecordDirection},
+            trace_context::TRACEPARENT,
+        },
+        session::AccountSession,
+    },
     log::{debug, error},
     tokio::{self, sync::mpsc},
     utils::error::RippleError,
 };
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use super::endpoint_broker::{
     BrokerCallback, BrokerCleaner, BrokerConnectRequest, BrokerOutputForwarder, BrokerRequest,
@@ -32,7 +46,8 @@ use super::endpoint_broker::{
 };
 
 use crate::{
-    broker::rules::rules_engine::{jq_compile, RuleTransformType},
+    broker::rules::rules_engine::{jq_compile, HttpMethod, RuleEndpoint, RuleTransformType},
+    service::session_recorder::SessionRecorder,
     state::platform_state::PlatformState,
 };
 
@@ -43,18 +58,138 @@ pub struct HttpBroker {
     cleaner: BrokerCleaner,
 }
 
-async fn send_http_request(
-    client: &Client<HttpConnector>,
+/// Builds the client of an endpoint, `https` urls are verified using the tls settings of the
+/// endpoint or the web PKI roots when there are none.
+fn build_client(
+    endpoint: &RuleEndpoint,
+) -> Result<Client<HttpsConnector<HttpConnector>>, RippleError> {
+    let tls = endpoint.tls.clone().unwrap_or_default();
+    let builder = HttpsConnectorBuilder::new()
+        .with_tls_config(tls.client_config()?)
+        .https_or_http();
+    let builder = match tls.server_name {
+        Some(server_name) => builder.with_server_name(server_name),
+        None => builder,
+    };
+    Ok(Client::builder().build(builder.enable_http1().build()))
+}
+
+fn template_input(broker_request: &BrokerRequest, session: &Option<AccountSession>) -> Value {
+    let params = match serde_json::from_str::<Vec<Value>>(&broker_request.rpc.params_json) {
+        Ok(mut params) => params.pop().unwrap_or(Value::Null),
+        Err(_) => Value::Null,
+    };
+    let ctx = &broker_request.rpc.ctx;
+    json!({
+        "params": params,
+        "context": {
+            "appId": ctx.app_id,
+            "method": ctx.method,
+            "requestId": ctx.request_id,
+            "sessionId": ctx.session_id,
+        },
+        "session": session.as_ref().map(|s| json!({
+            "id": s.id,
+            "token": s.token,
+            "accountId": s.account_id,
+            "deviceId": s.device_id,
+        })),
+    })
+}
+
+/// Percent-encodes everything but the unreserved characters, so a value stays one path segment.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// the template input with every string encoded, for the path template
+fn encode_template_input(input: &Value) -> Value {
+    match input {
+        Value::String(s) => Value::String(encode_path_segment(s)),
+        Value::Array(values) => Value::Array(values.iter().map(encode_template_input).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), encode_template_input(v)))
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+/// Renders the path template. Values are encoded, and a path which could still leave the
+/// templated location, by a dot segment, a query or a fragment, is rejected.
+fn render_path(input: &Value, template: &str, reference: String) -> Result<String, RippleError> {
+    let path = template_string(&jq_compile(
+        encode_template_input(input),
+        template,
+        reference,
+    )?);
+    if path.contains("..") || path.contains('?') || path.contains('#') {
+        error!("http path {} is not allowed", path);
+        return Err(RippleError::InvalidInput);
+    }
+    Ok(path)
+}
+
+fn template_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn apply_template(
+    input: &Value,
+    filter: &str,
+    reference: String,
+) -> Result<serde_json::Map<String, Value>, RippleError> {
+    match jq_compile(input.clone(), filter, reference)? {
+        Value::Object(map) => Ok(map),
+        Value::Null => Ok(serde_json::Map::new()),
+        v => {
+            error!("http template has to yield an object, got {:?}", v);
+            Err(RippleError::RuleError)
+        }
+    }
+}
+
+fn to_hyper_method(method: HttpMethod) -> Method {
+    match method {
+        HttpMethod::Get => Method::GET,
+        HttpMethod::Post => Method::POST,
+        HttpMethod::Put => Method::PUT,
+        HttpMethod::Delete => Method::DELETE,
+        HttpMethod::Patch => Method::PATCH,
+        HttpMethod::Head => Method::HEAD,
+    }
+}
+
+async fn send_http_request<C: Connect + Clone + Send + Sync + 'static>(
+    client: &Client<C>,
     uri: &Uri,
     broker_request: BrokerRequest,
+    session: Option<AccountSession>,
 ) -> Result<Response<Body>, RippleError> {
     let mut method = Method::GET;
     let mut body = Body::empty();
+    let mut body_json = Value::Null;
+    let http = broker_request.rule.http.clone().unwrap_or_default();
+    let reference = broker_request.rpc.ctx.method.clone();
 
     // A rule with a request transform defined indicates that the request is a POST, where
     // the request transform is the body of the request. Otherwise, it is a GET request.
+    // Both can be overridden by the http descriptor of the rule.
 
-    if let Some(request_transform) = broker_request
+    let has_body = if let Some(request_transform) = broker_request
         .rule
         .transform
         .get_transform_data(RuleTransformType::Request)
@@ -76,21 +211,84 @@ async fn send_http_request(
         let body_val = jq_compile(
             transform_params,
             &request_transform,
-            format!("{}_http_post", broker_request.rpc.ctx.method),
+            format!("{}_http_post", reference),
         )?;
 
         body = Body::from(body_val.to_string());
+        body_json = body_val;
+        true
+    } else {
+        false
+    };
+
+    if let Some(http_method) = http.method {
+        method = to_hyper_method(http_method);
     }
 
-    let uri: Uri = format!("{}{}", uri, broker_request.rule.alias)
+    let input = template_input(&broker_request, &session);
+    let path = match &http.path {
+        Some(path) => render_path(&input, path, format!("{}_http_path", reference))?,
+        None => broker_request.rule.alias.clone(),
+    };
+
+    let mut uri = format!("{}{}", uri, path);
+    if let Some(query) = &http.query {
+        let query = apply_template(&input, query, format!("{}_http_query", reference))?;
+        if !query.is_empty() {
+            let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+            for (key, value) in query.iter().filter(|(_, v)| !v.is_null()) {
+                serializer.append_pair(key, &template_string(value));
+            }
+            let separator = if uri.contains('?') { '&' } else { '?' };
+            uri = format!("{}{}{}", uri, separator, serializer.finish());
+        }
+    }
+    let uri: Uri = uri
         .parse()
         .map_err(|e: InvalidUri| RippleError::BrokerError(e.to_string()))?;
 
     debug!("http_broker sending {} request={}", method, uri,);
+    if SessionRecorder::is_recording() {
+        let recorded = json!({
+            "id": broker_request.rpc.ctx.call_id,
+            "method": method.as_str(),
+            "uri": uri.to_string(),
+            "body": body_json,
+        });
+        SessionRecorder::record(
+            RecordChannel::Http,
+            RecordDirection::Outbound,
+            &recorded.to_string(),
+        );
+    }
 
-    let http_request = Request::builder()
-        .uri(uri)
-        .method(method)
+    let mut builder = Request::builder().uri(uri).method(method);
+    if has_body {
+        builder = builder.header(CONTENT_TYPE, "application/json");
+    }
+    if http.bearer_auth {
+        match &session {
+            Some(session) => {
+                builder = builder.header(AUTHORIZATION, format!("Bearer {}", session.token))
+            }
+            None => {
+                error!("send_http_request: bearer_auth set but no account session");
+                return Err(RippleError::ApiAuthenticationFailed);
+            }
+        }
+    }
+    if let Some(headers) = &http.headers {
+        for (name, value) in apply_template(&input, headers, format!("{}_http_headers", reference))?
+        {
+            builder = builder.header(name.as_str(), template_string(&value));
+        }
+    }
+
+    if let Some(traceparent) = &broker_request.rpc.ctx.traceparent {
+        builder = builder.header(TRACEPARENT, traceparent.as_str());
+    }
+
+    let http_request = builder
         .body(body)
         .map_err(|e| RippleError::BrokerError(e.to_string()))?;
 
@@ -103,6 +301,34 @@ async fn send_http_request(
     }
 }
 
+/// Wraps the response into `{ status, headers, body }` for rules which asked for the response
+/// metadata. Bodies which are not JSON are passed as a string.
+fn response_with_metadata(parts: &Parts, body: &[u8]) -> Vec<u8> {
+    let headers: serde_json::Map<String, Value> = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|v| (name.to_string(), Value::String(v.to_owned())))
+        })
+        .collect();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice::<Value>(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).to_string()))
+    };
+    json!({
+        "status": parts.status.as_u16(),
+        "headers": headers,
+        "body": body,
+    })
+    .to_string()
+    .into_bytes()
+}
+
 async fn send_broker_response(
     callback: &BrokerCallback,
     request: &BrokerRequest,
@@ -137,22 +363,37 @@ async fn body_to_bytes(body: Body) -> Vec<u8> {
 
 impl EndpointBroker for HttpBroker {
     fn get_broker(
-        _ps: Option<PlatformState>,
+        ps: Option<PlatformState>,
         request: BrokerConnectRequest,
         callback: BrokerCallback,
         _broker_state: &mut EndpointBrokerState,
     ) -> Self {
         let endpoint = request.endpoint.clone();
+        let connect_session = request.session.clone();
         let (tx, mut tr) = mpsc::channel(BROKER_CHANNEL_BUFFER_SIZE);
         let broker = BrokerSender { sender: tx };
-        let client = Client::new();
+        let client = match build_client(&endpoint) {
+            Ok(client) => client,
+            Err(e) => {
+                error!("tls settings of endpoint {:?} are invalid, cannot start http broker. error={:?}", endpoint, e);
+                return Self {
+                    sender: broker,
+                    cleaner: BrokerCleaner { cleaner: None },
+                };
+            }
+        };
 
         let _ =  endpoint.get_url().parse().map_err(|e| error!("broker url {:?} in endpoint is invalid, cannot start http broker. error={}",endpoint,e) ).map(|uri| tokio::spawn(async move {
             while let Some(mut request) = tr.recv().await {
                 LogSignal::new("http_broker".to_string(), format!("received request - start processing request={:?}", request), request.rpc.ctx.clone())
                     .with_diagnostic_context_item("rule_alias", request.rule.alias.as_str()).emit_debug();
 
-                match send_http_request(&client, &uri, request.clone())
+                // the session can be refreshed after the broker started, prefer the current one
+                let session = ps
+                    .as_ref()
+                    .and_then(|ps| ps.session_state.get_account_session())
+                    .or_else(|| connect_session.clone());
+                match send_http_request(&client, &uri, request.clone(), session)
                     .await
                 {
                     Ok(response) => {
@@ -165,6 +406,20 @@ impl EndpointBroker for HttpBroker {
 
                         let (parts, body) = response.into_parts();
                         let body = body_to_bytes(body).await;
+                        if SessionRecorder::is_recording() {
+                            let recorded = json!({
+                                "id": request.rpc.ctx.call_id,
+                                "status": parts.status.as_u16(),
+                                "body": serde_json::from_slice::<Value>(&body)
+                                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).to_string())),
+                            });
+                            SessionRecorder::record(RecordChannel::Http, RecordDirection::Inbound, &recorded.to_string());
+                        }
+                        let body = if request.rule.http.as_ref().is_some_and(|h| h.response_metadata) {
+                            response_with_metadata(&parts, &body)
+                        } else {
+                            body
+                        };
 
                         if !body.is_empty() {
                             if let Ok(json_str) = serde_json::from_slice::<serde_json::Value>(&body).map(|v| vec![v])
@@ -193,6 +448,20 @@ impl EndpointBroker for HttpBroker {
                             .emit_error();
                         }
                     }
+                    Err(err @ (RippleError::ApiAuthenticationFailed | RippleError::InvalidInput)) => {
+                        // the request was not sent, the caller gets an error instead
+                        let msg = match err {
+                            RippleError::ApiAuthenticationFailed => "No account session for the http request",
+                            _ => "Http request not allowed by its path",
+                        };
+                        LogSignal::new("http_broker".to_string(), "Prepare request failed".to_string(), request.rpc.ctx.clone())
+                                .with_diagnostic_context_item("error", msg)
+                                .emit_error();
+                        Self::send_broker_failure_response(&callback,
+                            JsonRpcApiError::default()
+                            .with_id(request.rpc.ctx.call_id)
+                            .with_message(msg.to_string()).into());
+                    }
                     Err(err) => {
                         let msg = format!("An error message from calling the downstream http service={} in http broker {:?}", uri, err);
                         LogSignal::new("http_broker".to_string(), "Prepare request failed".to_string(), request.rpc.ctx.clone())
@@ -227,7 +496,7 @@ mod tests {
 
     use crate::broker::{
         endpoint_broker::BrokerOutput,
-        rules::rules_engine::{Rule, RuleEndpoint, RuleEndpointProtocol},
+        rules::rules_engine::{HttpDescriptor, Rule, RuleEndpoint, RuleEndpointProtocol},
     };
 
     use super::*;
@@ -235,6 +504,7 @@ mod tests {
     use ripple_sdk::{
         api::gateway::rpc_gateway_api::{JsonRpcApiResponse, RpcRequest},
         tokio::{runtime::Runtime, task::JoinHandle, time::timeout},
+        utils::tls_utils::TlsConfig,
         Mockable,
     };
 
@@ -285,6 +555,7 @@ mod tests {
             url: base_uri.to_string(),
             protocol: RuleEndpointProtocol::Http,
             jsonrpc: false,
+            tls: None,
         };
 
         let (tx, _) = mpsc::channel(BROKER_CHANNEL_BUFFER_SIZE);
@@ -505,7 +776,7 @@ mod tests {
             let broker_request_clone = broker_request.clone();
             let handle: JoinHandle<Result<Value, String>> = tokio::spawn(async move {
                 let response_result =
-                    send_http_request(&client_clone, &uri_clone, broker_request).await;
+                    send_http_request(&client_clone, &uri_clone, broker_request, None).await;
                 match response_result {
                     Ok(response) => {
                         if response.status() != StatusCode::OK {
@@ -572,7 +843,7 @@ mod tests {
         };
 
         let base_uri: Uri = "http://localhost:1234/".parse().unwrap();
-        let result = send_http_request(&client, &base_uri, broker_request).await;
+        let result = send_http_request(&client, &base_uri, broker_request, None).await;
         assert!(matches!(result, Err(RippleError::BrokerError(_))));
     }
 
@@ -602,7 +873,7 @@ mod tests {
         };
 
         let client = Client::new();
-        let response = send_http_request(&client, &base_uri, broker_request)
+        let response = send_http_request(&client, &base_uri, broker_request, None)
             .await
             .unwrap();
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
@@ -641,7 +912,7 @@ mod tests {
         };
 
         let client = Client::new();
-        let response = send_http_request(&client, &base_uri, broker_request)
+        let response = send_http_request(&client, &base_uri, broker_request, None)
             .await
             .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
@@ -650,4 +921,302 @@ mod tests {
         let body_json: Value = serde_json::from_slice(&body_bytes).unwrap();
         assert_eq!(body_json, json!({"ok": true}));
     }
+
+    fn get_http_broker_request(params: Value, http: HttpDescriptor) -> BrokerRequest {
+        let mut rpc = RpcRequest::mock();
+        rpc.params_json = RpcRequest::prepend_ctx(Some(params), &rpc.ctx);
+        BrokerRequest {
+            rpc,
+            rule: Rule {
+                alias: "unused".to_string(),
+                http: Some(http),
+                ..Default::default()
+            },
+            subscription_processed: None,
+            workflow_callback: None,
+            telemetry_response_listeners: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_templates() {
+        let mock_server = MockServer::start();
+        let mock = mock_server.mock(|when, then| {
+            when.method(PUT)
+                .path("/devices/dev1/settings")
+                .query_param("lang", "en US")
+                .query_param("limit", "5")
+                .header("x-app-id", "some_app_id")
+                .header("authorization", "Bearer token1");
+            then.status(204);
+        });
+
+        let broker_request = get_http_broker_request(
+            json!({"lang": "en US", "limit": 5}),
+            HttpDescriptor {
+                method: Some(HttpMethod::Put),
+                path: Some("\"devices/\\(.session.deviceId)/settings\"".to_owned()),
+                query: Some("{lang: .params.lang, limit: .params.limit}".to_owned()),
+                headers: Some("{\"x-app-id\": .context.appId}".to_owned()),
+                bearer_auth: true,
+                ..Default::default()
+            },
+        );
+        let session = AccountSession {
+            id: "id1".to_owned(),
+            token: "token1".to_owned(),
+            account_id: "acc1".to_owned(),
+            device_id: "dev1".to_owned(),
+        };
+
+        let base_uri = mock_server.base_url().parse::<Uri>().unwrap();
+        let response = send_http_request(&Client::new(), &base_uri, broker_request, Some(session))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_hostile_path_param() {
+        let mock_server = MockServer::start();
+        let mock = mock_server.mock(|when, then| {
+            when.method(GET)
+                .path("/devices/a%2Fadmin%3Fx%3D1%23%40evil");
+            then.status(200);
+        });
+        let base_uri = mock_server.base_url().parse::<Uri>().unwrap();
+        let request = |id: &str| {
+            get_http_broker_request(
+                json!({ "id": id }),
+                HttpDescriptor {
+                    path: Some("\"devices/\\(.params.id)\"".to_owned()),
+                    ..Default::default()
+                },
+            )
+        };
+
+        // the param stays a single segment of the templated path
+        let response = send_http_request(
+            &Client::new(),
+            &base_uri,
+            request("a/admin?x=1#@evil"),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        mock.assert();
+
+        // nor can it climb up the path
+        for id in ["..", "../admin", "a/../../admin"] {
+            let result = send_http_request(&Client::new(), &base_uri, request(id), None).await;
+            assert!(matches!(result, Err(RippleError::InvalidInput)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_bearer_auth_without_session() {
+        let mock_server = MockServer::start();
+        let mock = mock_server.mock(|when, then| {
+            when.method(GET).path("/unused");
+            then.status(200);
+        });
+        let base_uri = mock_server.base_url().parse::<Uri>().unwrap();
+        let http = HttpDescriptor {
+            bearer_auth: true,
+            ..Default::default()
+        };
+        let result = send_http_request(
+            &Client::new(),
+            &base_uri,
+            get_http_broker_request(json!({}), http.clone()),
+            None,
+        )
+        .await;
+        assert!(matches!(result, Err(RippleError::ApiAuthenticationFailed)));
+
+        // the broker answers with an error without calling the service
+        let endpoint = RuleEndpoint {
+            url: base_uri.to_string(),
+            protocol: RuleEndpointProtocol::Http,
+            jsonrpc: false,
+            tls: None,
+        };
+        let (tx, _) = mpsc::channel(BROKER_CHANNEL_BUFFER_SIZE);
+        let (btx, mut brx) = mpsc::channel::<BrokerOutput>(BROKER_CHANNEL_BUFFER_SIZE);
+        let callback = BrokerCallback { sender: btx };
+        let broker = HttpBroker::get_broker(
+            None,
+            BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx),
+            callback,
+            &mut EndpointBrokerState::default(),
+        );
+        let mut request = get_http_broker_request(json!({}), http);
+        request.rpc.ctx.call_id = 12;
+        broker.get_sender().sender.send(request).await.unwrap();
+        let output = timeout(Duration::from_secs(5), brx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.data.id, Some(12));
+        assert!(output.data.error.is_some());
+        mock.assert_hits(0);
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_traceparent() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mock_server = MockServer::start();
+        let mock = mock_server.mock(|when, then| {
+            when.method(GET)
+                .path("/unused")
+                .header("traceparent", traceparent);
+            then.status(200);
+        });
+
+        let mut broker_request = get_http_broker_request(json!({}), HttpDescriptor::default());
+        broker_request.rpc.ctx.traceparent = Some(traceparent.to_owned());
+        let base_uri = mock_server.base_url().parse::<Uri>().unwrap();
+        let response = send_http_request(&Client::new(), &base_uri, broker_request, None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_invalid_header_template() {
+        let broker_request = get_http_broker_request(
+            json!({}),
+            HttpDescriptor {
+                headers: Some("\"not an object\"".to_owned()),
+                ..Default::default()
+            },
+        );
+        let base_uri: Uri = "http://localhost:1234/".parse().unwrap();
+        let result = send_http_request(&Client::new(), &base_uri, broker_request, None).await;
+        assert!(matches!(result, Err(RippleError::RuleError)));
+    }
+
+    #[test]
+    fn test_response_with_metadata() {
+        let (mut parts, _) = Response::new(()).into_parts();
+        parts.status = StatusCode::CREATED;
+        parts
+            .headers
+            .insert("etag", hyper::header::HeaderValue::from_static("abc"));
+
+        let body = response_with_metadata(&parts, b"{\"id\": 1}");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({"status": 201, "headers": {"etag": "abc"}, "body": {"id": 1}})
+        );
+
+        let body = response_with_metadata(&parts, b"plain");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["body"], json!("plain"));
+    }
+
+    async fn start_https_server(name: &str) -> (u16, String) {
+        use ripple_sdk::tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+        use ripple_sdk::tokio_rustls::{
+            rustls::{Certificate, PrivateKey, ServerConfig},
+            TlsAcceptor,
+        };
+
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_owned()]).unwrap();
+        let pem = cert.serialize_pem().unwrap();
+        let ca_path = std::env::temp_dir().join(format!(
+            "ripple_http_broker_{}_{}.pem",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&ca_path, pem).unwrap();
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(cert.serialize_der().unwrap())],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(std::sync::Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(mut tls) = acceptor.accept(stream).await {
+                    let mut buf = vec![0u8; 1024];
+                    let _ = tls.read(&mut buf).await;
+                    let body = r#"{"secure": true}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = tls.write_all(response.as_bytes()).await;
+                    let _ = tls.shutdown().await;
+                }
+            }
+        });
+
+        (port, ca_path.to_str().unwrap().to_owned())
+    }
+
+    #[tokio::test]
+    async fn test_send_http_request_https_self_signed() {
+        let (port, ca_bundle) = start_https_server("ripple.test").await;
+        let endpoint = RuleEndpoint {
+            protocol: RuleEndpointProtocol::Http,
+            url: format!("https://127.0.0.1:{}", port),
+            jsonrpc: false,
+            tls: Some(TlsConfig {
+                ca_bundle: Some(ca_bundle),
+                server_name: Some("ripple.test".to_owned()),
+                ..Default::default()
+            }),
+        };
+
+        let client = build_client(&endpoint).unwrap();
+        let base_uri = endpoint.get_url().parse::<Uri>().unwrap();
+        let response =
+            send_http_request(&client, &base_uri, get_mock_broker_request("secure"), None)
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({"secure": true})
+        );
+
+        // without the CA bundle the self signed certificate is rejected
+        let client = build_client(&RuleEndpoint {
+            tls: None,
+            ..endpoint
+        })
+        .unwrap();
+        let result =
+            send_http_request(&client, &base_uri, get_mock_broker_request("secure"), None).await;
+        assert!(matches!(result, Err(RippleError::BrokerError(_))));
+    }
+
+    #[test]
+    fn test_build_client_invalid_tls() {
+        let endpoint = RuleEndpoint {
+            url: "https://127.0.0.1:1234".to_owned(),
+            tls: Some(TlsConfig {
+                ca_bundle: Some("/does/not/exist.pem".to_owned()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(build_client(&endpoint).is_err());
+    }
 }
@@ -17,3 +17,4 @@
 
 pub mod rules_engine;
 pub mod rules_functions;
+pub mod rules_watcher;
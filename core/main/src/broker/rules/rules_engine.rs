@@ -23,7 +23,7 @@ use ripple_sdk::{
     chrono::Utc,
     log::{debug, error, info, trace, warn},
     serde_json::Value,
-    utils::error::RippleError,
+    utils::{error::RippleError, tls_utils::TlsConfig},
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -67,12 +67,15 @@ impl RuleSet {
         self.rules.get(key)
     }
 }
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct RuleEndpoint {
     pub protocol: RuleEndpointProtocol,
     pub url: String,
     #[serde(default = "default_autostart")]
     pub jsonrpc: bool,
+    // required for https and wss endpoints using a private CA, client certificates or SNI override
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 impl RuleEndpoint {
@@ -92,9 +95,8 @@ fn default_autostart() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug, Clone, Default)]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
-#[cfg_attr(test, derive(PartialEq))]
 pub enum RuleEndpointProtocol {
     #[default]
     Websocket,
@@ -109,7 +111,93 @@ pub struct JsonDataSource {
     // configurable namespace to "stuff" an in individual result payload into
     pub namespace: Option<String>,
     pub method: String,
+    // JSON params, or a jq filter over the outputs of the completed sources keyed by namespace
     pub params: Option<String>,
+    // namespaces of the sources which have to complete before this one is called
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    // jq filter over the completed outputs, the source is skipped when it yields false or null
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    // overrides the workflow failure policy for this source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<WorkflowFailurePolicy>,
+}
+
+impl JsonDataSource {
+    pub fn get_namespace(&self) -> String {
+        make_name_json_safe(self.namespace.as_ref().unwrap_or(&self.method))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowMode {
+    /// All sources without `depends_on` are called at once
+    #[default]
+    Parallel,
+    /// Every source depends on the source declared before it
+    Sequential,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowFailurePolicy {
+    /// The error is composed into the result and sources depending on the failed one are skipped
+    #[default]
+    Continue,
+    /// No further sources are called and the workflow fails with the error of the source
+    Abort,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WorkflowOptions {
+    #[serde(default)]
+    pub mode: WorkflowMode,
+    #[serde(default)]
+    pub on_failure: WorkflowFailurePolicy,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    #[serde(alias = "get")]
+    Get,
+    #[serde(alias = "post")]
+    Post,
+    #[serde(alias = "put")]
+    Put,
+    #[serde(alias = "delete")]
+    Delete,
+    #[serde(alias = "patch")]
+    Patch,
+    #[serde(alias = "head")]
+    Head,
+}
+
+/// Describes how a rule is sent by the http broker. The `path`, `query` and `headers` templates
+/// are jq filters evaluated over `{ params, context, session }` of the request, `path` has to
+/// yield a string and `query` and `headers` an object.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HttpDescriptor {
+    // defaults to POST when the rule has a request transform and GET otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<HttpMethod>,
+    // defaults to the rule alias
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<String>,
+    // adds an Authorization bearer header with the token of the account session, a request
+    // without a session fails
+    #[serde(default)]
+    pub bearer_auth: bool,
+    // response transform receives { status, headers, body } instead of the body
+    #[serde(default)]
+    pub response_metadata: bool,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -133,6 +221,10 @@ pub struct Rule {
     pub endpoint: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sources: Option<Vec<JsonDataSource>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<WorkflowOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpDescriptor>,
 }
 impl std::fmt::Display for Rule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -191,6 +283,65 @@ impl Rule {
         }
         self
     }
+    pub fn with_workflow(&mut self, workflow: WorkflowOptions) -> &mut Self {
+        self.workflow = Some(workflow);
+        self
+    }
+    pub fn with_http(&mut self, http: HttpDescriptor) -> &mut Self {
+        self.http = Some(http);
+        self
+    }
+    /// Resolves the indexes of the sources each source depends on. Fails when a source depends
+    /// on an unknown namespace or when the dependencies contain a cycle.
+    pub fn get_source_dependencies(&self) -> Result<Vec<Vec<usize>>, RippleError> {
+        let sources = self.sources.clone().unwrap_or_default();
+        let mode = self.workflow.clone().unwrap_or_default().mode;
+        let namespaces: Vec<String> = sources.iter().map(|s| s.get_namespace()).collect();
+        let mut dependencies = Vec::with_capacity(sources.len());
+        for (index, source) in sources.iter().enumerate() {
+            let mut depends_on = Vec::new();
+            if mode == WorkflowMode::Sequential && index > 0 {
+                depends_on.push(index - 1);
+            }
+            for namespace in source.depends_on.iter().flatten() {
+                match namespaces
+                    .iter()
+                    .position(|n| n == &make_name_json_safe(namespace))
+                {
+                    Some(dep) if dep != index => {
+                        if !depends_on.contains(&dep) {
+                            depends_on.push(dep);
+                        }
+                    }
+                    _ => {
+                        error!(
+                            "rule={} source={} depends on unknown source {}",
+                            self.alias, namespaces[index], namespace
+                        );
+                        return Err(RippleError::RuleError);
+                    }
+                }
+            }
+            dependencies.push(depends_on);
+        }
+
+        // every source has to be reachable by resolving dependencies, otherwise there is a cycle
+        let mut resolved = vec![false; sources.len()];
+        loop {
+            let ready: Vec<usize> = (0..sources.len())
+                .filter(|i| !resolved[*i] && dependencies[*i].iter().all(|d| resolved[*d]))
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            ready.into_iter().for_each(|i| resolved[i] = true);
+        }
+        if resolved.contains(&false) {
+            error!("rule={} has cyclic source dependencies", self.alias);
+            return Err(RippleError::RuleError);
+        }
+        Ok(dependencies)
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -344,35 +495,138 @@ impl RuleEngine {
         engine
     }
 
+    /// Strict variant of [RuleEngine::build] used for reloading rules at runtime.
+    /// Unlike `build` it does not skip bad files, any unreadable or invalid rule or import file
+    /// fails the whole load and the resulting engine is validated before it is returned. This
+    /// allows the caller to keep the currently active rules when an edit is broken.
+    pub fn try_build(extn_manifest: &ExtnManifest) -> Result<Self, RippleError> {
+        let mut engine = RuleEngine::default();
+        for path in extn_manifest.rules_path.iter() {
+            let path_for_rule = Self::build_path(path, &extn_manifest.default_path);
+            debug!("reloading rules file {}", path_for_rule);
+            let contents = fs::read_to_string(&path_for_rule).map_err(|e| {
+                error!("try_build: Invalid path: path={}, e={:?}", path_for_rule, e);
+                RippleError::InvalidInput
+            })?;
+            let (_, rule_set) = Self::load_from_content(contents)?;
+            for import in rule_set.imports.iter() {
+                let path_to_import = Self::build_path(import, &extn_manifest.default_path);
+                engine
+                    .functions
+                    .extend(Self::read_import(&path_to_import)?.functions);
+            }
+            engine.rules.append(rule_set);
+        }
+        engine.validate()?;
+        Ok(engine)
+    }
+
+    /// Paths of the rule files and their imports which back this engine.
+    pub fn source_paths(&self, extn_manifest: &ExtnManifest) -> Vec<String> {
+        extn_manifest
+            .rules_path
+            .iter()
+            .chain(self.rules.imports.iter())
+            .map(|path| Self::build_path(path, &extn_manifest.default_path))
+            .collect()
+    }
+
+    fn read_import(path_to_import: &str) -> Result<RulesImport, RippleError> {
+        let import_contents = fs::read_to_string(path_to_import).map_err(|e| {
+            error!(
+                "load_imports: Invalid path: path_to_import={}, e={:?}",
+                path_to_import, e
+            );
+            RippleError::InvalidInput
+        })?;
+        serde_json::from_str::<RulesImport>(&import_contents).map_err(|e| {
+            error!(
+                "load_imports: Invalid import: path_to_import={}, e={:?}",
+                path_to_import, e
+            );
+            RippleError::InvalidInput
+        })
+    }
+
     fn load_imports(&mut self, imports: &Vec<String>, default_path: &str) {
         for import in imports {
             let path_to_import = Self::build_path(import, default_path);
-            match fs::read_to_string(&path_to_import) {
-                Ok(import_contents) => {
-                    match serde_json::from_str::<RulesImport>(&import_contents) {
-                        Ok(import) => {
-                            for (function_name, function) in import.functions {
-                                // Last loaded import file will overwrite any pre-exsting functions to allow overriding.
-                                self.functions
-                                    .insert(function_name.clone(), function.clone());
-                            }
-                        }
-                        Err(e) => {
-                            error!(
-                                "load_imports: Invalid import: path_to_import={}, e={:?}",
-                                path_to_import, e
-                            )
-                        }
-                    }
+            if let Ok(import) = Self::read_import(&path_to_import) {
+                // Last loaded import file will overwrite any pre-exsting functions to allow overriding.
+                self.functions.extend(import.functions);
+            }
+        }
+    }
+
+    fn validate_filter(&self, rule_name: &str, filter: &str) -> Result<(), RippleError> {
+        let filter = apply_functions(filter, &self.functions).inspect_err(|_| {
+            error!("validate: rule={} has unresolved functions", rule_name);
+        })?;
+        let (_, errs) = jaq_parse::parse(&filter, jaq_parse::main());
+        if !errs.is_empty() {
+            error!("validate: rule={} invalid filter: {:?}", rule_name, errs);
+            return Err(RippleError::RuleError);
+        }
+        Ok(())
+    }
+
+    /// Checks that every jq filter in the loaded rules resolves its imported functions and parses.
+    /// Endpoint references are also checked against the endpoints declared in the rules, and
+    /// the tls settings of the endpoints against the certificate files they refer to.
+    pub fn validate(&self) -> Result<(), RippleError> {
+        for (endpoint_name, endpoint) in self.rules.endpoints.iter() {
+            if let Some(tls) = &endpoint.tls {
+                tls.client_config().inspect_err(|e| {
+                    error!(
+                        "validate: endpoint={} has invalid tls settings: {:?}",
+                        endpoint_name, e
+                    );
+                })?;
+            }
+        }
+        for (rule_name, rule) in self.rules.rules.iter() {
+            let transform = &rule.transform;
+            let filters = [
+                &transform.request,
+                &transform.response,
+                &transform.event,
+                &transform.rpcv2_event,
+                &rule.filter,
+            ];
+            for filter in filters.into_iter().flatten() {
+                self.validate_filter(rule_name, filter)?;
+            }
+            if let Some(http) = &rule.http {
+                for filter in [&http.path, &http.query, &http.headers]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.validate_filter(rule_name, filter)?;
+                }
+            }
+            if let Some(params) = rule.event_handler.as_ref().and_then(|e| e.params.as_ref()) {
+                self.validate_filter(rule_name, params)?;
+            }
+            for source in rule.sources.iter().flatten() {
+                if let Some(params) = &source.params {
+                    self.validate_filter(rule_name, params)?;
+                }
+                if let Some(condition) = &source.condition {
+                    self.validate_filter(rule_name, condition)?;
                 }
-                Err(e) => {
+            }
+            rule.get_source_dependencies()?;
+            if let Some(endpoint) = &rule.endpoint {
+                if !self.rules.endpoints.contains_key(endpoint) {
                     error!(
-                        "load_imports: Invalid path: path_to_import={}, e={:?}",
-                        path_to_import, e
+                        "validate: rule={} references unknown endpoint={}",
+                        rule_name, endpoint
                     );
+                    return Err(RippleError::RuleError);
                 }
             }
         }
+        Ok(())
     }
 
     pub fn load_from_content(contents: String) -> Result<(String, RuleSet), RippleError> {
@@ -764,6 +1018,122 @@ mod tests {
         }
     }
 
+    fn write_rules_file(name: &str, contents: &str) -> ExtnManifest {
+        let dir =
+            std::env::temp_dir().join(format!("ripple_rules_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("rules.json"), contents).unwrap();
+        ExtnManifest {
+            default_path: format!("{}/", dir.display()),
+            rules_path: vec!["rules.json".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_try_build_valid_rules() {
+        let manifest = write_rules_file(
+            "valid",
+            r#"{
+                "endpoints": {},
+                "rules": {
+                    "Device.Make": {
+                        "alias": "org.rdk.System.getDeviceInfo",
+                        "transform": { "response": ".result.make" }
+                    }
+                }
+            }"#,
+        );
+        let engine = RuleEngine::try_build(&manifest).unwrap();
+        assert!(engine.has_rule("device.make"));
+    }
+
+    #[test]
+    fn test_try_build_invalid_filter() {
+        let manifest = write_rules_file(
+            "invalid_filter",
+            r#"{
+                "endpoints": {},
+                "rules": {
+                    "device.make": {
+                        "alias": "org.rdk.System.getDeviceInfo",
+                        "transform": { "response": "if .result then" }
+                    }
+                }
+            }"#,
+        );
+        assert_eq!(
+            RuleEngine::try_build(&manifest).err(),
+            Some(RippleError::RuleError)
+        );
+    }
+
+    #[test]
+    fn test_try_build_missing_import_and_endpoint() {
+        let manifest = write_rules_file(
+            "missing_import",
+            r#"{ "imports": ["missing.json"], "endpoints": {}, "rules": {} }"#,
+        );
+        assert!(RuleEngine::try_build(&manifest).is_err());
+
+        let manifest = write_rules_file(
+            "missing_endpoint",
+            r#"{
+                "endpoints": {},
+                "rules": { "device.make": { "alias": "getDeviceInfo", "endpoint": "nope" } }
+            }"#,
+        );
+        assert!(RuleEngine::try_build(&manifest).is_err());
+    }
+
+    #[test]
+    fn test_try_build_invalid_json() {
+        let manifest = write_rules_file("invalid_json", "{ \"rules\": ");
+        assert_eq!(
+            RuleEngine::try_build(&manifest).err(),
+            Some(RippleError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_get_source_dependencies() {
+        let source = |namespace: &str, depends_on: Option<Vec<&str>>| JsonDataSource {
+            method: format!("module.{}", namespace),
+            namespace: Some(namespace.to_string()),
+            depends_on: depends_on.map(|d| d.into_iter().map(String::from).collect()),
+            ..Default::default()
+        };
+        let mut rule = Rule::default();
+        rule.with_sources(vec![
+            source("a", None),
+            source("b", Some(vec!["a"])),
+            source("c", None),
+        ]);
+        assert_eq!(
+            rule.get_source_dependencies().unwrap(),
+            vec![vec![], vec![0], vec![]]
+        );
+
+        rule.with_workflow(WorkflowOptions {
+            mode: WorkflowMode::Sequential,
+            ..Default::default()
+        });
+        assert_eq!(
+            rule.get_source_dependencies().unwrap(),
+            vec![vec![], vec![0], vec![1]]
+        );
+
+        rule.with_workflow(WorkflowOptions::default())
+            .with_sources(vec![source("a", Some(vec!["unknown"]))]);
+        assert!(rule.get_source_dependencies().is_err());
+
+        rule.with_sources(vec![
+            source("a", Some(vec!["b"])),
+            source("b", Some(vec!["a"])),
+        ]);
+        assert!(rule.get_source_dependencies().is_err());
+    }
+
     #[test]
     fn test_get_rule_no_match() {
         let rule_set = RuleSet::default();
@@ -0,0 +1,72 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{collections::HashMap, fs, time::SystemTime};
+
+use ripple_sdk::{
+    log::{error, info},
+    tokio::{
+        self,
+        time::{sleep, Duration},
+    },
+};
+
+use crate::state::platform_state::PlatformState;
+
+/// Polls the rule files and their imports for modifications and reloads the rules in the
+/// [crate::broker::endpoint_broker::EndpointBrokerState] when any of them changes.
+pub struct RulesWatcher;
+
+impl RulesWatcher {
+    fn snapshot(paths: Vec<String>) -> HashMap<String, Option<SystemTime>> {
+        paths
+            .into_iter()
+            .map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+
+    fn current_snapshot(ps: &PlatformState) -> HashMap<String, Option<SystemTime>> {
+        Self::snapshot(ps.endpoint_state.get_rule_source_paths(&ps.extn_manifest))
+    }
+
+    pub fn start(ps: PlatformState) {
+        let interval = match ps.extn_manifest.rules_reload_interval {
+            Some(interval) if interval > 0 => interval,
+            _ => return,
+        };
+        info!("Watching rules for changes every {}s", interval);
+        tokio::spawn(async move {
+            let mut last = Self::current_snapshot(&ps);
+            loop {
+                sleep(Duration::from_secs(interval)).await;
+                let current = Self::current_snapshot(&ps);
+                if current == last {
+                    continue;
+                }
+                match ps.endpoint_state.reload_rules(ps.clone()) {
+                    Ok(count) => info!("Rules changed on disk, reloaded {} rules", count),
+                    Err(e) => error!("Rules changed on disk but failed to reload {:?}", e),
+                }
+                // Snapshot again as a reload can add or remove imports
+                last = Self::current_snapshot(&ps);
+            }
+        });
+    }
+}
@@ -23,12 +23,15 @@ use super::{
     thunder::thunder_plugins_status_mgr::StatusManager,
     thunder::user_data_migrator::UserDataMigrator,
 };
-use crate::state::platform_state::PlatformState;
+use crate::{service::session_recorder::SessionRecorder, state::platform_state::PlatformState};
 use futures_util::{SinkExt, StreamExt};
 use ripple_sdk::{
     api::{
         gateway::rpc_gateway_api::{JsonRpcApiResponse, RpcRequest},
-        observability::log_signal::LogSignal,
+        observability::{
+            log_signal::LogSignal,
+            session_recording::{RecordChannel, RecordDirection},
+        },
     },
     log::{debug, error, info, trace},
     tokio::{
@@ -270,6 +273,7 @@ impl ThunderBroker {
 
                                 if let Message::Text(t) = v {
                                     debug!("Broker Websocket message {:?}", t);
+                                    SessionRecorder::record(RecordChannel::Thunder, RecordDirection::Inbound, &t);
 
                                     if broker_c.status_manager.is_controller_response(broker_c.get_sender(), broker_c.get_default_callback(), t.as_bytes()).await {
                                         broker_c.status_manager.handle_controller_response(broker_c.get_sender(), broker_c.get_default_callback(), t.as_bytes()).await;
@@ -303,6 +307,7 @@ impl ThunderBroker {
 
                                     let mut ws_tx = ws_tx_wrap.lock().await;
                                     for r in requests {
+                                        SessionRecorder::record(RecordChannel::Thunder, RecordDirection::Outbound, &r);
                                         let _feed = ws_tx.feed(Message::Text(r)).await;
                                         let _flush = ws_tx.flush().await;
                                     }
@@ -341,6 +346,7 @@ impl ThunderBroker {
                                                 let binding = ws_tx_wrap.clone();
                                                 let mut ws_tx = binding.lock().await;
                                                 for r in updated_request {
+                                                    SessionRecorder::record(RecordChannel::Thunder, RecordDirection::Outbound, &r);
                                                     let _ = ws_tx.feed(Message::Text(r)).await;
 
                                                     let _ = ws_tx.flush().await;
@@ -724,6 +730,7 @@ mod tests {
                 protocol: RuleEndpointProtocol::Thunder,
                 url: $server_handle.get_address(),
                 jsonrpc: true,
+                tls: None,
             };
             let (reconnect_tx, _rec_rx) = mpsc::channel(2);
 
@@ -814,6 +821,7 @@ mod tests {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
+            tls: None,
         };
         let (tx, _) = mpsc::channel(1);
         let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx);
@@ -840,6 +848,8 @@ mod tests {
                 filter: event_filter,
                 event_handler,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             subscription_processed: None,
             workflow_callback: None,
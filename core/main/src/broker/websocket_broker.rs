@@ -27,7 +27,10 @@ use ripple_sdk::{
     api::observability::log_signal::LogSignal,
     log::{debug, error},
     tokio::{self, sync::mpsc},
-    utils::ws_utils::{WebSocketConfigBuilder, WebSocketUtils},
+    utils::{
+        tls_utils::TlsConfig,
+        ws_utils::{WebSocketConfigBuilder, WebSocketUtils},
+    },
 };
 use std::{
     collections::HashMap,
@@ -48,11 +51,18 @@ impl WebsocketBroker {
         let map_clone = non_json_rpc_map.clone();
         let broker = BrokerSender { sender: tx };
         tokio::spawn(async move {
+            let tls = endpoint.tls.clone();
             if endpoint.jsonrpc {
-                let resp = WebSocketUtils::get_ws_stream(&endpoint.get_url(), None).await;
+                let resp =
+                    WebSocketUtils::get_ws_stream_with_tls(&endpoint.get_url(), None, tls.as_ref())
+                        .await;
                 if resp.is_err() {
                     error!("Error connecting to websocket broker");
-                    tr.close();
+                    Self::reject_queued_requests(
+                        &mut tr,
+                        &callback,
+                        "websocket broker could not connect to its endpoint",
+                    );
                     return false;
                 }
                 let (mut ws_tx, mut ws_rx) = resp.unwrap();
@@ -82,7 +92,11 @@ impl WebsocketBroker {
                             }
 
                         },
-                        Some(request) = tr.recv() => {
+                        request = tr.recv() => {
+                            // the endpoint was removed or restarted
+                            let Some(request) = request else {
+                                break true
+                            };
                             LogSignal::new(
                                 "websocket_broker".to_string(),
                                 format!("Got request from receiver for broker: {:?}", request),
@@ -127,6 +141,7 @@ impl WebsocketBroker {
                         v.clone(),
                         callback.clone(),
                         endpoint.get_url().clone(),
+                        tls.clone(),
                     );
                     {
                         let mut map = map_clone.write().unwrap();
@@ -135,6 +150,8 @@ impl WebsocketBroker {
                         let _ = map.insert(id, sender_list);
                     }
                 }
+                // closes the notification connections once the endpoint is removed or restarted
+                map_clone.write().unwrap().clear();
 
                 true
             }
@@ -156,6 +173,7 @@ impl WSNotificationBroker {
         request_c: BrokerRequest,
         callback_c: BrokerCallback,
         url: String,
+        tls: Option<TlsConfig>,
     ) -> mpsc::Sender<String> {
         let (tx, mut tr) = mpsc::channel::<String>(1);
         tokio::spawn(async move {
@@ -170,7 +188,8 @@ impl WSNotificationBroker {
                 .fail_after(1)
                 .build();
 
-            let resp = WebSocketUtils::get_ws_stream(&url, Some(config)).await;
+            let resp =
+                WebSocketUtils::get_ws_stream_with_tls(&url, Some(config), tls.as_ref()).await;
             if resp.is_err() {
                 error!("Error connecting to websocket broker");
                 tr.close();
@@ -209,7 +228,12 @@ impl WSNotificationBroker {
                         }
 
                     },
-                    Some(request) = tr.recv() => {
+                    request = tr.recv() => {
+                        let Some(request) = request else {
+                            let _feed = ws_tx.feed(Message::Close(None)).await;
+                            let _flush = ws_tx.flush().await;
+                            break;
+                        };
                         debug!("Recieved cleaner request for {}", request);
                         if request.eq(&app_id) {
                             let _feed = ws_tx.feed(Message::Close(None)).await;
@@ -278,6 +302,7 @@ mod tests {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
+            tls: None,
         };
         let (tx, _) = mpsc::channel(1);
         let request = BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx);
@@ -304,6 +329,8 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             workflow_callback: None,
             subscription_processed: None,
@@ -351,6 +378,8 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             workflow_callback: None,
             subscription_processed: None,
@@ -382,6 +411,8 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             workflow_callback: None,
             subscription_processed: None,
@@ -415,6 +446,7 @@ mod tests {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
+            tls: None,
         };
 
         let request = BrokerRequest {
@@ -426,12 +458,14 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             workflow_callback: None,
             subscription_processed: None,
             telemetry_response_listeners: vec![],
         };
-        WSNotificationBroker::start(request, callback, endpoint.get_url().clone())
+        WSNotificationBroker::start(request, callback, endpoint.get_url().clone(), None)
     }
 
     #[tokio::test]
@@ -505,6 +539,8 @@ mod tests {
                 filter: None,
                 event_handler: None,
                 sources: None,
+                workflow: None,
+                http: None,
             },
             workflow_callback: None,
             subscription_processed: None,
@@ -515,8 +551,45 @@ mod tests {
             url: format!("ws://127.0.0.1:{}", port),
             protocol: crate::broker::rules::rules_engine::RuleEndpointProtocol::Websocket,
             jsonrpc: false,
+            tls: None,
         };
-        let _ = WSNotificationBroker::start(request, callback, endpoint.get_url().clone());
+        let _ = WSNotificationBroker::start(request, callback, endpoint.get_url().clone(), None);
         assert!(rec.recv().await.is_none());
     }
+
+    #[tokio::test]
+    async fn connect_failure_rejects_queued_requests() {
+        let (sender, mut rec) = mpsc::channel(1);
+        let endpoint = RuleEndpoint {
+            url: "wss://127.0.0.1:1".to_owned(),
+            protocol: crate::broker::rules::rules_engine::RuleEndpointProtocol::Websocket,
+            jsonrpc: true,
+            tls: Some(TlsConfig {
+                ca_bundle: Some("/nonexistent/ca.pem".to_owned()),
+                ..Default::default()
+            }),
+        };
+        let (tx, _) = mpsc::channel(1);
+        let broker = WebsocketBroker::start(
+            BrokerConnectRequest::new("somekey".to_owned(), endpoint, tx),
+            BrokerCallback { sender },
+        );
+        let mut rpc = RpcRequest::get_new_internal("some_method".to_owned(), None);
+        rpc.ctx.call_id = 7;
+        let request = BrokerRequest {
+            rpc,
+            rule: Rule::default(),
+            workflow_callback: None,
+            subscription_processed: None,
+            telemetry_response_listeners: vec![],
+        };
+        broker.sender.send(request).await.unwrap();
+
+        let output = tokio::time::timeout(Duration::from_secs(2), rec.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.data.id, Some(7));
+        assert!(output.data.error.is_some());
+    }
 }
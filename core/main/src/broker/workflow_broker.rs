@@ -2,9 +2,9 @@ use super::endpoint_broker::{
     BrokerCallback, BrokerCleaner, BrokerConnectRequest, BrokerRequest, BrokerSender,
     EndpointBroker, HandleBrokerageError, BROKER_CHANNEL_BUFFER_SIZE,
 };
-use super::rules::rules_engine::JsonDataSource;
+use super::rules::rules_engine::{JsonDataSource, WorkflowFailurePolicy};
 use crate::broker::endpoint_broker::{BrokerOutput, EndpointBrokerState};
-use crate::broker::rules::rules_engine::{compose_json_values, make_name_json_safe};
+use crate::broker::rules::rules_engine::{compose_json_values, jq_compile};
 use crate::state::platform_state::PlatformState;
 use futures::future::{join_all, BoxFuture};
 use futures::FutureExt;
@@ -47,8 +47,6 @@ impl From<HandleBrokerageError> for SubBrokerErr {
     }
 }
 
-//TODO: decide fate of this function
-#[allow(dead_code)]
 async fn subbroker_call(
     endpoint_broker: EndpointBrokerState,
     rpc_request: RpcRequest,
@@ -73,12 +71,7 @@ async fn subbroker_call(
                     msg.get_error_string(),
                 )))
             } else {
-                Ok(json!({make_name_json_safe(
-                    &source
-                        .clone()
-                        .namespace
-                        .unwrap_or(source.method.to_string()),
-                ): msg.data.result.unwrap_or(json!({}))}))
+                Ok(json!({source.get_namespace(): msg.data.result.unwrap_or(json!({}))}))
             }
         }
         None => {
@@ -90,83 +83,136 @@ async fn subbroker_call(
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SourceState {
+    Pending,
+    Completed,
+    Skipped,
+    Failed,
+}
+
 impl WorkflowBroker {
-    pub fn create_the_futures(
-        sources: Vec<JsonDataSource>,
-        rpc_request: RpcRequest,
-        endpoint_broker: EndpointBrokerState,
-    ) -> Vec<BoxFuture<'static, Result<serde_json::Value, SubBrokerErr>>> {
-        let mut futures = vec![];
-
-        for source in sources.clone() {
-            trace!("Source {:?}", source.clone());
-            LogSignal::new(
-                "workflow_broker".into(),
-                "Workflow task".into(),
-                rpc_request.ctx.clone(),
-            )
-            .with_diagnostic_context_item("source", &format!("{:?}", source))
-            .with_diagnostic_context_item("method", &format!("{:?}", source.method))
-            .with_diagnostic_context_item(
-                "params",
-                &format!("{:?}", source.params.clone().unwrap_or_default()),
-            )
-            .emit_debug();
+    /// Builds the request for a single workflow source. Plain JSON `params` are appended to the
+    /// params of the original request as before, anything else is evaluated as a jq filter over
+    /// the outputs of the sources which already completed, keyed by their namespace.
+    fn create_source_request(
+        source: &JsonDataSource,
+        rpc_request: &RpcRequest,
+        outputs: &serde_json::Value,
+    ) -> Result<RpcRequest, SubBrokerErr> {
+        trace!("Source {:?}", source.clone());
+        LogSignal::new(
+            "workflow_broker".into(),
+            "Workflow task".into(),
+            rpc_request.ctx.clone(),
+        )
+        .with_diagnostic_context_item("source", &format!("{:?}", source))
+        .with_diagnostic_context_item("method", &format!("{:?}", source.method))
+        .with_diagnostic_context_item(
+            "params",
+            &format!("{:?}", source.params.clone().unwrap_or_default()),
+        )
+        .emit_debug();
 
-            let mut rpc_request = rpc_request.clone();
-            rpc_request.method = source.method.clone();
+        let mut rpc_request = rpc_request.clone();
+        rpc_request.method = source.method.clone();
+
+        // Deserialize the existing params_json
+        let mut existing_params =
+            serde_json::from_str::<serde_json::Value>(&rpc_request.params_json).map_err(|e| {
+                error!("Failed to parse existing params_json: {:?}", e);
+                SubBrokerErr::RpcError(RippleError::ParseError)
+            })?;
+
+        // Handle new params from the rule source
+        if let Some(ref params) = source.params {
+            let new_params = match serde_json::from_str::<serde_json::Value>(params) {
+                Ok(new_params) => new_params,
+                Err(_) => jq_compile(
+                    outputs.clone(),
+                    params,
+                    format!("{}_params", source.get_namespace()),
+                )
+                .map_err(SubBrokerErr::RpcError)?,
+            };
+            // Merge the new params with existing params
+            if let Some(existing_array) = existing_params.as_array_mut() {
+                existing_array.push(new_params);
+            } else {
+                error!(
+                    "Existing params_json is not an array: {:?}",
+                    existing_params
+                );
+            }
+        }
 
-            // Deserialize the existing params_json
-            let mut existing_params =
-                match serde_json::from_str::<serde_json::Value>(&rpc_request.params_json) {
-                    Ok(params) => params,
-                    Err(e) => {
-                        error!("Failed to parse existing params_json: {:?}", e);
-                        continue;
-                    }
-                };
+        // Serialize the merged parameters back into params_json
+        rpc_request.params_json = serde_json::to_string(&existing_params).unwrap();
+        Ok(rpc_request)
+    }
 
-            // Handle new params from the rule source
-            if let Some(ref params) = source.params {
-                // Ensure params is valid JSON
-                match serde_json::from_str::<serde_json::Value>(params) {
-                    Ok(new_params) => {
-                        // Merge the new params with existing params
-                        if let Some(existing_array) = existing_params.as_array_mut() {
-                            existing_array.push(new_params);
-                        } else {
-                            error!(
-                                "Existing params_json is not an array: {:?}",
-                                existing_params
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        error!("Invalid params JSON string: {:?}", e);
-                        continue;
-                    }
-                }
-            }
+    fn check_condition(
+        source: &JsonDataSource,
+        outputs: &serde_json::Value,
+    ) -> Result<bool, SubBrokerErr> {
+        match &source.condition {
+            Some(condition) => jq_compile(
+                outputs.clone(),
+                condition,
+                format!("{}_condition", source.get_namespace()),
+            )
+            .map(|v| !matches!(v, serde_json::Value::Null | serde_json::Value::Bool(false)))
+            .map_err(SubBrokerErr::RpcError),
+            None => Ok(true),
+        }
+    }
 
-            // Serialize the merged parameters back into params_json
-            rpc_request.params_json = serde_json::to_string(&existing_params).unwrap();
-            let t = subbroker_call(endpoint_broker.clone(), rpc_request, source).boxed(); // source is still usable here
-            futures.push(t);
+    fn handle_source_failure(
+        broker_request: &BrokerRequest,
+        source: &JsonDataSource,
+        error: SubBrokerErr,
+        results: &mut Vec<serde_json::Value>,
+    ) -> Result<(), SubBrokerErr> {
+        error!(
+            "Error {:?} in subbroker call for workflow: {} id: {} source: {}",
+            error,
+            broker_request.rpc.method,
+            broker_request.rpc.ctx.call_id,
+            source.get_namespace()
+        );
+        let policy = source.on_failure.unwrap_or(
+            broker_request
+                .rule
+                .workflow
+                .clone()
+                .unwrap_or_default()
+                .on_failure,
+        );
+        match policy {
+            WorkflowFailurePolicy::Abort => Err(SubBrokerErr::RpcError(RippleError::BrokerError(
+                format!("source {} failed: {:?}", source.get_namespace(), error),
+            ))),
+            WorkflowFailurePolicy::Continue => {
+                results.push(json!({"error": format!("{:?}", error)}));
+                Ok(())
+            }
         }
-        futures
     }
 
     pub async fn run_workflow(
         broker_request: &BrokerRequest,
         endpoint_broker: EndpointBrokerState,
     ) -> SubBrokerResult {
-        let mut futures = Self::create_the_futures(
-            broker_request.rule.sources.clone().unwrap_or_default(),
-            broker_request.rpc.clone(),
-            endpoint_broker.clone(),
-        );
+        let sources = broker_request.rule.sources.clone().unwrap_or_default();
+        let dependencies = broker_request
+            .rule
+            .get_source_dependencies()
+            .map_err(SubBrokerErr::RpcError)?;
         /*
-        workflow steps are currently all or nothing/sudden death: if one step fails, the whole workflow fails
+        sources are called in waves: every wave holds the sources whose dependencies are resolved,
+        without any dependencies this is a single wave of parallel calls.
+        A failing source either composes its error into the result or aborts the whole workflow
+        depending on the failure policy, sources depending on a failed or skipped source are skipped.
         */
         LogSignal::new(
             "workflow_broker".into(),
@@ -177,20 +223,81 @@ impl WorkflowBroker {
 
         // Define your batch size here
         let batch_size = 10;
+        let mut states = vec![SourceState::Pending; sources.len()];
+        let mut outputs = serde_json::Map::new();
         let mut results = vec![];
-        for chunk in futures.chunks_mut(batch_size) {
-            let vec = join_all(chunk.iter_mut().map(|f| f.as_mut()).collect::<Vec<_>>()).await;
-            for res in vec {
-                match res {
-                    Ok(success) => {
-                        results.push(success);
+        loop {
+            let ready: Vec<usize> = (0..sources.len())
+                .filter(|i| {
+                    states[*i] == SourceState::Pending
+                        && dependencies[*i]
+                            .iter()
+                            .all(|d| states[*d] != SourceState::Pending)
+                })
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+
+            let current_outputs = serde_json::Value::Object(outputs.clone());
+            let mut futures: Vec<
+                BoxFuture<'static, (usize, Result<serde_json::Value, SubBrokerErr>)>,
+            > = vec![];
+            for index in ready {
+                let source = &sources[index];
+                if dependencies[index]
+                    .iter()
+                    .any(|d| states[*d] != SourceState::Completed)
+                {
+                    trace!("Skipping source {}, dependency did not complete", index);
+                    states[index] = SourceState::Skipped;
+                    continue;
+                }
+                let request = match Self::check_condition(source, &current_outputs) {
+                    Ok(false) => {
+                        trace!("Skipping source {}, condition not met", index);
+                        states[index] = SourceState::Skipped;
+                        continue;
                     }
+                    Ok(true) => {
+                        Self::create_source_request(source, &broker_request.rpc, &current_outputs)
+                    }
+                    Err(e) => Err(e),
+                };
+                match request {
+                    Ok(rpc_request) => futures.push(
+                        subbroker_call(endpoint_broker.clone(), rpc_request, source.clone())
+                            .map(move |res| (index, res))
+                            .boxed(),
+                    ),
                     Err(e) => {
-                        error!(
-                            "Error {:?} in subbroker call for workflow: {} id: {}",
-                            e, broker_request.rpc.method, broker_request.rpc.ctx.call_id
-                        );
-                        results.push(json!({"error": format!("{:?}", e)}));
+                        states[index] = SourceState::Failed;
+                        Self::handle_source_failure(broker_request, source, e, &mut results)?;
+                    }
+                }
+            }
+
+            for chunk in futures.chunks_mut(batch_size) {
+                let vec = join_all(chunk.iter_mut().map(|f| f.as_mut()).collect::<Vec<_>>()).await;
+                for (index, res) in vec {
+                    match res {
+                        Ok(success) => {
+                            let namespace = sources[index].get_namespace();
+                            if let Some(output) = success.get(&namespace) {
+                                outputs.insert(namespace, output.clone());
+                            }
+                            states[index] = SourceState::Completed;
+                            results.push(success);
+                        }
+                        Err(e) => {
+                            states[index] = SourceState::Failed;
+                            Self::handle_source_failure(
+                                broker_request,
+                                &sources[index],
+                                e,
+                                &mut results,
+                            )?;
+                        }
                     }
                 }
             }
@@ -314,7 +421,7 @@ pub mod tests {
 
     use crate::broker::{
         endpoint_broker::{BrokerCallback, BrokerRequest, EndpointBrokerState},
-        rules::rules_engine::{JsonDataSource, Rule, RuleEngine},
+        rules::rules_engine::{JsonDataSource, Rule, RuleEngine, WorkflowMode, WorkflowOptions},
     };
     pub fn broker_request(callback: BrokerCallback) -> BrokerRequest {
         let mut rule = Rule {
@@ -388,6 +495,89 @@ pub mod tests {
         assert!(foo.is_ok());
     }
 
+    fn workflow_request(sources: Vec<JsonDataSource>, workflow: WorkflowOptions) -> BrokerRequest {
+        let (tx, _) = tokio::sync::mpsc::channel(32);
+        let mut request = broker_request(BrokerCallback { sender: tx });
+        request.rule.sources = Some(sources);
+        request.rule.workflow = Some(workflow);
+        request
+    }
+
+    fn source(namespace: &str) -> JsonDataSource {
+        JsonDataSource {
+            method: "static.rule".to_string(),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_run_workflow_abort_on_failure() {
+        use super::*;
+        // without a thunder endpoint the static sub rule fails
+        let request = workflow_request(
+            vec![source("first"), source("second")],
+            WorkflowOptions {
+                mode: WorkflowMode::Sequential,
+                on_failure: WorkflowFailurePolicy::Abort,
+            },
+        );
+        let result = WorkflowBroker::run_workflow(&request, endppoint_broker_state()).await;
+        assert!(matches!(result, Err(SubBrokerErr::RpcError(_))));
+    }
+
+    #[tokio::test]
+    pub async fn test_run_workflow_skips_dependents_of_failed_source() {
+        use super::*;
+        let mut second = source("second");
+        second.depends_on = Some(vec!["first".to_string()]);
+        second.on_failure = Some(WorkflowFailurePolicy::Abort);
+        let request = workflow_request(vec![source("first"), second], WorkflowOptions::default());
+        let result = WorkflowBroker::run_workflow(&request, endppoint_broker_state())
+            .await
+            .unwrap();
+        assert!(result.result.unwrap().get("error").is_some());
+    }
+
+    #[tokio::test]
+    pub async fn test_run_workflow_condition_skips_source() {
+        use super::*;
+        let mut first = source("first");
+        first.condition = Some("false".to_string());
+        first.on_failure = Some(WorkflowFailurePolicy::Abort);
+        let request = workflow_request(vec![first], WorkflowOptions::default());
+        let result = WorkflowBroker::run_workflow(&request, endppoint_broker_state()).await;
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn test_create_source_request_params_from_outputs() {
+        use super::*;
+        let mut rpc_request = RpcRequest::mock();
+        rpc_request.params_json = json!([{}]).to_string();
+        let mut source = source("second");
+        source.params = Some("{ \"id\": .first.id }".to_string());
+        let request = WorkflowBroker::create_source_request(
+            &source,
+            &rpc_request,
+            &json!({"first": {"id": "abc"}}),
+        )
+        .unwrap();
+        assert_eq!(request.method, "static.rule");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&request.params_json).unwrap(),
+            json!([{}, {"id": "abc"}])
+        );
+
+        source.params = Some("{ \"id\": 1 }".to_string());
+        let request =
+            WorkflowBroker::create_source_request(&source, &rpc_request, &json!({})).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&request.params_json).unwrap(),
+            json!([{}, {"id": 1}])
+        );
+    }
+
     #[tokio::test]
     pub async fn test_log_error_and_send_broker_failure_response() {
         use super::*;
@@ -15,7 +15,10 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use jsonrpsee::{core::server::rpc_module::Methods, types::TwoPointZero};
+use jsonrpsee::{
+    core::server::rpc_module::Methods,
+    types::{error::INTERNAL_ERROR_CODE, TwoPointZero},
+};
 use ripple_sdk::{
     api::{
         firebolt::{
@@ -28,6 +31,7 @@ use ripple_sdk::{
                 ApiMessage, ApiProtocol, CallContext, JsonRpcApiResponse, RpcRequest,
             },
         },
+        manifest::device_manifest::ResponseValidation,
         observability::{log_signal::LogSignal, metrics_util::ApiStats},
     },
     chrono::Utc,
@@ -42,16 +46,20 @@ use std::collections::HashMap;
 
 use crate::{
     broker::endpoint_broker::BrokerOutput,
-    firebolt::firebolt_gatekeeper::FireboltGatekeeper,
+    firebolt::{firebolt_gatekeeper::FireboltGatekeeper, openrpc_mock::OpenRpcMock},
     service::{
         apps::{app_events::AppEvents, provider_broker::ProviderBroker},
         telemetry_builder::TelemetryBuilder,
+        tracer::Tracer,
     },
     state::{
         bootstrap_state::BootstrapState, openrpc_state::OpenRpcState,
-        platform_state::PlatformState, session_state::Session,
+        ops_metrics_state::UNKNOWN_METHOD, platform_state::PlatformState, session_state::Session,
+    },
+    utils::{
+        router_utils::{capture_stage, get_rpc_header_with_status},
+        rpc_utils::RATE_LIMIT_EXCEEDED_ERROR_CODE,
     },
-    utils::router_utils::{capture_stage, get_rpc_header_with_status},
 };
 
 use super::rpc_router::RpcRouter;
@@ -305,9 +313,14 @@ impl FireboltGateway {
         let mut request_c = request.clone();
         request_c.method = FireboltOpenRpcMethod::name_with_lowercase_module(&request.method);
 
-        platform_state
-            .metrics
-            .add_api_stats(&request_c.ctx.request_id, &request_c.method);
+        platform_state.metrics.add_api_stats(
+            &request_c.ctx.request_id,
+            get_metrics_method(&platform_state, &request_c.method),
+        );
+        // requests of apps are answered through their connection, which ends the span
+        if !extn_request && !service_request {
+            Tracer::start_request_span(&mut request_c.ctx);
+        }
 
         let fail_open = matches!(
             platform_state
@@ -321,6 +334,29 @@ impl FireboltGateway {
 
         tokio::spawn(async move {
             capture_stage(&platform_state.metrics, &request_c, "context_ready");
+            if !extn_request && !service_request {
+                if let Err(scope) = platform_state
+                    .rate_limit_state
+                    .check(&request_c.ctx.app_id, &request_c.method)
+                {
+                    let scope = scope.to_string();
+                    platform_state.metrics.record_rate_limit_hit(&scope);
+                    LogSignal::new(
+                        "firebolt_gateway".into(),
+                        "rate_limit_exceeded".into(),
+                        request_c.clone(),
+                    )
+                    .with_diagnostic_context_item("scope", &scope)
+                    .emit_error();
+                    let json_rpc_error = JsonRpcError {
+                        code: RATE_LIMIT_EXCEEDED_ERROR_CODE,
+                        message: format!("Rate limit exceeded for {}", request_c.method),
+                        data: Some(serde_json::json!({ "scope": scope })),
+                    };
+                    send_json_rpc_error(&mut platform_state, &request, json_rpc_error).await;
+                    return;
+                }
+            }
             // Validate incoming request parameters.
             if let Err(error_string) = validate_request(open_rpc_state, &request_c, fail_open) {
                 let json_rpc_error = JsonRpcError {
@@ -401,6 +437,19 @@ impl FireboltGateway {
                             }
                             _ => {
                                 if let Some(session) = session {
+                                    if platform_state
+                                        .router_state
+                                        .get_method_entry(&request_c.method)
+                                        .is_none()
+                                        && OpenRpcMock::respond(
+                                            &platform_state,
+                                            &request_c,
+                                            &session,
+                                        )
+                                        .await
+                                    {
+                                        return;
+                                    }
                                     LogSignal::new(
                                         "firebolt_gateway".into(),
                                         "routing".into(),
@@ -453,6 +502,20 @@ impl FireboltGateway {
     }
 }
 
+/// Label of the request in the metrics, [UNKNOWN_METHOD] unless the method is routed or brokered.
+fn get_metrics_method<'a>(platform_state: &PlatformState, method: &'a str) -> &'a str {
+    if platform_state
+        .router_state
+        .get_method_entry(method)
+        .is_some()
+        || platform_state.endpoint_state.has_rule(method)
+    {
+        method
+    } else {
+        UNKNOWN_METHOD
+    }
+}
+
 fn validate_request(
     open_rpc_state: OpenRpcState,
     request: &RpcRequest,
@@ -529,6 +592,83 @@ fn validate_request(
     Ok(())
 }
 
+/// Validates the result of a response against the OpenRPC result schema of the method, as
+/// configured by the `response_validation` feature of the device manifest. Returns the error
+/// which replaces the result of an invalid response.
+pub fn validate_response(
+    platform_state: &PlatformState,
+    request: &RpcRequest,
+    result: &Value,
+) -> Option<Value> {
+    let mode = platform_state.get_response_validation();
+    // subscriptions answer with a listener response instead of the event result
+    if mode == ResponseValidation::Off || request.is_subscription() {
+        return None;
+    }
+    let validation = platform_state
+        .open_rpc_state
+        .validate_result(&request.method, result);
+    apply_response_validation(platform_state, request, &mode, validation)
+}
+
+/// Counts and logs a failed response validation, returns the error to respond with in
+/// [ResponseValidation::Error] mode.
+fn apply_response_validation(
+    platform_state: &PlatformState,
+    request: &RpcRequest,
+    mode: &ResponseValidation,
+    validation: Result<(), String>,
+) -> Option<Value> {
+    let Err(error) = validation else {
+        return None;
+    };
+    if *mode == ResponseValidation::Off {
+        return None;
+    }
+    platform_state
+        .metrics
+        .record_response_violation(&request.method);
+    LogSignal::new(
+        "firebolt_gateway".into(),
+        "invalid_response".into(),
+        request.clone(),
+    )
+    .with_diagnostic_context_item("error", &error)
+    .emit_error();
+    if *mode == ResponseValidation::Error {
+        Some(serde_json::json!({
+            "code": INTERNAL_ERROR_CODE,
+            "message": format!("Invalid response for {}: {}", request.method, error),
+        }))
+    } else {
+        None
+    }
+}
+
+/// Same as [validate_response] for a serialized response.
+pub fn validate_response_message(
+    platform_state: &PlatformState,
+    request: &RpcRequest,
+    message: String,
+) -> String {
+    if platform_state.get_response_validation() == ResponseValidation::Off {
+        return message;
+    }
+    let Ok(mut response) = serde_json::from_str::<JsonRpcApiResponse>(&message) else {
+        return message;
+    };
+    let Some(error) = response
+        .result
+        .as_ref()
+        .and_then(|result| validate_response(platform_state, request, result))
+    else {
+        return message;
+    };
+    response.result = None;
+    response.error = Some(error);
+    serde_json::to_string(&response).unwrap_or(message)
+}
+
 async fn send_json_rpc_error(
     platform_state: &mut PlatformState,
     request: &RpcRequest,
@@ -584,3 +724,69 @@ async fn send_json_rpc_error(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::rules::rules_engine::Rule;
+    use ripple_sdk::Mockable as _;
+    use ripple_tdk::utils::test_utils::Mockable;
+
+    #[test]
+    fn test_response_validation_modes() {
+        let state = PlatformState::mock();
+        let request = RpcRequest::mock();
+        let invalid = || Err("\"1\" is not of type \"integer\"".to_owned());
+
+        assert!(
+            apply_response_validation(&state, &request, &ResponseValidation::Off, invalid())
+                .is_none()
+        );
+        assert!(state.metrics.get_response_violations().is_empty());
+
+        assert!(
+            apply_response_validation(&state, &request, &ResponseValidation::Log, invalid())
+                .is_none()
+        );
+        let error =
+            apply_response_validation(&state, &request, &ResponseValidation::Error, invalid())
+                .unwrap();
+        assert_eq!(error["code"], INTERNAL_ERROR_CODE);
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("Invalid response for {}", request.method)));
+        assert!(
+            apply_response_validation(&state, &request, &ResponseValidation::Error, Ok(()))
+                .is_none()
+        );
+        assert_eq!(
+            state.metrics.get_response_violations().get(&request.method),
+            Some(&2)
+        );
+    }
+
+    #[test]
+    fn test_response_validation_off_by_default() {
+        let state = PlatformState::mock();
+        let request = RpcRequest::mock();
+        let message = r#"{"jsonrpc":"2.0","id":1,"result":1}"#.to_owned();
+        assert_eq!(
+            validate_response_message(&state, &request, message.clone()),
+            message
+        );
+        assert!(state.metrics.get_response_violations().is_empty());
+    }
+
+    #[test]
+    fn test_get_metrics_method() {
+        let state = PlatformState::mock();
+        assert_eq!(get_metrics_method(&state, "device.name"), UNKNOWN_METHOD);
+        let _ = state.endpoint_state.clone().add_rule(Rule {
+            alias: "device.name".to_owned(),
+            ..Default::default()
+        });
+        assert_eq!(get_metrics_method(&state, "device.name"), "device.name");
+        assert_eq!(get_metrics_method(&state, "device.made_up"), UNKNOWN_METHOD);
+    }
+}
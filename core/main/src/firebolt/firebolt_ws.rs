@@ -17,13 +17,22 @@
 
 use std::{
     net::SocketAddr,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
-use super::firebolt_gateway::FireboltGatewayCommand;
+use super::{
+    firebolt_gateway::FireboltGatewayCommand,
+    rpc_batch::{get_batch, BatchElement, BatchResponse, RpcBatches},
+};
+
+// batches still waiting for a response after this time are answered with errors
+const BATCH_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
 use crate::{
     service::apps::delegated_launcher_handler::{AppManagerState, AppManagerState2_0},
     service::ripple_service::service_controller_state::ServiceControllerState,
+    service::session_recorder::SessionRecorder,
+    service::tracer::Tracer,
     state::{
         cap::permitted_state::PermissionHandler, platform_state::PlatformState,
         session_state::Session,
@@ -33,7 +42,8 @@ use futures::SinkExt;
 use futures::StreamExt;
 use jsonrpsee::types::{error::INVALID_REQUEST_CODE, ErrorObject, ErrorResponse, Id};
 use ripple_sdk::{
-    api::manifest::extn_manifest::ExtnSymbol,
+    api::manifest::{device_manifest::WsTlsConfiguration, extn_manifest::ExtnSymbol},
+    tokio_rustls::TlsAcceptor,
     tokio_tungstenite::{
         tungstenite::{self, Message},
         WebSocketStream,
@@ -44,14 +54,24 @@ use ripple_sdk::{
         gateway::rpc_gateway_api::{
             ApiMessage, ApiProtocol, ClientContext, JsonRpcApiResponse, RpcRequest, RPC_V2,
         },
-        observability::log_signal::LogSignal,
+        observability::{log_signal::LogSignal, session_recording::RecordDirection},
+    },
+    log::{error, info, trace, warn},
+    service::service_auth::{
+        now_secs, ServiceTokenCache, SERVICE_HANDSHAKE_QUERY, SERVICE_NONCE_QUERY,
+        SERVICE_TIMESTAMP_QUERY, SERVICE_TOKEN_QUERY,
     },
-    log::{error, info, trace},
     tokio::{
-        net::{TcpListener, TcpStream},
-        sync::{mpsc, oneshot},
+        io::{AsyncRead, AsyncWrite},
+        net::TcpListener,
+        sync::{mpsc, oneshot, watch},
+    },
+    utils::{
+        channel_utils::{mpsc_send_and_log, oneshot_send_and_log},
+        digest_utils::sha256_file,
+        error::RippleError,
+        tls_utils,
     },
-    utils::channel_utils::oneshot_send_and_log,
     uuid::Uuid,
 };
 use ripple_sdk::{log::debug, tokio};
@@ -75,6 +95,9 @@ struct ConnectionCallbackConfig {
     pub secure: bool,
     pub internal_app_id: Option<String>,
     extns: Vec<ExtnSymbol>,
+    allow_unauthenticated_services: bool,
+    // tokens already used by services of this gateway
+    service_tokens: ServiceTokenCache,
 }
 
 impl ConnectionCallbackConfig {
@@ -86,6 +109,66 @@ impl ConnectionCallbackConfig {
         }
         None
     }
+
+    /// Returns the symbol of a service connecting with `service_handshake`. The service has to
+    /// be declared in the manifest and present a valid token for the secret of its symbol, which
+    /// was not used before, unless unauthenticated services are allowed.
+    #[allow(clippy::result_large_err)]
+    fn authenticate_service(
+        &self,
+        request: &tungstenite::handshake::server::Request,
+        extn_id: &str,
+    ) -> Result<ExtnSymbol, tungstenite::handshake::server::ErrorResponse> {
+        let result = match self.get_extn(extn_id) {
+            Some(symbol) => match &symbol.secret {
+                Some(secret) => self
+                    .service_tokens
+                    .verify(
+                        extn_id,
+                        secret,
+                        get_query(request, SERVICE_TIMESTAMP_QUERY, false)?.as_deref(),
+                        get_query(request, SERVICE_NONCE_QUERY, false)?.as_deref(),
+                        get_query(request, SERVICE_TOKEN_QUERY, false)?.as_deref(),
+                        now_secs(),
+                    )
+                    .map(|_| symbol)
+                    .map_err(|e| format!("Service {} not authenticated: {:?}", extn_id, e)),
+                None => Err(format!("Service {} has no secret in the manifest", extn_id)),
+            },
+            None => Err(format!("Service {} not found in the manifest", extn_id)),
+        };
+        match result {
+            Ok(symbol) => Ok(symbol),
+            Err(e) if self.allow_unauthenticated_services => {
+                warn!("{}, accepted as unauthenticated services are allowed", e);
+                // the service will be registered later.
+                Ok(self.get_extn(extn_id).unwrap_or_else(|| ExtnSymbol {
+                    id: extn_id.to_owned(),
+                    ..Default::default()
+                }))
+            }
+            Err(e) => {
+                error!("{}", e);
+                Err(tungstenite::http::response::Builder::new()
+                    .status(403)
+                    .body(Some(e))
+                    .unwrap())
+            }
+        }
+    }
+}
+
+// The handshake token must not end up in the logs
+fn redact_token(query: Option<&str>) -> Option<String> {
+    query.map(|q| {
+        q.split('&')
+            .map(|p| match p.split_once('=') {
+                Some((SERVICE_TOKEN_QUERY, _)) => format!("{}=***", SERVICE_TOKEN_QUERY),
+                _ => p.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    })
 }
 pub struct ConnectionCallback(ConnectionCallbackConfig);
 
@@ -135,34 +218,18 @@ impl tungstenite::handshake::server::Callback for ConnectionCallback {
         tungstenite::handshake::server::ErrorResponse,
     > {
         let query = request.uri().query();
-        info!("New firebolt connection {:?}", query);
+        info!("New firebolt connection {:?}", redact_token(query));
         let cfg = self.0;
 
         if !cfg.secure {
-            if let Ok(Some(extn_id)) = get_query(request, "service_handshake", false) {
+            if let Ok(Some(extn_id)) = get_query(request, SERVICE_HANDSHAKE_QUERY, false) {
                 info!("Service handshake for extn_id={}", extn_id);
-                let cid = if let Some(c) = cfg.get_extn(&extn_id) {
-                    // valid extn_id
-                    ClientIdentity {
-                        session_id: Uuid::new_v4().to_string(),
-                        app_id: extn_id.clone(),
-                        rpc_v2: true,
-                        service_info: Some(c),
-                    }
-                } else {
-                    // extn_id without any symbol in the manifest
-                    info!("Extn not found for extn_id={} in {:?}", extn_id, cfg.extns);
-                    // Accept the connection, the service will be registered later.
-                    let extn_symbol = ExtnSymbol {
-                        id: extn_id.clone(),
-                        ..Default::default()
-                    };
-                    ClientIdentity {
-                        session_id: Uuid::new_v4().to_string(),
-                        app_id: extn_id.clone(),
-                        rpc_v2: true,
-                        service_info: Some(extn_symbol),
-                    }
+                let extn_symbol = cfg.authenticate_service(request, &extn_id)?;
+                let cid = ClientIdentity {
+                    session_id: Uuid::new_v4().to_string(),
+                    app_id: extn_id.clone(),
+                    rpc_v2: true,
+                    service_info: Some(extn_symbol),
                 };
                 info!("New Service connection {:?}", extn_id);
                 oneshot_send_and_log(cfg.next, cid, "ResolveClientIdentity");
@@ -239,7 +306,7 @@ impl tungstenite::handshake::server::Callback for ConnectionCallback {
             );
         }
 
-        info!("{:?} {} is_rpc_v2={}", query, app_id, rpc_v2);
+        info!("{:?} {} is_rpc_v2={}", redact_token(query), app_id, rpc_v2);
 
         let cid = ClientIdentity {
             session_id: session_id.clone(),
@@ -253,19 +320,116 @@ impl tungstenite::handshake::server::Callback for ConnectionCallback {
     }
 }
 
+/// TLS termination of a gateway. The acceptor is replaced when the certificate files change,
+/// connections which are already established keep their session.
+#[derive(Clone)]
+struct GatewayTls {
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+    /// Number of reloads, watched by tests to wait for the watcher.
+    reloads: Arc<watch::Sender<u64>>,
+}
+
+impl GatewayTls {
+    fn new(config: &WsTlsConfiguration) -> Result<Self, RippleError> {
+        Ok(Self {
+            acceptor: Arc::new(RwLock::new(Self::load(config)?)),
+            reloads: Arc::new(watch::channel(0).0),
+        })
+    }
+
+    fn load(config: &WsTlsConfiguration) -> Result<TlsAcceptor, RippleError> {
+        let server_config = tls_utils::server_config(&config.cert, &config.key)?;
+        Ok(TlsAcceptor::from(Arc::new(server_config)))
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap().clone()
+    }
+
+    fn reload(&self, config: &WsTlsConfiguration) -> Result<(), RippleError> {
+        let acceptor = Self::load(config)?;
+        *self.acceptor.write().unwrap() = acceptor;
+        self.reloads.send_modify(|reloads| *reloads += 1);
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn subscribe(&self) -> watch::Receiver<u64> {
+        self.reloads.subscribe()
+    }
+
+    /// Digests of the certificate files, unlike modification times they also change when the
+    /// files are rewritten within the resolution of the file system clock.
+    fn fingerprint(config: &WsTlsConfiguration) -> Vec<Option<String>> {
+        [&config.cert, &config.key]
+            .iter()
+            .map(|path| sha256_file(path).ok())
+            .collect()
+    }
+
+    fn start_watcher(&self, config: WsTlsConfiguration) {
+        let Some(interval) = config.reload_interval.filter(|i| *i > 0) else {
+            return;
+        };
+        let tls = self.clone();
+        let mut last_fingerprint = Self::fingerprint(&config);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                let fingerprint = Self::fingerprint(&config);
+                if fingerprint == last_fingerprint {
+                    continue;
+                }
+                // a failed reload is retried with the next change, e.g. once both files are written
+                match tls.reload(&config) {
+                    Ok(_) => {
+                        info!("Reloaded gateway certificate {}", config.cert);
+                        last_fingerprint = fingerprint;
+                    }
+                    Err(e) => error!("Failed to reload gateway certificate {:?}", e),
+                }
+            }
+        });
+    }
+}
+
 impl FireboltWs {
     pub async fn start(
         server_addr: &str,
         state: PlatformState,
         secure: bool,
         internal_app_id: Option<String>,
+        tls_config: Option<WsTlsConfiguration>,
     ) {
+        let tls = tls_config.map(|config| {
+            let tls = GatewayTls::new(&config)
+                .unwrap_or_else(|e| panic!("Invalid tls configuration {:?}: {:?}", config, e));
+            tls.start_watcher(config);
+            tls
+        });
         // Create the event loop and TCP listener we'll accept connections on.
         let try_socket = TcpListener::bind(&server_addr).await; //create the server on the address
         let listener = try_socket.unwrap_or_else(|_| panic!("Failed to bind {:?}", server_addr));
-        info!("Listening on: {} secure={}", server_addr, secure);
+        info!(
+            "Listening on: {} secure={} tls={}",
+            server_addr,
+            secure,
+            tls.is_some()
+        );
+        Self::serve(listener, state, secure, internal_app_id, tls).await
+    }
+
+    async fn serve(
+        listener: TcpListener,
+        state: PlatformState,
+        secure: bool,
+        internal_app_id: Option<String>,
+        tls: Option<GatewayTls>,
+    ) {
         let state_for_connection = state.clone();
         let extns = state.extn_manifest.get_all_extns();
+        let allow_unauthenticated_services = state.extn_manifest.allow_unauthenticated_services;
+        let service_tokens = ServiceTokenCache::default();
         let app_state = state.app_manager_state.clone();
         let app_state2_0 = state.lifecycle2_app_state.clone();
         let app_lifecycle_2_enabled = std::env::var("RIPPLE_LIFECYCLE_2_ENABLED")
@@ -283,39 +447,87 @@ impl FireboltWs {
                 secure,
                 internal_app_id: internal_app_id.clone(),
                 extns: extns.clone(),
+                allow_unauthenticated_services,
+                service_tokens: service_tokens.clone(),
             };
-            match ripple_sdk::tokio_tungstenite::accept_hdr_async(stream, ConnectionCallback(cfg))
-                .await
-            {
-                Err(e) => {
-                    error!("websocket connection error {:?}", e);
+            match &tls {
+                None => {
+                    Self::accept_connection(
+                        stream,
+                        client_addr,
+                        cfg,
+                        connect_rx,
+                        state_for_connection.clone(),
+                        secure,
+                    )
+                    .await
                 }
-                Ok(ws_stream) => {
-                    trace!("websocket connection success");
+                Some(tls) => {
+                    // the tls handshake runs in its own task so a slow client does not hold the listener
+                    let acceptor = tls.acceptor();
                     let state_for_connection_c = state_for_connection.clone();
                     tokio::spawn(async move {
-                        FireboltWs::handle_connection(
-                            client_addr,
-                            ws_stream,
-                            connect_rx,
-                            state_for_connection_c.clone(),
-                            secure,
-                        )
-                        .await;
+                        match acceptor.accept(stream).await {
+                            Ok(stream) => {
+                                Self::accept_connection(
+                                    stream,
+                                    client_addr,
+                                    cfg,
+                                    connect_rx,
+                                    state_for_connection_c,
+                                    secure,
+                                )
+                                .await
+                            }
+                            Err(e) => error!("tls handshake error {} {:?}", client_addr, e),
+                        }
                     });
                 }
             }
         }
     }
 
-    async fn handle_app_connection(
+    async fn accept_connection<S>(
+        stream: S,
+        client_addr: SocketAddr,
+        cfg: ConnectionCallbackConfig,
+        connect_rx: oneshot::Receiver<ClientIdentity>,
+        state: PlatformState,
+        secure: bool,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        match ripple_sdk::tokio_tungstenite::accept_hdr_async(stream, ConnectionCallback(cfg)).await
+        {
+            Err(e) => {
+                error!("websocket connection error {:?}", e);
+            }
+            Ok(ws_stream) => {
+                trace!("websocket connection success");
+                tokio::spawn(async move {
+                    FireboltWs::handle_connection(
+                        client_addr,
+                        ws_stream,
+                        connect_rx,
+                        state,
+                        secure,
+                    )
+                    .await;
+                });
+            }
+        }
+    }
+
+    async fn handle_app_connection<S>(
         _client_addr: SocketAddr,
-        ws_stream: WebSocketStream<TcpStream>,
+        ws_stream: WebSocketStream<S>,
         state: PlatformState,
         identity: ClientIdentity,
         connection_id: String,
         gateway_secure: bool,
-    ) {
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
         info!(
             "Creating new app connection_id={} app_id={} session_id={}, gateway_secure={}, port={}",
             connection_id,
@@ -361,20 +573,55 @@ impl FireboltWs {
         }
 
         let rpc_context: Arc<RwLock<Vec<String>>> = Arc::new(RwLock::new(context));
+        let batches = Arc::new(Mutex::new(RpcBatches::default()));
+        let batches_c = batches.clone();
+        let batches_expiry = batches.clone();
+        let expiry_tx = session_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            while !expiry_tx.is_closed() {
+                interval.tick().await;
+                let frames = batches_expiry
+                    .lock()
+                    .unwrap()
+                    .expire(BATCH_RESPONSE_TIMEOUT);
+                for frame in frames {
+                    let request_id = Uuid::new_v4().to_string();
+                    let api_msg = ApiMessage::new(ApiProtocol::JsonRpc, frame, request_id);
+                    mpsc_send_and_log(&expiry_tx, api_msg, "BatchTimeout").await;
+                }
+            }
+        });
         let (mut sender, mut receiver) = ws_stream.split();
         let mut platform_state = state.clone();
         let context_clone = ctx.clone();
 
         tokio::spawn(async move {
             while let Some(api_message) = resp_rx.recv().await {
-                let send_result = sender
-                    .send(Message::Text(api_message.jsonrpc_msg.clone()))
-                    .await;
+                let frame = match batches_c.lock().unwrap().collect(&api_message) {
+                    BatchResponse::Unbatched => api_message.jsonrpc_msg.clone(),
+                    BatchResponse::Complete(frame) => frame,
+                    BatchResponse::Pending => {
+                        platform_state
+                            .metrics
+                            .finish_api_stats(&api_message.request_id);
+                        Tracer::end_request_span(&api_message.request_id, &api_message.jsonrpc_msg);
+                        continue;
+                    }
+                };
+                SessionRecorder::record_session(
+                    &app_id_c,
+                    &session_id_c,
+                    RecordDirection::Outbound,
+                    &frame,
+                );
+                let send_result = sender.send(Message::Text(frame.clone())).await;
                 match send_result {
                     Ok(_) => {
                         platform_state
                             .metrics
                             .update_api_stage(&api_message.request_id, "response");
+                        Tracer::end_request_span(&api_message.request_id, &api_message.jsonrpc_msg);
 
                         LogSignal::new(
                             "sent_firebolt_response".to_string(),
@@ -382,7 +629,7 @@ impl FireboltWs {
                             context_clone.clone(),
                         )
                         .with_diagnostic_context_item("cid", &connection_id_c.clone())
-                        .with_diagnostic_context_item("result", &api_message.jsonrpc_msg.clone())
+                        .with_diagnostic_context_item("result", &frame)
                         .emit_debug();
                         if let Some(stats) = platform_state
                             .metrics
@@ -400,13 +647,13 @@ impl FireboltWs {
                             );
                             platform_state
                                 .metrics
-                                .remove_api_stats(&api_message.request_id);
+                                .finish_api_stats(&api_message.request_id);
                         }
 
                         info!(
                             "Sent Firebolt response cid={} msg={}",
                             connection_id_c.clone(),
-                            api_message.jsonrpc_msg
+                            frame
                         );
                     }
                     Err(err) => error!("{:?}", err),
@@ -428,7 +675,58 @@ impl FireboltWs {
                         debug!("Received JsonRpc Request {}", msg);
                         let req_id = Uuid::new_v4().to_string();
                         let req_text = String::from(msg.to_text().unwrap());
+                        SessionRecorder::record_session(
+                            &app_id_c,
+                            &session_id_c,
+                            RecordDirection::Inbound,
+                            &req_text,
+                        );
                         let context = { rpc_context.read().unwrap().clone() };
+                        if let Some(elements) = get_batch(&req_text) {
+                            if elements.is_empty() {
+                                return_invalid_format_error_message(req_id, &state, &connection_id)
+                                    .await;
+                                continue;
+                            }
+                            let elements: Vec<BatchElement> = elements
+                                .into_iter()
+                                .map(|element| {
+                                    BatchElement::parse(element, |json, request_id| {
+                                        RpcRequest::parse(
+                                            json,
+                                            app_id_c.clone(),
+                                            session_id_c.clone(),
+                                            request_id,
+                                            Some(connection_id.clone()),
+                                            gateway_secure,
+                                            context.clone(),
+                                        )
+                                    })
+                                })
+                                .collect();
+                            // registered before handling, so no response can pass the batch
+                            let frame = batches.lock().unwrap().register(&elements);
+                            if let Some(frame) = frame {
+                                let api_msg = ApiMessage::new(ApiProtocol::JsonRpc, frame, req_id);
+                                mpsc_send_and_log(&session_tx, api_msg, "BatchResponse").await;
+                            }
+                            for element in elements {
+                                let msg = match element {
+                                    BatchElement::Request(request)
+                                    | BatchElement::Notification(request) => {
+                                        FireboltGatewayCommand::HandleRpc { request }
+                                    }
+                                    BatchElement::Response(response) => {
+                                        FireboltGatewayCommand::HandleResponse { response }
+                                    }
+                                    BatchElement::Invalid(_) => continue,
+                                };
+                                if let Err(e) = client.clone().send_gateway_command(msg) {
+                                    error!("failed to send request {:?}", e);
+                                }
+                            }
+                            continue;
+                        }
                         if let Ok(request) = RpcRequest::parse(
                             req_text.clone(),
                             app_id_c.clone(),
@@ -470,13 +768,15 @@ impl FireboltWs {
         }
     }
 
-    async fn handle_connection(
+    async fn handle_connection<S>(
         _client_addr: SocketAddr,
-        ws_stream: WebSocketStream<TcpStream>,
+        ws_stream: WebSocketStream<S>,
         connect_rx: oneshot::Receiver<ClientIdentity>,
         state: PlatformState,
         gateway_secure: bool,
-    ) {
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
         let identity = connect_rx.await.unwrap();
 
         // Generate a unique connection ID
@@ -554,3 +854,310 @@ async fn return_invalid_format_error_message(
         let _ = session.send_json_rpc(api_msg).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::extn::ripple_client::RippleClient, state::bootstrap_state::ChannelsState,
+    };
+    use ripple_sdk::{tokio::net::TcpStream, utils::tls_utils::TlsConfig};
+
+    struct TestCert {
+        config: WsTlsConfiguration,
+        ca_bundle: String,
+    }
+
+    fn write_cert(name: &str, dir: &std::path::Path) -> TestCert {
+        let cert = rcgen::generate_simple_self_signed(vec!["ripple.test".to_owned()]).unwrap();
+        let pem = cert.serialize_pem().unwrap();
+        let ca_bundle = dir.join(format!("{}_ca.pem", name));
+        std::fs::write(&ca_bundle, &pem).unwrap();
+        std::fs::write(dir.join("cert.pem"), &pem).unwrap();
+        std::fs::write(dir.join("key.pem"), cert.serialize_private_key_pem()).unwrap();
+        TestCert {
+            config: WsTlsConfiguration {
+                cert: dir.join("cert.pem").to_str().unwrap().to_owned(),
+                key: dir.join("key.pem").to_str().unwrap().to_owned(),
+                reload_interval: None,
+            },
+            ca_bundle: ca_bundle.to_str().unwrap().to_owned(),
+        }
+    }
+
+    /// Serves the gateway on a free port, returns the port and the commands it sends.
+    async fn start_gateway(tls: GatewayTls) -> (u16, mpsc::Receiver<FireboltGatewayCommand>) {
+        use ripple_tdk::utils::test_utils::Mockable;
+        let mut state = PlatformState::mock();
+        let channels = ChannelsState::new();
+        let gateway_rx = channels.get_gateway_receiver().unwrap();
+        state.ripple_client = RippleClient::new(channels);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(FireboltWs::serve(listener, state, false, None, Some(tls)));
+        (port, gateway_rx)
+    }
+
+    type TestStream = WebSocketStream<ripple_sdk::tokio_rustls::client::TlsStream<TcpStream>>;
+
+    async fn connect(port: u16, ca_bundle: &str) -> Result<TestStream, RippleError> {
+        let connector = TlsConfig {
+            ca_bundle: Some(ca_bundle.to_owned()),
+            server_name: Some("ripple.test".to_owned()),
+            ..Default::default()
+        }
+        .connector()?;
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let stream = connector.connect("127.0.0.1", stream).await?;
+        let url = format!("wss://127.0.0.1:{}/?appId=test", port);
+        let (ws, _) = ripple_sdk::tokio_tungstenite::client_async(url, stream)
+            .await
+            .map_err(|_| RippleError::NotAvailable)?;
+        Ok(ws)
+    }
+
+    /// Sends a request over the connection and waits for the gateway to get it.
+    async fn request(
+        ws: &mut TestStream,
+        gateway_rx: &mut mpsc::Receiver<FireboltGatewayCommand>,
+        id: u64,
+    ) {
+        let request = format!(r#"{{"jsonrpc":"2.0","id":{},"method":"device.name"}}"#, id);
+        ws.send(Message::Text(request)).await.unwrap();
+        let handled = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(command) = gateway_rx.recv().await {
+                if let FireboltGatewayCommand::HandleRpc { request } = command {
+                    return request.ctx.call_id;
+                }
+            }
+            0
+        })
+        .await
+        .unwrap();
+        assert_eq!(handled, id);
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ripple_gateway_tls_{}_{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_gateway_tls_invalid_files() {
+        let config = WsTlsConfiguration {
+            cert: "/does/not/exist.pem".to_owned(),
+            key: "/does/not/exist.key".to_owned(),
+            reload_interval: None,
+        };
+        assert!(GatewayTls::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gateway_tls_reload_keeps_connections() {
+        let dir = temp_dir("reload");
+        let first = write_cert("first", &dir);
+        let tls = GatewayTls::new(&first.config).unwrap();
+        let (port, mut gateway_rx) = start_gateway(tls.clone()).await;
+
+        let mut existing = connect(port, &first.ca_bundle).await.unwrap();
+        request(&mut existing, &mut gateway_rx, 1).await;
+
+        let second = write_cert("second", &dir);
+        tls.reload(&second.config).unwrap();
+
+        // new connections get the renewed certificate, the existing one keeps working
+        assert!(connect(port, &first.ca_bundle).await.is_err());
+        let mut renewed = connect(port, &second.ca_bundle).await.unwrap();
+        request(&mut renewed, &mut gateway_rx, 2).await;
+        request(&mut existing, &mut gateway_rx, 3).await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_gateway_tls_watcher_reloads_changed_files() {
+        let dir = temp_dir("watcher");
+        let first = write_cert("first", &dir);
+        let tls = GatewayTls::new(&first.config).unwrap();
+        tls.start_watcher(WsTlsConfiguration {
+            reload_interval: Some(1),
+            ..first.config.clone()
+        });
+        let mut reloads = tls.subscribe();
+        let (port, mut gateway_rx) = start_gateway(tls).await;
+
+        let second = write_cert("second", &dir);
+        tokio::time::timeout(Duration::from_secs(10), reloads.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut renewed = connect(port, &second.ca_bundle).await.unwrap();
+        request(&mut renewed, &mut gateway_rx, 1).await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn handshake(
+        extns: Vec<ExtnSymbol>,
+        allow_unauthenticated_services: bool,
+        query: &str,
+    ) -> Result<ClientIdentity, u16> {
+        handshake_with_tokens(
+            extns,
+            allow_unauthenticated_services,
+            query,
+            ServiceTokenCache::default(),
+        )
+    }
+
+    fn handshake_with_tokens(
+        extns: Vec<ExtnSymbol>,
+        allow_unauthenticated_services: bool,
+        query: &str,
+        service_tokens: ServiceTokenCache,
+    ) -> Result<ClientIdentity, u16> {
+        use ripple_tdk::utils::test_utils::Mockable;
+        use tungstenite::handshake::server::Callback;
+        let state = PlatformState::mock();
+        let (next, mut next_rx) = oneshot::channel();
+        let cfg = ConnectionCallbackConfig {
+            next,
+            app_state: state.app_manager_state.clone(),
+            app_state2_0: state.lifecycle2_app_state.clone(),
+            app_lifecycle_2_enabled: false,
+            secure: false,
+            internal_app_id: None,
+            extns,
+            allow_unauthenticated_services,
+            service_tokens,
+        };
+        let request = tungstenite::handshake::server::Request::builder()
+            .uri(format!("ws://127.0.0.1:3474/?{}", query))
+            .body(())
+            .unwrap();
+        let response = tungstenite::handshake::server::Response::builder()
+            .body(())
+            .unwrap();
+        match ConnectionCallback(cfg).on_request(&request, response) {
+            Ok(_) => Ok(next_rx.try_recv().unwrap()),
+            Err(e) => Err(e.status().as_u16()),
+        }
+    }
+
+    #[test]
+    fn test_service_handshake_authentication() {
+        use ripple_sdk::service::service_auth::{service_handshake_query, service_token};
+        let id = "ripple:channel:gateway:badger";
+        let symbol = ExtnSymbol {
+            id: id.to_owned(),
+            secret: Some("secret".to_owned()),
+            ..Default::default()
+        };
+        let extns = vec![symbol.clone()];
+
+        let cid = handshake(
+            extns.clone(),
+            false,
+            &service_handshake_query(id, Some("secret")),
+        )
+        .unwrap();
+        assert_eq!(cid.app_id, id);
+        assert_eq!(cid.service_info.unwrap().secret, symbol.secret);
+
+        // wrong secret, missing token, expired token
+        assert_eq!(
+            handshake(
+                extns.clone(),
+                false,
+                &service_handshake_query(id, Some("other"))
+            )
+            .unwrap_err(),
+            403
+        );
+        assert_eq!(
+            handshake(extns.clone(), false, &service_handshake_query(id, None)).unwrap_err(),
+            403
+        );
+        let query = format!(
+            "service_handshake={}&timestamp=1000&nonce=n1&token={}",
+            id,
+            service_token(id, "secret", 1000, "n1")
+        );
+        assert_eq!(handshake(extns.clone(), false, &query).unwrap_err(), 403);
+
+        // unknown service and symbol without a secret
+        let unknown = "ripple:channel:gateway:unknown";
+        assert_eq!(
+            handshake(
+                extns.clone(),
+                false,
+                &service_handshake_query(unknown, None)
+            )
+            .unwrap_err(),
+            403
+        );
+        let no_secret = vec![ExtnSymbol {
+            id: id.to_owned(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            handshake(no_secret, false, &service_handshake_query(id, None)).unwrap_err(),
+            403
+        );
+
+        // permissive mode for development
+        let cid = handshake(extns, true, &service_handshake_query(unknown, None)).unwrap();
+        assert_eq!(cid.app_id, unknown);
+        assert_eq!(cid.service_info.unwrap().id, unknown);
+    }
+
+    #[test]
+    fn test_service_handshake_replay() {
+        use ripple_sdk::service::service_auth::{now_secs, service_handshake_query, service_token};
+        let id = "ripple:channel:gateway:badger";
+        let extns = vec![ExtnSymbol {
+            id: id.to_owned(),
+            secret: Some("secret".to_owned()),
+            ..Default::default()
+        }];
+        let tokens = ServiceTokenCache::default();
+        let query = service_handshake_query(id, Some("secret"));
+        assert!(handshake_with_tokens(extns.clone(), false, &query, tokens.clone()).is_ok());
+        // a captured handshake can not be used again
+        assert_eq!(
+            handshake_with_tokens(extns.clone(), false, &query, tokens.clone()).unwrap_err(),
+            403
+        );
+        // a new handshake of the service in the same second is accepted
+        let query = service_handshake_query(id, Some("secret"));
+        assert!(handshake_with_tokens(extns.clone(), false, &query, tokens.clone()).is_ok());
+        // a token without a nonce is refused
+        let timestamp = now_secs();
+        let query = format!(
+            "service_handshake={}&timestamp={}&token={}",
+            id,
+            timestamp,
+            service_token(id, "secret", timestamp, "")
+        );
+        assert_eq!(
+            handshake_with_tokens(extns, false, &query, tokens).unwrap_err(),
+            403
+        );
+    }
+
+    #[test]
+    fn test_redact_token() {
+        assert_eq!(
+            redact_token(Some("service_handshake=id&timestamp=1&token=abcd")),
+            Some("service_handshake=id&timestamp=1&token=***".to_owned())
+        );
+        assert_eq!(
+            redact_token(Some("appId=app")),
+            Some("appId=app".to_owned())
+        );
+        assert_eq!(redact_token(None), None);
+    }
+}
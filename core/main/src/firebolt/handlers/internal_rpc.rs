@@ -22,6 +22,7 @@ use ripple_sdk::{
     api::{
         apps::{AppEvent, AppEventRequest, AppManagerResponse, AppMethod, AppRequest, AppResponse},
         caps::CapsRequest,
+        device::device_events::DEVICE_CONNECTION_CHANGED_EVENT,
         firebolt::{
             fb_discovery::{AgePolicy, PolicyIdentifierAlias},
             fb_general::ListenRequestWithEvent,
@@ -35,26 +36,38 @@ use ripple_sdk::{
             provider::{ProviderRequestPayload, ProviderResponsePayload},
         },
         gateway::rpc_gateway_api::CallContext,
+        manifest::{app_library::APP_LIBRARY_CHANGED_EVENT, device_manifest::AppLibraryEntry},
         settings::{SettingValue, SettingsRequest, SettingsRequestParam},
     },
     async_trait::async_trait,
     log::{debug, error},
+    serde_json::Value,
     service::service_event_state::Event,
     tokio::sync::oneshot,
-    utils::{error::RippleError, rpc_utils::rpc_err},
+    utils::{
+        error::RippleError,
+        logger::{get_log_levels, LogLevels},
+        rpc_utils::rpc_err,
+    },
 };
 
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
 };
 
 use crate::{
-    firebolt::rpc::RippleRPCProvider,
+    firebolt::{openrpc_mock::OpenRpcMock, rpc::RippleRPCProvider},
     service::{
         apps::{
             app_events::AppEvents,
-            provider_broker::{ProviderBroker, ProviderBrokerRequest},
+            app_library::DynamicAppLibrary,
+            provider_broker::{ProviderBroker, ProviderBrokerRequest, ProviderSessions},
+        },
+        log_levels::{LogLevelController, SetLogLevelRequest},
+        ripple_service::service_controller_state::{
+            ServiceControllerState, ServiceDetails, SERVICE_CHANGED_EVENT,
         },
         telemetry_builder::TelemetryBuilder,
     },
@@ -104,6 +117,13 @@ pub trait Internal {
         request: ListenRequest,
     ) -> RpcResult<ListenerResponse>;
 
+    #[method(name = "ripple.onDeviceConnectionChanged")]
+    async fn on_device_connection_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse>;
+
     #[method(name = "account.setPolicyIdentifierAlias")]
     async fn set_policy_identifier_alias(
         &self,
@@ -151,6 +171,91 @@ pub trait Internal {
         ctx: CallContext,
         request: SettingsRequestParam,
     ) -> RpcResult<()>;
+
+    #[method(name = "ripple.reloadRules")]
+    async fn reload_rules(&self, ctx: CallContext) -> RpcResult<usize>;
+
+    #[method(name = "ripple.emitMockEvent")]
+    async fn emit_mock_event(&self, ctx: CallContext, request: MockEventRequest) -> RpcResult<()>;
+
+    #[method(name = "ripple.getProviderSessions")]
+    async fn get_provider_sessions(&self, ctx: CallContext) -> RpcResult<ProviderSessions>;
+
+    #[method(name = "ripple.cancelProviderSession")]
+    async fn cancel_provider_session(
+        &self,
+        ctx: CallContext,
+        request: CancelProviderSessionRequest,
+    ) -> RpcResult<()>;
+
+    #[method(name = "ripple.getServices")]
+    async fn get_services(&self, ctx: CallContext) -> RpcResult<Vec<ServiceDetails>>;
+
+    #[method(name = "ripple.onServiceChanged")]
+    async fn on_service_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse>;
+
+    #[method(name = "ripple.getAppLibrary")]
+    async fn get_app_library(&self, ctx: CallContext) -> RpcResult<Vec<AppLibraryEntry>>;
+
+    #[method(name = "ripple.addAppLibraryEntry")]
+    async fn add_app_library_entry(
+        &self,
+        ctx: CallContext,
+        entry: AppLibraryEntry,
+    ) -> RpcResult<()>;
+
+    #[method(name = "ripple.updateAppLibraryEntry")]
+    async fn update_app_library_entry(
+        &self,
+        ctx: CallContext,
+        entry: AppLibraryEntry,
+    ) -> RpcResult<()>;
+
+    #[method(name = "ripple.removeAppLibraryEntry")]
+    async fn remove_app_library_entry(
+        &self,
+        ctx: CallContext,
+        request: RemoveAppLibraryEntryRequest,
+    ) -> RpcResult<()>;
+
+    #[method(name = "ripple.onAppLibraryChanged")]
+    async fn on_app_library_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse>;
+
+    #[method(name = "ripple.getLogLevels")]
+    async fn get_log_levels(&self, ctx: CallContext) -> RpcResult<LogLevels>;
+
+    #[method(name = "ripple.setLogLevel")]
+    async fn set_log_level(
+        &self,
+        ctx: CallContext,
+        request: SetLogLevelRequest,
+    ) -> RpcResult<LogLevels>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockEventRequest {
+    pub event: String,
+    // defaults to the result of the first example of the event
+    pub result: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelProviderSessionRequest {
+    // correlation id of an active session or id of a queued request
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveAppLibraryEntryRequest {
+    pub app_id: String,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -272,6 +377,14 @@ impl InternalServer for InternalImpl {
         .await
     }
 
+    async fn on_device_connection_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse> {
+        rpc_add_event_listener(&self.state, ctx, request, DEVICE_CONNECTION_CHANGED_EVENT).await
+    }
+
     async fn get_second_screen_payload(&self, ctx: CallContext) -> RpcResult<String> {
         let (app_resp_tx, app_resp_rx) = oneshot::channel::<AppResponse>();
 
@@ -416,6 +529,126 @@ impl InternalServer for InternalImpl {
         subscribe_to_settings(&self.state, request).await;
         Ok(())
     }
+
+    async fn reload_rules(&self, _ctx: CallContext) -> RpcResult<usize> {
+        self.state
+            .endpoint_state
+            .reload_rules(self.state.clone())
+            .map_err(|e| {
+                error!("Failed to reload rules {:?}", e);
+                rpc_err(format!("Failed to reload rules: {}", e))
+            })
+    }
+
+    async fn emit_mock_event(&self, _ctx: CallContext, request: MockEventRequest) -> RpcResult<()> {
+        OpenRpcMock::emit_event(&self.state, &request.event, request.result)
+            .await
+            .map_err(|e| match e {
+                RippleError::InvalidAccess => rpc_err("Mock events need the mock gateway mode"),
+                _ => rpc_err(format!("No example for event {}", request.event)),
+            })
+    }
+
+    async fn get_provider_sessions(&self, _ctx: CallContext) -> RpcResult<ProviderSessions> {
+        Ok(ProviderBroker::get_sessions(&self.state))
+    }
+
+    async fn cancel_provider_session(
+        &self,
+        _ctx: CallContext,
+        request: CancelProviderSessionRequest,
+    ) -> RpcResult<()> {
+        if ProviderBroker::cancel_session(&self.state, &request.id).await {
+            Ok(())
+        } else {
+            Err(rpc_err(format!("No provider session {}", request.id)))
+        }
+    }
+
+    async fn get_services(&self, _ctx: CallContext) -> RpcResult<Vec<ServiceDetails>> {
+        Ok(ServiceControllerState::get_service_details(&self.state).await)
+    }
+
+    async fn on_service_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse> {
+        rpc_add_event_listener(&self.state, ctx, request, SERVICE_CHANGED_EVENT).await
+    }
+
+    async fn get_app_library(&self, _ctx: CallContext) -> RpcResult<Vec<AppLibraryEntry>> {
+        Ok(self.state.app_library_state.get_all_apps())
+    }
+
+    async fn add_app_library_entry(
+        &self,
+        _ctx: CallContext,
+        entry: AppLibraryEntry,
+    ) -> RpcResult<()> {
+        let app_id = entry.app_id.clone();
+        DynamicAppLibrary::add(&self.state, entry)
+            .await
+            .map_err(|e| match e {
+                RippleError::InvalidInput => {
+                    rpc_err(format!("App {} is already in the library", app_id))
+                }
+                e => e.into(),
+            })
+    }
+
+    async fn update_app_library_entry(
+        &self,
+        _ctx: CallContext,
+        entry: AppLibraryEntry,
+    ) -> RpcResult<()> {
+        let app_id = entry.app_id.clone();
+        DynamicAppLibrary::update(&self.state, entry)
+            .await
+            .map_err(|e| match e {
+                RippleError::NotAvailable => {
+                    rpc_err(format!("App {} is not in the library", app_id))
+                }
+                e => e.into(),
+            })
+    }
+
+    async fn remove_app_library_entry(
+        &self,
+        _ctx: CallContext,
+        request: RemoveAppLibraryEntryRequest,
+    ) -> RpcResult<()> {
+        DynamicAppLibrary::remove(&self.state, &request.app_id)
+            .await
+            .map_err(|e| match e {
+                RippleError::NotAvailable => {
+                    rpc_err(format!("App {} is not in the library", request.app_id))
+                }
+                e => e.into(),
+            })
+    }
+
+    async fn on_app_library_changed(
+        &self,
+        ctx: CallContext,
+        request: ListenRequest,
+    ) -> RpcResult<ListenerResponse> {
+        rpc_add_event_listener(&self.state, ctx, request, APP_LIBRARY_CHANGED_EVENT).await
+    }
+
+    async fn get_log_levels(&self, _ctx: CallContext) -> RpcResult<LogLevels> {
+        Ok(get_log_levels())
+    }
+
+    async fn set_log_level(
+        &self,
+        _ctx: CallContext,
+        request: SetLogLevelRequest,
+    ) -> RpcResult<LogLevels> {
+        LogLevelController::set(&self.state, request.clone())
+            .await
+            .map_err(|_| rpc_err(format!("Invalid log level {}", request.level)))
+    }
 }
 
 pub struct InternalProvider;
@@ -21,7 +21,9 @@ use crate::{
     firebolt::rpc::register_aliases,
     service::apps::{
         app_events::AppEvents,
-        provider_broker::{ProviderBroker, ProviderBrokerRequest},
+        provider_broker::{
+            ProviderBroker, ProviderBrokerRequest, DEFAULT_PROVIDER_RESPONSE_TIMEOUT_MS,
+        },
     },
     state::{openrpc_state::ProviderRelationSet, platform_state::PlatformState},
 };
@@ -77,9 +79,6 @@ fn load_app_injection_method_list() -> HashMap<String, String> {
     }
 }
 
-// TODO: Add to config
-const DEFAULT_PROVIDER_RESPONSE_TIMEOUT_MS: u64 = 15000;
-
 #[derive(Debug)]
 enum MethodType {
     AppEventListener,
@@ -517,7 +516,7 @@ impl ProviderRegistrar {
             app_id: None,
         };
 
-        let provider_app_id =
+        let invoked_provider =
             ProviderBroker::invoke_method(&context.platform_state, provider_broker_request).await;
 
         let result = match timeout(
@@ -576,7 +575,9 @@ impl ProviderRegistrar {
                             );
 
                             if !response_map.contains_key("appId") {
-                                if let Some(app_id) = provider_app_id.clone() {
+                                // the provider which answered, also after a failover or
+                                // when the request was queued
+                                if let Some(app_id) = invoked_provider.get_app_id() {
                                     response_map.insert("appId".to_string(), Value::String(app_id));
                                 }
                             }
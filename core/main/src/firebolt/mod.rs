@@ -41,5 +41,7 @@ pub mod handlers {
 pub mod firebolt_gatekeeper;
 pub mod firebolt_gateway;
 pub mod firebolt_ws;
+pub mod openrpc_mock;
 pub mod rpc;
+pub mod rpc_batch;
 pub mod rpc_router;
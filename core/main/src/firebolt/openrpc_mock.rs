@@ -0,0 +1,196 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use ripple_sdk::{
+    api::{
+        firebolt::fb_general::{ListenRequest, ListenerResponse},
+        gateway::rpc_gateway_api::{ApiMessage, JsonRpcApiResponse, RpcRequest},
+    },
+    serde_json::{self, Value},
+    utils::error::RippleError,
+};
+
+use crate::{
+    service::apps::app_events::AppEvents,
+    state::{platform_state::PlatformState, session_state::Session},
+};
+
+/// Mock gateway mode, answering requests from the examples of the Firebolt OpenRPC.
+pub struct OpenRpcMock;
+
+impl OpenRpcMock {
+    /// Returns the response for a request without a rule or handler, `None` if the mode is off or
+    /// the method has no examples. Subscriptions are registered, so events can be emitted with
+    /// [OpenRpcMock::emit_event].
+    pub fn get_response(platform_state: &PlatformState, request: &RpcRequest) -> Option<Value> {
+        let open_rpc_state = &platform_state.open_rpc_state;
+        if !open_rpc_state.has_examples() {
+            return None;
+        }
+        let result = if request.is_subscription() {
+            let listen = request.is_listening();
+            AppEvents::add_listener(
+                platform_state,
+                request.method.clone(),
+                request.ctx.clone(),
+                ListenRequest { listen },
+            );
+            serde_json::to_value(ListenerResponse {
+                listening: listen,
+                event: request.method.clone(),
+            })
+            .ok()?
+        } else {
+            open_rpc_state.get_example_result(&request.method, request.get_params().as_ref())?
+        };
+        serde_json::to_value(JsonRpcApiResponse {
+            id: Some(request.ctx.call_id),
+            result: Some(result),
+            ..Default::default()
+        })
+        .ok()
+    }
+
+    pub async fn respond(
+        platform_state: &PlatformState,
+        request: &RpcRequest,
+        session: &Session,
+    ) -> bool {
+        let Some(response) = Self::get_response(platform_state, request) else {
+            return false;
+        };
+        let mut message = ApiMessage::new(
+            request.ctx.protocol.clone(),
+            response.to_string(),
+            request.ctx.request_id.clone(),
+        );
+        message.stats = platform_state
+            .metrics
+            .get_api_stats(&request.ctx.request_id);
+        let _ = session.send_json_rpc(message).await;
+        true
+    }
+
+    /// Emits an event to its listeners. Without a result the result of the first example of the
+    /// event is sent. Fails with [RippleError::InvalidAccess] outside of the mock gateway mode.
+    pub async fn emit_event(
+        platform_state: &PlatformState,
+        event: &str,
+        result: Option<Value>,
+    ) -> Result<(), RippleError> {
+        // examples are only loaded in the mock gateway mode
+        if !platform_state.open_rpc_state.has_examples() {
+            return Err(RippleError::InvalidAccess);
+        }
+        let result = match result {
+            Some(result) => result,
+            None => platform_state
+                .open_rpc_state
+                .get_example_result(event, None)
+                .ok_or(RippleError::NotAvailable)?,
+        };
+        AppEvents::emit(platform_state, event, &result).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::{
+        api::gateway::rpc_gateway_api::{ApiProtocol, CallContext},
+        serde_json::json,
+        tokio,
+    };
+    use ripple_tdk::utils::test_utils::Mockable;
+
+    fn request(method: &str, params: Value) -> RpcRequest {
+        let mut ctx = CallContext::mock();
+        ctx.method = method.to_owned();
+        ctx.protocol = ApiProtocol::JsonRpc;
+        RpcRequest {
+            method: method.to_owned(),
+            params_json: RpcRequest::prepend_ctx(Some(params), &ctx),
+            ctx,
+        }
+    }
+
+    fn load_examples(state: &PlatformState) {
+        let content = json!({
+            "apis": {
+                "1": {
+                    "methods": [
+                        {
+                            "name": "Device.version",
+                            "examples": [{"name": "Default", "params": [], "result": {"name": "versions", "value": {"api": {"major": 1}}}}]
+                        },
+                        {
+                            "name": "Device.onNameChanged",
+                            "examples": [{"name": "Default", "params": [{"name": "listen", "value": true}], "result": {"name": "value", "value": "Living Room"}}]
+                        }
+                    ]
+                }
+            }
+        });
+        state
+            .open_rpc_state
+            .add_examples(&content.to_string())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mock_responses() {
+        let state = PlatformState::mock();
+        let version = request("device.version", json!({}));
+        assert!(OpenRpcMock::get_response(&state, &version).is_none());
+
+        load_examples(&state);
+        let response = OpenRpcMock::get_response(&state, &version).unwrap();
+        assert_eq!(response["id"], json!(version.ctx.call_id));
+        assert_eq!(response["result"]["api"]["major"], json!(1));
+
+        let subscription = request("device.onNameChanged", json!({"listen": true}));
+        let response = OpenRpcMock::get_response(&state, &subscription).unwrap();
+        assert_eq!(response["result"]["listening"], json!(true));
+
+        assert!(OpenRpcMock::get_response(&state, &request("unknown.method", json!({}))).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emit_event() {
+        let state = PlatformState::mock();
+        assert_eq!(
+            OpenRpcMock::emit_event(&state, "device.onNameChanged", Some(json!("Kitchen"))).await,
+            Err(RippleError::InvalidAccess)
+        );
+        load_examples(&state);
+        assert!(
+            OpenRpcMock::emit_event(&state, "device.onNameChanged", Some(json!("Kitchen")))
+                .await
+                .is_ok()
+        );
+        assert!(
+            OpenRpcMock::emit_event(&state, "device.onNameChanged", None)
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            OpenRpcMock::emit_event(&state, "device.onModelChanged", None).await,
+            Err(RippleError::NotAvailable)
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use jsonrpsee::types::{
+    error::{INTERNAL_ERROR_CODE, INVALID_REQUEST_CODE},
+    ErrorObject, ErrorResponse, Id,
+};
+use ripple_sdk::{
+    api::gateway::rpc_gateway_api::{
+        ApiMessage, JsonRpcApiResponse, RequestParseError, RpcRequest,
+    },
+    uuid::Uuid,
+};
+use serde_json::Value;
+
+/// One element of a JSON-RPC batch request.
+#[derive(Debug)]
+pub enum BatchElement {
+    Request(RpcRequest),
+    // a request without an id, its response is left out of the batch response
+    Notification(RpcRequest),
+    // an app answering a provider request
+    Response(JsonRpcApiResponse),
+    // the error response for an element which is not a valid request
+    Invalid(Value),
+}
+
+impl BatchElement {
+    /// Parses an element, `parse` receives the element text and a new request id.
+    pub fn parse<F>(element: Value, parse: F) -> BatchElement
+    where
+        F: Fn(String, String) -> Result<RpcRequest, RequestParseError>,
+    {
+        let text = element.to_string();
+        match parse(text.clone(), Uuid::new_v4().to_string()) {
+            Ok(request) => {
+                if element.get("id").is_some() {
+                    BatchElement::Request(request)
+                } else {
+                    BatchElement::Notification(request)
+                }
+            }
+            Err(_) => match JsonRpcApiResponse::get_response(&text) {
+                Some(response) => BatchElement::Response(response),
+                None => BatchElement::Invalid(invalid_request(element.get("id"))),
+            },
+        }
+    }
+}
+
+/// Returns the array of a batch request, `None` for anything else.
+pub fn get_batch(text: &str) -> Option<Vec<Value>> {
+    if !text.trim_start().starts_with('[') {
+        return None;
+    }
+    match serde_json::from_str::<Value>(text) {
+        Ok(Value::Array(elements)) => Some(elements),
+        _ => None,
+    }
+}
+
+pub fn invalid_request(id: Option<&Value>) -> Value {
+    let id = match id {
+        Some(Value::Number(n)) if n.is_u64() => Id::Number(n.as_u64().unwrap()),
+        Some(Value::String(s)) => Id::Str(s.clone().into()),
+        _ => Id::Null,
+    };
+    error_response(INVALID_REQUEST_CODE, "invalid request", id)
+}
+
+fn error_response(code: i32, message: &str, id: Id<'static>) -> Value {
+    let err = ErrorResponse::owned(ErrorObject::owned::<()>(code, message.to_owned(), None), id);
+    serde_json::to_value(&err).unwrap_or(Value::Null)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BatchResponse {
+    // the message is not part of a batch and is sent as is
+    Unbatched,
+    // the message was collected into a batch which still waits for other responses
+    Pending,
+    // the message completed a batch, the frame holds all of its responses
+    Complete(String),
+}
+
+enum BatchEntry {
+    Response { batch_id: String, index: usize },
+    Notification(Instant),
+}
+
+struct PendingBatch {
+    registered: Instant,
+    // call ids of the requests, used to answer the ones which time out
+    call_ids: Vec<u64>,
+    responses: Vec<Option<Value>>,
+}
+
+/// Collects the responses of the batch requests of one connection.
+///
+/// Responses are matched by request id. Only the first message of a request is collected, so
+/// events of a subscription made in a batch are sent as they arrive. Batches which are not
+/// complete within their timeout are answered with an error for each missing response, see
+/// [RpcBatches::expire].
+#[derive(Default)]
+pub struct RpcBatches {
+    entries: HashMap<String, BatchEntry>,
+    batches: HashMap<String, PendingBatch>,
+}
+
+impl RpcBatches {
+    /// Registers the requests of a batch before they are handled. Returns the batch response
+    /// right away if there is no response to wait for, `None` if nothing has to be sent.
+    pub fn register(&mut self, elements: &[BatchElement]) -> Option<String> {
+        let batch_id = Uuid::new_v4().to_string();
+        let registered = Instant::now();
+        let mut call_ids = Vec::new();
+        let mut responses = Vec::new();
+        for element in elements {
+            match element {
+                BatchElement::Request(request) => {
+                    self.entries.insert(
+                        request.ctx.request_id.clone(),
+                        BatchEntry::Response {
+                            batch_id: batch_id.clone(),
+                            index: responses.len(),
+                        },
+                    );
+                    call_ids.push(request.ctx.call_id);
+                    responses.push(None);
+                }
+                BatchElement::Notification(request) => {
+                    self.entries.insert(
+                        request.ctx.request_id.clone(),
+                        BatchEntry::Notification(registered),
+                    );
+                }
+                BatchElement::Invalid(error) => {
+                    call_ids.push(0);
+                    responses.push(Some(error.clone()));
+                }
+                BatchElement::Response(_) => {}
+            }
+        }
+        if responses.iter().any(|r| r.is_none()) {
+            self.batches.insert(
+                batch_id,
+                PendingBatch {
+                    registered,
+                    call_ids,
+                    responses,
+                },
+            );
+            None
+        } else if responses.is_empty() {
+            None
+        } else {
+            Some(Self::frame(responses))
+        }
+    }
+
+    pub fn collect(&mut self, message: &ApiMessage) -> BatchResponse {
+        let (batch_id, index) = match self.entries.remove(&message.request_id) {
+            None => return BatchResponse::Unbatched,
+            Some(BatchEntry::Notification(_)) => return BatchResponse::Pending,
+            Some(BatchEntry::Response { batch_id, index }) => (batch_id, index),
+        };
+        let Some(batch) = self.batches.get_mut(&batch_id) else {
+            return BatchResponse::Unbatched;
+        };
+        batch.responses[index] = Some(
+            serde_json::from_str(&message.jsonrpc_msg)
+                .unwrap_or_else(|_| Value::String(message.jsonrpc_msg.clone())),
+        );
+        if batch.responses.iter().any(|r| r.is_none()) {
+            return BatchResponse::Pending;
+        }
+        match self.batches.remove(&batch_id) {
+            Some(batch) => BatchResponse::Complete(Self::frame(batch.responses)),
+            None => BatchResponse::Pending,
+        }
+    }
+
+    /// Removes the batches registered longer than `timeout` ago and returns their frames, each
+    /// missing response is replaced by an error. Forgets notifications older than `timeout`.
+    pub fn expire(&mut self, timeout: Duration) -> Vec<String> {
+        let expired: Vec<String> = self
+            .batches
+            .iter()
+            .filter(|(_, batch)| batch.registered.elapsed() >= timeout)
+            .map(|(batch_id, _)| batch_id.clone())
+            .collect();
+        self.entries.retain(|_, entry| match entry {
+            BatchEntry::Response { batch_id, .. } => !expired.contains(batch_id),
+            BatchEntry::Notification(registered) => registered.elapsed() < timeout,
+        });
+        expired
+            .iter()
+            .filter_map(|batch_id| self.batches.remove(batch_id))
+            .map(|batch| {
+                let responses = batch
+                    .responses
+                    .into_iter()
+                    .zip(batch.call_ids)
+                    .map(|(response, call_id)| {
+                        response.or_else(|| {
+                            Some(error_response(
+                                INTERNAL_ERROR_CODE,
+                                "request timed out",
+                                Id::Number(call_id),
+                            ))
+                        })
+                    })
+                    .collect();
+                Self::frame(responses)
+            })
+            .collect()
+    }
+
+    fn frame(responses: Vec<Option<Value>>) -> String {
+        Value::Array(responses.into_iter().flatten().collect()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::api::gateway::rpc_gateway_api::ApiProtocol;
+    use serde_json::json;
+
+    fn parse(element: Value) -> BatchElement {
+        BatchElement::parse(element, |json, request_id| {
+            RpcRequest::parse(
+                json,
+                "app".to_owned(),
+                "session".to_owned(),
+                request_id,
+                None,
+                false,
+                vec![],
+            )
+        })
+    }
+
+    fn request_id(element: &BatchElement) -> String {
+        match element {
+            BatchElement::Request(r) | BatchElement::Notification(r) => r.ctx.request_id.clone(),
+            _ => panic!("not a request"),
+        }
+    }
+
+    fn message(request_id: &str, response: Value) -> ApiMessage {
+        ApiMessage::new(
+            ApiProtocol::JsonRpc,
+            response.to_string(),
+            request_id.to_owned(),
+        )
+    }
+
+    #[test]
+    fn test_get_batch() {
+        assert!(get_batch(r#"{"jsonrpc": "2.0", "id": 1, "method": "a.b"}"#).is_none());
+        assert!(get_batch("[").is_none());
+        assert_eq!(get_batch(" []"), Some(vec![]));
+        assert_eq!(get_batch("[1, 2]").map(|b| b.len()), Some(2));
+    }
+
+    #[test]
+    fn test_parse_elements() {
+        let request = parse(json!({"jsonrpc": "2.0", "id": 1, "method": "device.name"}));
+        assert!(matches!(request, BatchElement::Request(_)));
+        let notification = parse(json!({"jsonrpc": "2.0", "method": "device.name"}));
+        assert!(matches!(notification, BatchElement::Notification(_)));
+        let response = parse(json!({"jsonrpc": "2.0", "id": 3, "result": true}));
+        assert!(matches!(response, BatchElement::Response(_)));
+        match parse(json!({"id": 4, "method": "device.name"})) {
+            BatchElement::Invalid(error) => {
+                assert_eq!(error["id"], json!(4));
+                assert_eq!(error["error"]["code"], json!(INVALID_REQUEST_CODE));
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        match parse(json!(1)) {
+            BatchElement::Invalid(error) => assert_eq!(error["id"], Value::Null),
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_collect_batch_in_request_order() {
+        let elements = vec![
+            parse(json!({"jsonrpc": "2.0", "id": 1, "method": "device.name"})),
+            parse(json!({"jsonrpc": "2.0", "method": "device.model"})),
+            parse(json!("invalid")),
+            parse(json!({"jsonrpc": "2.0", "id": 2, "method": "device.type"})),
+        ];
+        let mut batches = RpcBatches::default();
+        assert!(batches.register(&elements).is_none());
+
+        let first = request_id(&elements[0]);
+        let notification = request_id(&elements[1]);
+        let last = request_id(&elements[3]);
+
+        assert_eq!(
+            batches.collect(&message("other", json!({"id": 9}))),
+            BatchResponse::Unbatched
+        );
+        assert_eq!(
+            batches.collect(&message(&last, json!({"id": 2, "result": "tv"}))),
+            BatchResponse::Pending
+        );
+        assert_eq!(
+            batches.collect(&message(&notification, json!({"id": 0, "result": "m"}))),
+            BatchResponse::Pending
+        );
+        let frame = match batches.collect(&message(&first, json!({"id": 1, "result": "n"}))) {
+            BatchResponse::Complete(frame) => frame,
+            r => panic!("unexpected {:?}", r),
+        };
+        let frame: Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(frame.as_array().unwrap().len(), 3);
+        assert_eq!(frame[0]["result"], json!("n"));
+        assert_eq!(frame[1]["error"]["code"], json!(INVALID_REQUEST_CODE));
+        assert_eq!(frame[2]["result"], json!("tv"));
+
+        // later messages of the same request, e.g. events, are not batched
+        assert_eq!(
+            batches.collect(&message(&first, json!({"id": 1, "result": "n"}))),
+            BatchResponse::Unbatched
+        );
+    }
+
+    #[test]
+    fn test_expire_incomplete_batch() {
+        let elements = vec![
+            parse(json!({"jsonrpc": "2.0", "id": 1, "method": "device.name"})),
+            parse(json!({"jsonrpc": "2.0", "id": 2, "method": "device.model"})),
+            parse(json!({"jsonrpc": "2.0", "method": "device.make"})),
+        ];
+        let mut batches = RpcBatches::default();
+        assert!(batches.register(&elements).is_none());
+        let answered = request_id(&elements[0]);
+        assert_eq!(
+            batches.collect(&message(
+                &answered,
+                json!({"jsonrpc": "2.0", "id": 1, "result": "a"})
+            )),
+            BatchResponse::Pending
+        );
+        assert!(batches.expire(Duration::from_secs(60)).is_empty());
+
+        let frames = batches.expire(Duration::ZERO);
+        assert_eq!(frames.len(), 1);
+        let frame: Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(frame[0]["result"], json!("a"));
+        assert_eq!(frame[1]["id"], json!(2));
+        assert_eq!(frame[1]["error"]["code"], json!(INTERNAL_ERROR_CODE));
+        assert!(batches.entries.is_empty());
+        assert!(batches.batches.is_empty());
+
+        // a response arriving after the timeout is no longer part of the batch
+        let late = request_id(&elements[1]);
+        assert_eq!(
+            batches.collect(&message(
+                &late,
+                json!({"jsonrpc": "2.0", "id": 2, "result": "b"})
+            )),
+            BatchResponse::Unbatched
+        );
+    }
+
+    #[test]
+    fn test_register_without_pending_responses() {
+        let mut batches = RpcBatches::default();
+        let notifications = vec![parse(json!({"jsonrpc": "2.0", "method": "device.name"}))];
+        assert!(batches.register(&notifications).is_none());
+
+        let invalid = vec![parse(json!(1)), parse(json!(2))];
+        let frame: Value = serde_json::from_str(&batches.register(&invalid).unwrap()).unwrap();
+        assert_eq!(frame.as_array().unwrap().len(), 2);
+    }
+}
@@ -45,7 +45,7 @@ use ripple_sdk::{
 use std::sync::{Arc, RwLock};
 
 use crate::{
-    firebolt::firebolt_gateway::JsonRpcMessage,
+    firebolt::firebolt_gateway::{validate_response_message, JsonRpcMessage},
     service::telemetry_builder::TelemetryBuilder,
     state::{platform_state::PlatformState, session_state::Session},
     utils::router_utils::{
@@ -180,6 +180,7 @@ async fn resolve_route(
 
     if let Some(r) = sink_rx.next().await {
         debug!("Received response from method sink {}", r.clone());
+        let r = validate_response_message(platform_state, &req, r);
         let rpc_header = get_rpc_header(&req);
         let protocol = req.ctx.protocol.clone();
         let request_id = req.clone().ctx.request_id;
@@ -16,7 +16,10 @@
 //
 
 use ripple_sdk::{
-    api::apps::AppEventRequest,
+    api::{
+        apps::AppEventRequest,
+        device::device_events::{DeviceConnectionStatus, DEVICE_CONNECTION_CHANGED_EVENT},
+    },
     async_trait::async_trait,
     extn::{
         client::extn_processor::{
@@ -24,6 +27,7 @@ use ripple_sdk::{
         },
         extn_client_message::ExtnMessage,
     },
+    serde_json,
     tokio::sync::mpsc::Sender,
 };
 
@@ -70,6 +74,15 @@ impl ExtnEventProcessor for AppEventsProcessor {
     ) -> Option<bool> {
         match extracted_message.clone() {
             AppEventRequest::Emit(event) => {
+                // the thunder extension reports a connection which was lost and regained
+                if event.event_name == DEVICE_CONNECTION_CHANGED_EVENT
+                    && matches!(
+                        serde_json::from_value(event.result.clone()),
+                        Ok(DeviceConnectionStatus::Connected)
+                    )
+                {
+                    state.metrics.record_thunder_reconnect();
+                }
                 if let Some(app_id) = event.app_id {
                     let event_name = &event.event_name;
                     let result = &event.result;
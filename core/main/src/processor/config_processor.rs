@@ -89,7 +89,7 @@ impl ExtnRequestProcessor for ConfigRequestProcessor {
                 let config = LauncherConfig {
                     lifecycle_policy: device_manifest.get_lifecycle_policy(),
                     retention_policy: device_manifest.get_retention_policy(),
-                    app_library_state: state.app_library_state.clone(),
+                    app_library_state: state.app_library_state.get_state(),
                 };
                 if let ExtnPayload::Response(r) = config.get_extn_payload() {
                     r
@@ -16,6 +16,7 @@
 //
 
 pub mod default_storage_properties;
+pub mod storage_backend;
 pub mod storage_manager;
 pub mod storage_manager_processor;
 pub mod storage_manager_utils;
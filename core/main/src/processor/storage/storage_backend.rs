@@ -0,0 +1,371 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use ripple_sdk::{
+    api::{
+        device::device_peristence::{
+            DeleteStorageProperty, DevicePersistenceRequest, GetStorageProperty,
+            SetStorageProperty, StorageData,
+        },
+        manifest::device_manifest::{DeviceManifest, StorageBackendType},
+    },
+    async_trait::async_trait,
+    chrono::Utc,
+    extn::extn_client_message::ExtnResponse,
+    framework::file_store::FileStore,
+    log::{error, info},
+    utils::error::RippleError,
+};
+
+use crate::state::platform_state::PlatformState;
+
+/// Scope of properties stored without one.
+const DEFAULT_SCOPE: &str = "device";
+
+/// Where `StorageManager` reads and writes properties. Responses follow the device persistence
+/// extension: a get answers [ExtnResponse::StorageData] or [ExtnResponse::None] when the key is
+/// not stored, a delete answers whether the key existed.
+#[async_trait]
+pub trait StorageBackend: Send + Sync + Debug {
+    async fn get(
+        &self,
+        state: &PlatformState,
+        data: GetStorageProperty,
+    ) -> Result<ExtnResponse, RippleError>;
+
+    async fn set(&self, state: &PlatformState, data: SetStorageProperty)
+        -> Result<(), RippleError>;
+
+    async fn delete(
+        &self,
+        state: &PlatformState,
+        data: DeleteStorageProperty,
+    ) -> Result<ExtnResponse, RippleError>;
+}
+
+pub fn get_storage_backend(manifest: &DeviceManifest) -> Arc<dyn StorageBackend> {
+    let config = manifest.get_storage_configuration();
+    match config.backend {
+        StorageBackendType::Device => Arc::new(DeviceStorageBackend),
+        StorageBackendType::Local => {
+            let path = config.path.unwrap_or_else(|| {
+                Path::new(&manifest.configuration.saved_dir)
+                    .join("local_storage")
+                    .to_string_lossy()
+                    .to_string()
+            });
+            info!("Storing properties in {}", path);
+            Arc::new(LocalStorageBackend::new(path))
+        }
+    }
+}
+
+/// Sends the requests to the extension fulfilling the local storage contract.
+#[derive(Debug)]
+pub struct DeviceStorageBackend;
+
+impl DeviceStorageBackend {
+    async fn request(
+        state: &PlatformState,
+        request: DevicePersistenceRequest,
+    ) -> Result<ExtnResponse, RippleError> {
+        let msg = state.get_client().send_extn_request(request).await?;
+        msg.payload.extract().ok_or(RippleError::ParseError)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for DeviceStorageBackend {
+    async fn get(
+        &self,
+        state: &PlatformState,
+        data: GetStorageProperty,
+    ) -> Result<ExtnResponse, RippleError> {
+        Self::request(state, DevicePersistenceRequest::Get(data)).await
+    }
+
+    async fn set(
+        &self,
+        state: &PlatformState,
+        data: SetStorageProperty,
+    ) -> Result<(), RippleError> {
+        state
+            .get_client()
+            .send_extn_request(DevicePersistenceRequest::Set(data))
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete(
+        &self,
+        state: &PlatformState,
+        data: DeleteStorageProperty,
+    ) -> Result<ExtnResponse, RippleError> {
+        Self::request(state, DevicePersistenceRequest::Delete(data)).await
+    }
+}
+
+/// scope -> namespace -> key
+type LocalStorage = HashMap<String, HashMap<String, HashMap<String, StorageData>>>;
+
+/// Keeps the properties in a JSON file, written on every change. Values keep the update time
+/// they were set with.
+#[derive(Debug, Clone)]
+pub struct LocalStorageBackend {
+    store: Arc<RwLock<FileStore<LocalStorage>>>,
+}
+
+impl LocalStorageBackend {
+    /// Starts empty when there is no file yet. A file which can not be loaded, nor its backup,
+    /// is moved aside instead of being overwritten by the next change.
+    pub fn new(path: String) -> LocalStorageBackend {
+        let store = match FileStore::load(path.clone()) {
+            Ok(store) => store,
+            Err(RippleError::MissingInput) => FileStore::new(path, HashMap::new()),
+            Err(_) => {
+                let corrupt_path = format!("{}.corrupt.{}", path, Utc::now().timestamp());
+                if let Err(e) = fs::rename(&path, &corrupt_path) {
+                    panic!(
+                        "properties in {} can not be loaded nor moved aside {:?}",
+                        path, e
+                    );
+                }
+                error!(
+                    "properties in {} can not be loaded, moved to {} and starting empty",
+                    path, corrupt_path
+                );
+                FileStore::new(path, HashMap::new())
+            }
+        };
+        LocalStorageBackend {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+
+    fn scope(scope: &Option<String>) -> String {
+        scope.clone().unwrap_or_else(|| DEFAULT_SCOPE.to_owned())
+    }
+
+    pub fn get_data(&self, data: &GetStorageProperty) -> Option<StorageData> {
+        let store = self.store.read().unwrap();
+        store
+            .value
+            .get(&Self::scope(&data.scope))
+            .and_then(|namespaces| namespaces.get(&data.namespace))
+            .and_then(|keys| keys.get(&data.key))
+            .cloned()
+    }
+
+    pub fn set_data(&self, data: SetStorageProperty) {
+        let mut store = self.store.write().unwrap();
+        store
+            .value
+            .entry(Self::scope(&data.scope))
+            .or_default()
+            .entry(data.namespace)
+            .or_default()
+            .insert(data.key, data.data);
+        store.sync();
+    }
+
+    pub fn delete_data(&self, data: &DeleteStorageProperty) -> bool {
+        let mut store = self.store.write().unwrap();
+        let scope = Self::scope(&data.scope);
+        let Some(namespaces) = store.value.get_mut(&scope) else {
+            return false;
+        };
+        let Some(keys) = namespaces.get_mut(&data.namespace) else {
+            return false;
+        };
+        let removed = keys.remove(&data.key).is_some();
+        if keys.is_empty() {
+            namespaces.remove(&data.namespace);
+        }
+        if namespaces.is_empty() {
+            store.value.remove(&scope);
+        }
+        if removed {
+            store.sync();
+        }
+        removed
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorageBackend {
+    async fn get(
+        &self,
+        _state: &PlatformState,
+        data: GetStorageProperty,
+    ) -> Result<ExtnResponse, RippleError> {
+        Ok(match self.get_data(&data) {
+            Some(storage_data) => ExtnResponse::StorageData(storage_data),
+            None => ExtnResponse::None(()),
+        })
+    }
+
+    async fn set(
+        &self,
+        _state: &PlatformState,
+        data: SetStorageProperty,
+    ) -> Result<(), RippleError> {
+        self.set_data(data);
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        _state: &PlatformState,
+        data: DeleteStorageProperty,
+    ) -> Result<ExtnResponse, RippleError> {
+        Ok(ExtnResponse::Boolean(self.delete_data(&data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::storage::storage_manager::{StorageManager, StorageManagerResponse};
+    use ripple_sdk::{serde_json::json, tokio};
+    use ripple_tdk::utils::test_utils::Mockable;
+
+    fn property(namespace: &str, key: &str, scope: Option<&str>) -> GetStorageProperty {
+        GetStorageProperty {
+            namespace: namespace.to_owned(),
+            key: key.to_owned(),
+            scope: scope.map(String::from),
+        }
+    }
+
+    fn set(backend: &LocalStorageBackend, property: &GetStorageProperty, data: StorageData) {
+        backend.set_data(SetStorageProperty {
+            namespace: property.namespace.clone(),
+            key: property.key.clone(),
+            data,
+            scope: property.scope.clone(),
+        });
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "ripple_local_storage_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_local_storage_backend() {
+        let path = temp_path("backend");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().to_string();
+        let backend = LocalStorageBackend::new(path.clone());
+
+        let device = property("Localization", "language", None);
+        let account = property("Localization", "language", Some("account"));
+        let data = StorageData {
+            value: json!("en"),
+            update_time: "2025-01-01T00:00:00+00:00".to_owned(),
+        };
+        set(&backend, &device, data.clone());
+        set(&backend, &account, StorageData::new(json!("fr")));
+        assert_eq!(backend.get_data(&device), Some(data.clone()));
+        assert_eq!(backend.get_data(&account).unwrap().value, json!("fr"));
+        assert_eq!(
+            backend.get_data(&property("Privacy", "language", None)),
+            None
+        );
+
+        // reloaded from the file
+        let backend = LocalStorageBackend::new(path.clone());
+        assert_eq!(backend.get_data(&device), Some(data));
+        assert!(backend.delete_data(&account));
+        assert!(!backend.delete_data(&account));
+        assert_eq!(backend.get_data(&account), None);
+        assert!(backend.get_data(&device).is_some());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_local_storage_backend_corrupt_file() {
+        let dir = temp_path("corrupt");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("local_storage").to_string_lossy().to_string();
+        std::fs::write(&path, "{\"device\":").unwrap();
+
+        let backend = LocalStorageBackend::new(path.clone());
+        let device = property("Localization", "language", None);
+        assert_eq!(backend.get_data(&device), None);
+        let moved: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(moved.len(), 1);
+        assert!(moved[0]
+            .to_string_lossy()
+            .starts_with(&format!("{}.corrupt.", path)));
+        assert_eq!(std::fs::read_to_string(&moved[0]).unwrap(), "{\"device\":");
+
+        // the next change starts a new file
+        set(&backend, &device, StorageData::new(json!("en")));
+        let backend = LocalStorageBackend::new(path);
+        assert_eq!(backend.get_data(&device).unwrap().value, json!("en"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_storage_manager_with_local_backend() {
+        let path = temp_path("manager");
+        let _ = std::fs::remove_file(&path);
+        let mut state = PlatformState::mock();
+        state.storage_backend =
+            Arc::new(LocalStorageBackend::new(path.to_string_lossy().to_string()));
+
+        let set = StorageManager::set_in_namespace(
+            &state,
+            "Device".to_owned(),
+            "name".to_owned(),
+            json!("Living Room"),
+            Some("account".to_owned()),
+            None,
+            None,
+        )
+        .await;
+        assert!(matches!(set, Ok(StorageManagerResponse::Ok(()))));
+        let name = StorageManager::get_string_from_namespace(
+            &state,
+            "Device".to_owned(),
+            "name",
+            Some("account".to_owned()),
+        )
+        .await;
+        assert!(matches!(name, Ok(StorageManagerResponse::Ok(v)) if v == "Living Room"));
+        let name =
+            StorageManager::get_string_from_namespace(&state, "Device".to_owned(), "name", None)
+                .await;
+        assert!(name.is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,683 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use hyper::Uri;
+use ripple_sdk::{
+    api::manifest::{
+        app_library::{
+            AppLibrary, AppLibraryChange, AppLibraryChangedEvent, AppLibraryState, DefaultLibrary,
+            APP_LIBRARY_CHANGED_EVENT,
+        },
+        apps::AppManifest,
+        device_manifest::{AppLibraryEntry, AppManifestLoad},
+    },
+    framework::file_store::FileStore,
+    log::{debug, error, info, warn},
+    tokio,
+    utils::error::RippleError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    service::apps::{
+        app_events::AppEvents,
+        remote_manifest::{
+            get_https_client, read_body, RemoteManifestCache, RemoteManifestFetcher, HTTP_TIMEOUT,
+        },
+    },
+    state::{cap::permitted_state::PermissionHandler, platform_state::PlatformState},
+};
+
+const APP_LIBRARY_FILE_NAME: &str = "app_library.json";
+const MAX_CATALOG_SIZE: usize = 4 * 1024 * 1024;
+
+/// Runtime changes to the library loaded from the app library file, applied on top of it on
+/// every start so later edits of the file are not lost.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AppLibraryOverrides {
+    #[serde(default)]
+    upserted: Vec<AppLibraryEntry>,
+    #[serde(default)]
+    removed: Vec<String>,
+    /// Apps added by the catalog sync, removed again once they leave the catalog.
+    #[serde(default)]
+    synced: Vec<String>,
+}
+
+impl AppLibraryOverrides {
+    fn apply(&self, library: &mut AppLibraryState) {
+        for app_id in &self.removed {
+            library.remove(app_id);
+        }
+        for entry in &self.upserted {
+            library.upsert(entry.clone());
+        }
+    }
+
+    fn upsert(&mut self, entry: AppLibraryEntry) {
+        self.removed.retain(|app_id| *app_id != entry.app_id);
+        match self.upserted.iter_mut().find(|e| e.app_id == entry.app_id) {
+            Some(existing) => *existing = entry,
+            None => self.upserted.push(entry),
+        }
+    }
+
+    fn remove(&mut self, app_id: &str, from_file: bool) {
+        self.upserted.retain(|e| e.app_id != app_id);
+        self.synced.retain(|id| id != app_id);
+        if from_file && !self.removed.iter().any(|id| id == app_id) {
+            self.removed.push(app_id.to_owned());
+        }
+    }
+}
+
+/// App library which can be changed at runtime, changes are persisted in the saved dir.
+#[derive(Debug, Clone)]
+pub struct DynamicAppLibraryState {
+    library: Arc<RwLock<AppLibraryState>>,
+    /// Apps of the app library file
+    file_apps: Arc<Vec<String>>,
+    overrides: Arc<RwLock<FileStore<AppLibraryOverrides>>>,
+    remote_manifests: RemoteManifestCache,
+}
+
+impl DynamicAppLibraryState {
+    pub fn new(default_apps: Vec<AppLibraryEntry>, saved_dir: &str) -> Self {
+        let path = Path::new(saved_dir)
+            .join(APP_LIBRARY_FILE_NAME)
+            .to_string_lossy()
+            .into_owned();
+        let overrides = FileStore::load(path.clone())
+            .unwrap_or_else(|_| FileStore::new(path, AppLibraryOverrides::default()));
+        let file_apps = default_apps.iter().map(|a| a.app_id.clone()).collect();
+        let mut library = AppLibraryState::new(default_apps);
+        overrides.value.apply(&mut library);
+        // start from the last good copies until the remote manifests are fetched again
+        let remote_manifests = RemoteManifestCache::new(saved_dir);
+        for entry in library.get_all_apps() {
+            if let AppManifestLoad::Remote(url) = &entry.manifest {
+                if let Some(cached) = remote_manifests
+                    .get(&entry.app_id)
+                    .filter(|c| c.url == *url)
+                {
+                    library.set_remote_manifest(&entry.app_id, cached.manifest);
+                }
+            }
+        }
+        DynamicAppLibraryState {
+            library: Arc::new(RwLock::new(library)),
+            file_apps: Arc::new(file_apps),
+            overrides: Arc::new(RwLock::new(overrides)),
+            remote_manifests,
+        }
+    }
+
+    pub fn get_state(&self) -> AppLibraryState {
+        self.library.read().unwrap().clone()
+    }
+
+    pub fn get_all_apps(&self) -> Vec<AppLibraryEntry> {
+        self.library.read().unwrap().get_all_apps()
+    }
+
+    pub fn get_app(&self, app_id: &str) -> Option<AppLibraryEntry> {
+        self.library.read().unwrap().get_app(app_id)
+    }
+
+    pub fn get_provider(&self, capability: &str) -> Option<String> {
+        AppLibrary::get_provider(&self.library.read().unwrap(), capability.to_owned())
+    }
+
+    pub fn get_manifest(&self, app_id: &str) -> Option<AppManifest> {
+        AppLibrary::get_manifest(&self.library.read().unwrap(), app_id)
+    }
+
+    fn get_synced_apps(&self) -> Vec<String> {
+        self.overrides.read().unwrap().value.synced.clone()
+    }
+
+    fn upsert(&self, entry: AppLibraryEntry, synced: bool) -> AppLibraryChangedEvent {
+        let app_id = entry.app_id.clone();
+        let (previous, capabilities) = {
+            let mut library = self.library.write().unwrap();
+            let before = library.providers.clone();
+            let previous = library.upsert(entry.clone());
+            (
+                previous,
+                AppLibrary::get_changed_providers(&before, &library.providers),
+            )
+        };
+        {
+            let mut overrides = self.overrides.write().unwrap();
+            overrides.value.upsert(entry);
+            if !synced {
+                // an app edited over RPC is no longer owned by the catalog
+                overrides.value.synced.retain(|id| *id != app_id);
+            } else if previous.is_none() {
+                overrides.value.synced.push(app_id.clone());
+            }
+            overrides.sync();
+        }
+        AppLibraryChangedEvent {
+            app_id,
+            change: match previous {
+                Some(_) => AppLibraryChange::Updated,
+                None => AppLibraryChange::Added,
+            },
+            capabilities,
+        }
+    }
+
+    /// Applies a fetched manifest of an app whose entry still points to `url`, returns an
+    /// event if the manifest changed.
+    fn set_remote_manifest(
+        &self,
+        app_id: &str,
+        url: &str,
+        manifest: AppManifest,
+    ) -> Option<AppLibraryChangedEvent> {
+        let mut library = self.library.write().unwrap();
+        match library.get_app(app_id).map(|entry| entry.manifest) {
+            Some(AppManifestLoad::Remote(current)) if current == url => {}
+            _ => return None,
+        }
+        if library.remote_manifests.get(app_id) == Some(&manifest) {
+            return None;
+        }
+        let before = library.providers.clone();
+        library.set_remote_manifest(app_id, manifest);
+        Some(AppLibraryChangedEvent {
+            app_id: app_id.to_owned(),
+            change: AppLibraryChange::Updated,
+            capabilities: AppLibrary::get_changed_providers(&before, &library.providers),
+        })
+    }
+
+    fn remove(&self, app_id: &str) -> Option<AppLibraryChangedEvent> {
+        let capabilities = {
+            let mut library = self.library.write().unwrap();
+            let before = library.providers.clone();
+            library.remove(app_id)?;
+            AppLibrary::get_changed_providers(&before, &library.providers)
+        };
+        self.remote_manifests.remove(app_id);
+        {
+            let mut overrides = self.overrides.write().unwrap();
+            let from_file = self.file_apps.iter().any(|id| id == app_id);
+            overrides.value.remove(app_id, from_file);
+            overrides.sync();
+        }
+        Some(AppLibraryChangedEvent {
+            app_id: app_id.to_owned(),
+            change: AppLibraryChange::Removed,
+            capabilities,
+        })
+    }
+}
+
+pub struct DynamicAppLibrary;
+
+impl DynamicAppLibrary {
+    pub async fn add(state: &PlatformState, entry: AppLibraryEntry) -> Result<(), RippleError> {
+        if state.app_library_state.get_app(&entry.app_id).is_some() {
+            return Err(RippleError::InvalidInput);
+        }
+        let app_id = entry.app_id.clone();
+        let event = state.app_library_state.upsert(entry, false);
+        Self::notify(state, event).await;
+        Self::spawn_refresh_manifest(state, app_id);
+        Ok(())
+    }
+
+    pub async fn update(state: &PlatformState, entry: AppLibraryEntry) -> Result<(), RippleError> {
+        if state.app_library_state.get_app(&entry.app_id).is_none() {
+            return Err(RippleError::NotAvailable);
+        }
+        let app_id = entry.app_id.clone();
+        let event = state.app_library_state.upsert(entry, false);
+        Self::notify(state, event).await;
+        Self::spawn_refresh_manifest(state, app_id);
+        Ok(())
+    }
+
+    pub async fn remove(state: &PlatformState, app_id: &str) -> Result<(), RippleError> {
+        let event = state
+            .app_library_state
+            .remove(app_id)
+            .ok_or(RippleError::NotAvailable)?;
+        Self::notify(state, event).await;
+        Ok(())
+    }
+
+    /// Tells the launcher and the apps listening to [APP_LIBRARY_CHANGED_EVENT] about the change.
+    async fn notify(state: &PlatformState, event: AppLibraryChangedEvent) {
+        info!("app library changed {:?}", event);
+        if state.has_internal_launcher() {
+            if let Err(e) = state.get_client().send_event(event.clone()) {
+                error!("send app library event error {:?}", e);
+            }
+        }
+        match serde_json::to_value(&event) {
+            Ok(value) => AppEvents::emit(state, APP_LIBRARY_CHANGED_EVENT, &value).await,
+            Err(e) => error!("app library event not serializable {:?}", e),
+        }
+    }
+
+    /// Fetches the remote manifest of the app, if it has one. When the manifest changed the
+    /// library is updated and, with cloud permissions, the permissions of the app are fetched
+    /// again. They are never derived from the manifest. Returns whether the manifest changed.
+    pub async fn refresh_manifest(
+        state: &PlatformState,
+        app_id: &str,
+    ) -> Result<bool, RippleError> {
+        let Some(AppManifestLoad::Remote(url)) = state
+            .app_library_state
+            .get_app(app_id)
+            .map(|entry| entry.manifest)
+        else {
+            return Ok(false);
+        };
+        let config = state
+            .get_device_manifest()
+            .get_app_manifest_fetch_configuration();
+        let manifest = RemoteManifestFetcher::fetch(
+            &state.app_library_state.remote_manifests,
+            &config,
+            app_id,
+            &url,
+        )
+        .await?;
+        let Some(event) = state
+            .app_library_state
+            .set_remote_manifest(app_id, &url, manifest)
+        else {
+            return Ok(false);
+        };
+        Self::notify(state, event).await;
+        if state.get_device_manifest().get_features().cloud_permissions {
+            if let Err(e) = PermissionHandler::cloud_fetch_and_store(state, app_id).await {
+                error!("permissions of {} not updated {:?}", app_id, e);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Refreshes the manifests of all apps with a remote manifest, returns the number changed.
+    pub async fn refresh_manifests(state: &PlatformState) -> usize {
+        let mut changed = 0;
+        for entry in state.app_library_state.get_all_apps() {
+            match Self::refresh_manifest(state, &entry.app_id).await {
+                Ok(true) => changed += 1,
+                Ok(false) => {}
+                Err(e) => warn!("manifest of {} not available {:?}", entry.app_id, e),
+            }
+        }
+        changed
+    }
+
+    fn spawn_refresh_manifest(state: &PlatformState, app_id: String) {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = Self::refresh_manifest(&state, &app_id).await {
+                warn!("manifest of {} not available {:?}", app_id, e);
+            }
+        });
+    }
+
+    async fn fetch_catalog(url: &str) -> Result<Vec<AppLibraryEntry>, RippleError> {
+        let uri: Uri = url.parse().map_err(|_| RippleError::InvalidInput)?;
+        let client = get_https_client()?;
+        let response = tokio::time::timeout(HTTP_TIMEOUT, client.get(uri))
+            .await
+            .map_err(|_| {
+                warn!("app catalog {} timed out", url);
+                RippleError::TimeoutError
+            })?
+            .map_err(|e| {
+                warn!("app catalog {} not reachable {:?}", url, e);
+                RippleError::NoResponse
+            })?;
+        if !response.status().is_success() {
+            warn!("app catalog {} returned {}", url, response.status());
+            return Err(RippleError::NoResponse);
+        }
+        let body = tokio::time::timeout(
+            HTTP_TIMEOUT,
+            read_body(response.into_body(), MAX_CATALOG_SIZE),
+        )
+        .await
+        .map_err(|_| {
+            warn!("app catalog {} timed out", url);
+            RippleError::TimeoutError
+        })??;
+        serde_json::from_slice::<DefaultLibrary>(&body)
+            .map(|library| library.default_library)
+            .map_err(|e| {
+                warn!("app catalog {} could not be parsed {:?}", url, e);
+                RippleError::ParseError
+            })
+    }
+
+    /// Applies the catalog at `url` to the library and returns the number of changed apps.
+    pub async fn sync(state: &PlatformState, url: &str) -> Result<usize, RippleError> {
+        let catalog = Self::fetch_catalog(url).await?;
+        let mut events = Vec::new();
+        for entry in &catalog {
+            if state.app_library_state.get_app(&entry.app_id).as_ref() != Some(entry) {
+                events.push(state.app_library_state.upsert(entry.clone(), true));
+            }
+        }
+        for app_id in state.app_library_state.get_synced_apps() {
+            if !catalog.iter().any(|e| e.app_id == app_id) {
+                events.extend(state.app_library_state.remove(&app_id));
+            }
+        }
+        let changed = events.len();
+        for event in events {
+            Self::notify(state, event).await;
+        }
+        if changed > 0 {
+            Self::refresh_manifests(state).await;
+        }
+        Ok(changed)
+    }
+
+    /// Keeps the library in sync with the catalog of the device manifest, if there is one.
+    pub fn start_sync(state: &PlatformState) {
+        let config = state
+            .get_device_manifest()
+            .get_app_library_sync_configuration();
+        let Some(url) = config.catalog_url else {
+            return;
+        };
+        let state = state.clone();
+        let interval = Duration::from_secs(config.interval_seconds.max(1));
+        tokio::spawn(async move {
+            loop {
+                match Self::sync(&state, &url).await {
+                    Ok(changed) => debug!("app catalog synced, {} apps changed", changed),
+                    Err(e) => warn!("app catalog sync failed {:?}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Fetches the remote manifests of the library and revalidates them every `ttl_seconds`.
+    pub fn start_manifest_refresh(state: &PlatformState) {
+        let state = state.clone();
+        let config = state
+            .get_device_manifest()
+            .get_app_manifest_fetch_configuration();
+        let interval = Duration::from_secs(config.ttl_seconds.max(1));
+        tokio::spawn(async move {
+            loop {
+                let changed = Self::refresh_manifests(&state).await;
+                debug!("app manifests refreshed, {} changed", changed);
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::{apps::remote_manifest::signed_payload, extn::ripple_client::RippleClient},
+        state::bootstrap_state::ChannelsState,
+    };
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use ripple_sdk::api::manifest::{
+        apps::AppManifest,
+        device_manifest::{AppManifestLoad, BootState},
+    };
+    use ripple_sdk::api::manifest::{device_manifest::DeviceManifest, extn_manifest::ExtnManifest};
+    use ripple_tdk::utils::test_utils::Mockable;
+    use serde_json::json;
+    use std::convert::Infallible;
+
+    fn entry(app_id: &str, provides: &[&str]) -> AppLibraryEntry {
+        let mut manifest = AppManifest::default();
+        manifest.capabilities.provided.required = provides.iter().map(|c| c.to_string()).collect();
+        AppLibraryEntry {
+            app_id: app_id.to_owned(),
+            manifest: AppManifestLoad::Embedded(manifest),
+            boot_state: BootState::Unloaded,
+        }
+    }
+
+    fn saved_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    fn platform_state(saved_dir: &str) -> PlatformState {
+        let mut state = PlatformState::mock();
+        state.app_library_state =
+            DynamicAppLibraryState::new(vec![entry("file_app", &["cap:a"])], saved_dir);
+        state
+    }
+
+    #[tokio::test]
+    async fn test_add_update_remove() {
+        let dir = saved_dir("ripple_app_library_test");
+        let state = platform_state(&dir);
+
+        DynamicAppLibrary::add(&state, entry("new_app", &["cap:b"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            DynamicAppLibrary::add(&state, entry("new_app", &[])).await,
+            Err(RippleError::InvalidInput)
+        );
+        assert_eq!(
+            state.app_library_state.get_provider("cap:b"),
+            Some("new_app".to_owned())
+        );
+
+        DynamicAppLibrary::update(&state, entry("file_app", &["cap:c"]))
+            .await
+            .unwrap();
+        assert_eq!(state.app_library_state.get_provider("cap:a"), None);
+        assert_eq!(
+            DynamicAppLibrary::update(&state, entry("missing", &[])).await,
+            Err(RippleError::NotAvailable)
+        );
+
+        DynamicAppLibrary::remove(&state, "new_app").await.unwrap();
+        assert_eq!(
+            DynamicAppLibrary::remove(&state, "new_app").await,
+            Err(RippleError::NotAvailable)
+        );
+
+        // the changes are applied on top of the app library file on the next start
+        let reloaded = DynamicAppLibraryState::new(
+            vec![entry("file_app", &["cap:a"]), entry("other_app", &[])],
+            &dir,
+        );
+        assert_eq!(
+            reloaded.get_all_apps(),
+            vec![entry("file_app", &["cap:c"]), entry("other_app", &[])]
+        );
+
+        DynamicAppLibrary::remove(&state, "file_app").await.unwrap();
+        let reloaded = DynamicAppLibraryState::new(vec![entry("file_app", &["cap:a"])], &dir);
+        assert!(reloaded.get_all_apps().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_changed_event() {
+        let dir = saved_dir("ripple_app_library_event_test");
+        let library = DynamicAppLibraryState::new(vec![entry("file_app", &["cap:a"])], &dir);
+
+        let event = library.upsert(entry("file_app", &["cap:b"]), false);
+        assert_eq!(event.change, AppLibraryChange::Updated);
+        assert_eq!(event.capabilities, vec!["cap:a", "cap:b"]);
+
+        let event = library.remove("file_app").unwrap();
+        assert_eq!(event.change, AppLibraryChange::Removed);
+        assert_eq!(event.capabilities, vec!["cap:b"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_sync() {
+        let catalog = Arc::new(RwLock::new(json!({
+            "default_library": [
+                entry("file_app", &["cap:a"]),
+                entry("catalog_app", &["cap:b"]),
+            ]
+        })));
+        let served = catalog.clone();
+        let make_service = make_service_fn(move |_| {
+            let served = served.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_: Request<Body>| {
+                    let body = served.read().unwrap().to_string();
+                    async move { Ok::<_, Infallible>(Response::new(Body::from(body))) }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let url = format!("http://{}/catalog", server.local_addr());
+        tokio::spawn(server);
+
+        let dir = saved_dir("ripple_app_library_sync_test");
+        let state = platform_state(&dir);
+        assert_eq!(DynamicAppLibrary::sync(&state, &url).await, Ok(1));
+        assert_eq!(
+            state.app_library_state.get_provider("cap:b"),
+            Some("catalog_app".to_owned())
+        );
+        // nothing changed since the last sync
+        assert_eq!(DynamicAppLibrary::sync(&state, &url).await, Ok(0));
+
+        // apps leaving the catalog are removed unless they came from the app library file
+        *catalog.write().unwrap() = json!({ "default_library": [] });
+        assert_eq!(DynamicAppLibrary::sync(&state, &url).await, Ok(1));
+        assert_eq!(
+            state.app_library_state.get_all_apps(),
+            vec![entry("file_app", &["cap:a"])]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_remote_manifest() {
+        let mut manifest = AppManifest::default();
+        manifest.capabilities.provided.required = vec!["discovery:interest".to_owned()];
+        let body = serde_json::to_string(&manifest).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(
+            Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                .unwrap()
+                .as_ref(),
+        )
+        .unwrap();
+        let payload = signed_payload("remote_app", 1, body.as_bytes());
+        let signature = json!({
+            "version": 1,
+            "signature": STANDARD.encode(key_pair.sign(&payload).as_ref()),
+        })
+        .to_string();
+        let make_service = make_service_fn(move |_| {
+            let (body, signature) = (body.clone(), signature.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let body = if request.uri().path().ends_with(".sig") {
+                        signature.clone()
+                    } else {
+                        body.clone()
+                    };
+                    async move { Ok::<_, Infallible>(Response::new(Body::from(body))) }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let url = format!("http://{}/remote_app.json", server.local_addr());
+        tokio::spawn(server);
+
+        let dir = saved_dir("ripple_app_library_remote_test");
+        // served over plain http, so the manifest has to be signed
+        let mut device_manifest = DeviceManifest::default();
+        device_manifest.configuration.saved_dir = dir.clone();
+        device_manifest.configuration.app_manifest_fetch.public_key =
+            Some(STANDARD.encode(key_pair.public_key().as_ref()));
+        let state = PlatformState::new(
+            ExtnManifest::default(),
+            device_manifest,
+            RippleClient::new(ChannelsState::new()),
+            vec![entry("file_app", &["cap:a"])],
+            None,
+        );
+        let remote_entry = AppLibraryEntry {
+            app_id: "remote_app".to_owned(),
+            manifest: AppManifestLoad::Remote(url),
+            boot_state: BootState::Unloaded,
+        };
+        state.app_library_state.upsert(remote_entry.clone(), false);
+        assert_eq!(state.app_library_state.get_manifest("remote_app"), None);
+
+        assert_eq!(
+            DynamicAppLibrary::refresh_manifest(&state, "remote_app").await,
+            Ok(true)
+        );
+        assert_eq!(
+            state.app_library_state.get_manifest("remote_app"),
+            Some(manifest.clone())
+        );
+        assert_eq!(
+            state.app_library_state.get_provider("discovery:interest"),
+            Some("remote_app".to_owned())
+        );
+        // unchanged manifest
+        assert_eq!(
+            DynamicAppLibrary::refresh_manifest(&state, "remote_app").await,
+            Ok(false)
+        );
+
+        // permissions are not derived from the manifest
+        assert_eq!(
+            state
+                .cap_state
+                .permitted_state
+                .get_app_permissions("remote_app"),
+            None
+        );
+
+        // the cached copy is used after a restart
+        let reloaded = DynamicAppLibraryState::new(vec![remote_entry], &dir);
+        assert_eq!(reloaded.get_manifest("remote_app"), Some(manifest));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -42,6 +42,7 @@ use ripple_sdk::{
         },
         gateway::rpc_gateway_api::{AppIdentification, CallerSession},
     },
+    framework::file_store::FileStore,
     log::{debug, error, warn},
     serde_json::{self},
     tokio::sync::{mpsc, oneshot},
@@ -68,7 +69,7 @@ use ripple_sdk::{
     log::info,
     tokio::{self, sync::mpsc::Receiver},
 };
-use serde_json::{json, Value};
+use serde_json::json;
 
 use crate::{
     broker::{broker_utils::BrokerUtils, endpoint_broker::BrokerCallback},
@@ -192,30 +193,14 @@ impl AppManagerState {
         }
     }
 
-    fn restore_data_from_storage(storage_path: &str, file_name: &str) -> Result<Value, String> {
-        let file_path = std::path::Path::new(storage_path).join(file_name);
-        let file = fs::OpenOptions::new()
-            .read(true)
-            .open(file_path)
-            .map_err(|error| format!("Failed to open storage file: {}", error))?;
-
-        let data: Value = serde_json::from_reader(&file)
-            .map_err(|error| format!("Failed to read data from storage file: {}", error))?;
-        Ok(data)
-    }
-
     fn load_persisted_data<T>(storage_path: &str, file_name: &str) -> HashMap<String, T>
     where
-        T: serde::de::DeserializeOwned,
+        T: serde::Serialize + serde::de::DeserializeOwned + Clone,
     {
-        // let storage_path = Self::get_storage_path(saved_dir);
-        let stored_value_res = Self::restore_data_from_storage(storage_path, file_name);
-        if let Ok(stored_value) = stored_value_res {
-            let map_res: Result<HashMap<String, T>, _> = serde_json::from_value(stored_value);
-            map_res.unwrap_or_default()
-        } else {
-            HashMap::new()
-        }
+        let file_path = std::path::Path::new(storage_path).join(file_name);
+        FileStore::load(file_path.display().to_string())
+            .map(|store| store.value)
+            .unwrap_or_default()
     }
 
     fn get_storage_path(saved_dir: &str, dir_name: &str) -> String {
@@ -252,22 +237,15 @@ impl AppManagerState {
         file_name: &str,
     ) -> bool
     where
-        T: serde::Serialize + std::fmt::Debug,
+        T: serde::Serialize + serde::de::DeserializeOwned + Clone,
     {
-        let map = { data.read().unwrap() };
+        let map = { data.read().unwrap().clone() };
         let path = std::path::Path::new(persist_path).join(file_name);
-        if let Ok(file) = fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)
-        {
-            return serde_json::to_writer_pretty(&file, &serde_json::to_value(&*map).unwrap())
-                .is_ok();
-        } else {
-            error!("unable to create file: {}:", file_name);
+        if let Err(e) = FileStore::new(path.display().to_string(), map).persist() {
+            error!("unable to persist file: {}: {:?}", file_name, e);
+            return false;
         }
-        false
+        true
     }
 
     pub fn get_persisted_app_title_for_app_id(&self, app_id: &str) -> Option<String> {
@@ -1862,4 +1840,29 @@ mod tests {
             LifecycleState::Initializing
         ),);
     }
+
+    #[test]
+    fn test_persisted_app_state() {
+        let saved_dir = std::env::temp_dir().join(format!(
+            "ripple_app_manager_state_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&saved_dir);
+        let saved_dir = saved_dir.display().to_string();
+        let state = AppManagerState::new(&saved_dir);
+        assert!(state.persist_app_title("app", "App"));
+        assert!(state.persist_app_title("app", "Renamed App"));
+        assert!(state.persist_migrated_state("app", "privacy".to_owned()));
+
+        let state = AppManagerState::new(&saved_dir);
+        assert_eq!(
+            state.get_persisted_app_title_for_app_id("app"),
+            Some("Renamed App".to_owned())
+        );
+        assert_eq!(
+            state.get_persisted_migrated_state_for_app_id("app"),
+            Some(vec!["privacy".to_owned()])
+        );
+        let _ = fs::remove_dir_all(saved_dir);
+    }
 }
@@ -16,5 +16,7 @@
 //
 
 pub mod app_events;
+pub mod app_library;
 pub mod delegated_launcher_handler;
 pub mod provider_broker;
+pub mod remote_manifest;
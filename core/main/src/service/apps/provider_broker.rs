@@ -15,12 +15,13 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use arrayvec::ArrayVec;
+use futures::future::{BoxFuture, FutureExt};
 use ripple_sdk::{
     api::{
         firebolt::{
-            fb_capabilities::{CapEvent, FireboltCap},
+            fb_capabilities::{CapEvent, FireboltCap, CAPABILITY_NOT_AVAILABLE},
             fb_general::ListenRequest,
+            fb_lifecycle::LifecycleState,
             fb_lifecycle_management::{
                 LifecycleManagementEventRequest, LifecycleManagementProviderEvent,
             },
@@ -31,17 +32,23 @@ use ripple_sdk::{
             },
         },
         gateway::rpc_gateway_api::{CallContext, CallerSession},
+        manifest::device_manifest::{ProviderSessionConfiguration, QueueOverflowPolicy},
     },
     log::{debug, error, info, warn},
     serde_json,
-    tokio::sync::oneshot,
+    tokio::{
+        self,
+        sync::{oneshot, Notify},
+    },
     utils::channel_utils::oneshot_send_and_log,
     uuid::Uuid,
 };
 
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use crate::{
@@ -49,7 +56,12 @@ use crate::{
     state::{cap::cap_state::CapState, platform_state::PlatformState},
 };
 
-const REQUEST_QUEUE_CAPACITY: usize = 3;
+/// Callers give up on a provider response after this time.
+// TODO: Add to config
+pub const DEFAULT_PROVIDER_RESPONSE_TIMEOUT_MS: u64 = 15000;
+// failovers end early enough for callers to get the error instead of timing out themselves
+const MAX_FAILOVER_DURATION: Duration =
+    Duration::from_millis(DEFAULT_PROVIDER_RESPONSE_TIMEOUT_MS - 1000);
 
 #[derive(Debug)]
 pub enum ProviderError {
@@ -61,9 +73,12 @@ pub enum ProviderError {
 
 #[derive(Default, Clone)]
 pub struct ProviderBrokerState {
-    provider_methods: Arc<RwLock<HashMap<String, ProviderMethod>>>,
+    // providers of each capability:method in the order they registered
+    provider_methods: Arc<RwLock<HashMap<String, Vec<ProviderMethod>>>>,
     active_sessions: Arc<RwLock<HashMap<String, ProviderSession>>>,
-    request_queue: Arc<RwLock<ArrayVec<ProviderBrokerRequest, REQUEST_QUEUE_CAPACITY>>>,
+    request_queue: Arc<RwLock<VecDeque<QueuedRequest>>>,
+    // notified whenever a request leaves the queue
+    queue_space: Arc<Notify>,
 }
 
 impl std::fmt::Debug for ProviderBrokerState {
@@ -84,8 +99,66 @@ struct ProviderMethod {
 struct ProviderSession {
     caller: ProviderCaller,
     provider: ProviderMethod,
-    _capability: String,
+    capability: String,
+    method: String,
+    request: ProviderRequestPayload,
+    failover: Failover,
     focused: bool,
+    started_at: Instant,
+}
+
+/// What a request takes along when it fails over from one provider to the next.
+#[derive(Debug)]
+struct Failover {
+    // next providers in rank order
+    fallbacks: Vec<ProviderMethod>,
+    // no failover is started after this time
+    deadline: Instant,
+    invoked: InvokedProvider,
+}
+
+/// The app of the provider handling a request, which changes when the request fails over to
+/// another provider. Read it once the response arrived to get the app which answered.
+#[derive(Debug, Clone, Default)]
+pub struct InvokedProvider(Arc<RwLock<Option<String>>>);
+
+impl InvokedProvider {
+    pub fn get_app_id(&self) -> Option<String> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set_app_id(&self, app_id: &str) {
+        *self.0.write().unwrap() = Some(app_id.to_owned());
+    }
+}
+
+#[derive(Debug)]
+struct QueuedRequest {
+    id: String,
+    request: ProviderBrokerRequest,
+    invoked: InvokedProvider,
+    queued_at: Instant,
+}
+
+/// An active or queued provider session, the id of an active session is its correlation id.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderSessionInfo {
+    pub id: String,
+    pub capability: String,
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_app_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caller_app_id: Option<String>,
+    pub focused: bool,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProviderSessions {
+    pub active: Vec<ProviderSessionInfo>,
+    pub queued: Vec<ProviderSessionInfo>,
 }
 
 #[derive(Debug)]
@@ -146,15 +219,38 @@ impl ProviderBroker {
         method: String,
         provider: CallContext,
     ) {
-        let mut provider_methods = pst.provider_broker_state.provider_methods.write().unwrap();
         let cap_method = format!("{}:{}", capability, method);
-        if let Some(method) = provider_methods.get(&cap_method) {
-            // unregister the capability if it is provided by the session
-            // that is making the unregister call
-            if method.provider.session_id == provider.session_id {
-                provider_methods.remove(&cap_method);
+        let unavailable = {
+            let mut provider_methods = pst.provider_broker_state.provider_methods.write().unwrap();
+            match provider_methods.get_mut(&cap_method) {
+                Some(methods) => {
+                    // unregister the capability if it is provided by the session
+                    // that is making the unregister call
+                    methods.retain(|m| m.provider.session_id != provider.session_id);
+                    let unavailable = methods.is_empty();
+                    if unavailable {
+                        provider_methods.remove(&cap_method);
+                    }
+                    unavailable
+                }
+                None => false,
             }
-            ProviderBroker::remove_request(pst, &capability);
+        };
+        if !unavailable {
+            return;
+        }
+        // queued requests of the method can not be served anymore
+        while let Some(queued) = Self::remove_queued(pst, |q| {
+            Self::cap_method(&q.request.capability, &q.request.method) == cap_method
+        }) {
+            oneshot_send_and_log(
+                queued.request.tx,
+                Self::session_error(format!(
+                    "Provider unavailable for {}",
+                    queued.request.method
+                )),
+                "ProviderUnavailable",
+            );
         }
 
         // TODO Add permissions
@@ -176,18 +272,18 @@ impl ProviderBroker {
         AppEvents::add_listener(pst, event_name.clone(), provider.clone(), listen_request);
         {
             let mut provider_methods = pst.provider_broker_state.provider_methods.write().unwrap();
-            provider_methods.insert(
-                cap_method,
-                ProviderMethod {
-                    event_name,
-                    provider,
-                },
-            );
+            let methods = provider_methods.entry(cap_method).or_default();
+            // a new registration of the same app replaces the previous one
+            methods.retain(|m| m.provider.app_id != provider.app_id);
+            methods.push(ProviderMethod {
+                event_name,
+                provider,
+            });
         }
         let existing = ProviderBroker::remove_request(pst, &capability);
-        if let Some(request) = existing {
+        if let Some(queued) = existing {
             info!("register_provider: Found pending provider request, invoking");
-            ProviderBroker::invoke_method(pst, request).await;
+            ProviderBroker::invoke(pst, queued.request, queued.invoked).await;
         }
 
         CapState::emit(
@@ -202,108 +298,436 @@ impl ProviderBroker {
     pub fn get_provider_methods(pst: &PlatformState) -> ProviderResult {
         let provider_methods = pst.provider_broker_state.provider_methods.read().unwrap();
         let mut result: HashMap<String, Vec<String>> = HashMap::new();
-        let caps_keys = provider_methods.keys();
-        let all_caps = caps_keys.cloned().collect::<Vec<String>>();
-        for cap in all_caps {
-            if let Some(provider) = provider_methods.get(&cap) {
-                if let Some(list) = result.get_mut(&provider.provider.app_id) {
-                    list.push(provider.event_name.clone());
-                } else {
-                    result.insert(
-                        provider.provider.app_id.clone(),
-                        vec![provider.event_name.clone()],
-                    );
-                }
+        for provider in provider_methods.values().flatten() {
+            if let Some(list) = result.get_mut(&provider.provider.app_id) {
+                list.push(provider.event_name.clone());
+            } else {
+                result.insert(
+                    provider.provider.app_id.clone(),
+                    vec![provider.event_name.clone()],
+                );
             }
         }
         ProviderResult::new(result)
     }
 
+    fn is_foreground(pst: &PlatformState, app_id: &str) -> bool {
+        pst.app_manager_state
+            .get(app_id)
+            .is_some_and(|app| app.state == LifecycleState::Foreground)
+    }
+
+    /// Returns the providers of the method ordered by the rankings of the device manifest, the
+    /// provider declared in the app library and the app in the foreground. Among equally ranked
+    /// providers the one which registered last comes first.
+    fn get_ranked_providers(
+        pst: &PlatformState,
+        capability: &str,
+        cap_method: &str,
+    ) -> Vec<ProviderMethod> {
+        let mut providers = {
+            let provider_methods = pst.provider_broker_state.provider_methods.read().unwrap();
+            provider_methods
+                .get(cap_method)
+                .cloned()
+                .unwrap_or_default()
+        };
+        providers.reverse();
+        let config = pst.get_device_manifest().get_provider_configuration();
+        let ranking = config.rankings.get(capability);
+        let library_provider = pst.app_library_state.get_provider(capability);
+        providers.sort_by_cached_key(|p| {
+            let app_id = &p.provider.app_id;
+            (
+                ranking
+                    .and_then(|r| r.iter().position(|a| a == app_id))
+                    .unwrap_or(usize::MAX),
+                library_provider.as_ref() != Some(app_id),
+                config.prefer_foreground && !Self::is_foreground(pst, app_id),
+            )
+        });
+        providers
+    }
+
+    fn is_registered(pst: &PlatformState, cap_method: &str, provider: &ProviderMethod) -> bool {
+        let provider_methods = pst.provider_broker_state.provider_methods.read().unwrap();
+        provider_methods.get(cap_method).is_some_and(|methods| {
+            methods
+                .iter()
+                .any(|m| m.provider.session_id == provider.provider.session_id)
+        })
+    }
+
+    fn cap_method(capability: &str, method: &str) -> String {
+        format!(
+            "{}:{}",
+            capability,
+            FireboltOpenRpcMethod::name_with_lowercase_module(method)
+        )
+    }
+
+    fn get_session_config(
+        pst: &PlatformState,
+        capability: &str,
+    ) -> Option<ProviderSessionConfiguration> {
+        pst.get_device_manifest()
+            .get_provider_configuration()
+            .sessions
+            .get(capability)
+            .cloned()
+    }
+
+    fn session_error(message: String) -> ProviderResponsePayload {
+        ProviderResponsePayload::GenericError(GenericProviderError {
+            code: CAPABILITY_NOT_AVAILABLE,
+            message,
+            data: None,
+        })
+    }
+
+    fn is_busy(pst: &PlatformState, capability: &str) -> bool {
+        let state = &pst.provider_broker_state;
+        state
+            .active_sessions
+            .read()
+            .unwrap()
+            .values()
+            .any(|s| s.capability == capability)
+            || state
+                .request_queue
+                .read()
+                .unwrap()
+                .iter()
+                .any(|q| q.request.capability == capability)
+    }
+
+    /// Sends the request to the provider. Requests for a capability with a queue wait while
+    /// another session of the capability is active. The returned provider follows the request
+    /// to the provider which handles it.
     pub async fn invoke_method(
         pst: &PlatformState,
         request: ProviderBrokerRequest,
-    ) -> Option<String> {
-        let mut provider_app_id = None;
+    ) -> InvokedProvider {
+        let invoked = InvokedProvider::default();
+        Self::invoke(pst, request, invoked.clone()).await;
+        invoked
+    }
 
-        let cap_method = format!(
-            "{}:{}",
-            request.capability,
-            FireboltOpenRpcMethod::name_with_lowercase_module(&request.method)
-        );
+    async fn invoke(pst: &PlatformState, request: ProviderBrokerRequest, invoked: InvokedProvider) {
+        if let Some(config) = Self::get_session_config(pst, &request.capability) {
+            if let Some(queue_size) = config.queue_size {
+                if Self::is_busy(pst, &request.capability) {
+                    Self::enqueue(pst, request, invoked, queue_size, &config).await;
+                    return;
+                }
+            }
+        }
+        Self::invoke_now(pst, request, invoked).await;
+    }
 
-        debug!("invoking provider for {}", cap_method);
+    async fn enqueue(
+        pst: &PlatformState,
+        request: ProviderBrokerRequest,
+        invoked: InvokedProvider,
+        queue_size: usize,
+        config: &ProviderSessionConfiguration,
+    ) {
+        let deadline = config
+            .timeout
+            .map(|t| Instant::now() + Duration::from_millis(t));
+        let queue_space = pst.provider_broker_state.queue_space.clone();
+        loop {
+            // created before the check so a place freed in between is not missed
+            let notified = queue_space.notified();
+            {
+                let mut request_queue = pst.provider_broker_state.request_queue.write().unwrap();
+                let queued = request_queue
+                    .iter()
+                    .filter(|q| q.request.capability == request.capability)
+                    .count();
+                if queued < queue_size {
+                    let id = Uuid::new_v4().to_string();
+                    debug!("queued provider request {} {}", id, request.capability);
+                    request_queue.push_back(QueuedRequest {
+                        id: id.clone(),
+                        request,
+                        invoked,
+                        queued_at: Instant::now(),
+                    });
+                    if let Some(deadline) = deadline {
+                        let pst_c = pst.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep_until(deadline.into()).await;
+                            Self::expire_queued_request(&pst_c, &id);
+                        });
+                    }
+                    return;
+                }
+            }
+            let waited = match (config.overflow, deadline) {
+                (QueueOverflowPolicy::Reject, _) => false,
+                (QueueOverflowPolicy::Wait, Some(deadline)) => {
+                    tokio::time::timeout_at(deadline.into(), notified)
+                        .await
+                        .is_ok()
+                }
+                (QueueOverflowPolicy::Wait, None) => {
+                    notified.await;
+                    true
+                }
+            };
+            if !waited {
+                warn!("provider queue of {} is full", request.capability);
+                oneshot_send_and_log(
+                    request.tx,
+                    Self::session_error(format!("Provider queue full for {}", request.method)),
+                    "ProviderQueueFull",
+                );
+                return;
+            }
+        }
+    }
 
-        let provider_opt = {
-            let provider_methods = pst.provider_broker_state.provider_methods.read().unwrap();
-            provider_methods.get(&cap_method).cloned()
+    fn expire_queued_request(pst: &PlatformState, id: &str) {
+        if let Some(queued) = Self::remove_queued(pst, |q| q.id == id) {
+            warn!(
+                "provider request {} for {} expired in the queue",
+                id, queued.request.capability
+            );
+            oneshot_send_and_log(
+                queued.request.tx,
+                Self::session_error(format!(
+                    "Provider session timed out for {}",
+                    queued.request.method
+                )),
+                "ProviderQueueTimeout",
+            );
+        }
+    }
+
+    fn remove_queued<P>(pst: &PlatformState, predicate: P) -> Option<QueuedRequest>
+    where
+        P: Fn(&QueuedRequest) -> bool,
+    {
+        let removed = {
+            let mut request_queue = pst.provider_broker_state.request_queue.write().unwrap();
+            let index = request_queue.iter().position(predicate)?;
+            request_queue.remove(index)
         };
+        pst.provider_broker_state.queue_space.notify_waiters();
+        removed
+    }
 
-        if let Some(provider_method) = provider_opt {
-            let event_name = provider_method.event_name.clone();
-            let req_params = request.request.clone();
+    /// Starts the next queued request of the capability once a session ended.
+    async fn start_next_request(pst: &PlatformState, capability: &str) {
+        // requests of callers which went away are dropped
+        while let Some(queued) = Self::remove_queued(pst, |q| q.request.capability == capability) {
+            if !queued.request.tx.is_closed() {
+                debug!("starting queued provider request {}", queued.id);
+                Self::invoke_now(pst, queued.request, queued.invoked).await;
+                return;
+            }
+        }
+    }
 
-            let mut app_id_opt = request.app_id.clone();
-            if app_id_opt.is_none() {
-                if let ProviderRequestPayload::Generic(ref payload) = request.request {
-                    if let Some(app_id_value) = payload.get("appId") {
-                        app_id_opt = app_id_value.as_str().map(|s| s.to_string());
-                    }
+    async fn invoke_now(
+        pst: &PlatformState,
+        request: ProviderBrokerRequest,
+        invoked: InvokedProvider,
+    ) {
+        let cap_method = Self::cap_method(&request.capability, &request.method);
+
+        debug!("invoking provider for {}", cap_method);
+
+        let mut providers = Self::get_ranked_providers(pst, &request.capability, &cap_method);
+        if providers.is_empty() {
+            // If no provider found, send error response
+            oneshot_send_and_log(
+                request.tx,
+                ProviderResponsePayload::GenericError(GenericProviderError {
+                    code: 32001,
+                    message: format!("Provider not found for {}", request.method),
+                    data: None,
+                }),
+                "ProviderNotFound",
+            );
+            return;
+        }
+
+        let mut app_id_opt = request.app_id.clone();
+        if app_id_opt.is_none() {
+            if let ProviderRequestPayload::Generic(ref payload) = request.request {
+                if let Some(app_id_value) = payload.get("appId") {
+                    app_id_opt = app_id_value.as_str().map(|s| s.to_string());
                 }
             }
+        }
 
-            let c_id =
-                ProviderBroker::start_provider_session(pst, request, provider_method.clone());
-            if let Some(app_id) = app_id_opt {
+        let deadline = Instant::now() + MAX_FAILOVER_DURATION;
+        match app_id_opt {
+            Some(app_id) => {
+                // requests for a specific app do not fail over
                 debug!("Sending request to specific app {}", app_id);
-                AppEvents::emit_to_app(
-                    pst,
-                    app_id.clone(),
-                    &event_name,
-                    &serde_json::to_value(ProviderRequest {
-                        correlation_id: c_id,
-                        parameters: req_params,
-                    })
-                    .unwrap(),
-                )
-                .await;
-                provider_app_id = Some(app_id.clone());
-            } else {
-                debug!("Broadcasting request to all the apps!!");
-                AppEvents::emit(
-                    pst,
-                    &event_name,
-                    &serde_json::to_value(ProviderRequest {
-                        correlation_id: c_id,
-                        parameters: req_params,
-                    })
-                    .unwrap(),
-                )
-                .await;
-                provider_app_id = Some(provider_method.provider.app_id);
+                let Some(index) = providers.iter().position(|p| p.provider.app_id == app_id) else {
+                    oneshot_send_and_log(
+                        request.tx,
+                        Self::session_error(format!(
+                            "{} does not provide {}",
+                            app_id, request.method
+                        )),
+                        "ProviderNotFound",
+                    );
+                    return;
+                };
+                let provider_method = providers.remove(index);
+                let failover = Failover {
+                    fallbacks: Vec::new(),
+                    deadline,
+                    invoked,
+                };
+                Self::dispatch(pst, request, provider_method, failover).await;
             }
-        } else {
-            // If no provider found, send error response
-            request
-                .tx
-                .send(ProviderResponsePayload::GenericError(
-                    GenericProviderError {
-                        code: 32001,
-                        message: format!("Provider not found for {}", request.method),
-                        data: None,
-                    },
-                ))
-                .unwrap();
+            None => {
+                let provider_method = providers.remove(0);
+                let failover = Failover {
+                    fallbacks: providers,
+                    deadline,
+                    invoked,
+                };
+                Self::dispatch(pst, request, provider_method, failover).await;
+            }
+        }
+    }
+
+    async fn dispatch(
+        pst: &PlatformState,
+        request: ProviderBrokerRequest,
+        provider_method: ProviderMethod,
+        failover: Failover,
+    ) {
+        let app_id = provider_method.provider.app_id.clone();
+        let event_name = provider_method.event_name.clone();
+        let req_params = request.request.clone();
+        let session_timeout =
+            Self::get_session_config(pst, &request.capability).and_then(|c| c.timeout);
+        let failover_deadline = failover.deadline;
+        failover.invoked.set_app_id(&app_id);
+        let c_id = ProviderBroker::start_provider_session(pst, request, provider_method, failover);
+        AppEvents::emit_to_app(
+            pst,
+            app_id,
+            &event_name,
+            &serde_json::to_value(ProviderRequest {
+                correlation_id: c_id.clone(),
+                parameters: req_params,
+            })
+            .unwrap(),
+        )
+        .await;
+
+        if let Some(timeout) = pst
+            .get_device_manifest()
+            .get_provider_configuration()
+            .response_timeout
+        {
+            let pst_c = pst.clone();
+            let c_id = c_id.clone();
+            let timeout = Duration::from_millis(timeout)
+                .min(failover_deadline.saturating_duration_since(Instant::now()));
+            tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                Self::on_response_timeout(pst_c, c_id).await;
+            });
         }
+        if let Some(timeout) = session_timeout {
+            let pst_c = pst.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(timeout)).await;
+                Self::on_session_timeout(pst_c, c_id).await;
+            });
+        }
+    }
+
+    // Boxed as it leads back to dispatch through the next queued request.
+    fn on_session_timeout(pst: PlatformState, c_id: String) -> BoxFuture<'static, ()> {
+        async move {
+            let session = {
+                let mut active_sessions =
+                    pst.provider_broker_state.active_sessions.write().unwrap();
+                active_sessions.remove(&c_id)
+            };
+            if let Some(session) = session {
+                warn!("provider session {} for {} timed out", c_id, session.method);
+                pst.metrics
+                    .record_provider_session_timeout(&session.capability);
+                let error = Self::session_error(format!(
+                    "Provider session timed out for {}",
+                    session.method
+                ));
+                Self::end_session(&pst, session, error).await;
+            }
+        }
+        .boxed()
+    }
+
+    // Boxed as it leads back to dispatch through the failover.
+    fn on_response_timeout(pst: PlatformState, c_id: String) -> BoxFuture<'static, ()> {
+        async move {
+            let session = {
+                let mut active_sessions =
+                    pst.provider_broker_state.active_sessions.write().unwrap();
+                match active_sessions.get(&c_id) {
+                    // a focused provider is interacting with the user
+                    Some(session) if !session.focused => active_sessions.remove(&c_id),
+                    _ => None,
+                }
+            };
+            if let Some(session) = session {
+                warn!(
+                    "provider {} did not respond to {} in time",
+                    session.provider.provider.app_id, session.method
+                );
+                pst.metrics
+                    .record_provider_session_timeout(&session.capability);
+                Self::failover(&pst, session).await;
+            }
+        }
+        .boxed()
+    }
 
-        provider_app_id
+    /// Sends the request of the session to the next provider which is still registered, the
+    /// caller gets an error when there is none left or the failover deadline passed.
+    async fn failover(pst: &PlatformState, mut session: ProviderSession) {
+        let cap_method = Self::cap_method(&session.capability, &session.method);
+        let fallbacks = &mut session.failover.fallbacks;
+        fallbacks.retain(|p| Self::is_registered(pst, &cap_method, p));
+        if fallbacks.is_empty() || Instant::now() >= session.failover.deadline {
+            let error =
+                Self::session_error(format!("No provider responded for {}", session.method));
+            Self::end_session(pst, session, error).await;
+            return;
+        }
+        let next = fallbacks.remove(0);
+        info!("failing over {} to {}", cap_method, next.provider.app_id);
+        let request = ProviderBrokerRequest {
+            capability: session.capability,
+            method: session.method,
+            caller: session.caller.session,
+            request: session.request,
+            tx: session.caller.tx,
+            app_id: None,
+        };
+        Self::dispatch(pst, request, next, session.failover).await;
     }
 
     fn start_provider_session(
         pst: &PlatformState,
         request: ProviderBrokerRequest,
         provider: ProviderMethod,
+        failover: Failover,
     ) -> String {
         let c_id = Uuid::new_v4().to_string();
+        pst.metrics.record_provider_session(&request.capability);
         let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
         debug!("started provider session {} {}", c_id, request.capability);
         active_sessions.insert(
@@ -314,83 +738,193 @@ impl ProviderBroker {
                     tx: request.tx,
                 },
                 provider,
-                _capability: request.capability,
+                capability: request.capability,
+                method: request.method,
+                request: request.request,
+                failover,
                 focused: false,
+                started_at: Instant::now(),
             },
         );
         c_id
     }
 
+    fn release_focus(pst: &PlatformState, session: &ProviderSession) {
+        if session.focused {
+            let app_id = session.provider.provider.app_id.clone();
+            let event = LifecycleManagementEventRequest::Provide(
+                LifecycleManagementProviderEvent::Remove(app_id),
+            );
+            let client = pst.clone().get_client();
+            if let Err(e) = client.send_event(event) {
+                error!("send event error {:?}", e);
+            }
+        }
+    }
+
+    /// Completes a session which was removed from the active sessions and starts the next
+    /// queued request of its capability.
+    async fn end_session(
+        pst: &PlatformState,
+        session: ProviderSession,
+        result: ProviderResponsePayload,
+    ) {
+        Self::release_focus(pst, &session);
+        oneshot_send_and_log(session.caller.tx, result, "ProviderResponse");
+        Self::start_next_request(pst, &session.capability).await;
+    }
+
+    /// Ends an active or queued session with an error to the caller, returns false if there is
+    /// no session with the id.
+    pub async fn cancel_session(pst: &PlatformState, id: &str) -> bool {
+        let session = {
+            let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
+            active_sessions.remove(id)
+        };
+        if let Some(session) = session {
+            info!("cancelled provider session {}", id);
+            let error =
+                Self::session_error(format!("Provider session cancelled for {}", session.method));
+            Self::end_session(pst, session, error).await;
+            return true;
+        }
+        if let Some(queued) = Self::remove_queued(pst, |q| q.id == id) {
+            info!("cancelled queued provider request {}", id);
+            oneshot_send_and_log(
+                queued.request.tx,
+                Self::session_error(format!(
+                    "Provider session cancelled for {}",
+                    queued.request.method
+                )),
+                "ProviderCancel",
+            );
+            return true;
+        }
+        false
+    }
+
+    pub fn get_sessions(pst: &PlatformState) -> ProviderSessions {
+        let state = &pst.provider_broker_state;
+        let mut active: Vec<ProviderSessionInfo> = state
+            .active_sessions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(c_id, s)| ProviderSessionInfo {
+                id: c_id.clone(),
+                capability: s.capability.clone(),
+                method: s.method.clone(),
+                provider_app_id: Some(s.provider.provider.app_id.clone()),
+                caller_app_id: s.caller.session.app_id.clone(),
+                focused: s.focused,
+                elapsed_ms: s.started_at.elapsed().as_millis() as u64,
+            })
+            .collect();
+        // oldest first like the queue
+        active.sort_by(|a, b| b.elapsed_ms.cmp(&a.elapsed_ms));
+        let queued = state
+            .request_queue
+            .read()
+            .unwrap()
+            .iter()
+            .map(|q| ProviderSessionInfo {
+                id: q.id.clone(),
+                capability: q.request.capability.clone(),
+                method: q.request.method.clone(),
+                provider_app_id: None,
+                caller_app_id: q.request.caller.app_id.clone(),
+                focused: false,
+                elapsed_ms: q.queued_at.elapsed().as_millis() as u64,
+            })
+            .collect();
+        ProviderSessions { active, queued }
+    }
+
     pub async fn provider_response(pst: &PlatformState, resp: ProviderResponse) {
         debug!(
             "provider_response, {}, {:?}",
             resp.correlation_id, resp.result
         );
-        let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
-        match active_sessions.remove(&resp.correlation_id) {
-            Some(session) => {
-                oneshot_send_and_log(session.caller.tx, resp.result, "ProviderResponse");
-                if session.focused {
-                    let app_id = session.provider.provider.app_id;
-                    let event = LifecycleManagementEventRequest::Provide(
-                        LifecycleManagementProviderEvent::Remove(app_id),
-                    );
-                    let client = pst.clone().get_client();
-                    if let Err(e) = client.send_event(event) {
-                        error!("send event error {:?}", e);
-                    }
-                }
-            }
+        let session = {
+            let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
+            active_sessions.remove(&resp.correlation_id)
+        };
+        match session {
+            Some(session) => Self::end_session(pst, session, resp.result).await,
             None => {
                 error!("Ignored provider response because there was no active session waiting")
             }
         }
     }
 
-    fn cleanup_caps_for_unregister(pst: &PlatformState, session_id: String) -> Vec<String> {
+    /// Removes the sessions, queued requests and providers of the session being unregistered.
+    /// Returns the capabilities left without a provider, the sessions whose provider went away
+    /// and the capabilities of the sessions whose caller went away.
+    fn cleanup_caps_for_unregister(
+        pst: &PlatformState,
+        session_id: String,
+    ) -> (Vec<String>, Vec<ProviderSession>, Vec<String>) {
+        while Self::remove_queued(pst, |q| {
+            q.request.caller.session_id.as_ref() == Some(&session_id)
+        })
+        .is_some()
+        {}
+        let mut ended_caps = Vec::new();
         let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
         let cid_keys = active_sessions.keys();
         let all_cids = cid_keys.cloned().collect::<Vec<String>>();
-        let mut clear_cids = Vec::<String>::new();
+        let mut orphaned = Vec::new();
         // find all the sessions where either the caller or the provider are being unregistered and clear that session
-        // the oneshot for the caller should then get descoped and called with an error
+        // the oneshot for the caller should then get descoped and called with an error,
+        // sessions which lost their provider fail over to the next one
         for cid in all_cids {
             if let Some(session) = active_sessions.get(&cid) {
-                if session.provider.provider.session_id == session_id {
-                    clear_cids.push(cid.clone());
-                }
-                if let Some(caller_session_id) = &session.caller.session.session_id {
-                    if *caller_session_id == session_id {
-                        clear_cids.push(cid);
+                let caller_left = session
+                    .caller
+                    .session
+                    .session_id
+                    .as_ref()
+                    .is_some_and(|caller_session_id| *caller_session_id == session_id);
+                let provider_left = session.provider.provider.session_id == session_id;
+                if caller_left {
+                    if let Some(session) = active_sessions.remove(&cid) {
+                        Self::release_focus(pst, &session);
+                        ended_caps.push(session.capability);
+                    }
+                } else if provider_left {
+                    if let Some(session) = active_sessions.remove(&cid) {
+                        orphaned.push(session);
                     }
                 }
             }
         }
 
-        for cid in clear_cids {
-            active_sessions.remove(&cid);
-        }
         let mut provider_methods = pst.provider_broker_state.provider_methods.write().unwrap();
         // find all providers for the session being unregistered
-        // remove the provided capability
+        // remove the provided capability when no other provider is left
         let mut clear_caps = Vec::new();
-        let caps_keys = provider_methods.keys();
-        let all_caps = caps_keys.cloned().collect::<Vec<String>>();
-        for cap in all_caps {
-            if let Some(provider) = provider_methods.get(&cap) {
-                if provider.provider.session_id == session_id {
-                    clear_caps.push(cap);
-                }
+        for (cap, methods) in provider_methods.iter_mut() {
+            let count = methods.len();
+            methods.retain(|m| m.provider.session_id != session_id);
+            if methods.is_empty() && count > 0 {
+                clear_caps.push(cap.clone());
             }
         }
-        for cap in clear_caps.clone() {
-            provider_methods.remove(&cap);
+        for cap in clear_caps.iter() {
+            provider_methods.remove(cap);
         }
-        clear_caps
+        (clear_caps, orphaned, ended_caps)
     }
 
     pub async fn unregister_session(pst: &PlatformState, session_id: String) {
-        let cleaned_caps = Self::cleanup_caps_for_unregister(&pst.clone(), session_id);
+        let (cleaned_caps, orphaned, ended_caps) =
+            Self::cleanup_caps_for_unregister(&pst.clone(), session_id);
+        for session in orphaned {
+            Self::failover(pst, session).await;
+        }
+        for capability in ended_caps {
+            Self::start_next_request(pst, &capability).await;
+        }
         let caps: Vec<FireboltCap> = cleaned_caps
             .iter()
             .map(|x| FireboltCap::Full(x.clone()))
@@ -400,15 +934,8 @@ impl ProviderBroker {
         }
     }
 
-    fn remove_request(pst: &PlatformState, capability: &String) -> Option<ProviderBrokerRequest> {
-        let mut request_queue = pst.provider_broker_state.request_queue.write().unwrap();
-        let mut iter = request_queue.iter();
-        let cap = iter.position(|request| request.capability.eq(capability));
-        if let Some(index) = cap {
-            let request = request_queue.remove(index);
-            return Some(request);
-        }
-        None
+    fn remove_request(pst: &PlatformState, capability: &String) -> Option<QueuedRequest> {
+        Self::remove_queued(pst, |q| q.request.capability.eq(capability))
     }
 
     pub async fn focus(
@@ -435,3 +962,401 @@ impl ProviderBroker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::extn::ripple_client::RippleClient, state::bootstrap_state::ChannelsState,
+    };
+    use ripple_sdk::api::manifest::{device_manifest::DeviceManifest, extn_manifest::ExtnManifest};
+    use ripple_tdk::utils::test_utils::Mockable;
+
+    const CAPABILITY: &str = "xrn:firebolt:capability:usergrant:pinchallenge";
+    const METHOD: &str = "challenge";
+
+    fn platform_state(rankings: Vec<&str>, response_timeout: Option<u64>) -> PlatformState {
+        let mut manifest = DeviceManifest::default();
+        let config = &mut manifest.configuration.provider_configuration;
+        config.rankings.insert(
+            CAPABILITY.to_owned(),
+            rankings.into_iter().map(String::from).collect(),
+        );
+        config.response_timeout = response_timeout;
+        PlatformState::new(
+            ExtnManifest::default(),
+            manifest,
+            RippleClient::new(ChannelsState::new()),
+            Vec::new(),
+            None,
+        )
+    }
+
+    fn queued_platform_state(
+        queue_size: usize,
+        overflow: QueueOverflowPolicy,
+        timeout: Option<u64>,
+    ) -> PlatformState {
+        let mut manifest = DeviceManifest::default();
+        manifest
+            .configuration
+            .provider_configuration
+            .sessions
+            .insert(
+                CAPABILITY.to_owned(),
+                ProviderSessionConfiguration {
+                    timeout,
+                    queue_size: Some(queue_size),
+                    overflow,
+                },
+            );
+        PlatformState::new(
+            ExtnManifest::default(),
+            manifest,
+            RippleClient::new(ChannelsState::new()),
+            Vec::new(),
+            None,
+        )
+    }
+
+    async fn register(pst: &PlatformState, app_id: &str) -> CallContext {
+        let mut ctx = CallContext::mock();
+        ctx.app_id = app_id.to_owned();
+        ctx.session_id = format!("{}_session", app_id);
+        ProviderBroker::register_provider(
+            pst,
+            CAPABILITY.to_owned(),
+            METHOD.to_owned(),
+            "pinchallenge.onRequestChallenge".to_owned(),
+            ctx.clone(),
+            ListenRequest { listen: true },
+        )
+        .await;
+        ctx
+    }
+
+    fn new_request() -> (
+        ProviderBrokerRequest,
+        oneshot::Receiver<ProviderResponsePayload>,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let request = ProviderBrokerRequest {
+            capability: CAPABILITY.to_owned(),
+            method: METHOD.to_owned(),
+            caller: CallerSession::default(),
+            request: ProviderRequestPayload::Generic(serde_json::Value::Null),
+            tx,
+            app_id: None,
+        };
+        (request, rx)
+    }
+
+    async fn respond(pst: &PlatformState, c_id: String) {
+        ProviderBroker::provider_response(
+            pst,
+            ProviderResponse {
+                correlation_id: c_id,
+                result: ProviderResponsePayload::GenericResponse(serde_json::json!(true)),
+            },
+        )
+        .await
+    }
+
+    fn assert_session_error(response: ProviderResponsePayload) {
+        match response {
+            ProviderResponsePayload::GenericError(e) => {
+                assert_eq!(e.code, CAPABILITY_NOT_AVAILABLE)
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    // the correlation id and provider app of the only active session
+    fn active_session(pst: &PlatformState) -> Option<(String, String)> {
+        let active_sessions = pst.provider_broker_state.active_sessions.read().unwrap();
+        active_sessions
+            .iter()
+            .next()
+            .map(|(c_id, s)| (c_id.clone(), s.provider.provider.app_id.clone()))
+    }
+
+    fn ranked_app_ids(pst: &PlatformState) -> Vec<String> {
+        let cap_method = ProviderBroker::cap_method(CAPABILITY, METHOD);
+        ProviderBroker::get_ranked_providers(pst, CAPABILITY, &cap_method)
+            .into_iter()
+            .map(|p| p.provider.app_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ranked_providers() {
+        let pst = platform_state(vec!["b"], None);
+        for app_id in ["a", "b", "c"] {
+            register(&pst, app_id).await;
+        }
+        // ranked app first, then the latest registration
+        assert_eq!(ranked_app_ids(&pst), vec!["b", "c", "a"]);
+
+        // registering again replaces the previous registration of the app
+        register(&pst, "a").await;
+        assert_eq!(ranked_app_ids(&pst), vec!["b", "a", "c"]);
+
+        let pst = platform_state(vec![], None);
+        for app_id in ["a", "b"] {
+            register(&pst, app_id).await;
+        }
+        assert_eq!(ranked_app_ids(&pst), vec!["b", "a"]);
+        assert_eq!(ProviderBroker::get_provider_methods(&pst).entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_timeout() {
+        let pst = platform_state(vec!["a", "b"], Some(200));
+        register(&pst, "a").await;
+        register(&pst, "b").await;
+
+        let (request, rx) = new_request();
+        let invoked = ProviderBroker::invoke_method(&pst, request).await;
+        assert_eq!(invoked.get_app_id(), Some("a".to_owned()));
+        assert_eq!(active_session(&pst).unwrap().1, "a");
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let (c_id, app_id) = active_session(&pst).unwrap();
+        assert_eq!(app_id, "b");
+        ProviderBroker::provider_response(
+            &pst,
+            ProviderResponse {
+                correlation_id: c_id,
+                result: ProviderResponsePayload::GenericResponse(serde_json::json!(true)),
+            },
+        )
+        .await;
+        assert!(matches!(
+            rx.await.unwrap(),
+            ProviderResponsePayload::GenericResponse(_)
+        ));
+        // the app which answered
+        assert_eq!(invoked.get_app_id(), Some("b".to_owned()));
+
+        // the caller gets an error once every provider timed out
+        let (request, rx) = new_request();
+        ProviderBroker::invoke_method(&pst, request).await;
+        match rx.await.unwrap() {
+            ProviderResponsePayload::GenericError(e) => {
+                assert_eq!(e.code, CAPABILITY_NOT_AVAILABLE)
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(active_session(&pst).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failover_deadline() {
+        let pst = platform_state(vec!["a", "b"], Some(100));
+        register(&pst, "a").await;
+        register(&pst, "b").await;
+
+        let (request, rx) = new_request();
+        ProviderBroker::invoke_method(&pst, request).await;
+        {
+            let mut active_sessions = pst.provider_broker_state.active_sessions.write().unwrap();
+            let session = active_sessions.values_mut().next().unwrap();
+            session.failover.deadline = Instant::now();
+        }
+        // b is left but there is no time for another provider
+        assert_session_error(rx.await.unwrap());
+        assert!(active_session(&pst).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_for_app() {
+        let pst = platform_state(vec!["a", "b"], Some(100));
+        register(&pst, "a").await;
+        register(&pst, "b").await;
+
+        let (mut request, rx) = new_request();
+        request.app_id = Some("b".to_owned());
+        let invoked = ProviderBroker::invoke_method(&pst, request).await;
+        assert_eq!(invoked.get_app_id(), Some("b".to_owned()));
+        let (c_id, app_id) = active_session(&pst).unwrap();
+        assert_eq!(app_id, "b");
+        respond(&pst, c_id).await;
+        assert!(matches!(
+            rx.await.unwrap(),
+            ProviderResponsePayload::GenericResponse(_)
+        ));
+
+        let (mut request, rx) = new_request();
+        request.app_id = Some("c".to_owned());
+        ProviderBroker::invoke_method(&pst, request).await;
+        assert_session_error(rx.await.unwrap());
+        assert!(active_session(&pst).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_unregister() {
+        let pst = platform_state(vec![], None);
+        let a = register(&pst, "a").await;
+        let b = register(&pst, "b").await;
+
+        let (request, mut rx) = new_request();
+        ProviderBroker::invoke_method(&pst, request).await;
+        assert_eq!(active_session(&pst).unwrap().1, "b");
+
+        ProviderBroker::unregister_session(&pst, b.session_id).await;
+        assert_eq!(active_session(&pst).unwrap().1, "a");
+        assert!(rx.try_recv().is_err());
+
+        ProviderBroker::unregister_session(&pst, a.session_id).await;
+        assert!(matches!(
+            rx.await.unwrap(),
+            ProviderResponsePayload::GenericError(_)
+        ));
+        assert!(ProviderBroker::get_provider_methods(&pst)
+            .entries
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queue_reject_overflow() {
+        let pst = queued_platform_state(1, QueueOverflowPolicy::Reject, None);
+        register(&pst, "a").await;
+
+        let (first, mut first_rx) = new_request();
+        assert!(ProviderBroker::invoke_method(&pst, first)
+            .await
+            .get_app_id()
+            .is_some());
+        let (second, mut second_rx) = new_request();
+        let second_invoked = ProviderBroker::invoke_method(&pst, second).await;
+        assert!(second_invoked.get_app_id().is_none());
+        let (third, third_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, third).await;
+        assert_session_error(third_rx.await.unwrap());
+
+        let sessions = ProviderBroker::get_sessions(&pst);
+        assert_eq!(sessions.active.len(), 1);
+        assert_eq!(sessions.queued.len(), 1);
+        assert_eq!(sessions.active[0].provider_app_id, Some("a".to_owned()));
+
+        // the queued request starts once the active session completed
+        let (c_id, _) = active_session(&pst).unwrap();
+        respond(&pst, c_id.clone()).await;
+        assert!(first_rx.try_recv().is_ok());
+        let (next_id, _) = active_session(&pst).unwrap();
+        assert_ne!(next_id, c_id);
+        assert!(ProviderBroker::get_sessions(&pst).queued.is_empty());
+        respond(&pst, next_id).await;
+        assert!(second_rx.try_recv().is_ok());
+        assert_eq!(second_invoked.get_app_id(), Some("a".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_unregister_rejects_queued_requests() {
+        let pst = queued_platform_state(2, QueueOverflowPolicy::Reject, None);
+        let a = register(&pst, "a").await;
+        let b = register(&pst, "b").await;
+
+        let (first, _first_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, first).await;
+        let (second, mut second_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, second).await;
+
+        let unregister = |ctx: CallContext| {
+            ProviderBroker::register_or_unregister_provider(
+                &pst,
+                CAPABILITY.to_owned(),
+                METHOD.to_owned(),
+                "pinchallenge.onRequestChallenge".to_owned(),
+                ctx,
+                ListenRequest { listen: false },
+            )
+        };
+        // another provider is left to serve the queued request
+        unregister(b).await;
+        assert!(second_rx.try_recv().is_err());
+        assert_eq!(ProviderBroker::get_sessions(&pst).queued.len(), 1);
+
+        unregister(a).await;
+        match second_rx.await.unwrap() {
+            ProviderResponsePayload::GenericError(e) => {
+                assert_eq!(e.code, CAPABILITY_NOT_AVAILABLE);
+                assert_eq!(e.message, format!("Provider unavailable for {}", METHOD));
+            }
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(ProviderBroker::get_sessions(&pst).queued.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_overflow() {
+        let pst = queued_platform_state(1, QueueOverflowPolicy::Wait, None);
+        register(&pst, "a").await;
+
+        let (first, _first_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, first).await;
+        let (second, _second_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, second).await;
+
+        let pst_c = pst.clone();
+        let (third, mut third_rx) = new_request();
+        let waiting =
+            tokio::spawn(async move { ProviderBroker::invoke_method(&pst_c, third).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        assert!(third_rx.try_recv().is_err());
+
+        // completing the active session frees a place in the queue
+        let (c_id, _) = active_session(&pst).unwrap();
+        respond(&pst, c_id).await;
+        waiting.await.unwrap();
+        assert_eq!(ProviderBroker::get_sessions(&pst).queued.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_timeout() {
+        let pst = queued_platform_state(1, QueueOverflowPolicy::Reject, Some(200));
+        register(&pst, "a").await;
+
+        let (first, first_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, first).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (second, mut second_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, second).await;
+
+        // the abandoned session ends with an error and the queued request takes over
+        assert_session_error(first_rx.await.unwrap());
+        let sessions = ProviderBroker::get_sessions(&pst);
+        assert_eq!(sessions.active.len(), 1);
+        assert!(sessions.queued.is_empty());
+        assert!(second_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_session() {
+        let pst = queued_platform_state(2, QueueOverflowPolicy::Reject, None);
+        register(&pst, "a").await;
+
+        let (first, first_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, first).await;
+        let (second, second_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, second).await;
+        let (third, mut third_rx) = new_request();
+        ProviderBroker::invoke_method(&pst, third).await;
+
+        let sessions = ProviderBroker::get_sessions(&pst);
+        let queued_id = sessions.queued[0].id.clone();
+        assert!(ProviderBroker::cancel_session(&pst, &queued_id).await);
+        assert_session_error(second_rx.await.unwrap());
+
+        let active_id = sessions.active[0].id.clone();
+        assert!(ProviderBroker::cancel_session(&pst, &active_id).await);
+        assert_session_error(first_rx.await.unwrap());
+        assert!(!ProviderBroker::cancel_session(&pst, &active_id).await);
+
+        let sessions = ProviderBroker::get_sessions(&pst);
+        assert_eq!(sessions.active.len(), 1);
+        assert!(sessions.queued.is_empty());
+        assert!(third_rx.try_recv().is_err());
+    }
+}
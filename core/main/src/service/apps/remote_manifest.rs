@@ -0,0 +1,525 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::{
+    body::HttpBody,
+    client::HttpConnector,
+    header::{ETAG, IF_NONE_MATCH},
+    Body, Client, Request, StatusCode, Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use ripple_sdk::{
+    api::manifest::{apps::AppManifest, device_manifest::AppManifestFetchConfiguration},
+    framework::file_store::FileStore,
+    log::{debug, error, warn},
+    tokio,
+    utils::{digest_utils::verify_ed25519, error::RippleError, tls_utils::TlsConfig},
+};
+use serde::{Deserialize, Serialize};
+
+const APP_MANIFESTS_FILE_NAME: &str = "app_manifests.json";
+const SIGNATURE_SUFFIX: &str = ".sig";
+// Limit of a whole download, the signature included.
+pub const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_MANIFEST_SIZE: usize = 1024 * 1024;
+const MAX_SIGNATURE_SIZE: usize = 1024;
+
+pub type HttpsClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+pub fn get_https_client() -> Result<HttpsClient, RippleError> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_tls_config(TlsConfig::default().client_config()?)
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Ok(Client::builder().build(connector))
+}
+
+/// Reads the body, failing once it grows beyond `limit` bytes.
+pub async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, RippleError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| RippleError::NoResponse)?;
+        if bytes.len() + chunk.len() > limit {
+            warn!("response body larger than {} bytes", limit);
+            return Err(RippleError::InvalidOutput);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Last good copy of a remote manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedManifest {
+    pub url: String,
+    pub etag: Option<String>,
+    /// Seconds since the epoch of the last fetch or revalidation.
+    pub fetched_at: u64,
+    /// Version of the signed manifest, older versions are not accepted after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    pub manifest: AppManifest,
+}
+
+impl CachedManifest {
+    fn is_fresh(&self, ttl_seconds: u64) -> bool {
+        now() < self.fetched_at.saturating_add(ttl_seconds)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Remote manifests by app id, persisted in the saved dir so the last good copy survives a
+/// restart.
+#[derive(Debug, Clone)]
+pub struct RemoteManifestCache {
+    store: Arc<RwLock<FileStore<HashMap<String, CachedManifest>>>>,
+}
+
+impl RemoteManifestCache {
+    pub fn new(saved_dir: &str) -> Self {
+        let path = Path::new(saved_dir)
+            .join(APP_MANIFESTS_FILE_NAME)
+            .to_string_lossy()
+            .into_owned();
+        let store =
+            FileStore::load(path.clone()).unwrap_or_else(|_| FileStore::new(path, HashMap::new()));
+        RemoteManifestCache {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+
+    pub fn get(&self, app_id: &str) -> Option<CachedManifest> {
+        self.store.read().unwrap().value.get(app_id).cloned()
+    }
+
+    fn set(&self, app_id: &str, cached: CachedManifest) {
+        let mut store = self.store.write().unwrap();
+        store.value.insert(app_id.to_owned(), cached);
+        store.sync();
+    }
+
+    pub fn remove(&self, app_id: &str) {
+        let mut store = self.store.write().unwrap();
+        if store.value.remove(app_id).is_some() {
+            store.sync();
+        }
+    }
+}
+
+enum Download {
+    NotModified,
+    Fetched {
+        etag: Option<String>,
+        version: Option<u64>,
+        manifest: AppManifest,
+    },
+}
+
+/// Detached signature served next to a manifest. The signature covers the app id, the version
+/// and the manifest, so a manifest signed for another app or an older version is refused.
+#[derive(Debug, Deserialize)]
+struct ManifestSignature {
+    version: u64,
+    signature: String,
+}
+
+/// Returns the bytes covered by the signature of version `version` of the manifest of the app.
+pub fn signed_payload(app_id: &str, version: u64, manifest: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{}\n{}\n", app_id, version).into_bytes();
+    payload.extend_from_slice(manifest);
+    payload
+}
+
+pub struct RemoteManifestFetcher;
+
+impl RemoteManifestFetcher {
+    /// Returns the manifest of the app served at `url`. The cached copy is used while it is
+    /// fresh, then revalidated with its ETag. If the fetch or the signature check fails the last
+    /// good copy is returned. Manifests are only accepted over https or with a signature, a
+    /// signed manifest older than the last accepted one is refused.
+    pub async fn fetch(
+        cache: &RemoteManifestCache,
+        config: &AppManifestFetchConfiguration,
+        app_id: &str,
+        url: &str,
+    ) -> Result<AppManifest, RippleError> {
+        let cached = cache.get(app_id).filter(|c| c.url == url);
+        if let Some(cached) = &cached {
+            if cached.is_fresh(config.ttl_seconds) {
+                return Ok(cached.manifest.clone());
+            }
+        }
+        let etag = cached.as_ref().and_then(|c| c.etag.clone());
+        // the version floor is kept when the url of the app changes
+        let min_version = cache.get(app_id).and_then(|c| c.version);
+        let download = tokio::time::timeout(
+            HTTP_TIMEOUT,
+            Self::download(app_id, url, etag, min_version, config),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            warn!("app manifest {} timed out", url);
+            Err(RippleError::TimeoutError)
+        });
+        match (download, cached) {
+            (
+                Ok(Download::Fetched {
+                    etag,
+                    version,
+                    manifest,
+                }),
+                _,
+            ) => {
+                debug!("manifest of {} fetched from {}", app_id, url);
+                cache.set(
+                    app_id,
+                    CachedManifest {
+                        url: url.to_owned(),
+                        etag,
+                        fetched_at: now(),
+                        version,
+                        manifest: manifest.clone(),
+                    },
+                );
+                Ok(manifest)
+            }
+            (Ok(Download::NotModified), Some(mut cached)) => {
+                debug!("manifest of {} not modified", app_id);
+                cached.fetched_at = now();
+                let manifest = cached.manifest.clone();
+                cache.set(app_id, cached);
+                Ok(manifest)
+            }
+            (Ok(Download::NotModified), None) => Err(RippleError::InvalidOutput),
+            (Err(e), Some(cached)) => {
+                warn!(
+                    "manifest of {} not updated from {} {:?}, using the last good copy",
+                    app_id, url, e
+                );
+                Ok(cached.manifest)
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    async fn download(
+        app_id: &str,
+        url: &str,
+        etag: Option<String>,
+        min_version: Option<u64>,
+        config: &AppManifestFetchConfiguration,
+    ) -> Result<Download, RippleError> {
+        let uri: Uri = url.parse().map_err(|_| RippleError::InvalidInput)?;
+        if uri.scheme_str() != Some("https") && config.public_key.is_none() {
+            error!(
+                "app manifest {} needs https or a public_key to check it",
+                url
+            );
+            return Err(RippleError::InvalidInput);
+        }
+        let client = get_https_client()?;
+        let mut request = Request::get(uri);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let request = request
+            .body(Body::empty())
+            .map_err(|_| RippleError::InvalidInput)?;
+        let response = client.request(request).await.map_err(|e| {
+            warn!("app manifest {} not reachable {:?}", url, e);
+            RippleError::NoResponse
+        })?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Download::NotModified);
+        }
+        if !response.status().is_success() {
+            warn!("app manifest {} returned {}", url, response.status());
+            return Err(RippleError::NoResponse);
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let body = read_body(response.into_body(), MAX_MANIFEST_SIZE).await?;
+        let version = match &config.public_key {
+            Some(public_key) => {
+                let version = Self::verify(&client, app_id, url, &body, public_key).await?;
+                if min_version.is_some_and(|min| version < min) {
+                    error!(
+                        "app manifest {} version {} is older than {:?}",
+                        url, version, min_version
+                    );
+                    return Err(RippleError::InvalidInput);
+                }
+                Some(version)
+            }
+            None => None,
+        };
+        let manifest = serde_json::from_slice(&body).map_err(|e| {
+            warn!("app manifest {} could not be parsed {:?}", url, e);
+            RippleError::ParseError
+        })?;
+        Ok(Download::Fetched {
+            etag,
+            version,
+            manifest,
+        })
+    }
+
+    /// Checks the manifest against the detached signature served next to it and returns its
+    /// signed version.
+    async fn verify(
+        client: &HttpsClient,
+        app_id: &str,
+        url: &str,
+        manifest: &[u8],
+        public_key: &str,
+    ) -> Result<u64, RippleError> {
+        let public_key = STANDARD.decode(public_key.trim()).map_err(|_| {
+            error!("app manifest public key is not valid base64");
+            RippleError::InvalidInput
+        })?;
+        let signature_url = format!("{}{}", url, SIGNATURE_SUFFIX);
+        let uri = signature_url
+            .parse()
+            .map_err(|_| RippleError::InvalidInput)?;
+        let response = client.get(uri).await.map_err(|e| {
+            warn!(
+                "app manifest signature {} not reachable {:?}",
+                signature_url, e
+            );
+            RippleError::NoResponse
+        })?;
+        if !response.status().is_success() {
+            warn!(
+                "app manifest signature {} returned {}",
+                signature_url,
+                response.status()
+            );
+            return Err(RippleError::NoResponse);
+        }
+        let body = read_body(response.into_body(), MAX_SIGNATURE_SIZE).await?;
+        let signed: ManifestSignature =
+            serde_json::from_slice(&body).map_err(|_| RippleError::ParseError)?;
+        let signature = STANDARD
+            .decode(signed.signature.trim())
+            .map_err(|_| RippleError::ParseError)?;
+        let payload = signed_payload(app_id, signed.version, manifest);
+        verify_ed25519(&public_key, &payload, &signature)
+            .inspect_err(|_| error!("app manifest {} signature mismatch", url))?;
+        Ok(signed.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use ripple_sdk::tokio;
+    use serde_json::json;
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    struct Served {
+        etag: String,
+        manifest: String,
+        signature: String,
+    }
+
+    fn manifest(name: &str) -> AppManifest {
+        AppManifest {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn cache(name: &str) -> (RemoteManifestCache, String) {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir = dir.to_string_lossy().into_owned();
+        (RemoteManifestCache::new(&dir), dir)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_revalidate() {
+        let key_pair = Ed25519KeyPair::from_pkcs8(
+            Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                .unwrap()
+                .as_ref(),
+        )
+        .unwrap();
+        let sign = |app_id: &str, version: u64, body: &str| {
+            let payload = signed_payload(app_id, version, body.as_bytes());
+            json!({
+                "version": version,
+                "signature": STANDARD.encode(key_pair.sign(&payload).as_ref()),
+            })
+            .to_string()
+        };
+        let body = serde_json::to_string(&manifest("v1")).unwrap();
+        let served = Arc::new(RwLock::new(Served {
+            etag: "\"v1\"".to_owned(),
+            signature: sign("app", 1, &body),
+            manifest: body,
+        }));
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let (server_served, server_requests) = (served.clone(), requests.clone());
+        let make_service = make_service_fn(move |_| {
+            let (served, requests) = (server_served.clone(), server_requests.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let served = served.read().unwrap();
+                    let response = if request.uri().path().ends_with(SIGNATURE_SUFFIX) {
+                        Response::new(Body::from(served.signature.clone()))
+                    } else {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        let if_none_match = request.headers().get(IF_NONE_MATCH);
+                        if if_none_match.and_then(|v| v.to_str().ok()) == Some(&served.etag) {
+                            Response::builder()
+                                .status(StatusCode::NOT_MODIFIED)
+                                .body(Body::empty())
+                                .unwrap()
+                        } else {
+                            Response::builder()
+                                .header(ETAG, served.etag.clone())
+                                .body(Body::from(served.manifest.clone()))
+                                .unwrap()
+                        }
+                    };
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let url = format!("http://{}/app.json", server.local_addr());
+        tokio::spawn(server);
+
+        let (cache, dir) = cache("ripple_remote_manifest_test");
+        let mut config = AppManifestFetchConfiguration {
+            ttl_seconds: 3600,
+            public_key: Some(STANDARD.encode(key_pair.public_key().as_ref())),
+        };
+        let fetch = |config: AppManifestFetchConfiguration| {
+            let (cache, url) = (cache.clone(), url.clone());
+            async move { RemoteManifestFetcher::fetch(&cache, &config, "app", &url).await }
+        };
+
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v1")));
+        assert_eq!(cache.get("app").unwrap().etag.as_deref(), Some("\"v1\""));
+        // served from the cache within the ttl
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v1")));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // revalidated once the ttl elapsed
+        config.ttl_seconds = 0;
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v1")));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        {
+            let mut served = served.write().unwrap();
+            served.etag = "\"v2\"".to_owned();
+            served.manifest = serde_json::to_string(&manifest("v2")).unwrap();
+            served.signature = sign("app", 2, &served.manifest);
+        }
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v2")));
+        assert_eq!(cache.get("app").unwrap().version, Some(2));
+        // the last good copy survives a restart
+        assert_eq!(
+            RemoteManifestCache::new(&dir).get("app").unwrap().manifest,
+            manifest("v2")
+        );
+
+        // a manifest failing the signature check is not used
+        {
+            let mut served = served.write().unwrap();
+            served.etag = "\"v3\"".to_owned();
+            served.manifest = serde_json::to_string(&manifest("v3")).unwrap();
+        }
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v2")));
+        // nor a replayed manifest with an older version
+        {
+            let mut served = served.write().unwrap();
+            served.manifest = serde_json::to_string(&manifest("v1")).unwrap();
+            served.signature = sign("app", 1, &served.manifest);
+        }
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v2")));
+        // nor a manifest signed for another app
+        {
+            let mut served = served.write().unwrap();
+            served.manifest = serde_json::to_string(&manifest("v3")).unwrap();
+            served.signature = sign("other", 3, &served.manifest);
+        }
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v2")));
+        // neither is a manifest served over plain http without a signature
+        config.public_key = None;
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v2")));
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+        {
+            let mut served = served.write().unwrap();
+            served.signature = sign("app", 3, &served.manifest);
+        }
+        config.public_key = Some(STANDARD.encode(key_pair.public_key().as_ref()));
+        assert_eq!(fetch(config.clone()).await, Ok(manifest("v3")));
+
+        cache.remove("app");
+        // nothing to fall back to
+        assert_eq!(
+            RemoteManifestFetcher::fetch(&cache, &config, "app", "https://127.0.0.1:1/app.json")
+                .await,
+            Err(RippleError::NoResponse)
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_read_body_limit() {
+        assert_eq!(
+            read_body(Body::from(vec![0u8; 8]), 8).await.unwrap().len(),
+            8
+        );
+        assert_eq!(
+            read_body(Body::from(vec![0u8; 9]), 8).await,
+            Err(RippleError::InvalidOutput)
+        );
+    }
+}
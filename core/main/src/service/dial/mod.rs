@@ -0,0 +1,214 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Built-in DIAL 2.x server, see <http://www.dial-multiscreen.org>. Second screen devices
+//! discover the device with SSDP and launch or stop apps through the REST resources.
+
+pub mod rest;
+pub mod ssdp;
+
+use std::{net::SocketAddr, sync::Arc};
+
+use ripple_sdk::{
+    api::{
+        apps::{AppMethod, AppRequest, AppResponse},
+        device::entertainment_data::{NavigationIntent, NavigationIntentLoose},
+        firebolt::{
+            fb_discovery::{DiscoveryContext, LaunchRequest},
+            fb_parameters::SecondScreenEvent,
+            fb_secondscreen::{
+                SECOND_SCREEN_EVENT_ON_CLOSE_REQUEST, SECOND_SCREEN_EVENT_ON_LAUNCH_REQUEST,
+            },
+        },
+        manifest::device_manifest::DialConfiguration,
+    },
+    log::{error, info},
+    tokio::{self, sync::oneshot},
+    utils::error::RippleError,
+    uuid::Uuid,
+};
+
+use serde_json::Value;
+
+use crate::{service::apps::app_events::AppEvents, state::platform_state::PlatformState};
+
+use self::{rest::DialRest, ssdp::SsdpResponder};
+
+pub const DIAL_VERSION: &str = "2.1";
+pub const DIAL_EVENT_TYPE: &str = "dial";
+// origins of native clients, which are always allowed
+const NATIVE_ORIGIN_SCHEMES: [&str; 3] = ["package:", "proxy:", "file:"];
+
+#[derive(Debug, PartialEq)]
+pub enum DialLaunch {
+    // the app was not running and is being launched
+    Launched,
+    // the app was running, the payload was sent to it
+    Running,
+}
+
+/// Maps DIAL application names to apps of the library and turns DIAL requests into second
+/// screen events or launches.
+#[derive(Clone)]
+pub struct DialApps {
+    state: PlatformState,
+    config: Arc<DialConfiguration>,
+}
+
+impl DialApps {
+    pub fn new(state: PlatformState, config: DialConfiguration) -> Self {
+        Self {
+            state,
+            config: Arc::new(config),
+        }
+    }
+
+    /// Returns the app id of a DIAL application, names without a mapping in the configuration
+    /// are looked up as app ids.
+    pub fn get_app_id(&self, name: &str) -> Option<String> {
+        let app_id = self
+            .config
+            .apps
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_owned());
+        self.state
+            .app_library_state
+            .get_all_apps()
+            .iter()
+            .any(|entry| entry.app_id == app_id)
+            .then_some(app_id)
+    }
+
+    /// Requests without an origin come from native clients, web clients need to be allowed in
+    /// the configuration.
+    pub fn is_origin_allowed(&self, origin: Option<&str>) -> bool {
+        let Some(origin) = origin else {
+            return true;
+        };
+        NATIVE_ORIGIN_SCHEMES
+            .iter()
+            .any(|scheme| origin.starts_with(scheme))
+            || self.config.allowed_origins.iter().any(|o| o == origin)
+    }
+
+    pub fn is_running(&self, app_id: &str) -> bool {
+        self.state.app_manager_state.get(app_id).is_some()
+    }
+
+    // the payload of an app which is not running yet is passed as its launch intent
+    fn get_intent(payload: Option<String>) -> Option<NavigationIntent> {
+        payload.map(|payload| {
+            NavigationIntent::NavigationIntentLoose(NavigationIntentLoose {
+                action: "launch".to_owned(),
+                data: Some(Value::String(payload)),
+                context: DiscoveryContext::new(DIAL_EVENT_TYPE, None),
+            })
+        })
+    }
+
+    fn get_event(payload: Option<String>) -> SecondScreenEvent {
+        SecondScreenEvent {
+            _type: DIAL_EVENT_TYPE.to_owned(),
+            version: Some(DIAL_VERSION.to_owned()),
+            data: payload,
+        }
+    }
+
+    pub async fn launch(
+        &self,
+        app_id: &str,
+        payload: Option<String>,
+    ) -> Result<DialLaunch, RippleError> {
+        if self.is_running(app_id) {
+            let event = serde_json::to_value(Self::get_event(payload)).unwrap_or_default();
+            AppEvents::emit_to_app(
+                &self.state,
+                app_id.to_owned(),
+                SECOND_SCREEN_EVENT_ON_LAUNCH_REQUEST,
+                &event,
+            )
+            .await;
+            return Ok(DialLaunch::Running);
+        }
+        let (app_resp_tx, app_resp_rx) = oneshot::channel::<AppResponse>();
+        let app_request = AppRequest::new(
+            AppMethod::Launch(LaunchRequest {
+                app_id: app_id.to_owned(),
+                intent: Self::get_intent(payload),
+            }),
+            app_resp_tx,
+        );
+        self.state.get_client().send_app_request(app_request)?;
+        match app_resp_rx.await {
+            Ok(Ok(_)) => Ok(DialLaunch::Launched),
+            Ok(Err(e)) => {
+                error!("DIAL launch of {} failed {:?}", app_id, e);
+                Err(RippleError::ProcessorError)
+            }
+            Err(_) => Err(RippleError::ProcessorError),
+        }
+    }
+
+    /// Asks a running app to close, returns false if the app is not running.
+    pub async fn stop(&self, app_id: &str) -> bool {
+        if !self.is_running(app_id) {
+            return false;
+        }
+        let event = serde_json::to_value(Self::get_event(None)).unwrap_or_default();
+        AppEvents::emit_to_app(
+            &self.state,
+            app_id.to_owned(),
+            SECOND_SCREEN_EVENT_ON_CLOSE_REQUEST,
+            &event,
+        )
+        .await;
+        true
+    }
+}
+
+pub struct DialServer;
+
+impl DialServer {
+    /// Starts the REST server and the SSDP responder, returns the addresses they are bound to.
+    pub async fn start(
+        state: PlatformState,
+        config: DialConfiguration,
+    ) -> Result<(SocketAddr, SocketAddr), RippleError> {
+        let uuid = config
+            .uuid
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let friendly_name = config
+            .friendly_name
+            .clone()
+            .unwrap_or_else(|| "Ripple".to_owned());
+        let rest_address = config.rest_address.clone();
+        let ssdp_address = config.ssdp_address.clone();
+
+        let rest = DialRest::new(DialApps::new(state, config), uuid.clone(), friendly_name);
+        let rest_addr = rest.start(&rest_address)?;
+        let ssdp = SsdpResponder::bind(&ssdp_address, uuid, rest_addr).await?;
+        let ssdp_addr = ssdp.local_addr()?;
+        tokio::spawn(async move { ssdp.start().await });
+        info!(
+            "DIAL server listening on {}, SSDP on {}",
+            rest_addr, ssdp_addr
+        );
+        Ok((rest_addr, ssdp_addr))
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use hyper::{
+    header::{CONTENT_TYPE, HOST, LOCATION, ORIGIN},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use ripple_sdk::{
+    log::{debug, error},
+    tokio,
+    utils::error::RippleError,
+};
+
+use super::{DialApps, DialLaunch, DIAL_VERSION};
+
+// DIAL limits the payload of a launch request
+const MAX_PAYLOAD_SIZE: usize = 4096;
+const APPLICATION_URL: &str = "Application-URL";
+
+/// The device description and the `/apps/<name>` resources of the DIAL REST service.
+#[derive(Clone)]
+pub struct DialRest {
+    apps: DialApps,
+    uuid: Arc<String>,
+    friendly_name: Arc<String>,
+}
+
+impl DialRest {
+    pub fn new(apps: DialApps, uuid: String, friendly_name: String) -> Self {
+        Self {
+            apps,
+            uuid: Arc::new(uuid),
+            friendly_name: Arc::new(friendly_name),
+        }
+    }
+
+    /// Binds the server and serves requests in the background, returns the bound address.
+    pub fn start(self, address: &str) -> Result<SocketAddr, RippleError> {
+        let listener = std::net::TcpListener::bind(address).map_err(|e| {
+            error!("Unable to bind DIAL server on {}: {:?}", address, e);
+            RippleError::BootstrapError
+        })?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|_| RippleError::BootstrapError)?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|_| RippleError::BootstrapError)?;
+        let server = Server::from_tcp(listener).map_err(|e| {
+            error!("Unable to start DIAL server on {}: {:?}", address, e);
+            RippleError::BootstrapError
+        })?;
+        let make_service = make_service_fn(move |_| {
+            let rest = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let rest = rest.clone();
+                    async move { Ok::<_, Infallible>(rest.handle(request).await) }
+                }))
+            }
+        });
+        tokio::spawn(async move {
+            if let Err(e) = server.serve(make_service).await {
+                error!("DIAL server stopped {:?}", e);
+            }
+        });
+        Ok(local_addr)
+    }
+
+    pub async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        debug!("DIAL request {} {}", request.method(), request.uri());
+        let origin = request.headers().get(ORIGIN).map(|o| o.to_str());
+        let allowed = match origin {
+            None => self.apps.is_origin_allowed(None),
+            Some(Ok(origin)) => self.apps.is_origin_allowed(Some(origin)),
+            Some(Err(_)) => false,
+        };
+        if !allowed {
+            return status(StatusCode::FORBIDDEN);
+        }
+        let path = request.uri().path().to_owned();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (request.method(), segments.as_slice()) {
+            (&Method::GET, ["dd.xml"]) => self.get_device_description(&request),
+            (&Method::GET, ["apps", name]) => self.get_app(name),
+            (&Method::POST, ["apps", name]) => {
+                let name = name.to_string();
+                self.launch_app(&name, request).await
+            }
+            (&Method::DELETE, ["apps", name, "run"]) => self.stop_app(name).await,
+            (_, ["dd.xml"]) | (_, ["apps", _]) | (_, ["apps", _, "run"]) => {
+                status(StatusCode::METHOD_NOT_ALLOWED)
+            }
+            _ => status(StatusCode::NOT_FOUND),
+        }
+    }
+
+    fn get_device_description(&self, request: &Request<Body>) -> Response<Body> {
+        let Some(host) = get_host(request) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:dial-multiscreen-org:device:dial:1</deviceType>
+    <friendlyName>{}</friendlyName>
+    <manufacturer>RDK</manufacturer>
+    <modelName>Ripple</modelName>
+    <UDN>uuid:{}</UDN>
+  </device>
+</root>
+"#,
+            xml_escape(&self.friendly_name),
+            xml_escape(&self.uuid)
+        );
+        Response::builder()
+            .header(CONTENT_TYPE, "text/xml; charset=utf-8")
+            .header(APPLICATION_URL, format!("http://{}/apps/", host))
+            .body(Body::from(body))
+            .unwrap_or_default()
+    }
+
+    fn get_app(&self, name: &str) -> Response<Body> {
+        let Some(app_id) = self.apps.get_app_id(name) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+        let running = self.apps.is_running(&app_id);
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<service xmlns="urn:dial-multiscreen-org:schemas:dial" dialVer="{}">
+  <name>{}</name>
+  <options allowStop="true"/>
+  <state>{}</state>{}
+</service>
+"#,
+            DIAL_VERSION,
+            xml_escape(name),
+            if running { "running" } else { "stopped" },
+            if running {
+                "\n  <link rel=\"run\" href=\"run\"/>"
+            } else {
+                ""
+            }
+        );
+        Response::builder()
+            .header(CONTENT_TYPE, "text/xml; charset=utf-8")
+            .body(Body::from(body))
+            .unwrap_or_default()
+    }
+
+    async fn launch_app(&self, name: &str, request: Request<Body>) -> Response<Body> {
+        let Some(app_id) = self.apps.get_app_id(name) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+        // the instance URL is absolute, like the application URL
+        let Some(host) = get_host(&request).map(str::to_owned) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        let Ok(body) = hyper::body::to_bytes(request.into_body()).await else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        if body.len() > MAX_PAYLOAD_SIZE {
+            return status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        let payload = if body.is_empty() {
+            None
+        } else {
+            match String::from_utf8(body.to_vec()) {
+                Ok(payload) => Some(payload),
+                Err(_) => return status(StatusCode::BAD_REQUEST),
+            }
+        };
+        match self.apps.launch(&app_id, payload).await {
+            Ok(DialLaunch::Running) => status(StatusCode::OK),
+            Ok(DialLaunch::Launched) => Response::builder()
+                .status(StatusCode::CREATED)
+                .header(LOCATION, format!("http://{}/apps/{}/run", host, name))
+                .body(Body::empty())
+                .unwrap_or_default(),
+            Err(_) => status(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+
+    async fn stop_app(&self, name: &str) -> Response<Body> {
+        match self.apps.get_app_id(name) {
+            Some(app_id) if self.apps.stop(&app_id).await => status(StatusCode::OK),
+            _ => status(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+fn get_host(request: &Request<Body>) -> Option<&str> {
+    request.headers().get(HOST).and_then(|h| h.to_str().ok())
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::{apps::app_library::DynamicAppLibraryState, extn::ripple_client::RippleClient},
+        state::{bootstrap_state::ChannelsState, platform_state::PlatformState},
+    };
+    use hyper::Client;
+    use ripple_sdk::api::apps::AppRequest;
+    use ripple_sdk::api::device::entertainment_data::NavigationIntent;
+    use ripple_sdk::{
+        api::{
+            apps::{AppManagerResponse, AppMethod},
+            manifest::{
+                apps::AppManifest,
+                device_manifest::{AppLibraryEntry, AppManifestLoad, BootState, DialConfiguration},
+            },
+        },
+        tokio::sync::mpsc::Receiver,
+    };
+    use ripple_tdk::utils::test_utils::Mockable;
+    use serde_json::Value;
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static SERVERS: AtomicUsize = AtomicUsize::new(0);
+
+    fn start_server() -> (SocketAddr, Receiver<AppRequest>) {
+        start_server_with_origins(vec![])
+    }
+
+    fn start_server_with_origins(
+        allowed_origins: Vec<String>,
+    ) -> (SocketAddr, Receiver<AppRequest>) {
+        let channels = ChannelsState::new();
+        let mut state = PlatformState::mock();
+        state.ripple_client = RippleClient::new(channels.clone());
+        // dial only reads the library, it is kept in memory once loaded
+        let saved_dir = std::env::temp_dir().join(format!(
+            "ripple_dial_test_{}_{}",
+            std::process::id(),
+            SERVERS.fetch_add(1, Ordering::SeqCst)
+        ));
+        state.app_library_state = DynamicAppLibraryState::new(
+            vec![AppLibraryEntry {
+                app_id: "xrn:firebolt:application:YouTube".to_owned(),
+                boot_state: BootState::Unloaded,
+                manifest: AppManifestLoad::Embedded(AppManifest::default()),
+            }],
+            &saved_dir.to_string_lossy(),
+        );
+        let _ = std::fs::remove_dir_all(&saved_dir);
+        let config = DialConfiguration {
+            apps: HashMap::from([(
+                "YouTube".to_owned(),
+                "xrn:firebolt:application:YouTube".to_owned(),
+            )]),
+            allowed_origins,
+            ..Default::default()
+        };
+        let rest = DialRest::new(
+            DialApps::new(state, config),
+            "1234".to_owned(),
+            "Living Room".to_owned(),
+        );
+        let addr = rest.start("127.0.0.1:0").unwrap();
+        (addr, channels.get_app_mgr_receiver().unwrap())
+    }
+
+    async fn send(method: Method, url: String, body: &str) -> Response<Body> {
+        send_from(None, method, url, body).await
+    }
+
+    async fn send_from(
+        origin: Option<&str>,
+        method: Method,
+        url: String,
+        body: &str,
+    ) -> Response<Body> {
+        let mut request = Request::builder().method(method).uri(url);
+        if let Some(origin) = origin {
+            request = request.header(ORIGIN, origin);
+        }
+        let request = request.body(Body::from(body.to_owned())).unwrap();
+        Client::new().request(request).await.unwrap()
+    }
+
+    async fn text(response: Response<Body>) -> String {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_device_description() {
+        let (addr, _) = start_server();
+        let response = send(Method::GET, format!("http://{}/dd.xml", addr), "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(APPLICATION_URL).unwrap(),
+            &format!("http://{}/apps/", addr)
+        );
+        let body = text(response).await;
+        assert!(body.contains("<friendlyName>Living Room</friendlyName>"));
+        assert!(body.contains("<UDN>uuid:1234</UDN>"));
+    }
+
+    #[tokio::test]
+    async fn test_app_resource() {
+        let (addr, mut app_requests) = start_server();
+        let response = send(Method::GET, format!("http://{}/apps/Netflix", addr), "").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send(Method::GET, format!("http://{}/apps/YouTube", addr), "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = text(response).await;
+        assert!(body.contains("<name>YouTube</name>"));
+        assert!(body.contains("<state>stopped</state>"));
+
+        // a stopped app is launched through the app manager
+        tokio::spawn(async move {
+            let request = app_requests.recv().await.unwrap();
+            match &request.method {
+                AppMethod::Launch(launch) => {
+                    assert_eq!(launch.app_id, "xrn:firebolt:application:YouTube");
+                    match &launch.intent {
+                        Some(NavigationIntent::NavigationIntentLoose(intent)) => {
+                            assert_eq!(intent.data, Some(Value::String("v=abc".to_owned())));
+                            assert_eq!(intent.context.source, "dial");
+                        }
+                        i => panic!("unexpected intent {:?}", i),
+                    }
+                }
+                m => panic!("unexpected {:?}", m),
+            }
+            request.send_response(Ok(AppManagerResponse::None)).unwrap();
+        });
+        let response = send(
+            Method::POST,
+            format!("http://{}/apps/YouTube", addr),
+            "v=abc",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get(LOCATION).unwrap(),
+            &format!("http://{}/apps/YouTube/run", addr)
+        );
+
+        let response = send(
+            Method::DELETE,
+            format!("http://{}/apps/YouTube/run", addr),
+            "",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send(Method::PUT, format!("http://{}/apps/YouTube", addr), "").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let payload = "x".repeat(MAX_PAYLOAD_SIZE + 1);
+        let response = send(
+            Method::POST,
+            format!("http://{}/apps/YouTube", addr),
+            &payload,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_origin_check() {
+        let (addr, _) = start_server_with_origins(vec!["https://www.youtube.com".to_owned()]);
+        let url = format!("http://{}/apps/YouTube", addr);
+        for origin in [
+            "https://www.youtube.com",
+            "package:com.google.android.youtube",
+            "proxy:cast",
+            "file://",
+        ] {
+            let response = send_from(Some(origin), Method::GET, url.clone(), "").await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", origin);
+        }
+        for origin in ["https://evil.example.com", "http://www.youtube.com"] {
+            let response = send_from(Some(origin), Method::GET, url.clone(), "").await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", origin);
+            let response = send_from(Some(origin), Method::POST, url.clone(), "v=abc").await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", origin);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use ripple_sdk::{
+    log::{debug, error},
+    tokio::net::UdpSocket,
+    utils::error::RippleError,
+};
+
+pub const SSDP_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+pub const DIAL_SERVICE_TYPE: &str = "urn:dial-multiscreen-org:service:dial:1";
+
+/// Answers SSDP `M-SEARCH` requests for the DIAL service with the location of the device
+/// description.
+pub struct SsdpResponder {
+    socket: UdpSocket,
+    uuid: String,
+    rest_addr: SocketAddr,
+}
+
+impl SsdpResponder {
+    /// Binds the responder, the multicast group is joined when bound to all interfaces.
+    pub async fn bind(
+        address: &str,
+        uuid: String,
+        rest_addr: SocketAddr,
+    ) -> Result<SsdpResponder, RippleError> {
+        let socket = UdpSocket::bind(address).await.map_err(|e| {
+            error!("Unable to bind SSDP socket on {}: {:?}", address, e);
+            RippleError::BootstrapError
+        })?;
+        if socket.local_addr().is_ok_and(|a| a.ip().is_unspecified()) {
+            socket
+                .join_multicast_v4(SSDP_MULTICAST_ADDRESS, Ipv4Addr::UNSPECIFIED)
+                .map_err(|e| {
+                    error!("Unable to join SSDP multicast group: {:?}", e);
+                    RippleError::BootstrapError
+                })?;
+        }
+        Ok(SsdpResponder {
+            socket,
+            uuid,
+            rest_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, RippleError> {
+        self.socket
+            .local_addr()
+            .map_err(|_| RippleError::BootstrapError)
+    }
+
+    pub async fn start(self) {
+        let mut buf = [0u8; 2048];
+        loop {
+            let (len, peer) = match self.socket.recv_from(&mut buf).await {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("SSDP receive failed {:?}", e);
+                    continue;
+                }
+            };
+            let message = String::from_utf8_lossy(&buf[..len]);
+            if !is_dial_search(&message) {
+                continue;
+            }
+            let Some(ip) = self.get_rest_ip(peer) else {
+                continue;
+            };
+            let location = format!(
+                "http://{}/dd.xml",
+                SocketAddr::new(ip, self.rest_addr.port())
+            );
+            debug!("SSDP search from {}, answering with {}", peer, location);
+            let response = search_response(&location, &self.uuid);
+            if let Err(e) = self.socket.send_to(response.as_bytes(), peer).await {
+                error!("SSDP response to {} failed {:?}", peer, e);
+            }
+        }
+    }
+
+    // The address the peer can reach the REST server on. When the server listens on all
+    // interfaces it is the address of the interface routing to the peer.
+    fn get_rest_ip(&self, peer: SocketAddr) -> Option<IpAddr> {
+        if !self.rest_addr.ip().is_unspecified() {
+            return Some(self.rest_addr.ip());
+        }
+        let probe = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+        probe.connect(peer).ok()?;
+        probe.local_addr().ok().map(|a| a.ip())
+    }
+}
+
+/// Whether the message is a search for the DIAL service or for all services.
+pub fn is_dial_search(message: &str) -> bool {
+    let mut lines = message.lines();
+    if !lines
+        .next()
+        .is_some_and(|l| l.trim().eq_ignore_ascii_case("M-SEARCH * HTTP/1.1"))
+    {
+        return false;
+    }
+    lines.any(|line| match line.split_once(':') {
+        Some((name, value)) if name.trim().eq_ignore_ascii_case("ST") => {
+            let value = value.trim();
+            value == DIAL_SERVICE_TYPE || value == "ssdp:all"
+        }
+        _ => false,
+    })
+}
+
+pub fn search_response(location: &str, uuid: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         CACHE-CONTROL: max-age=1800\r\n\
+         EXT:\r\n\
+         LOCATION: {location}\r\n\
+         SERVER: Linux/1.0 UPnP/1.1 Ripple/1.0\r\n\
+         ST: {DIAL_SERVICE_TYPE}\r\n\
+         USN: uuid:{uuid}::{DIAL_SERVICE_TYPE}\r\n\
+         BOOTID.UPNP.ORG: 1\r\n\
+         CONFIGID.UPNP.ORG: 1\r\n\
+         \r\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::tokio;
+
+    #[test]
+    fn test_is_dial_search() {
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: {}\r\n\r\n",
+            DIAL_SERVICE_TYPE
+        );
+        assert!(is_dial_search(&search));
+        assert!(is_dial_search(
+            "M-SEARCH * HTTP/1.1\r\nst: ssdp:all\r\n\r\n"
+        ));
+        assert!(!is_dial_search(
+            "M-SEARCH * HTTP/1.1\r\nST: urn:schemas-upnp-org:device:MediaRenderer:1\r\n\r\n"
+        ));
+        assert!(!is_dial_search(&format!(
+            "NOTIFY * HTTP/1.1\r\nST: {}\r\n\r\n",
+            DIAL_SERVICE_TYPE
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_search_over_loopback() {
+        let rest_addr: SocketAddr = "127.0.0.1:8009".parse().unwrap();
+        let responder = SsdpResponder::bind("127.0.0.1:0", "1234".to_owned(), rest_addr)
+            .await
+            .unwrap();
+        let ssdp_addr = responder.local_addr().unwrap();
+        tokio::spawn(async move { responder.start().await });
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let search = format!("M-SEARCH * HTTP/1.1\r\nST: {}\r\n\r\n", DIAL_SERVICE_TYPE);
+        client.send_to(search.as_bytes(), ssdp_addr).await.unwrap();
+        let mut buf = [0u8; 2048];
+        let (len, _) = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            client.recv_from(&mut buf),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let response = String::from_utf8_lossy(&buf[..len]);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("LOCATION: http://127.0.0.1:8009/dd.xml\r\n"));
+        assert!(response.contains("USN: uuid:1234::urn:dial-multiscreen-org:service:dial:1"));
+    }
+}
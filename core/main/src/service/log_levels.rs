@@ -0,0 +1,250 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use ripple_sdk::{
+    log::{error, info},
+    service::service_message::ServiceMessage,
+    tokio::{self, sync::mpsc},
+    tokio_tungstenite::tungstenite::Message,
+    utils::{
+        error::RippleError,
+        logger::{
+            get_log_levels, parse_level, set_log_level, set_module_log_level, LogLevels,
+            LOG_LEVELS_CHANGED_NOTIFICATION,
+        },
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::state::platform_state::PlatformState;
+
+/// Incremented on every runtime change.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Generation of the last runtime change per target, `None` being the global level. A pending
+/// TTL revert only runs if its target did not change again after the change that scheduled it.
+static GENERATIONS: Mutex<BTreeMap<Option<String>, u64>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLogLevelRequest {
+    pub level: String,
+    /// Module path prefix, the global level is changed when absent.
+    pub module: Option<String>,
+    /// Seconds after which the previous levels are restored.
+    pub ttl_seconds: Option<u64>,
+}
+
+pub struct LogLevelController;
+
+impl LogLevelController {
+    pub async fn set(
+        state: &PlatformState,
+        request: SetLogLevelRequest,
+    ) -> Result<LogLevels, RippleError> {
+        let level = parse_level(&request.level)?;
+        let previous = get_log_levels();
+        let generation = {
+            let mut generations = GENERATIONS.lock().unwrap();
+            match &request.module {
+                Some(module) => set_module_log_level(module, Some(level)),
+                None => set_log_level(level),
+            }
+            let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+            generations.insert(request.module.clone(), generation);
+            generation
+        };
+        let current = get_log_levels();
+        info!(
+            "log levels changed to {:?} ttl={:?}",
+            current, request.ttl_seconds
+        );
+        Self::propagate(state, &current).await;
+
+        if let Some(ttl) = request.ttl_seconds {
+            let state = state.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(ttl)).await;
+                match Self::revert(&request.module, &previous, generation) {
+                    Ok(true) => {
+                        let current = get_log_levels();
+                        info!("log levels reverted to {:?}", current);
+                        Self::propagate(&state, &current).await;
+                    }
+                    Ok(false) => info!(
+                        "log level revert of {:?} skipped, it changed since",
+                        request.module
+                    ),
+                    Err(e) => error!("Failed to revert log levels {:?}", e),
+                }
+            });
+        }
+        Ok(current)
+    }
+
+    /// Restores the level of the target from `previous` unless it changed after `generation`,
+    /// the levels of other targets are kept.
+    fn revert(
+        module: &Option<String>,
+        previous: &LogLevels,
+        generation: u64,
+    ) -> Result<bool, RippleError> {
+        let generations = GENERATIONS.lock().unwrap();
+        if generations.get(module) != Some(&generation) {
+            return Ok(false);
+        }
+        match module {
+            Some(module) => {
+                let level = previous.modules.get(module).map(|l| parse_level(l));
+                set_module_log_level(module, level.transpose()?);
+            }
+            None => set_log_level(parse_level(&previous.level)?),
+        }
+        Ok(true)
+    }
+
+    /// Pushes the levels to loaded extensions and connected services.
+    pub async fn propagate(state: &PlatformState, levels: &LogLevels) {
+        state
+            .get_client()
+            .get_extn_client()
+            .propagate_log_levels(levels);
+        let message = Self::get_notification(levels);
+        for (service_id, info) in state.service_controller_state.get_services().await {
+            if let Err(e) = info.tx.send(message.clone()).await {
+                error!("Failed to send log levels to {}: {:?}", service_id, e);
+            }
+        }
+    }
+
+    /// Services connecting after a runtime change start from their own environment,
+    /// bring them up to date with Main.
+    pub async fn on_service_connected(tx: &mpsc::Sender<Message>) {
+        if GENERATION.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        if let Err(e) = tx.send(Self::get_notification(&get_log_levels())).await {
+            error!("Failed to send log levels to service: {:?}", e);
+        }
+    }
+
+    fn get_notification(levels: &LogLevels) -> Message {
+        let notification = ServiceMessage::new_notification(
+            LOG_LEVELS_CHANGED_NOTIFICATION.to_owned(),
+            serde_json::to_value(levels).ok(),
+        );
+        Message::Text(notification.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::service::service_message::JsonRpcMessage;
+    use ripple_tdk::utils::test_utils::Mockable;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_log_levels_notification() {
+        let levels = LogLevels {
+            level: "info".to_owned(),
+            modules: HashMap::from([("ripple::broker".to_owned(), "trace".to_owned())]),
+        };
+        let Message::Text(text) = LogLevelController::get_notification(&levels) else {
+            panic!("expected a text message");
+        };
+        let message: ServiceMessage = serde_json::from_str(&text).unwrap();
+        let JsonRpcMessage::Notification(notification) = message.message else {
+            panic!("expected a notification");
+        };
+        assert_eq!(notification.method, LOG_LEVELS_CHANGED_NOTIFICATION);
+        assert_eq!(
+            serde_json::from_value::<LogLevels>(notification.params.unwrap()).unwrap(),
+            levels
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_per_target() {
+        let state = PlatformState::mock();
+        let first = Some("ripple::log_levels_test::first".to_owned());
+        let second = Some("ripple::log_levels_test::second".to_owned());
+        let request = |module: &Option<String>, level: &str| SetLogLevelRequest {
+            level: level.to_owned(),
+            module: module.clone(),
+            ttl_seconds: None,
+        };
+        let previous = get_log_levels();
+        LogLevelController::set(&state, request(&first, "trace"))
+            .await
+            .unwrap();
+        let generation = GENERATION.load(Ordering::SeqCst);
+        LogLevelController::set(&state, request(&second, "debug"))
+            .await
+            .unwrap();
+
+        // a change of another module does not skip the revert, nor is it reverted
+        assert!(LogLevelController::revert(&first, &previous, generation).unwrap());
+        let levels = get_log_levels();
+        assert!(!levels.modules.contains_key(first.as_ref().unwrap()));
+        assert_eq!(
+            levels.modules.get(second.as_ref().unwrap()).unwrap(),
+            "DEBUG"
+        );
+
+        // a later change of the module itself does
+        let previous = get_log_levels();
+        LogLevelController::set(&state, request(&second, "trace"))
+            .await
+            .unwrap();
+        let generation = GENERATION.load(Ordering::SeqCst);
+        LogLevelController::set(&state, request(&second, "warn"))
+            .await
+            .unwrap();
+        assert!(!LogLevelController::revert(&second, &previous, generation).unwrap());
+        assert_eq!(
+            get_log_levels()
+                .modules
+                .get(second.as_ref().unwrap())
+                .unwrap(),
+            "WARN"
+        );
+        set_module_log_level(second.as_ref().unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_log_level_request() {
+        let request: SetLogLevelRequest = serde_json::from_value(serde_json::json!({
+            "level": "debug",
+            "module": "ripple::broker",
+            "ttlSeconds": 300
+        }))
+        .unwrap();
+        assert_eq!(request.level, "debug");
+        assert_eq!(request.module.as_deref(), Some("ripple::broker"));
+        assert_eq!(request.ttl_seconds, Some(300));
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use ripple_sdk::{log::error, tokio, utils::error::RippleError};
+
+use crate::state::ops_metrics_state::OpMetricState;
+
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Serves the aggregated gateway and broker metrics at `/metrics` for Prometheus to scrape.
+#[derive(Clone)]
+pub struct MetricsExporter {
+    metrics: OpMetricState,
+}
+
+impl MetricsExporter {
+    pub fn new(metrics: OpMetricState) -> Self {
+        Self { metrics }
+    }
+
+    /// Binds the server and serves requests in the background, returns the bound address.
+    pub fn start(self, address: &str) -> Result<SocketAddr, RippleError> {
+        let listener = std::net::TcpListener::bind(address).map_err(|e| {
+            error!("Unable to bind metrics exporter on {}: {:?}", address, e);
+            RippleError::BootstrapError
+        })?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|_| RippleError::BootstrapError)?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|_| RippleError::BootstrapError)?;
+        let server = Server::from_tcp(listener).map_err(|e| {
+            error!("Unable to start metrics exporter on {}: {:?}", address, e);
+            RippleError::BootstrapError
+        })?;
+        let make_service = make_service_fn(move |_| {
+            let exporter = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let exporter = exporter.clone();
+                    async move { Ok::<_, Infallible>(exporter.handle(request)) }
+                }))
+            }
+        });
+        tokio::spawn(async move {
+            if let Err(e) = server.serve(make_service).await {
+                error!("Metrics exporter stopped {:?}", e);
+            }
+        });
+        Ok(local_addr)
+    }
+
+    pub fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/metrics") => {
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, METRICS_CONTENT_TYPE.parse().unwrap());
+                *response.body_mut() = Body::from(self.metrics.render_metrics());
+            }
+            (_, "/metrics") => *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED,
+            _ => *response.status_mut() = StatusCode::NOT_FOUND,
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Client;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let metrics = OpMetricState::default();
+        metrics.record_thunder_reconnect();
+        let addr = MetricsExporter::new(metrics).start("127.0.0.1:0").unwrap();
+
+        let response = Client::new()
+            .get(format!("http://{}/metrics", addr).parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            METRICS_CONTENT_TYPE
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("ripple_thunder_reconnects_total 1"));
+
+        let response = Client::new()
+            .get(format!("http://{}/", addr).parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -16,8 +16,13 @@
 //
 
 pub mod apps;
+pub mod dial;
 pub mod extn;
+pub mod log_levels;
+pub mod metrics_exporter;
 pub mod ripple_service;
+pub mod session_recorder;
 pub mod settings_processor;
 pub mod telemetry_builder;
+pub mod tracer;
 pub mod user_grants;
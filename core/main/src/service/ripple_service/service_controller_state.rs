@@ -14,10 +14,15 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 //
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use futures::{stream::SplitStream, SinkExt, StreamExt};
-use ripple_sdk::api::gateway::rpc_gateway_api::JsonRpcApiResponse;
+use ripple_sdk::api::gateway::rpc_gateway_api::{JsonRpcApiError, JsonRpcApiResponse};
 use ripple_sdk::{
     api::{gateway::rpc_gateway_api::ApiMessage, manifest::extn_manifest::ExtnSymbol},
     extn::{
@@ -25,15 +30,15 @@ use ripple_sdk::{
         extn_id::ExtnId,
     },
     framework::ripple_contract::RippleContract,
-    log::{error, info, trace},
+    log::{error, info, trace, warn},
     service::{
         service_event_state::ServiceEventState,
         service_message::{Id, JsonRpcMessage, ServiceMessage},
     },
     tokio::{
         self,
-        net::TcpStream,
-        sync::{mpsc, Mutex},
+        io::{AsyncRead, AsyncWrite},
+        sync::{mpsc, oneshot, Mutex},
     },
     tokio_tungstenite::{tungstenite::Message, WebSocketStream},
     utils::error::RippleError,
@@ -42,25 +47,73 @@ use ripple_sdk::{
 
 use crate::service::ripple_service::service_notification_processor::ServiceNotificationProcessor;
 use crate::{
-    broker::endpoint_broker::{BrokerCallback, BrokerOutput},
+    broker::endpoint_broker::{BrokerCallback, BrokerOutput, BrokerOutputForwarder},
     firebolt::{firebolt_gateway::FireboltGatewayCommand, firebolt_ws::ClientIdentity},
-    service::extn::ripple_client::RippleClient,
+    service::{
+        apps::app_events::AppEvents, extn::ripple_client::RippleClient,
+        log_levels::LogLevelController,
+    },
     state::{platform_state::PlatformState, session_state::Session},
 };
 
-use super::service_registry::ServiceRegistry;
+use super::service_registry::{Heartbeat, ServiceRegistry};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 const ALLOWED_SERVICES_LIST: [&str; 2] = [
     "ripple:channel:gateway:badger",
     "ripple:channel:distributor:eos",
 ];
 
+pub const SERVICE_CHANGED_EVENT: &str = "ripple.onServiceChanged";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceStatus {
+    Connected,
+    Disconnected,
+}
+
+/// Payload of [SERVICE_CHANGED_EVENT] sent when a service joins or leaves.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceChangedEvent {
+    pub service_id: String,
+    pub status: ServiceStatus,
+}
+
+/// A connected service as reported by the `ripple.getServices` internal method, times are
+/// milliseconds since the epoch.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceDetails {
+    pub service_id: String,
+    pub connection_id: String,
+    pub connected_at: u64,
+    pub contracts: Vec<String>,
+    pub methods: Vec<String>,
+    pub in_flight_requests: usize,
+    pub responsive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_heartbeat: Option<u64>,
+}
+
+fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 #[derive(Debug, Clone)]
 pub struct ServiceInfo {
     pub connection_id: String,
     pub tx: mpsc::Sender<Message>,
     pub is_sevice_registered: bool,
     callback_list: Arc<Mutex<HashMap<u64, BrokerCallback>>>,
+    connected_at: SystemTime,
+    contracts: Vec<String>,
+    ping_sequence: u64,
+    pending_ping: Option<u64>,
+    last_pong: Option<SystemTime>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -81,9 +134,19 @@ impl ServiceInfo {
             tx,
             is_sevice_registered,
             callback_list: Arc::new(Mutex::new(HashMap::new())),
+            connected_at: SystemTime::now(),
+            contracts: Vec::new(),
+            ping_sequence: 0,
+            pending_ping: None,
+            last_pong: None,
         }
     }
 
+    pub fn with_contracts(mut self, contracts: Vec<String>) -> Self {
+        self.contracts = contracts;
+        self
+    }
+
     pub async fn add_callback(&mut self, request_id: u64, callback: BrokerCallback) {
         let mut callback_list = self.callback_list.lock().await;
         callback_list.insert(request_id, callback);
@@ -114,6 +177,51 @@ impl ServiceInfo {
     pub fn get_sender(&self) -> &mpsc::Sender<Message> {
         &self.tx
     }
+    pub fn get_connected_at(&self) -> SystemTime {
+        self.connected_at
+    }
+    pub fn get_contracts(&self) -> &[String] {
+        &self.contracts
+    }
+    pub fn get_last_pong(&self) -> Option<SystemTime> {
+        self.last_pong
+    }
+    pub fn is_responsive(&self) -> bool {
+        self.pending_ping.is_none()
+    }
+    pub async fn get_in_flight_count(&self) -> usize {
+        self.callback_list.lock().await.len()
+    }
+
+    /// Starts the next ping, returns None while the previous ping is still unanswered.
+    pub fn next_ping(&mut self) -> Option<u64> {
+        if self.pending_ping.is_some() {
+            return None;
+        }
+        self.ping_sequence += 1;
+        self.pending_ping = Some(self.ping_sequence);
+        self.pending_ping
+    }
+
+    pub fn pong(&mut self, sequence: u64) {
+        if self.pending_ping == Some(sequence) {
+            self.pending_ping = None;
+            self.last_pong = Some(SystemTime::now());
+        }
+    }
+
+    // answers every brokered request still waiting on the service with an error
+    pub async fn fail_callbacks(&self, message: &str) {
+        let callbacks: Vec<(u64, BrokerCallback)> =
+            self.callback_list.lock().await.drain().collect();
+        for (request_id, callback) in callbacks {
+            let error = JsonRpcApiError::default()
+                .with_code(-32001)
+                .with_message(message.to_owned())
+                .with_id(request_id);
+            BrokerOutputForwarder::send_json_rpc_response_to_broker(error.into(), callback);
+        }
+    }
 }
 
 impl ServiceControllerState {
@@ -187,6 +295,10 @@ impl ServiceControllerState {
                     .await;
             }
             JsonRpcMessage::Success(_) | JsonRpcMessage::Error(_) => {
+                if let Some(sequence) = sm.get_pong_sequence() {
+                    state.service_controller_state.pong(&app_id, sequence).await;
+                    return;
+                }
                 // Handling response message
                 let request_id = sm.get_request_id();
                 let callback = state
@@ -246,14 +358,16 @@ impl ServiceControllerState {
         false
     }
 
-    pub async fn handle_service_connection(
+    pub async fn handle_service_connection<S>(
         _client_addr: SocketAddr,
-        ws_stream: WebSocketStream<TcpStream>,
+        ws_stream: WebSocketStream<S>,
         state: PlatformState,
         identity: ClientIdentity,
         connection_id: String,
         symbol: ExtnSymbol,
-    ) {
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
         let app_id = identity.app_id.clone();
         let session_id = identity.session_id.clone();
         let client = state.get_client();
@@ -276,8 +390,22 @@ impl ServiceControllerState {
             app_id.clone(),
             connection_id.clone(),
             message_tx.clone(),
+            symbol.fulfills.clone(),
         )
         .await;
+        LogLevelController::on_service_connected(&message_tx).await;
+        Self::emit_service_changed(&state, &app_id, ServiceStatus::Connected).await;
+
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        if let Some(interval) = state.extn_manifest.service_heartbeat_interval {
+            Self::start_heartbeat(
+                state.clone(),
+                app_id.clone(),
+                connection_id.clone(),
+                Duration::from_millis(interval),
+                stop_tx,
+            );
+        }
 
         let is_using_extn_contracts = Self::is_contract_used_for_routing(&symbol);
 
@@ -331,15 +459,20 @@ impl ServiceControllerState {
             });
         }
 
-        // Handle incoming messages for extensions/service (blocking)
-        Self::handle_incoming_service_messages(
-            &mut receiver,
-            &state,
-            &connection_id,
-            &identity,
-            &client,
-        )
-        .await;
+        // Handle incoming messages for extensions/service (blocking) until the connection closes
+        // or the heartbeat gives up on the service
+        tokio::select! {
+            _ = Self::handle_incoming_service_messages(
+                &mut receiver,
+                &state,
+                &connection_id,
+                &identity,
+                &client,
+            ) => {}
+            Ok(_) = stop_rx => {
+                let _ = message_tx.send(Message::Close(None)).await;
+            }
+        }
 
         // Cleanup service connection session
         Self::cleanup_service_connection(
@@ -359,13 +492,15 @@ impl ServiceControllerState {
         app_id: String,
         connection_id: String,
         message_tx: mpsc::Sender<Message>,
+        contracts: Vec<String>,
     ) -> Result<(), RippleError> {
         // Add the Message channel to the service registry
         let service_info = ServiceInfo::new(
             connection_id.clone(),
             message_tx.clone(),
             false, // Initially not registered
-        );
+        )
+        .with_contracts(contracts);
 
         state
             .service_controller_state
@@ -396,13 +531,15 @@ impl ServiceControllerState {
         }
     }
 
-    async fn handle_incoming_service_messages(
-        receiver: &mut SplitStream<WebSocketStream<TcpStream>>,
+    async fn handle_incoming_service_messages<S>(
+        receiver: &mut SplitStream<WebSocketStream<S>>,
         state: &PlatformState,
         connection_id: &str,
         identity: &ClientIdentity,
         client: &RippleClient,
-    ) {
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(msg) if msg.is_text() && !msg.is_empty() => {
@@ -454,16 +591,91 @@ impl ServiceControllerState {
             connection_id, app_id, session_id
         );
 
+        // a connection which was replaced by a reconnect of the service leaves the senders and
+        // contracts of the new connection alone
+        let Some(info) = state
+            .service_controller_state
+            .remove_service_connection(app_id, connection_id)
+            .await
+        else {
+            return;
+        };
+
         if is_using_extn_contracts {
             client
                 .get_extn_client()
                 .remove_sender(app_id.to_string(), symbol);
         }
 
-        let _ = state
-            .service_controller_state
-            .remove_service_info(&connection_id.to_string())
+        info.fail_callbacks(&format!("Service {} disconnected", app_id))
             .await;
+        Self::emit_service_changed(state, app_id, ServiceStatus::Disconnected).await;
+    }
+
+    /// Pings the service every interval, the connection is closed once a ping is not answered
+    /// by the next one.
+    fn start_heartbeat(
+        state: PlatformState,
+        service_id: String,
+        connection_id: String,
+        interval: Duration,
+        stop_tx: oneshot::Sender<()>,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match state
+                    .service_controller_state
+                    .next_ping(&service_id, &connection_id)
+                    .await
+                {
+                    Heartbeat::Ping(sender, sequence) => {
+                        let ping: String = ServiceMessage::new_ping(sequence).into();
+                        if let Err(err) = sender.send(Message::Text(ping)).await {
+                            error!("Failed to ping service {}: {}", service_id, err);
+                        }
+                    }
+                    Heartbeat::Unresponsive => {
+                        warn!(
+                            "Service {} did not answer ping, disconnecting connection_id={}",
+                            service_id, connection_id
+                        );
+                        let _ = stop_tx.send(());
+                        break;
+                    }
+                    Heartbeat::Disconnected => break,
+                }
+            }
+        });
+    }
+
+    async fn emit_service_changed(state: &PlatformState, service_id: &str, status: ServiceStatus) {
+        let event = ServiceChangedEvent {
+            service_id: service_id.to_owned(),
+            status,
+        };
+        if let Ok(value) = serde_json::to_value(event) {
+            AppEvents::emit(state, SERVICE_CHANGED_EVENT, &value).await;
+        }
+    }
+
+    pub async fn get_service_details(state: &PlatformState) -> Vec<ServiceDetails> {
+        let services = state.service_controller_state.get_services().await;
+        let mut details = Vec::with_capacity(services.len());
+        for (service_id, info) in services {
+            details.push(ServiceDetails {
+                methods: state.endpoint_state.get_service_methods(&service_id),
+                connection_id: info.get_connection_id().to_owned(),
+                connected_at: epoch_millis(info.get_connected_at()),
+                contracts: info.get_contracts().to_vec(),
+                in_flight_requests: info.get_in_flight_count().await,
+                responsive: info.is_responsive(),
+                last_heartbeat: info.get_last_pong().map(epoch_millis),
+                service_id,
+            });
+        }
+        details.sort_by(|a, b| a.service_id.cmp(&b.service_id));
+        details
     }
 
     fn handle_service_response(
@@ -508,6 +720,34 @@ impl ServiceControllerState {
             .remove_service_info(service_id)
             .await
     }
+    pub async fn remove_service_connection(
+        &self,
+        service_id: &str,
+        connection_id: &str,
+    ) -> Option<ServiceInfo> {
+        self.service_info
+            .lock()
+            .await
+            .remove_service_connection(service_id, connection_id)
+            .await
+    }
+    pub async fn get_services(&self) -> Vec<(String, ServiceInfo)> {
+        self.service_info.lock().await.get_services().await
+    }
+    pub async fn next_ping(&self, service_id: &str, connection_id: &str) -> Heartbeat {
+        self.service_info
+            .lock()
+            .await
+            .next_ping(service_id, connection_id)
+            .await
+    }
+    pub async fn pong(&self, service_id: &str, sequence: u64) {
+        self.service_info
+            .lock()
+            .await
+            .pong(service_id, sequence)
+            .await
+    }
     pub async fn set_broker_callback(
         &self,
         service_id: &String,
@@ -565,6 +805,11 @@ async fn return_invalid_service_error_message(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ripple_sdk::{
+        api::manifest::extn_manifest::ExtnManifest, extn::client::extn_client::ExtnClient,
+        tokio::net::TcpListener, tokio_tungstenite::accept_async,
+    };
+    use ripple_tdk::utils::test_utils::Mockable;
 
     #[tokio::test]
     async fn test_validate_sender() {
@@ -580,4 +825,95 @@ mod tests {
         let result = ServiceControllerState::validate_sender(context).await;
         assert!(!result, "{}", false);
     }
+
+    #[tokio::test]
+    async fn test_service_details_and_disconnect() {
+        let state = PlatformState::mock();
+        let (tx, _rx) = mpsc::channel(1);
+        let info = ServiceInfo::new("c1".to_owned(), tx, false)
+            .with_contracts(vec!["account.session".to_owned()]);
+        let controller = &state.service_controller_state;
+        controller
+            .add_service_info("svc".to_owned(), info)
+            .await
+            .unwrap();
+        let (callback_tx, mut callback_rx) = mpsc::channel(1);
+        controller
+            .set_broker_callback(
+                &"svc".to_owned(),
+                42,
+                BrokerCallback {
+                    sender: callback_tx,
+                },
+            )
+            .await
+            .unwrap();
+
+        let details = ServiceControllerState::get_service_details(&state).await;
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].service_id, "svc");
+        assert_eq!(details[0].contracts, vec!["account.session".to_owned()]);
+        assert_eq!(details[0].in_flight_requests, 1);
+        assert!(details[0].responsive);
+
+        // the pending request gets an error once the service is gone
+        let info = controller.remove_service_connection("svc", "c1").await;
+        info.unwrap()
+            .fail_callbacks("Service svc disconnected")
+            .await;
+        let output = callback_rx.recv().await.unwrap();
+        assert_eq!(output.data.id, Some(42));
+        assert!(output.data.error.is_some());
+        assert!(ServiceControllerState::get_service_details(&state)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_heartbeat_keeps_extn_connection() {
+        let mut state = PlatformState::mock();
+        state.extn_manifest = Arc::new(ExtnManifest {
+            service_heartbeat_interval: Some(50),
+            ..(*state.extn_manifest).clone()
+        });
+        let symbol = ExtnSymbol {
+            id: "ripple:channel:device:heartbeat".to_owned(),
+            ..Default::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (server_state, server_symbol) = (state.clone(), symbol.clone());
+        tokio::spawn(async move {
+            let (stream, client_addr) = listener.accept().await.unwrap();
+            let ws_stream = accept_async(stream).await.unwrap();
+            let identity = ClientIdentity {
+                session_id: "heartbeat_session".to_owned(),
+                app_id: server_symbol.id.clone(),
+                rpc_v2: false,
+                service_info: Some(server_symbol.clone()),
+            };
+            ServiceControllerState::handle_service_connection(
+                client_addr,
+                ws_stream,
+                server_state,
+                identity,
+                "c1".to_owned(),
+                server_symbol,
+            )
+            .await;
+        });
+        // an extension connected through its extn client, like thunder and the mock device
+        let (extn, _extn_rx) = ExtnClient::new_extn(symbol.clone());
+        let (_tx, tr) = mpsc::channel(1);
+        tokio::spawn(async move { extn.connect(&format!("ws://{}/", addr), tr).await });
+
+        // several intervals, a ping left unanswered would have closed the connection
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let details = ServiceControllerState::get_service_details(&state).await;
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].service_id, symbol.id);
+        assert!(details[0].responsive);
+        assert!(details[0].last_heartbeat.is_some());
+    }
 }
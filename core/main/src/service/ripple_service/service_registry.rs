@@ -23,6 +23,15 @@ use std::collections::HashMap;
 
 use super::service_controller_state::ServiceInfo;
 use crate::broker::endpoint_broker::BrokerCallback;
+
+/// Outcome of a heartbeat tick for a service connection.
+#[derive(Debug)]
+pub enum Heartbeat {
+    Ping(mpsc::Sender<Message>, u64),
+    Unresponsive,
+    Disconnected,
+}
+
 #[derive(Debug, Default)]
 pub struct ServiceRegistry {
     service_registry: Mutex<HashMap<String, ServiceInfo>>,
@@ -57,6 +66,50 @@ impl ServiceRegistry {
         }
     }
 
+    // removes the service only if it is still registered with the given connection, a service
+    // which reconnected keeps its new registration
+    pub async fn remove_service_connection(
+        &self,
+        service_id: &str,
+        connection_id: &str,
+    ) -> Option<ServiceInfo> {
+        let mut registry = self.service_registry.lock().await;
+        if registry
+            .get(service_id)
+            .is_some_and(|info| info.get_connection_id() == connection_id)
+        {
+            registry.remove(service_id)
+        } else {
+            None
+        }
+    }
+
+    pub async fn get_services(&self) -> Vec<(String, ServiceInfo)> {
+        let registry = self.service_registry.lock().await;
+        registry
+            .iter()
+            .map(|(service_id, info)| (service_id.clone(), info.clone()))
+            .collect()
+    }
+
+    pub async fn next_ping(&self, service_id: &str, connection_id: &str) -> Heartbeat {
+        let mut registry = self.service_registry.lock().await;
+        match registry.get_mut(service_id) {
+            Some(info) if info.get_connection_id() == connection_id => match info.next_ping() {
+                Some(sequence) => Heartbeat::Ping(info.tx.clone(), sequence),
+                None => Heartbeat::Unresponsive,
+            },
+            _ => Heartbeat::Disconnected,
+        }
+    }
+
+    pub async fn pong(&self, service_id: &str, sequence: u64) {
+        let mut registry = self.service_registry.lock().await;
+        if let Some(info) = registry.get_mut(service_id) {
+            info.pong(sequence);
+        }
+    }
+
     // get sender for a given service_id
     pub async fn get_sender(&self, service_id: &String) -> Option<mpsc::Sender<Message>> {
         let registry = self.service_registry.lock().await;
@@ -93,3 +146,66 @@ impl ServiceRegistry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::tokio;
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        let registry = ServiceRegistry::default();
+        let (tx, _rx) = mpsc::channel(1);
+        registry
+            .add_service_info(
+                "svc".to_owned(),
+                ServiceInfo::new("c1".to_owned(), tx, false),
+            )
+            .await
+            .unwrap();
+
+        let sequence = match registry.next_ping("svc", "c1").await {
+            Heartbeat::Ping(_, sequence) => sequence,
+            h => panic!("unexpected {:?}", h),
+        };
+        // a stale pong does not count
+        registry.pong("svc", sequence + 1).await;
+        assert!(matches!(
+            registry.next_ping("svc", "c1").await,
+            Heartbeat::Unresponsive
+        ));
+        registry.pong("svc", sequence).await;
+        assert!(matches!(
+            registry.next_ping("svc", "c1").await,
+            Heartbeat::Ping(_, s) if s == sequence + 1
+        ));
+        assert!(matches!(
+            registry.next_ping("svc", "c2").await,
+            Heartbeat::Disconnected
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_remove_service_connection() {
+        let registry = ServiceRegistry::default();
+        let (tx, _rx) = mpsc::channel(1);
+        registry
+            .add_service_info(
+                "svc".to_owned(),
+                ServiceInfo::new("c2".to_owned(), tx, false),
+            )
+            .await
+            .unwrap();
+        // the connection which was replaced leaves the registration alone
+        assert!(registry
+            .remove_service_connection("svc", "c1")
+            .await
+            .is_none());
+        assert_eq!(registry.get_services().await.len(), 1);
+        assert!(registry
+            .remove_service_connection("svc", "c2")
+            .await
+            .is_some());
+        assert!(registry.get_services().await.is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions, Permissions},
+    io::{LineWriter, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    sync::{Mutex, OnceLock},
+};
+
+use ripple_sdk::{
+    api::observability::session_recording::{
+        is_sensitive_method, RecordChannel, RecordDirection, RecordedMessage,
+    },
+    log::error,
+    serde_json::Value,
+    utils::error::RippleError,
+};
+
+static SESSION_RECORDER: OnceLock<SessionRecorder> = OnceLock::new();
+
+// requests whose responses are not answered are forgotten once there are this many
+const MAX_SENSITIVE_REQUESTS: usize = 1024;
+
+// channel, session and id of a request to a sensitive method
+type RequestKey = (RecordChannel, Option<String>, String);
+
+/// Appends every Firebolt message of the app connections and the brokered Thunder and HTTP
+/// traffic to a JSONL file, see [RecordedMessage]. Recording is started once at bootstrap when
+/// the device manifest has a `session_recording_path`, the record functions do nothing otherwise.
+///
+/// Credentials are redacted before they are written, the file is only readable by its owner.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    writer: Mutex<LineWriter<File>>,
+    // requests to sensitive methods waiting for the response whose result is redacted
+    sensitive: Mutex<HashSet<RequestKey>>,
+}
+
+impl SessionRecorder {
+    pub fn new(path: &str) -> Result<SessionRecorder, RippleError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .and_then(|file| {
+                // the mode only applies to a new file
+                file.set_permissions(Permissions::from_mode(0o600))?;
+                Ok(file)
+            })
+            .map_err(|e| {
+                error!("could not open session recording {} {:?}", path, e);
+                RippleError::InvalidOutput
+            })?;
+        Ok(SessionRecorder {
+            writer: Mutex::new(LineWriter::new(file)),
+            sensitive: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn start(path: &str) -> Result<(), RippleError> {
+        SESSION_RECORDER
+            .set(Self::new(path)?)
+            .map_err(|_| RippleError::InvalidAccess)
+    }
+
+    pub fn is_recording() -> bool {
+        SESSION_RECORDER.get().is_some()
+    }
+
+    /// Records a message of the Firebolt connection of an app.
+    pub fn record_session(app_id: &str, session_id: &str, direction: RecordDirection, msg: &str) {
+        if let Some(recorder) = SESSION_RECORDER.get() {
+            recorder.write(
+                RecordedMessage::new(RecordChannel::Firebolt, direction, msg)
+                    .with_session(app_id, session_id),
+            );
+        }
+    }
+
+    /// Records a message exchanged with the device.
+    pub fn record(channel: RecordChannel, direction: RecordDirection, msg: &str) {
+        if let Some(recorder) = SESSION_RECORDER.get() {
+            recorder.write(RecordedMessage::new(channel, direction, msg));
+        }
+    }
+
+    pub fn write(&self, message: RecordedMessage) {
+        let message = self.redact(message);
+        let Ok(line) = serde_json::to_string(&message) else {
+            return;
+        };
+        // a poisoned lock only means another write panicked, the file is still usable
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(writer, "{}", line) {
+            error!("could not write session recording {:?}", e);
+        }
+    }
+
+    // remembers the requests to sensitive methods and redacts the results of their responses
+    fn redact(&self, message: RecordedMessage) -> RecordedMessage {
+        let (channel, session_id) = (message.channel, message.session_id.clone());
+        let mut sensitive = self.sensitive.lock().unwrap_or_else(|e| e.into_inner());
+        message.redact().redact_results(|message| {
+            let Some(id) = message.get("id").filter(|id| !id.is_null()) else {
+                return false;
+            };
+            let key = (channel, session_id.clone(), id.to_string());
+            match message.get("method").and_then(Value::as_str) {
+                Some(method) if is_sensitive_method(method) => {
+                    if sensitive.len() >= MAX_SENSITIVE_REQUESTS {
+                        sensitive.clear();
+                    }
+                    sensitive.insert(key);
+                    false
+                }
+                Some(_) => false,
+                None => sensitive.remove(&key),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::api::observability::session_recording::{parse_recording, REDACTED};
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "ripple_session_recorder_{}_{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_session_recorder() {
+        let path = temp_file("write");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        let recorder = SessionRecorder::new(path).unwrap();
+        let request = RecordedMessage::new(
+            RecordChannel::Firebolt,
+            RecordDirection::Inbound,
+            r#"{"jsonrpc":"2.0","id":1,"method":"device.name"}"#,
+        )
+        .with_session("app", "session");
+        let thunder = RecordedMessage::new(
+            RecordChannel::Thunder,
+            RecordDirection::Outbound,
+            r#"{"jsonrpc":"2.0","id":5,"method":"org.rdk.System.1.getFriendlyName"}"#,
+        );
+        recorder.write(request.clone());
+        recorder.write(thunder.clone());
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert_eq!(parse_recording(&contents).unwrap(), vec![request, thunder]);
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_session_recorder_redacts() {
+        let path = temp_file("redact");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        let recorder = SessionRecorder::new(path).unwrap();
+        let record = |channel, direction, message: &str| {
+            let message = RecordedMessage::new(channel, direction, message);
+            let message = match channel {
+                RecordChannel::Firebolt => message.with_session("app", "session"),
+                _ => message,
+            };
+            recorder.write(message);
+        };
+        record(
+            RecordChannel::Firebolt,
+            RecordDirection::Inbound,
+            r#"{"jsonrpc":"2.0","id":1,"method":"authentication.token","params":{"type":"platform"}}"#,
+        );
+        record(
+            RecordChannel::Thunder,
+            RecordDirection::Outbound,
+            r#"{"jsonrpc":"2.0","id":1,"method":"org.rdk.AuthService.1.getServiceAccessToken"}"#,
+        );
+        record(
+            RecordChannel::Thunder,
+            RecordDirection::Inbound,
+            r#"{"jsonrpc":"2.0","id":1,"result":{"token":"abc","expires":3600}}"#,
+        );
+        record(
+            RecordChannel::Firebolt,
+            RecordDirection::Outbound,
+            r#"{"jsonrpc":"2.0","id":1,"result":{"value":"abc","expires":"2026"}}"#,
+        );
+        record(
+            RecordChannel::Firebolt,
+            RecordDirection::Outbound,
+            r#"{"jsonrpc":"2.0","id":2,"result":"Living Room"}"#,
+        );
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(!contents.contains("abc"));
+        let recording = parse_recording(&contents).unwrap();
+        assert_eq!(recording[2].message["result"], Value::from(REDACTED));
+        assert_eq!(recording[3].message["result"], Value::from(REDACTED));
+        assert_eq!(recording[4].message["result"], Value::from("Living Room"));
+        assert!(recorder.sensitive.lock().unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
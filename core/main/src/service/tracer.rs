@@ -0,0 +1,470 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{header::CONTENT_TYPE, Body, Client, Method, Request, Uri};
+use ripple_sdk::{
+    api::{
+        gateway::rpc_gateway_api::CallContext,
+        manifest::device_manifest::TraceExportConfiguration,
+        observability::trace_context::{Span, SpanStatus, TraceContext},
+    },
+    log::{error, warn},
+    tokio::{
+        self,
+        sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+    },
+    utils::error::RippleError,
+};
+use serde_json::{json, Value};
+
+static TRACER: OnceLock<Tracer> = OnceLock::new();
+
+// spans are sent to the collector in batches of at most this size
+const MAX_COLLECTOR_BATCH: usize = 64;
+// spans waiting for the collector, further spans are dropped while it is behind
+const MAX_QUEUED_SPANS: usize = 1024;
+// spans of requests which were never answered are dropped after this time
+const PENDING_SPAN_TIMEOUT: Duration = Duration::from_secs(300);
+const MAX_PENDING_SPANS: usize = 1024;
+
+#[derive(Debug)]
+enum SpanExporter {
+    File(Mutex<LineWriter<File>>),
+    Collector(Sender<Span>),
+}
+
+/// Propagates W3C trace contexts from Firebolt requests through the broker hops and exports
+/// the spans of both. A Firebolt request runs in a span which continues the `traceparent` sent
+/// by the client or starts a new trace, every request sent to a broker endpoint gets a child
+/// span and carries its context to HTTP and service endpoints. Contexts are always
+/// propagated, spans of sampled traces are only exported when tracing was started at bootstrap.
+#[derive(Debug)]
+pub struct Tracer {
+    pending: Mutex<HashMap<String, Span>>,
+    exporter: SpanExporter,
+    // spans dropped because the collector could not keep up
+    dropped: AtomicU64,
+}
+
+fn request_key(request_id: &str) -> String {
+    format!("request:{}", request_id)
+}
+
+fn broker_key(id: u64) -> String {
+    format!("broker:{}", id)
+}
+
+impl Tracer {
+    /// Appends the spans as JSON lines to `path`.
+    pub fn with_file(path: &str) -> Result<Tracer, RippleError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                error!("could not open trace export {} {:?}", path, e);
+                RippleError::InvalidOutput
+            })?;
+        Ok(Self::new(SpanExporter::File(Mutex::new(LineWriter::new(
+            file,
+        )))))
+    }
+
+    /// Posts the spans to an OTLP/HTTP collector accepting JSON, e.g. `http://127.0.0.1:4318/v1/traces`.
+    pub fn with_collector(url: &str) -> Result<Tracer, RippleError> {
+        let uri: Uri = url.parse().map_err(|_| {
+            error!("invalid trace collector url {}", url);
+            RippleError::InvalidInput
+        })?;
+        let (tx, rx) = mpsc::channel(MAX_QUEUED_SPANS);
+        tokio::spawn(Self::run_collector(uri, rx));
+        Ok(Self::new(SpanExporter::Collector(tx)))
+    }
+
+    fn new(exporter: SpanExporter) -> Tracer {
+        Tracer {
+            pending: Mutex::new(HashMap::new()),
+            exporter,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn start(config: &TraceExportConfiguration) -> Result<(), RippleError> {
+        let tracer = if let Some(url) = &config.collector_url {
+            Self::with_collector(url)?
+        } else if let Some(path) = &config.path {
+            Self::with_file(path)?
+        } else {
+            return Err(RippleError::InvalidInput);
+        };
+        TRACER.set(tracer).map_err(|_| RippleError::InvalidAccess)
+    }
+
+    pub fn is_tracing() -> bool {
+        TRACER.get().is_some()
+    }
+
+    /// Moves a Firebolt request into its own span, a child of the context sent by the client.
+    pub fn start_request_span(ctx: &mut CallContext) {
+        let parent = ctx.get_trace_context();
+        let trace = parent
+            .as_ref()
+            .map_or_else(TraceContext::new_root, TraceContext::child);
+        ctx.traceparent = Some(trace.to_traceparent());
+        if let Some(tracer) = TRACER.get() {
+            let span = Span::start(&ctx.method, &trace, parent.map(|p| p.span_id))
+                .with_attribute("app_id", &ctx.app_id)
+                .with_attribute("request_id", &ctx.request_id);
+            tracer.begin(request_key(&ctx.request_id), &trace, span);
+        }
+    }
+
+    /// Ends the span of a Firebolt request with the JSON-RPC response sent to the app.
+    pub fn end_request_span(request_id: &str, response: &str) {
+        if let Some(tracer) = TRACER.get() {
+            let failed = serde_json::from_str::<Value>(response)
+                .map(|response| response.get("error").is_some())
+                .unwrap_or(false);
+            tracer.finish(&request_key(request_id), failed);
+        }
+    }
+
+    /// Moves a request sent to a broker endpoint into a child span of the span it was made in,
+    /// the broker id of the request identifies the span.
+    pub fn start_broker_span(ctx: &mut CallContext, endpoint: &str, protocol: &str) {
+        let parent = ctx.get_trace_context();
+        let trace = parent
+            .as_ref()
+            .map_or_else(TraceContext::new_root, TraceContext::child);
+        ctx.traceparent = Some(trace.to_traceparent());
+        if let Some(tracer) = TRACER.get() {
+            let span = Span::start(&ctx.method, &trace, parent.map(|p| p.span_id))
+                .with_attribute("broker.endpoint", endpoint)
+                .with_attribute("broker.protocol", protocol)
+                .with_attribute("broker.id", &ctx.call_id.to_string())
+                .with_attribute("request_id", &ctx.request_id);
+            tracer.begin(broker_key(ctx.call_id), &trace, span);
+        }
+    }
+
+    pub fn end_broker_span(id: u64, failed: bool) {
+        if let Some(tracer) = TRACER.get() {
+            tracer.finish(&broker_key(id), failed);
+        }
+    }
+
+    // spans of traces which are not sampled are neither kept nor exported
+    fn begin(&self, key: String, trace: &TraceContext, span: Span) {
+        if !trace.sampled {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.len() >= MAX_PENDING_SPANS {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64;
+            let oldest = now.saturating_sub(PENDING_SPAN_TIMEOUT.as_nanos() as u64);
+            pending.retain(|_, span| span.start_time_unix_nano > oldest);
+            if pending.len() >= MAX_PENDING_SPANS {
+                warn!("too many open spans, {} is not traced", key);
+                return;
+            }
+        }
+        pending.insert(key, span);
+    }
+
+    fn finish(&self, key: &str, failed: bool) {
+        let span = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.remove(key)
+        };
+        if let Some(mut span) = span {
+            span.end(if failed {
+                SpanStatus::Error
+            } else {
+                SpanStatus::Ok
+            });
+            self.export(span);
+        }
+    }
+
+    pub fn export(&self, span: Span) {
+        match &self.exporter {
+            SpanExporter::File(writer) => {
+                let Ok(line) = serde_json::to_string(&span) else {
+                    return;
+                };
+                // a poisoned lock only means another write panicked, the file is still usable
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                if let Err(e) = writeln!(writer, "{}", line) {
+                    error!("could not write trace export {:?}", e);
+                }
+            }
+            SpanExporter::Collector(tx) => {
+                if let Err(TrySendError::Full(_)) = tx.try_send(span) {
+                    let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                    if dropped % MAX_QUEUED_SPANS as u64 == 1 {
+                        warn!("trace collector is behind, {} spans dropped", dropped);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn run_collector(uri: Uri, mut rx: Receiver<Span>) {
+        let client = Client::new();
+        while let Some(span) = rx.recv().await {
+            let mut spans = vec![span];
+            while spans.len() < MAX_COLLECTOR_BATCH {
+                match rx.try_recv() {
+                    Ok(span) => spans.push(span),
+                    Err(_) => break,
+                }
+            }
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(uri.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(to_otlp(&spans).to_string()));
+            let Ok(request) = request else {
+                continue;
+            };
+            match client.request(request).await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!(
+                    "trace collector rejected {} spans {}",
+                    spans.len(),
+                    response.status()
+                ),
+                Err(e) => warn!("could not send {} spans {:?}", spans.len(), e),
+            }
+        }
+    }
+}
+
+/// Converts spans to an OTLP trace export request in the JSON encoding.
+pub fn to_otlp(spans: &[Span]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let attributes: Vec<Value> = span
+                .attributes
+                .iter()
+                .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                .collect();
+            json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_span_id.clone().unwrap_or_default(),
+                "name": span.name,
+                // SPAN_KIND_SERVER
+                "kind": 2,
+                "startTimeUnixNano": span.start_time_unix_nano.to_string(),
+                "endTimeUnixNano": span.end_time_unix_nano.to_string(),
+                "attributes": attributes,
+                // STATUS_CODE_OK, STATUS_CODE_ERROR
+                "status": {"code": if span.status == SpanStatus::Error { 2 } else { 1 }},
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{"key": "service.name", "value": {"stringValue": "ripple"}}]
+            },
+            "scopeSpans": [{
+                "scope": {"name": "ripple"},
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use std::convert::Infallible;
+
+    fn context(request_id: &str, traceparent: Option<&str>) -> CallContext {
+        CallContext {
+            request_id: request_id.to_owned(),
+            method: "device.name".to_owned(),
+            app_id: "app".to_owned(),
+            traceparent: traceparent.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    fn read_spans(path: &str) -> Vec<Span> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "ripple_tracer_{}_{}.jsonl",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_file_export() {
+        let path = temp_file("file_export");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        let tracer = Tracer::with_file(path).unwrap();
+
+        let client_span = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mut ctx = context("1", Some(client_span));
+        let request = TraceContext::new_root();
+        ctx.traceparent = Some(request.to_traceparent());
+        tracer.begin(
+            request_key("1"),
+            &request,
+            Span::start(&ctx.method, &request, Some("00f067aa0ba902b7".to_owned())),
+        );
+        let hop = request.child();
+        tracer.begin(
+            broker_key(5),
+            &hop,
+            Span::start(&ctx.method, &hop, Some(request.span_id.clone())),
+        );
+        tracer.finish(&broker_key(5), true);
+        tracer.finish(&request_key("1"), false);
+        // spans are exported once
+        tracer.finish(&request_key("1"), false);
+
+        let spans = read_spans(path);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].span_id, hop.span_id);
+        assert_eq!(spans[0].parent_span_id, Some(request.span_id.clone()));
+        assert_eq!(spans[0].status, SpanStatus::Error);
+        assert_eq!(spans[1].trace_id, request.trace_id);
+        assert_eq!(spans[1].status, SpanStatus::Ok);
+        assert!(spans[1].end_time_unix_nano >= spans[1].start_time_unix_nano);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_unsampled_traces_are_not_exported() {
+        let path = temp_file("unsampled");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        let tracer = Tracer::with_file(path).unwrap();
+
+        let client =
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap();
+        let request = client.child();
+        tracer.begin(
+            request_key("4"),
+            &request,
+            Span::start("device.name", &request, Some(client.span_id.clone())),
+        );
+        tracer.finish(&request_key("4"), false);
+        assert!(read_spans(path).is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_collector_drops_spans_when_behind() {
+        let tracer = Tracer::with_collector("http://127.0.0.1:1/v1/traces").unwrap();
+        let trace = TraceContext::new_root();
+        // the collector task can not run before this test yields, the queue only fills up
+        for _ in 0..MAX_QUEUED_SPANS + 3 {
+            let mut span = Span::start("device.name", &trace, None);
+            span.end(SpanStatus::Ok);
+            tracer.export(span);
+        }
+        assert_eq!(tracer.dropped.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_context_propagation() {
+        let client_span = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mut ctx = context("2", Some(client_span));
+        Tracer::start_request_span(&mut ctx);
+        let request = ctx.get_trace_context().unwrap();
+        assert_eq!(request.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(request.span_id, "00f067aa0ba902b7");
+
+        Tracer::start_broker_span(&mut ctx, "thunder", "thunder");
+        let hop = ctx.get_trace_context().unwrap();
+        assert_eq!(hop.trace_id, request.trace_id);
+        assert_ne!(hop.span_id, request.span_id);
+
+        // requests without a context start a new trace
+        let mut ctx = context("3", None);
+        Tracer::start_request_span(&mut ctx);
+        assert!(ctx.get_trace_context().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_collector_export() {
+        let (body_tx, mut body_rx) = mpsc::unbounded_channel::<Value>();
+        let make_service = make_service_fn(move |_| {
+            let body_tx = body_tx.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let body_tx = body_tx.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        let _ = body_tx.send(serde_json::from_slice(&body).unwrap());
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let tracer = Tracer::with_collector(&format!("http://{}/v1/traces", addr)).unwrap();
+        let trace = TraceContext::new_root();
+        let mut span = Span::start("device.name", &trace, None).with_attribute("app_id", "app");
+        span.end(SpanStatus::Ok);
+        tracer.export(span);
+
+        let body = body_rx.recv().await.unwrap();
+        let exported = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(exported["traceId"], json!(trace.trace_id));
+        assert_eq!(exported["name"], json!("device.name"));
+        assert_eq!(
+            exported["attributes"][0],
+            json!({"key": "app_id", "value": {"stringValue": "app"}})
+        );
+    }
+}
@@ -33,7 +33,7 @@ pub mod openrpc_validator {
         }
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Default)]
     pub struct RpcMethodValidator;
 
     impl RpcMethodValidator {
@@ -71,6 +71,14 @@ pub mod openrpc_validator {
         ) -> Result<jsonschema::JSONSchema, super::ValidationError> {
             Ok(jsonschema::JSONSchema)
         }
+
+        pub fn result_validator(
+            &self,
+            _version: String,
+            _method: &str,
+        ) -> Result<jsonschema::JSONSchema, super::ValidationError> {
+            Ok(jsonschema::JSONSchema)
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -110,6 +118,7 @@ use ripple_sdk::{
     },
     utils::error::RippleError,
 };
+use serde::Deserialize;
 use serde_json::Value;
 use std::{
     collections::HashMap,
@@ -122,6 +131,49 @@ pub enum ApiSurface {
     Ripple,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenRpcExampleValue {
+    pub name: String,
+    pub value: Value,
+}
+
+/// Example of a method in the Firebolt OpenRPC, used to answer requests in the mock gateway mode.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenRpcExample {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<OpenRpcExampleValue>,
+    pub result: Option<OpenRpcExampleValue>,
+}
+
+impl OpenRpcExample {
+    /// An example matches when the request has the same value for each of its params.
+    fn matches(&self, params: Option<&Value>) -> bool {
+        self.params.iter().all(|p| {
+            params
+                .and_then(|params| params.get(&p.name))
+                .map_or(false, |value| value == &p.value)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenRpcExamplesMethod {
+    name: String,
+    #[serde(default)]
+    examples: Vec<OpenRpcExample>,
+}
+
+#[derive(Deserialize)]
+struct OpenRpcExamplesSpec {
+    methods: Vec<OpenRpcExamplesMethod>,
+}
+
+#[derive(Deserialize)]
+struct OpenRpcExamplesManifest {
+    apis: HashMap<String, OpenRpcExamplesSpec>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ProviderRelationSet {
     pub capability: Option<String>,
@@ -153,8 +205,11 @@ pub struct OpenRpcState {
     provider_relation_map: Arc<RwLock<HashMap<String, ProviderRelationSet>>>,
     openrpc_validator: Arc<RwLock<RpcMethodValidator>>,
     provider_registrations: Arc<Vec<String>>,
+    examples: Arc<RwLock<HashMap<String, Vec<OpenRpcExample>>>>,
     #[cfg(feature = "openrpc_validation")]
     json_schema_cache: Arc<RwLock<HashMap<String, JSONSchema>>>,
+    #[cfg(feature = "openrpc_validation")]
+    result_schema_cache: Arc<RwLock<HashMap<String, Option<Arc<JSONSchema>>>>>,
 }
 
 impl OpenRpcState {
@@ -225,8 +280,11 @@ impl OpenRpcState {
             provider_relation_map: Arc::new(RwLock::new(HashMap::new())),
             openrpc_validator: Arc::new(RwLock::new(rpc_method_validator)),
             provider_registrations: Arc::new(provider_registrations),
+            examples: Arc::new(RwLock::new(HashMap::new())),
             #[cfg(feature = "openrpc_validation")]
             json_schema_cache: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "openrpc_validation")]
+            result_schema_cache: Arc::new(RwLock::new(HashMap::new())),
         };
         v.build_provider_relation_sets(&firebolt_open_rpc.methods);
         for path in extn_sdks {
@@ -529,6 +587,47 @@ impl OpenRpcState {
             .extend(provider_relation_sets)
     }
 
+    /// Loads the method examples of the Firebolt OpenRPC, returns the number of methods with
+    /// examples.
+    pub fn load_examples(&self) -> Result<usize, RippleError> {
+        let content = load_firebolt_open_rpc_path()?;
+        self.add_examples(&content)
+    }
+
+    /// Adds the method examples of an OpenRPC document.
+    pub fn add_examples(&self, content: &str) -> Result<usize, RippleError> {
+        let manifest: OpenRpcExamplesManifest = serde_json::from_str(content).map_err(|e| {
+            error!("add_examples: can't parse open rpc examples e={:?}", e);
+            RippleError::ParseError
+        })?;
+        let mut examples = self.examples.write().unwrap();
+        for spec in manifest.apis.into_values() {
+            for method in spec.methods {
+                if !method.examples.is_empty() {
+                    examples.insert(method.name.to_lowercase(), method.examples);
+                }
+            }
+        }
+        Ok(examples.len())
+    }
+
+    /// Examples are only loaded in the mock gateway mode.
+    pub fn has_examples(&self) -> bool {
+        !self.examples.read().unwrap().is_empty()
+    }
+
+    /// Returns the result of the first example of the method matching the params, or of its
+    /// first example if none matches. `None` unless examples were loaded.
+    pub fn get_example_result(&self, method: &str, params: Option<&Value>) -> Option<Value> {
+        let examples = self.examples.read().unwrap();
+        let examples = examples.get(&method.to_lowercase())?;
+        examples
+            .iter()
+            .find(|e| e.matches(params))
+            .or_else(|| examples.first())
+            .map(|e| e.result.as_ref().map_or(Value::Null, |r| r.value.clone()))
+    }
+
     #[cfg(feature = "openrpc_validation")]
     pub fn add_json_schema_cache(&self, method: String, schema: JSONSchema) {
         let mut json_cache = self.json_schema_cache.write().unwrap();
@@ -563,6 +662,51 @@ impl OpenRpcState {
         let _ = (method, value); // Suppress unused variable warnings
         Err(None) // Always return "not found" when validation is disabled
     }
+
+    /// Validates the result of a method against the result schema in the Firebolt OpenRPC.
+    /// Methods which are not in the schema are not validated.
+    #[cfg(feature = "openrpc_validation")]
+    pub fn validate_result(&self, method: &str, result: &Value) -> Result<(), String> {
+        let method = method.to_lowercase();
+        let cached = self
+            .result_schema_cache
+            .read()
+            .unwrap()
+            .get(&method)
+            .cloned();
+        let schema = match cached {
+            Some(schema) => schema,
+            None => {
+                let validator = self.get_openrpc_validator();
+                let schema = validator.get_method(&method).and_then(|rpc_method| {
+                    validator
+                        .result_validator(self.get_version().major.to_string(), &rpc_method.name)
+                        .ok()
+                        .map(Arc::new)
+                });
+                self.result_schema_cache
+                    .write()
+                    .unwrap()
+                    .insert(method, schema.clone());
+                schema
+            }
+        };
+        match schema {
+            Some(schema) => schema.validate(result).map_err(|errors| {
+                errors
+                    .map(|e| format!("{} at {}", e, e.instance_path))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            }),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "openrpc_validation"))]
+    pub fn validate_result(&self, method: &str, result: &Value) -> Result<(), String> {
+        let _ = (method, result); // Suppress unused variable warnings
+        Ok(())
+    }
 }
 
 fn load_firebolt_open_rpc_from_file(fb_open_rpc_file: &str) -> Result<String, RippleError> {
@@ -661,4 +805,69 @@ mod tests {
         assert!(state.is_provider_enabled("integratedPlayer."));
         assert!(state.is_provider_enabled("integratedplayer."));
     }
+
+    #[test]
+    fn test_example_result() {
+        use ripple_sdk::serde_json::json;
+
+        let state = OpenRpcState::new(None, Vec::new(), default_providers());
+        assert!(state.get_example_result("device.name", None).is_none());
+
+        let content = json!({
+            "apis": {
+                "1": {
+                    "methods": [
+                        {
+                            "name": "Device.name",
+                            "examples": [{"name": "Default", "params": [], "result": {"name": "value", "value": "Living Room"}}]
+                        },
+                        {
+                            "name": "Localization.additionalInfo",
+                            "examples": [
+                                {"name": "Key a", "params": [{"name": "key", "value": "a"}], "result": {"name": "info", "value": 1}},
+                                {"name": "Key b", "params": [{"name": "key", "value": "b"}], "result": {"name": "info", "value": 2}}
+                            ]
+                        },
+                        {"name": "Device.model"}
+                    ]
+                }
+            }
+        });
+        assert_eq!(state.add_examples(&content.to_string()).unwrap(), 2);
+        assert_eq!(
+            state.get_example_result("device.name", Some(&json!({}))),
+            Some(json!("Living Room"))
+        );
+        assert_eq!(
+            state.get_example_result("localization.additionalInfo", Some(&json!({"key": "b"}))),
+            Some(json!(2))
+        );
+        assert_eq!(
+            state.get_example_result("localization.additionalInfo", None),
+            Some(json!(1))
+        );
+        assert!(state.get_example_result("device.model", None).is_none());
+    }
+
+    #[cfg(feature = "openrpc_validation")]
+    #[test]
+    fn test_validate_result() {
+        use ripple_sdk::serde_json::json;
+
+        let state = OpenRpcState::new(None, Vec::new(), default_providers());
+        assert!(state
+            .validate_result("device.name", &json!("Living Room"))
+            .is_ok());
+        assert!(state.validate_result("Device.name", &json!(1)).is_err());
+        // cached schema
+        assert!(state.validate_result("device.name", &json!(1)).is_err());
+        assert!(state
+            .validate_result("device.screenResolution", &json!([1920, 1080]))
+            .is_ok());
+        assert!(state
+            .validate_result("device.screenResolution", &json!("1080p"))
+            .is_err());
+        // methods without schema are not validated
+        assert!(state.validate_result("unknown.method", &json!(1)).is_ok());
+    }
 }
@@ -16,8 +16,10 @@
 //
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use ripple_sdk::{
@@ -30,12 +32,152 @@ include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
 const API_STATS_MAP_SIZE_WARNING: usize = 10;
 
+/// Label of the requests for methods which are neither routed nor brokered, the method of a
+/// request is set by the app and would add a series for every name it sends.
+pub const UNKNOWN_METHOD: &str = "unknown";
+
+// broker requests without a response for this long are no longer timed
+const BROKER_TIMING_TIMEOUT: Duration = Duration::from_secs(60);
+
+// upper bounds in seconds, the defaults of the Prometheus client libraries
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    // observations per bucket of DURATION_BUCKETS, the last one counts the rest
+    buckets: [u64; DURATION_BUCKETS.len() + 1],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, seconds: f64) {
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(DURATION_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Labels of a series, rendered in the given order.
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Default)]
+struct MetricFamilies {
+    request_durations: BTreeMap<String, Histogram>,
+    stage_durations: BTreeMap<String, Histogram>,
+    app_requests: BTreeMap<String, u64>,
+    request_errors: BTreeMap<(String, String), u64>,
+    broker_durations: BTreeMap<(String, String), Histogram>,
+    provider_sessions: BTreeMap<String, u64>,
+    provider_session_timeouts: BTreeMap<String, u64>,
+    thunder_reconnects: u64,
+}
+
+#[derive(Debug, Clone)]
+struct BrokerTiming {
+    endpoint: String,
+    protocol: String,
+    started_at: Instant,
+}
+
+#[derive(Debug)]
+struct BrokerTimings {
+    timings: HashMap<u64, BrokerTiming>,
+    // timings which timed out are dropped at most once per BROKER_TIMING_TIMEOUT
+    swept: Instant,
+}
+
+impl Default for BrokerTimings {
+    fn default() -> Self {
+        BrokerTimings {
+            timings: HashMap::new(),
+            swept: Instant::now(),
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_labels(labels: &Labels, extra: Option<(&str, String)>) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| (*name, value.clone()))
+        .chain(extra)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(&value)))
+        .collect();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+fn render_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, metric_type);
+}
+
+fn render_counter<'a, I>(out: &mut String, name: &str, help: &str, series: I)
+where
+    I: IntoIterator<Item = (Labels, &'a u64)>,
+{
+    render_header(out, name, "counter", help);
+    for (labels, value) in series {
+        let _ = writeln!(out, "{}{} {}", name, render_labels(&labels, None), value);
+    }
+}
+
+fn render_histogram<'a, I>(out: &mut String, name: &str, help: &str, series: I)
+where
+    I: IntoIterator<Item = (Labels, &'a Histogram)>,
+{
+    render_header(out, name, "histogram", help);
+    for (labels, histogram) in series {
+        let mut cumulative = 0;
+        for (i, count) in histogram.buckets.iter().enumerate() {
+            cumulative += count;
+            let bound = DURATION_BUCKETS
+                .get(i)
+                .map_or("+Inf".to_owned(), |b| b.to_string());
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                render_labels(&labels, Some(("le", bound))),
+                cumulative
+            );
+        }
+        let labels = render_labels(&labels, None);
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, histogram.count);
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct OpMetricState {
     pub start_time: DateTime<Utc>,
     operational_telemetry_listeners: Arc<RwLock<HashSet<String>>>,
     api_stats_map: Arc<RwLock<HashMap<String, ApiStats>>>,
     device_session_id: Arc<RwLock<Option<String>>>,
+    rate_limit_hits: Arc<RwLock<HashMap<String, u64>>>,
+    response_violations: Arc<RwLock<HashMap<String, u64>>>,
+    broker_timings: Arc<RwLock<BrokerTimings>>,
+    families: Arc<RwLock<MetricFamilies>>,
 }
 
 impl OpMetricState {
@@ -117,4 +259,310 @@ impl OpMetricState {
         let api_stats_map = self.api_stats_map.read().unwrap();
         api_stats_map.get(request_id).cloned()
     }
+
+    /// Counts a request rejected by the rate limit of `scope`.
+    pub fn record_rate_limit_hit(&self, scope: &str) {
+        let mut hits = self.rate_limit_hits.write().unwrap();
+        *hits.entry(scope.to_owned()).or_insert(0) += 1;
+    }
+
+    pub fn get_rate_limit_hits(&self) -> HashMap<String, u64> {
+        self.rate_limit_hits.read().unwrap().clone()
+    }
+
+    /// Counts a response of `method` which does not match its OpenRPC result schema.
+    pub fn record_response_violation(&self, method: &str) {
+        let mut violations = self.response_violations.write().unwrap();
+        *violations.entry(method.to_owned()).or_insert(0) += 1;
+    }
+
+    pub fn get_response_violations(&self) -> HashMap<String, u64> {
+        self.response_violations.read().unwrap().clone()
+    }
+
+    /// Aggregates the stage timings of a completed Firebolt request and drops its stats.
+    pub fn finish_api_stats(&mut self, request_id: &str) {
+        let stats = { self.api_stats_map.write().unwrap().remove(request_id) };
+        if let Some(stats) = stats {
+            self.record_api_stats(&stats);
+        }
+    }
+
+    pub fn record_api_stats(&self, stats: &ApiStats) {
+        let mut families = self.families.write().unwrap();
+        families
+            .request_durations
+            .entry(stats.api.clone())
+            .or_default()
+            .observe(stats.stats.get_total_time() as f64 / 1000.0);
+        for stage in stats.stats.get_stage_durations().split(',') {
+            if let Some((stage, duration)) = stage.split_once('=') {
+                if let Ok(duration) = duration.parse::<i64>() {
+                    families
+                        .stage_durations
+                        .entry(stage.to_owned())
+                        .or_default()
+                        .observe(duration as f64 / 1000.0);
+                }
+            }
+        }
+        // the stats ref is <app_id>,<method>,<status code> where 1 is a success
+        if let Some((header, code)) = stats.stats_ref.as_deref().and_then(|r| r.rsplit_once(',')) {
+            let app_id = header.split(',').next().unwrap_or_default();
+            *families.app_requests.entry(app_id.to_owned()).or_insert(0) += 1;
+            if code != "1" {
+                *families
+                    .request_errors
+                    .entry((stats.api.clone(), code.to_owned()))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Starts timing the request with the broker id `id` sent to a broker endpoint. Requests
+    /// which are not answered within [BROKER_TIMING_TIMEOUT] are dropped without a timing.
+    pub fn start_broker_timing(&self, id: u64, endpoint: &str, protocol: &str) {
+        let mut broker_timings = self.broker_timings.write().unwrap();
+        if broker_timings.swept.elapsed() >= BROKER_TIMING_TIMEOUT {
+            broker_timings
+                .timings
+                .retain(|_, timing| timing.started_at.elapsed() < BROKER_TIMING_TIMEOUT);
+            broker_timings.swept = Instant::now();
+        }
+        broker_timings.timings.insert(
+            id,
+            BrokerTiming {
+                endpoint: endpoint.to_owned(),
+                protocol: protocol.to_owned(),
+                started_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn finish_broker_timing(&self, id: u64) {
+        let timing = { self.broker_timings.write().unwrap().timings.remove(&id) };
+        if let Some(timing) = timing {
+            self.families
+                .write()
+                .unwrap()
+                .broker_durations
+                .entry((timing.endpoint, timing.protocol))
+                .or_default()
+                .observe(timing.started_at.elapsed().as_secs_f64());
+        }
+    }
+
+    pub fn record_provider_session(&self, capability: &str) {
+        let mut families = self.families.write().unwrap();
+        *families
+            .provider_sessions
+            .entry(capability.to_owned())
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_provider_session_timeout(&self, capability: &str) {
+        let mut families = self.families.write().unwrap();
+        *families
+            .provider_session_timeouts
+            .entry(capability.to_owned())
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_thunder_reconnect(&self) {
+        self.families.write().unwrap().thunder_reconnects += 1;
+    }
+
+    pub fn get_request_duration(&self, method: &str) -> Option<Histogram> {
+        self.families
+            .read()
+            .unwrap()
+            .request_durations
+            .get(method)
+            .cloned()
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render_metrics(&self) -> String {
+        let families = self.families.read().unwrap();
+        let mut out = String::new();
+        render_histogram(
+            &mut out,
+            "ripple_firebolt_request_duration_seconds",
+            "Time from receiving a Firebolt request to sending its response.",
+            families
+                .request_durations
+                .iter()
+                .map(|(method, h)| (vec![("method", method.clone())], h)),
+        );
+        render_histogram(
+            &mut out,
+            "ripple_firebolt_stage_duration_seconds",
+            "Time spent in a processing stage of a Firebolt request.",
+            families
+                .stage_durations
+                .iter()
+                .map(|(stage, h)| (vec![("stage", stage.clone())], h)),
+        );
+        render_counter(
+            &mut out,
+            "ripple_firebolt_app_requests_total",
+            "Firebolt requests answered per app.",
+            families
+                .app_requests
+                .iter()
+                .map(|(app_id, v)| (vec![("app_id", app_id.clone())], v)),
+        );
+        render_counter(
+            &mut out,
+            "ripple_firebolt_errors_total",
+            "Firebolt requests answered with an error.",
+            families.request_errors.iter().map(|((method, code), v)| {
+                (vec![("method", method.clone()), ("code", code.clone())], v)
+            }),
+        );
+        render_histogram(
+            &mut out,
+            "ripple_broker_request_duration_seconds",
+            "Time from sending a request to a broker endpoint to its response.",
+            families
+                .broker_durations
+                .iter()
+                .map(|((endpoint, protocol), h)| {
+                    (
+                        vec![
+                            ("endpoint", endpoint.clone()),
+                            ("protocol", protocol.clone()),
+                        ],
+                        h,
+                    )
+                }),
+        );
+        render_counter(
+            &mut out,
+            "ripple_provider_sessions_total",
+            "Provider sessions started.",
+            families
+                .provider_sessions
+                .iter()
+                .map(|(capability, v)| (vec![("capability", capability.clone())], v)),
+        );
+        render_counter(
+            &mut out,
+            "ripple_provider_session_timeouts_total",
+            "Provider sessions which timed out or whose provider did not respond.",
+            families
+                .provider_session_timeouts
+                .iter()
+                .map(|(capability, v)| (vec![("capability", capability.clone())], v)),
+        );
+        render_counter(
+            &mut out,
+            "ripple_thunder_reconnects_total",
+            "Reconnections to Thunder.",
+            [(vec![], &families.thunder_reconnects)],
+        );
+        let rate_limit_hits: BTreeMap<String, u64> =
+            self.get_rate_limit_hits().into_iter().collect();
+        render_counter(
+            &mut out,
+            "ripple_rate_limit_hits_total",
+            "Firebolt requests rejected by a rate limit.",
+            rate_limit_hits
+                .iter()
+                .map(|(scope, v)| (vec![("scope", scope.clone())], v)),
+        );
+        let response_violations: BTreeMap<String, u64> =
+            self.get_response_violations().into_iter().collect();
+        render_counter(
+            &mut out,
+            "ripple_response_violations_total",
+            "Firebolt responses not matching their OpenRPC result schema.",
+            response_violations
+                .iter()
+                .map(|(method, v)| (vec![("method", method.clone())], v)),
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.004);
+        histogram.observe(0.3);
+        histogram.observe(20.0);
+        assert_eq!(histogram.get_count(), 3);
+        assert_eq!(histogram.buckets[0], 1);
+        assert_eq!(histogram.buckets[6], 1);
+        assert_eq!(histogram.buckets[DURATION_BUCKETS.len()], 1);
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let mut state = OpMetricState::default();
+        state.add_api_stats("1", "device.name");
+        state.update_api_stage("1", "context_ready");
+        state.update_api_stats_ref("1", Some("app\"1,device.name,-50100".to_owned()));
+        state.finish_api_stats("1");
+        assert!(state.get_api_stats("1").is_none());
+        assert_eq!(
+            state
+                .get_request_duration("device.name")
+                .unwrap()
+                .get_count(),
+            1
+        );
+        state.start_broker_timing(7, "thunder", "thunder");
+        state.finish_broker_timing(7);
+        state.record_provider_session("xrn:firebolt:capability:usergrant:pinchallenge");
+        state.record_thunder_reconnect();
+        state.record_rate_limit_hit("app");
+
+        let metrics = state.render_metrics();
+        assert!(metrics.contains("# TYPE ripple_firebolt_request_duration_seconds histogram"));
+        assert!(metrics.contains(
+            "ripple_firebolt_request_duration_seconds_bucket{method=\"device.name\",le=\"+Inf\"} 1"
+        ));
+        assert!(metrics
+            .contains("ripple_firebolt_stage_duration_seconds_count{stage=\"context_ready\"} 1"));
+        assert!(metrics.contains("ripple_firebolt_app_requests_total{app_id=\"app\\\"1\"} 1"));
+        assert!(metrics
+            .contains("ripple_firebolt_errors_total{method=\"device.name\",code=\"-50100\"} 1"));
+        assert!(metrics.contains(
+            "ripple_broker_request_duration_seconds_count{endpoint=\"thunder\",protocol=\"thunder\"} 1"
+        ));
+        assert!(metrics.contains(
+            "ripple_provider_sessions_total{capability=\"xrn:firebolt:capability:usergrant:pinchallenge\"} 1"
+        ));
+        assert!(metrics.contains("ripple_thunder_reconnects_total 1"));
+        assert!(metrics.contains("ripple_rate_limit_hits_total{scope=\"app\"} 1"));
+    }
+
+    #[test]
+    fn test_unanswered_broker_timings_are_dropped() {
+        let state = OpMetricState::default();
+        state.start_broker_timing(1, "thunder", "thunder");
+        {
+            let mut broker_timings = state.broker_timings.write().unwrap();
+            let timed_out = Instant::now() - BROKER_TIMING_TIMEOUT;
+            broker_timings.timings.get_mut(&1).unwrap().started_at = timed_out;
+            broker_timings.swept = timed_out;
+        }
+        state.start_broker_timing(2, "thunder", "thunder");
+        let ids: Vec<u64> = {
+            let broker_timings = state.broker_timings.read().unwrap();
+            broker_timings.timings.keys().copied().collect()
+        };
+        assert_eq!(ids, vec![2]);
+
+        // a late response is not observed
+        state.finish_broker_timing(1);
+        assert!(!state
+            .render_metrics()
+            .contains("ripple_broker_request_duration_seconds_count"));
+    }
 }
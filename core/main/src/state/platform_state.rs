@@ -22,8 +22,7 @@ use ripple_sdk::{
         firebolt::fb_discovery::{AgePolicy, PolicyIdentifierAlias},
         gateway::rpc_gateway_api::RpcRequest,
         manifest::{
-            app_library::AppLibraryState,
-            device_manifest::{AppLibraryEntry, DeviceManifest},
+            device_manifest::{AppLibraryEntry, DeviceManifest, ResponseValidation},
             exclusory::ExclusoryImpl,
             extn_manifest::ExtnManifest,
         },
@@ -34,7 +33,7 @@ use ripple_sdk::{
         extn_id::ExtnId,
     },
     framework::ripple_contract::RippleContract,
-    log::debug,
+    log::{debug, error, info},
     serde_json::Value,
     tokio::sync::oneshot,
     utils::error::RippleError,
@@ -45,9 +44,11 @@ use std::{collections::HashMap, sync::Arc};
 use crate::{
     broker::{endpoint_broker::EndpointBrokerState, rules::rules_engine::RuleEngine},
     firebolt::rpc_router::RouterState,
+    processor::storage::storage_backend::{get_storage_backend, StorageBackend},
     service::{
         apps::{
             app_events::AppEventsState,
+            app_library::DynamicAppLibraryState,
             delegated_launcher_handler::{AppManagerState, AppManagerState2_0},
             provider_broker::ProviderBrokerState,
         },
@@ -58,7 +59,7 @@ use crate::{
 
 use super::{
     cap::cap_state::CapState, openrpc_state::OpenRpcState, ops_metrics_state::OpMetricState,
-    ripple_cache::RippleCache, session_state::SessionState,
+    rate_limit_state::RateLimitState, ripple_cache::RippleCache, session_state::SessionState,
 };
 
 /// Platform state encapsulates the internal state of the Ripple Main application.
@@ -102,7 +103,7 @@ pub struct PlatformState {
     pub extn_manifest: Arc<ExtnManifest>,
     device_manifest: Arc<DeviceManifest>,
     pub ripple_client: RippleClient,
-    pub app_library_state: AppLibraryState,
+    pub app_library_state: DynamicAppLibraryState,
     pub session_state: SessionState,
     pub cap_state: CapState,
     pub app_events_state: AppEventsState,
@@ -118,6 +119,8 @@ pub struct PlatformState {
     pub lifecycle2_app_state: AppManagerState2_0,
     pub service_controller_state: ServiceControllerState,
     pub policy_state: PolicyState,
+    pub rate_limit_state: RateLimitState,
+    pub storage_backend: Arc<dyn StorageBackend>,
 }
 
 impl PlatformState {
@@ -134,17 +137,29 @@ impl PlatformState {
         let extn_sdks = extn_manifest.extn_sdks.clone();
         let provider_registations = extn_manifest.provider_registrations.clone();
         let metrics_state = OpMetricState::default();
+        let open_rpc_state = OpenRpcState::new(Some(exclusory), extn_sdks, provider_registations);
+        if manifest.get_features().mock_gateway
+            || std::env::var("RIPPLE_MOCK_GATEWAY").map_or(false, |v| v == "true")
+        {
+            match open_rpc_state.load_examples() {
+                Ok(count) => info!("mock gateway: loaded examples of {} methods", count),
+                Err(e) => error!("mock gateway: failed to load examples {:?}", e),
+            }
+        }
         Self {
             extn_manifest: Arc::new(extn_manifest),
             cap_state: CapState::new(manifest.clone()),
             session_state: SessionState::default(),
             device_manifest: Arc::new(manifest.clone()),
             ripple_client: client.clone(),
-            app_library_state: AppLibraryState::new(app_library),
+            app_library_state: DynamicAppLibraryState::new(
+                app_library,
+                &manifest.configuration.saved_dir,
+            ),
             app_events_state: AppEventsState::default(),
             provider_broker_state: ProviderBrokerState::default(),
             app_manager_state: AppManagerState::new(&manifest.configuration.saved_dir.clone()),
-            open_rpc_state: OpenRpcState::new(Some(exclusory), extn_sdks, provider_registations),
+            open_rpc_state,
             router_state: RouterState::new(),
             metrics: metrics_state.clone(),
             device_session_id: DeviceSessionIdentifier::default(),
@@ -159,6 +174,8 @@ impl PlatformState {
             lifecycle2_app_state: AppManagerState2_0::new(),
             service_controller_state: ServiceControllerState::new(),
             policy_state: PolicyState::default(),
+            rate_limit_state: RateLimitState::new(manifest.get_rate_limits()),
+            storage_backend: get_storage_backend(&manifest),
         }
     }
 
@@ -203,6 +220,14 @@ impl PlatformState {
         (*self.device_manifest).clone()
     }
 
+    pub fn get_response_validation(&self) -> ResponseValidation {
+        self.device_manifest
+            .configuration
+            .features
+            .response_validation
+            .clone()
+    }
+
     pub fn get_client(&self) -> RippleClient {
         self.ripple_client.clone()
     }
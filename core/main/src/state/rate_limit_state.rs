@@ -0,0 +1,312 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ripple_sdk::api::manifest::device_manifest::{RateLimit, RateLimitConfiguration};
+
+// how often buckets which refilled completely are dropped, a new bucket is full as well
+const BUCKET_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitScope {
+    Gateway,
+    App(String),
+    Method { app_id: String, method: String },
+}
+
+impl Display for RateLimitScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitScope::Gateway => write!(f, "gateway"),
+            RateLimitScope::App(app_id) => write!(f, "app:{}", app_id),
+            RateLimitScope::Method { app_id, method } => {
+                write!(f, "method:{}:{}", app_id, method)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.capacity as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.refill_per_sec).min(self.limit.capacity as f64);
+        self.updated = now;
+    }
+
+    fn has_token(&self) -> bool {
+        self.tokens >= 1.0
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.capacity as f64
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<String, TokenBucket>,
+    swept: Option<Instant>,
+}
+
+impl Buckets {
+    fn sweep(&mut self, now: Instant) {
+        let due = self.swept.map_or(true, |swept| {
+            now.saturating_duration_since(swept) >= BUCKET_SWEEP_INTERVAL
+        });
+        if !due {
+            return;
+        }
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            !bucket.is_full()
+        });
+        self.swept = Some(now);
+    }
+}
+
+/// Token buckets limiting the rate of requests handled by the gateway, see
+/// [RateLimitConfiguration] for how limits are configured.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitState {
+    config: Arc<RateLimitConfiguration>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimitState {
+    pub fn new(config: RateLimitConfiguration) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    /// Returns the limit of a method along with the key of its bucket, the calls of all methods
+    /// of a module share one bucket when the limit is configured for the module.
+    fn get_method_limit<'a>(&self, method: &'a str) -> Option<(&'a str, RateLimit)> {
+        if let Some(limit) = self.config.methods.get(method) {
+            return Some((method, *limit));
+        }
+        let (module, _) = method.split_once('.')?;
+        self.config
+            .methods
+            .get(module)
+            .map(|limit| (module, *limit))
+    }
+
+    fn get_limits(&self, app_id: &str, method: &str) -> Vec<(RateLimitScope, RateLimit)> {
+        let mut limits = Vec::new();
+        if let Some(limit) = self.config.gateway {
+            limits.push((RateLimitScope::Gateway, limit));
+        }
+        if let Some(limit) = self.config.apps.get(app_id).copied().or(self.config.app) {
+            limits.push((RateLimitScope::App(app_id.to_owned()), limit));
+        }
+        if let Some((key, limit)) = self.get_method_limit(method) {
+            limits.push((
+                RateLimitScope::Method {
+                    app_id: app_id.to_owned(),
+                    method: key.to_owned(),
+                },
+                limit,
+            ));
+        }
+        limits
+    }
+
+    /// Takes a token from every bucket the request counts against. Nothing is taken when one of
+    /// them is empty, the scope of that bucket is returned instead.
+    pub fn check(&self, app_id: &str, method: &str) -> Result<(), RateLimitScope> {
+        self.check_at(app_id, method, Instant::now())
+    }
+
+    fn check_at(&self, app_id: &str, method: &str, now: Instant) -> Result<(), RateLimitScope> {
+        let limits = self.get_limits(app_id, method);
+        if limits.is_empty() {
+            return Ok(());
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.sweep(now);
+        let buckets = &mut buckets.buckets;
+        for (scope, limit) in &limits {
+            let bucket = buckets
+                .entry(scope.to_string())
+                .or_insert_with(|| TokenBucket::new(*limit, now));
+            bucket.refill(now);
+            if !bucket.has_token() {
+                return Err(scope.clone());
+            }
+        }
+        for (scope, _) in &limits {
+            if let Some(bucket) = buckets.get_mut(&scope.to_string()) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limit(capacity: u32, refill_per_sec: f64) -> RateLimit {
+        RateLimit {
+            capacity,
+            refill_per_sec,
+        }
+    }
+
+    #[test]
+    fn test_no_limits() {
+        let state = RateLimitState::default();
+        for _ in 0..100 {
+            assert!(state.check("app", "device.version").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_app_limit_refills() {
+        let state = RateLimitState::new(RateLimitConfiguration {
+            app: Some(limit(2, 1.0)),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(state.check_at("app", "device.version", now).is_ok());
+        assert!(state.check_at("app", "device.version", now).is_ok());
+        assert_eq!(
+            state.check_at("app", "device.version", now),
+            Err(RateLimitScope::App("app".to_owned()))
+        );
+        // other apps have their own bucket
+        assert!(state.check_at("other", "device.version", now).is_ok());
+        let later = now + Duration::from_secs(1);
+        assert!(state.check_at("app", "device.version", later).is_ok());
+        assert!(state.check_at("app", "device.version", later).is_err());
+    }
+
+    #[test]
+    fn test_method_and_module_limits() {
+        let state = RateLimitState::new(RateLimitConfiguration {
+            methods: HashMap::from([
+                ("device".to_owned(), limit(1, 0.0)),
+                ("device.name".to_owned(), limit(2, 0.0)),
+            ]),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(state.check_at("app", "device.version", now).is_ok());
+        assert_eq!(
+            state.check_at("app", "device.version", now),
+            Err(RateLimitScope::Method {
+                app_id: "app".to_owned(),
+                method: "device".to_owned()
+            })
+        );
+        assert!(state.check_at("app", "device.name", now).is_ok());
+        assert!(state.check_at("app", "device.name", now).is_ok());
+        assert!(state.check_at("app", "device.name", now).is_err());
+        assert!(state.check_at("app", "localization.language", now).is_ok());
+    }
+
+    #[test]
+    fn test_module_limit_is_shared_by_its_methods() {
+        let state = RateLimitState::new(RateLimitConfiguration {
+            methods: HashMap::from([("device".to_owned(), limit(2, 0.0))]),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(state.check_at("app", "device.version", now).is_ok());
+        assert!(state.check_at("app", "device.model", now).is_ok());
+        assert_eq!(
+            state.check_at("app", "device.make", now),
+            Err(RateLimitScope::Method {
+                app_id: "app".to_owned(),
+                method: "device".to_owned()
+            })
+        );
+        assert!(state.check_at("app", "device.version", now).is_err());
+        assert!(state.check_at("other", "device.version", now).is_ok());
+    }
+
+    #[test]
+    fn test_refilled_buckets_are_dropped() {
+        let state = RateLimitState::new(RateLimitConfiguration {
+            app: Some(limit(2, 1.0)),
+            methods: HashMap::from([("device".to_owned(), limit(1, 0.0))]),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        for app_id in ["first", "second"] {
+            assert!(state.check_at(app_id, "device.version", now).is_ok());
+        }
+        assert_eq!(state.buckets.lock().unwrap().buckets.len(), 4);
+
+        let later = now + BUCKET_SWEEP_INTERVAL;
+        assert!(state.check_at("third", "account.id", later).is_ok());
+        let buckets = state.buckets.lock().unwrap();
+        // the app buckets refilled, the method buckets never refill and stay
+        let mut keys: Vec<&String> = buckets.buckets.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec!["app:third", "method:first:device", "method:second:device"]
+        );
+    }
+
+    #[test]
+    fn test_rejected_request_takes_no_token() {
+        let state = RateLimitState::new(RateLimitConfiguration {
+            gateway: Some(limit(2, 0.0)),
+            apps: HashMap::from([("greedy".to_owned(), limit(1, 0.0))]),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert!(state.check_at("greedy", "device.version", now).is_ok());
+        assert_eq!(
+            state.check_at("greedy", "device.version", now),
+            Err(RateLimitScope::App("greedy".to_owned()))
+        );
+        assert!(state.check_at("other", "device.version", now).is_ok());
+        assert_eq!(
+            state.check_at("other", "device.version", now),
+            Err(RateLimitScope::Gateway)
+        );
+    }
+}
@@ -35,6 +35,7 @@ pub use ripple_sdk::utils::rpc_utils::rpc_err;
 pub const FIRE_BOLT_DEEPLINK_ERROR_CODE: i32 = -40400;
 pub const DOWNSTREAM_SERVICE_UNAVAILABLE_ERROR_CODE: i32 = -50200;
 pub const SESSION_NO_INTENT_ERROR_CODE: i32 = -40000;
+pub const RATE_LIMIT_EXCEEDED_ERROR_CODE: i32 = -40029;
 
 /// Awaits a oneshot to respond. If the oneshot fails to repond, creates a generic
 /// RPC internal error
@@ -106,6 +106,7 @@ impl MockCallContext {
             cid: Some("cid".to_owned()),
             gateway_secure: false,
             context: Vec::new(),
+            traceparent: None,
         }
     }
 }
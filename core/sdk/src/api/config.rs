@@ -194,6 +194,7 @@ mod tests {
                     boot_state: BootState::Inactive,
                 }],
                 providers: HashMap::new(),
+                remote_manifests: HashMap::new(),
             },
         };
         let contract_type: RippleContract = RippleContract::Config;
@@ -33,6 +33,15 @@ pub const AUDIO_CHANGED_EVENT: &str = "device.onAudioChanged";
 pub const VOICE_GUIDANCE_SETTINGS_CHANGED: &str = "accessibility.onVoiceGuidanceSettingsChanged";
 pub const VOICE_GUIDANCE_ENABLED_CHANGED: &str = "voiceguidance.onEnabledChanged";
 pub const VOICE_GUIDANCE_SPEED_CHANGED: &str = "voiceguidance.onSpeedChanged";
+pub const DEVICE_CONNECTION_CHANGED_EVENT: &str = "ripple.onDeviceConnectionChanged";
+
+/// State of the connection between Ripple and the device platform, e.g. Thunder.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeviceConnectionStatus {
+    Connected,
+    Disconnected,
+}
 
 // Is this from the device to thunder event handler???
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -85,6 +85,7 @@ mod tests {
             cid: Some("test_cid".to_string()),
             gateway_secure: false,
             context: vec!["test_context".to_string()],
+            traceparent: None,
         };
 
         let request_with_event = ListenRequestWithEvent {
@@ -173,6 +174,7 @@ mod tests {
             cid: Some("complex_cid_789".to_string()),
             gateway_secure: true,
             context: vec!["complex_context".to_string(), "another_context".to_string()],
+            traceparent: None,
         };
 
         let complex_request = ListenRequestWithEvent {
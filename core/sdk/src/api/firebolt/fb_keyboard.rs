@@ -202,6 +202,7 @@ mod tests {
                 cid: Some("test_cid".to_string()),
                 gateway_secure: true,
                 context: Vec::new(),
+                traceparent: None,
             },
             message: "test_message".to_string(),
         };
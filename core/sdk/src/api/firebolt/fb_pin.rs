@@ -155,6 +155,7 @@ mod tests {
             cid: Some("cid".to_string()),
             gateway_secure: true,
             context: Vec::new(),
+            traceparent: None,
         };
 
         let pin_challenge_request_with_context = PinChallengeRequestWithContext {
@@ -200,6 +201,7 @@ mod tests {
                 cid: Some("test_cid".to_string()),
                 gateway_secure: true,
                 context: Vec::new(),
+                traceparent: None,
             },
         };
         let contract_type: RippleContract = RippleContract::PinChallenge;
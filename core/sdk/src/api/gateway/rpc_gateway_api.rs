@@ -24,7 +24,10 @@ use uuid::Uuid;
 use crate::{
     api::{
         firebolt::{fb_general::ListenRequest, fb_openrpc::FireboltOpenRpcMethod},
-        observability::metrics_util::ApiStats,
+        observability::{
+            metrics_util::ApiStats,
+            trace_context::{TraceContext, TRACEPARENT},
+        },
     },
     extn::extn_client_message::{ExtnPayload, ExtnPayloadProvider, ExtnRequest},
     framework::ripple_contract::RippleContract,
@@ -69,6 +72,9 @@ pub struct CallContext {
     pub cid: Option<String>,
     pub gateway_secure: bool,
     pub context: Vec<String>,
+    /// W3C trace context of the span the call runs in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
 }
 impl From<CallContext> for serde_json::Value {
     fn from(ctx: CallContext) -> Self {
@@ -82,6 +88,7 @@ impl From<CallContext> for serde_json::Value {
             "cid": ctx.cid,
             "gateway_secure": ctx.gateway_secure,
             "context": ctx.context,
+            "traceparent": ctx.traceparent,
         })
     }
 }
@@ -124,6 +131,7 @@ impl CallContext {
             cid,
             gateway_secure,
             context: Vec::new(),
+            traceparent: None,
         }
     }
 
@@ -138,6 +146,10 @@ impl CallContext {
         self.context.contains(&RPC_V2.to_owned())
     }
 
+    pub fn get_trace_context(&self) -> Option<TraceContext> {
+        self.traceparent.as_deref().and_then(TraceContext::parse)
+    }
+
     pub fn internal(method: &str) -> Self {
         CallContext::new(
             Uuid::new_v4().to_string(),
@@ -164,6 +176,7 @@ impl crate::Mockable for CallContext {
             cid: Some("cid".to_owned()),
             gateway_secure: true,
             context: Vec::new(),
+            traceparent: None,
         }
     }
 }
@@ -604,6 +617,12 @@ impl RpcRequest {
         if !base.is_jsonrpc() {
             return Err(RequestParseError {});
         }
+        // the trace context of the client, the gateway starts its span as a child of it
+        let traceparent = parsed
+            .get(TRACEPARENT)
+            .and_then(|v| v.as_str())
+            .and_then(TraceContext::parse)
+            .map(|trace| trace.to_traceparent());
         let jsonrpc_req = serde_json::from_value::<JsonRpcApiRequest>(parsed)
             .map_err(|_| RequestParseError {})?;
 
@@ -620,6 +639,7 @@ impl RpcRequest {
             gateway_secure,
         );
         ctx.context = context;
+        ctx.traceparent = traceparent;
         let ps = RpcRequest::prepend_ctx(jsonrpc_req.params, &ctx);
         Ok(RpcRequest::new(method, ps, ctx))
     }
@@ -763,6 +783,7 @@ mod tests {
             cid: Some("cid123".to_string()),
             gateway_secure: true,
             context: Vec::new(),
+            traceparent: None,
         };
 
         let caller_session: CallerSession = ctx.into();
@@ -783,6 +804,7 @@ mod tests {
             cid: Some("cid123".to_string()),
             gateway_secure: true,
             context: Vec::new(),
+            traceparent: None,
         };
 
         let app_identification: AppIdentification = ctx.into();
@@ -986,6 +1008,7 @@ mod tests {
             cid: Some("some_cid".to_string()),
             gateway_secure: true,
             context: Vec::new(),
+            traceparent: None,
         };
 
         let rpc_request = RpcRequest {
@@ -19,14 +19,23 @@ use super::{
     apps::AppManifest,
     device_manifest::{AppLibraryEntry, AppManifestLoad, BootState},
 };
+use crate::{
+    extn::extn_client_message::{ExtnEvent, ExtnPayload, ExtnPayloadProvider},
+    framework::ripple_contract::RippleContract,
+};
 use log::{error, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub const APP_LIBRARY_CHANGED_EVENT: &str = "ripple.onAppLibraryChanged";
+
 #[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct AppLibraryState {
     pub default_apps: Vec<AppLibraryEntry>,
     pub providers: HashMap<String, String>,
+    /// Last fetched manifests of the apps with a [AppManifestLoad::Remote] manifest.
+    #[serde(default)]
+    pub remote_manifests: HashMap<String, AppManifest>,
 }
 
 impl std::fmt::Debug for AppLibraryState {
@@ -40,14 +49,54 @@ pub struct DefaultLibrary {
     pub default_library: Vec<AppLibraryEntry>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppLibraryChange {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// Sent to the launcher and emitted as [APP_LIBRARY_CHANGED_EVENT] when an entry of the app
+/// library changes at runtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLibraryChangedEvent {
+    pub app_id: String,
+    pub change: AppLibraryChange,
+    /// Capabilities whose provider in the library changed.
+    pub capabilities: Vec<String>,
+}
+
+impl ExtnPayloadProvider for AppLibraryChangedEvent {
+    fn get_extn_payload(&self) -> ExtnPayload {
+        ExtnPayload::Event(ExtnEvent::Value(serde_json::to_value(self).unwrap()))
+    }
+
+    fn get_from_payload(payload: ExtnPayload) -> Option<Self> {
+        if let ExtnPayload::Event(ExtnEvent::Value(value)) = payload {
+            if let Ok(v) = serde_json::from_value(value) {
+                return Some(v);
+            }
+        }
+
+        None
+    }
+
+    fn contract() -> RippleContract {
+        RippleContract::Launcher
+    }
+}
+
 pub struct AppLibrary {}
 
 impl AppLibraryState {
     pub fn new(default_apps: Vec<AppLibraryEntry>) -> AppLibraryState {
-        let providers = AppLibrary::generate_provider_relation_map(&default_apps);
+        let providers = AppLibrary::generate_provider_relation_map(&default_apps, &HashMap::new());
         AppLibraryState {
             default_apps,
             providers,
+            remote_manifests: HashMap::new(),
         }
     }
 
@@ -55,6 +104,54 @@ impl AppLibraryState {
         self.default_apps.clone()
     }
 
+    pub fn get_app(&self, app_id: &str) -> Option<AppLibraryEntry> {
+        self.default_apps
+            .iter()
+            .find(|a| a.app_id == app_id)
+            .cloned()
+    }
+
+    /// Adds the entry or replaces the one of the same app, returning the replaced entry.
+    pub fn upsert(&mut self, entry: AppLibraryEntry) -> Option<AppLibraryEntry> {
+        let previous = match self
+            .default_apps
+            .iter_mut()
+            .find(|a| a.app_id == entry.app_id)
+        {
+            Some(existing) => Some(std::mem::replace(existing, entry)),
+            None => {
+                self.default_apps.push(entry);
+                None
+            }
+        };
+        self.update_providers();
+        previous
+    }
+
+    pub fn remove(&mut self, app_id: &str) -> Option<AppLibraryEntry> {
+        let index = self.default_apps.iter().position(|a| a.app_id == app_id)?;
+        let removed = self.default_apps.remove(index);
+        self.remote_manifests.remove(app_id);
+        self.update_providers();
+        Some(removed)
+    }
+
+    /// Sets the fetched manifest of an app with a remote manifest, returning the previous one.
+    pub fn set_remote_manifest(
+        &mut self,
+        app_id: &str,
+        manifest: AppManifest,
+    ) -> Option<AppManifest> {
+        let previous = self.remote_manifests.insert(app_id.to_owned(), manifest);
+        self.update_providers();
+        previous
+    }
+
+    fn update_providers(&mut self) {
+        self.providers =
+            AppLibrary::generate_provider_relation_map(&self.default_apps, &self.remote_manifests);
+    }
+
     pub fn get_default_app(&self) -> Option<AppLibraryEntry> {
         if let Some(default_app) = self
             .default_apps
@@ -78,9 +175,12 @@ impl AppLibrary {
         let i = itr.position(|x| x.app_id == *app_id)?;
         let library_entry = state.default_apps.get(i).unwrap();
         match &library_entry.manifest {
-            AppManifestLoad::Remote(_) => {
-                error!("Remote manifests not supported yet");
-                None
+            AppManifestLoad::Remote(url) => {
+                let manifest = state.remote_manifests.get(app_id).cloned();
+                if manifest.is_none() {
+                    warn!("Remote manifest of {} not fetched yet from {}", app_id, url);
+                }
+                manifest
             }
             AppManifestLoad::Local(_) => {
                 error!("Local manifests not supported yet");
@@ -90,12 +190,35 @@ impl AppLibrary {
         }
     }
 
-    fn generate_provider_relation_map(apps: &[AppLibraryEntry]) -> HashMap<String, String> {
+    /// Capabilities which gained, lost or changed their provider between the two maps.
+    pub fn get_changed_providers(
+        before: &HashMap<String, String>,
+        after: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let mut changed: Vec<String> = before
+            .keys()
+            .chain(after.keys())
+            .filter(|capability| before.get(*capability) != after.get(*capability))
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+
+    fn generate_provider_relation_map(
+        apps: &[AppLibraryEntry],
+        remote_manifests: &HashMap<String, AppManifest>,
+    ) -> HashMap<String, String> {
         let mut map = HashMap::new();
 
         for app in apps.iter() {
-            let manifest = &app.manifest;
-            if let AppManifestLoad::Embedded(manifest) = manifest {
+            let manifest = match &app.manifest {
+                AppManifestLoad::Embedded(manifest) => Some(manifest),
+                AppManifestLoad::Remote(_) => remote_manifests.get(&app.app_id),
+                AppManifestLoad::Local(_) => None,
+            };
+            if let Some(manifest) = manifest {
                 for capability in manifest.capabilities.provided.required.iter() {
                     map.insert(capability.clone(), app.app_id.clone());
                 }
@@ -104,8 +227,8 @@ impl AppLibrary {
                 }
             } else {
                 warn!(
-                    "generate_provider_relation_map: Not supported: {:?}",
-                    app.manifest
+                    "generate_provider_relation_map: No manifest for {}: {:?}",
+                    app.app_id, app.manifest
                 );
             }
         }
@@ -140,7 +263,7 @@ mod tests {
         let app_library_state = AppLibraryState::new(default_apps.clone());
         assert_eq!(app_library_state.default_apps, default_apps);
 
-        let providers = AppLibrary::generate_provider_relation_map(&default_apps);
+        let providers = AppLibrary::generate_provider_relation_map(&default_apps, &HashMap::new());
         assert_eq!(app_library_state.providers, providers);
     }
 
@@ -193,6 +316,93 @@ mod tests {
         );
     }
 
+    fn get_provider_app(app_id: &str, capability: &str) -> AppLibraryEntry {
+        let mut manifest = AppManifest::default();
+        manifest.capabilities.provided.required = vec![capability.to_string()];
+        AppLibraryEntry {
+            app_id: app_id.to_string(),
+            boot_state: BootState::Unloaded,
+            manifest: AppManifestLoad::Embedded(manifest),
+        }
+    }
+
+    #[test]
+    fn test_upsert_and_remove() {
+        let mut app_library_state = AppLibraryState::new(get_default_apps());
+
+        assert_eq!(
+            app_library_state.upsert(get_provider_app("app3", "cap1")),
+            None
+        );
+        assert_eq!(app_library_state.default_apps.len(), 3);
+        assert_eq!(
+            AppLibrary::get_provider(&app_library_state, "cap1".to_string()),
+            Some("app3".to_string())
+        );
+
+        let previous = app_library_state.upsert(get_provider_app("app3", "cap2"));
+        assert_eq!(previous, Some(get_provider_app("app3", "cap1")));
+        assert_eq!(
+            AppLibrary::get_provider(&app_library_state, "cap1".to_string()),
+            None
+        );
+
+        assert_eq!(
+            app_library_state.remove("app3"),
+            Some(get_provider_app("app3", "cap2"))
+        );
+        assert_eq!(app_library_state.remove("app3"), None);
+        assert!(app_library_state.providers.is_empty());
+        assert_eq!(app_library_state.get_all_apps(), get_default_apps());
+    }
+
+    #[test]
+    fn test_remote_manifest() {
+        let entry = AppLibraryEntry {
+            app_id: "app3".to_string(),
+            boot_state: BootState::Unloaded,
+            manifest: AppManifestLoad::Remote("https://example.com/app3.json".to_string()),
+        };
+        let mut app_library_state = AppLibraryState::new(vec![entry]);
+        assert_eq!(AppLibrary::get_manifest(&app_library_state, "app3"), None);
+        assert!(app_library_state.providers.is_empty());
+
+        let mut manifest = AppManifest::default();
+        manifest.capabilities.provided.optional = vec!["cap1".to_string()];
+        assert_eq!(
+            app_library_state.set_remote_manifest("app3", manifest.clone()),
+            None
+        );
+        assert_eq!(
+            AppLibrary::get_manifest(&app_library_state, "app3"),
+            Some(manifest)
+        );
+        assert_eq!(
+            AppLibrary::get_provider(&app_library_state, "cap1".to_string()),
+            Some("app3".to_string())
+        );
+
+        app_library_state.remove("app3");
+        assert!(app_library_state.remote_manifests.is_empty());
+    }
+
+    #[test]
+    fn test_get_changed_providers() {
+        let before = HashMap::from([
+            ("cap1".to_string(), "app1".to_string()),
+            ("cap2".to_string(), "app1".to_string()),
+        ]);
+        let after = HashMap::from([
+            ("cap2".to_string(), "app2".to_string()),
+            ("cap3".to_string(), "app2".to_string()),
+        ]);
+        assert_eq!(
+            AppLibrary::get_changed_providers(&before, &after),
+            vec!["cap1", "cap2", "cap3"]
+        );
+        assert!(AppLibrary::get_changed_providers(&before, &before).is_empty());
+    }
+
     #[test]
     fn test_get_manifest() {
         let default_apps = get_default_apps();
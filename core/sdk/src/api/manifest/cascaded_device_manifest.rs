@@ -41,11 +41,14 @@ use crate::{
 
 use super::{
     device_manifest::{
+        AppLibrarySyncConfiguration, AppManifestFetchConfiguration,
         ApplicationDefaultsConfiguration, ApplicationsConfiguration, CapabilityConfiguration,
         CaptionStyle, DataGovernanceConfig, DataGovernancePolicy, DataGovernanceSettingTag,
-        DefaultValues, DeviceManifest, DistributionConfiguration, IdSalt, IntentValidation,
-        InternetMonitoringConfiguration, LifecycleConfiguration, PrivacySettingsStorageType,
-        RippleConfiguration, RippleFeatures, VoiceGuidance, WsConfiguration,
+        DefaultValues, DeviceManifest, DialConfiguration, DistributionConfiguration, IdSalt,
+        IntentValidation, InternetMonitoringConfiguration, LifecycleConfiguration,
+        MetricsExporterConfiguration, PrivacySettingsStorageType, ProviderConfiguration,
+        RateLimitConfiguration, ResponseValidation, RippleConfiguration, RippleFeatures,
+        StorageConfiguration, TraceExportConfiguration, VoiceGuidance, WsConfiguration,
     },
     exclusory::{AppAuthorizationRules, ExclusoryImpl},
     remote_feature::FeatureFlag,
@@ -123,6 +126,15 @@ pub struct CascadedRippleConfiguration {
     pub partner_exclusion_refresh_timeout: Option<u32>,
     pub metrics_logging_percentage: Option<u32>,
     pub internet_monitoring_configuration: Option<InternetMonitoringConfiguration>,
+    pub rate_limits: Option<RateLimitConfiguration>,
+    pub dial_configuration: Option<DialConfiguration>,
+    pub provider_configuration: Option<ProviderConfiguration>,
+    pub session_recording_path: Option<String>,
+    pub storage_configuration: Option<StorageConfiguration>,
+    pub metrics_exporter: Option<MetricsExporterConfiguration>,
+    pub trace_export: Option<TraceExportConfiguration>,
+    pub app_library_sync: Option<AppLibrarySyncConfiguration>,
+    pub app_manifest_fetch: Option<AppManifestFetchConfiguration>,
 }
 
 impl MergeConfig<CascadedRippleConfiguration> for RippleConfiguration {
@@ -197,6 +209,46 @@ impl MergeConfig<CascadedRippleConfiguration> for RippleConfiguration {
         if let Some(cas_internet_monitering_conf) = cascaded.internet_monitoring_configuration {
             self.internet_monitoring_configuration = cas_internet_monitering_conf;
         }
+        if let Some(cas_rate_limits) = cascaded.rate_limits {
+            self.rate_limits.merge_config(cas_rate_limits);
+        }
+        if let Some(cas_dial_configuration) = cascaded.dial_configuration {
+            self.dial_configuration = cas_dial_configuration;
+        }
+        if let Some(cas_provider_configuration) = cascaded.provider_configuration {
+            self.provider_configuration = cas_provider_configuration;
+        }
+        if let Some(cas_session_recording_path) = cascaded.session_recording_path {
+            self.session_recording_path = Some(cas_session_recording_path);
+        }
+        if let Some(cas_storage_configuration) = cascaded.storage_configuration {
+            self.storage_configuration = cas_storage_configuration;
+        }
+        if let Some(cas_metrics_exporter) = cascaded.metrics_exporter {
+            self.metrics_exporter = cas_metrics_exporter;
+        }
+        if let Some(cas_trace_export) = cascaded.trace_export {
+            self.trace_export = cas_trace_export;
+        }
+        if let Some(cas_app_library_sync) = cascaded.app_library_sync {
+            self.app_library_sync = cas_app_library_sync;
+        }
+        if let Some(cas_app_manifest_fetch) = cascaded.app_manifest_fetch {
+            self.app_manifest_fetch = cas_app_manifest_fetch;
+        }
+    }
+}
+
+impl MergeConfig<RateLimitConfiguration> for RateLimitConfiguration {
+    fn merge_config(&mut self, cascaded: RateLimitConfiguration) {
+        if cascaded.gateway.is_some() {
+            self.gateway = cascaded.gateway
+        }
+        if cascaded.app.is_some() {
+            self.app = cascaded.app
+        }
+        self.apps.extend(cascaded.apps);
+        self.methods.extend(cascaded.methods);
     }
 }
 
@@ -380,6 +432,8 @@ pub struct CascadedRippleFeatures {
     pub privacy_settings_storage_type: Option<PrivacySettingsStorageType>,
     pub intent_validation: Option<IntentValidation>,
     pub cloud_permissions: Option<bool>,
+    pub response_validation: Option<ResponseValidation>,
+    pub mock_gateway: Option<bool>,
 }
 
 impl MergeConfig<CascadedRippleFeatures> for RippleFeatures {
@@ -393,6 +447,12 @@ impl MergeConfig<CascadedRippleFeatures> for RippleFeatures {
         if let Some(cas_cloud_permission) = cascaded.cloud_permissions {
             self.cloud_permissions = cas_cloud_permission
         }
+        if let Some(cas_response_validation) = cascaded.response_validation {
+            self.response_validation = cas_response_validation
+        }
+        if let Some(cas_mock_gateway) = cascaded.mock_gateway {
+            self.mock_gateway = cas_mock_gateway
+        }
     }
 }
 
@@ -1217,7 +1277,9 @@ mod tests {
                 privacy_settings_storage_type: PrivacySettingsStorageType::Local,
                 intent_validation: IntentValidation::Fail,
                 cloud_permissions: true,
-                thunder_plugin_status_check_at_broker_start_up: true
+                thunder_plugin_status_check_at_broker_start_up: true,
+                response_validation: ResponseValidation::Off,
+                mock_gateway: false,
             }
         );
     }
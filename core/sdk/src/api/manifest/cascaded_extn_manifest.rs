@@ -14,7 +14,9 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 //
-use super::extn_manifest::{ExtnManifest, ExtnManifestEntry, ExtnResolutionEntry, ExtnSymbol};
+use super::extn_manifest::{
+    ExtnManifest, ExtnManifestEntry, ExtnResolutionEntry, ExtnRestartPolicy, ExtnSymbol,
+};
 use super::MergeConfig;
 use crate::utils::error::RippleError;
 use log::{info, warn};
@@ -35,6 +37,9 @@ pub struct CascadedExtnManifest {
     pub rules_path: Option<Vec<String>>,
     pub extn_sdks: Option<Vec<String>>,
     pub provider_registrations: Option<Vec<String>>,
+    pub rules_reload_interval: Option<u64>,
+    pub allow_unauthenticated_services: Option<bool>,
+    pub service_heartbeat_interval: Option<u64>,
 }
 impl MergeConfig<CascadedExtnManifest> for ExtnManifest {
     fn merge_config(&mut self, cascaded: CascadedExtnManifest) {
@@ -88,6 +93,15 @@ impl MergeConfig<CascadedExtnManifest> for ExtnManifest {
             self.provider_registrations.sort();
             self.provider_registrations.dedup();
         }
+        if let Some(cas_rules_reload_interval) = cascaded.rules_reload_interval {
+            self.rules_reload_interval = Some(cas_rules_reload_interval);
+        }
+        if let Some(cas_allow_unauthenticated) = cascaded.allow_unauthenticated_services {
+            self.allow_unauthenticated_services = cas_allow_unauthenticated;
+        }
+        if let Some(cas_service_heartbeat_interval) = cascaded.service_heartbeat_interval {
+            self.service_heartbeat_interval = Some(cas_service_heartbeat_interval);
+        }
     }
 }
 
@@ -135,9 +149,12 @@ impl ExtnManifestEntry {
                                 .collect()
                         })
                         .filter(|c: &std::collections::HashMap<String, String>| !c.is_empty()),
+                    secret: s.secret,
                 })
                 .collect(),
             resolution: cascaded.resolution,
+            sha256: cascaded.sha256,
+            restart: cascaded.restart.unwrap_or_default(),
         })
     }
 }
@@ -147,6 +164,8 @@ pub struct CascadedExtnManifestEntry {
     pub path: Option<String>,
     pub symbols: Option<Vec<CascadedExtnSymbol>>,
     pub resolution: Option<Vec<ExtnResolutionEntry>>,
+    pub sha256: Option<String>,
+    pub restart: Option<ExtnRestartPolicy>,
 }
 
 impl MergeConfig<CascadedExtnManifestEntry> for ExtnManifestEntry {
@@ -154,6 +173,12 @@ impl MergeConfig<CascadedExtnManifestEntry> for ExtnManifestEntry {
         if let Some(path) = cascaded.path {
             self.path = path;
         }
+        if let Some(sha256) = cascaded.sha256 {
+            self.sha256 = Some(sha256);
+        }
+        if let Some(restart) = cascaded.restart {
+            self.restart = restart;
+        }
         if let Some(cascaded_symbols) = cascaded.symbols {
             for cascaded_symbol in cascaded_symbols {
                 // Try to find a matching symbol by ID to merge, otherwise push a new one
@@ -174,6 +199,7 @@ impl MergeConfig<CascadedExtnManifestEntry> for ExtnManifestEntry {
                                 .filter_map(|(k, v)| v.map(|vv| (k, vv)))
                                 .collect()
                         }),
+                        secret: cascaded_symbol.secret,
                     });
                 }
             }
@@ -207,12 +233,16 @@ pub struct CascadedExtnSymbol {
     pub uses: Option<Vec<String>>,
     pub fulfills: Option<Vec<String>>,
     pub config: Option<HashMap<String, Option<String>>>,
+    pub secret: Option<String>,
 }
 impl MergeConfig<CascadedExtnSymbol> for ExtnSymbol {
     fn merge_config(&mut self, cascaded: CascadedExtnSymbol) {
         if let Some(id) = cascaded.id {
             self.id = id;
         }
+        if let Some(secret) = cascaded.secret {
+            self.secret = Some(secret);
+        }
         if let Some(uses) = cascaded.uses {
             self.uses.extend(uses);
             self.uses.sort();
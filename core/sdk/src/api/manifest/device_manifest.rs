@@ -70,6 +70,26 @@ pub struct RippleConfiguration {
     pub metrics_logging_percentage: u32,
     #[serde(default)]
     pub internet_monitoring_configuration: InternetMonitoringConfiguration,
+    #[serde(default)]
+    pub rate_limits: RateLimitConfiguration,
+    #[serde(default)]
+    pub dial_configuration: DialConfiguration,
+    #[serde(default)]
+    pub provider_configuration: ProviderConfiguration,
+    /// JSONL file the Firebolt sessions and the brokered device traffic are recorded to, nothing
+    /// is recorded when not set.
+    #[serde(default)]
+    pub session_recording_path: Option<String>,
+    #[serde(default)]
+    pub storage_configuration: StorageConfiguration,
+    #[serde(default)]
+    pub metrics_exporter: MetricsExporterConfiguration,
+    #[serde(default)]
+    pub trace_export: TraceExportConfiguration,
+    #[serde(default)]
+    pub app_library_sync: AppLibrarySyncConfiguration,
+    #[serde(default)]
+    pub app_manifest_fetch: AppManifestFetchConfiguration,
 }
 
 fn partner_exclusion_refresh_timeout_default() -> u32 {
@@ -207,12 +227,25 @@ pub struct ApplicationsConfiguration {
 pub struct WsConfiguration {
     pub enabled: bool,
     pub gateway: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<WsTlsConfiguration>,
+}
+
+/// TLS termination of a websocket gateway, `cert` and `key` are paths to PEM files.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WsTlsConfiguration {
+    pub cert: String,
+    pub key: String,
+    // seconds between checks of the files for a renewed certificate, disabled when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reload_interval: Option<u64>,
 }
 
 pub fn ws_configuration_default() -> WsConfiguration {
     WsConfiguration {
         enabled: true,
         gateway: "127.0.0.1:3473".into(),
+        tls: None,
     }
 }
 
@@ -220,6 +253,7 @@ pub fn ws_configuration_internal_default() -> WsConfiguration {
     WsConfiguration {
         enabled: true,
         gateway: "127.0.0.1:3474".into(),
+        tls: None,
     }
 }
 
@@ -548,6 +582,11 @@ pub struct RippleFeatures {
     pub cloud_permissions: bool,
     #[serde(default = "default_thunder_plugin_status_check_at_broker_start_up")]
     pub thunder_plugin_status_check_at_broker_start_up: bool,
+    #[serde(default)]
+    pub response_validation: ResponseValidation,
+    // methods without a rule or handler are answered from the Firebolt OpenRPC examples
+    #[serde(default)]
+    pub mock_gateway: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -557,6 +596,126 @@ pub enum IntentValidation {
     FailOpen,
 }
 
+/// Backend the properties of `StorageManager` are persisted in.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackendType {
+    /// the device persistence extension, e.g. the Thunder PersistentStore
+    #[default]
+    Device,
+    /// a JSON file owned by Ripple, for running without a device
+    Local,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StorageConfiguration {
+    #[serde(default)]
+    pub backend: StorageBackendType,
+    /// file of the local backend, defaults to `local_storage` in the saved dir
+    pub path: Option<String>,
+}
+
+/// Local HTTP endpoint serving gateway and broker metrics at `/metrics` in the Prometheus text
+/// format, for fleet dashboards to scrape.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricsExporterConfiguration {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "metrics_exporter_address_default")]
+    pub address: String,
+}
+
+pub fn metrics_exporter_address_default() -> String {
+    "127.0.0.1:9464".into()
+}
+
+impl Default for MetricsExporterConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: metrics_exporter_address_default(),
+        }
+    }
+}
+
+/// Periodic sync of the app library with the catalog served at `catalog_url`, in the format of
+/// the app library file. Apps added by the catalog are removed again once they leave it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppLibrarySyncConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_url: Option<String>,
+    #[serde(default = "app_library_sync_interval_default")]
+    pub interval_seconds: u64,
+}
+
+pub fn app_library_sync_interval_default() -> u64 {
+    3600
+}
+
+impl Default for AppLibrarySyncConfiguration {
+    fn default() -> Self {
+        Self {
+            catalog_url: None,
+            interval_seconds: app_library_sync_interval_default(),
+        }
+    }
+}
+
+/// Fetching of the manifests of app library entries with a remote manifest url. A fetched
+/// manifest is cached in the saved dir and reused for `ttl_seconds` before it is revalidated.
+/// When `public_key` (base64, raw Ed25519) is set the manifest must come with a detached
+/// signature of the app id, a version and the manifest served at `<url>.sig`, otherwise it is
+/// only fetched over https.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppManifestFetchConfiguration {
+    #[serde(default = "app_manifest_ttl_default")]
+    pub ttl_seconds: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+pub fn app_manifest_ttl_default() -> u64 {
+    3600
+}
+
+impl Default for AppManifestFetchConfiguration {
+    fn default() -> Self {
+        Self {
+            ttl_seconds: app_manifest_ttl_default(),
+            public_key: None,
+        }
+    }
+}
+
+/// Export of the spans of Firebolt requests and their broker hops. Spans are posted to the OTLP
+/// collector at `collector_url` when it is set, otherwise they are appended as JSON lines to
+/// `path`. Nothing is exported when neither is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TraceExportConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collector_url: Option<String>,
+}
+
+impl TraceExportConfiguration {
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some() || self.collector_url.is_some()
+    }
+}
+
+/// Validation of responses against the result schema of the Firebolt OpenRPC method.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ResponseValidation {
+    #[default]
+    Off,
+    // invalid responses are logged and sent to the app as they are
+    Log,
+    // invalid responses are logged and replaced by an error
+    Error,
+}
+
 fn default_saved_dir() -> String {
     String::from("/opt/persistent/ripple")
 }
@@ -583,6 +742,8 @@ impl Default for RippleFeatures {
             cloud_permissions: default_cloud_permissions(),
             thunder_plugin_status_check_at_broker_start_up:
                 default_thunder_plugin_status_check_at_broker_start_up(),
+            response_validation: ResponseValidation::default(),
+            mock_gateway: false,
         }
     }
 }
@@ -664,6 +825,129 @@ impl Default for InternetMonitoringConfiguration {
     }
 }
 
+/// Token bucket allowing bursts of `capacity` requests, refilled by `refill_per_sec` tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+}
+
+/// Limits on the rate of Firebolt requests handled by the gateway. `gateway` applies to all
+/// apps together and `app` to each app, unless the app has its own entry in `apps`. The limits
+/// in `methods` apply to each app calling a method, a module name like `device` covers all of
+/// its methods which have no limit of their own with one bucket shared by those methods.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RateLimitConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<RateLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<RateLimit>,
+    #[serde(default)]
+    pub apps: HashMap<String, RateLimit>,
+    #[serde(default)]
+    pub methods: HashMap<String, RateLimit>,
+}
+
+/// Built-in DIAL server letting second screen devices discover the device and launch apps.
+/// `apps` maps DIAL application names, e.g. `YouTube`, to app ids in the app library.
+/// `allowed_origins` lists the web origins, e.g. `https://www.youtube.com`, allowed to send
+/// requests in addition to native clients.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DialConfiguration {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "dial_rest_address_default")]
+    pub rest_address: String,
+    #[serde(default = "dial_ssdp_address_default")]
+    pub ssdp_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub friendly_name: Option<String>,
+    // stable device id announced over SSDP, generated at start up when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default)]
+    pub apps: HashMap<String, String>,
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+pub fn dial_rest_address_default() -> String {
+    "0.0.0.0:8009".into()
+}
+
+pub fn dial_ssdp_address_default() -> String {
+    "0.0.0.0:1900".into()
+}
+
+impl Default for DialConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rest_address: dial_rest_address_default(),
+            ssdp_address: dial_ssdp_address_default(),
+            friendly_name: None,
+            uuid: None,
+            apps: HashMap::new(),
+            allowed_origins: Vec::new(),
+        }
+    }
+}
+
+/// Selection of the provider when several apps provide a capability. `rankings` lists the
+/// preferred provider apps per capability, followed by the provider declared in the app library
+/// and the app in the foreground. A provider which does not answer or focus within
+/// `response_timeout` milliseconds is replaced by the next one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProviderConfiguration {
+    #[serde(default)]
+    pub rankings: HashMap<String, Vec<String>>,
+    #[serde(default = "provider_prefer_foreground_default")]
+    pub prefer_foreground: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_timeout: Option<u64>,
+    #[serde(default)]
+    pub sessions: HashMap<String, ProviderSessionConfiguration>,
+}
+
+/// Limits of the provider sessions of a capability. A capability with a `queue_size` has one
+/// session at a time, further requests wait in a queue of that size. A session which is not
+/// answered within `timeout` milliseconds is ended with an error to the caller.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ProviderSessionConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_size: Option<usize>,
+    #[serde(default)]
+    pub overflow: QueueOverflowPolicy,
+}
+
+/// What happens to a request when the queue of its capability is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum QueueOverflowPolicy {
+    /// the request is rejected with an error
+    #[default]
+    Reject,
+    /// the request waits for a free place until the session timeout of the capability
+    Wait,
+}
+
+pub fn provider_prefer_foreground_default() -> bool {
+    true
+}
+
+impl Default for ProviderConfiguration {
+    fn default() -> Self {
+        Self {
+            rankings: HashMap::new(),
+            prefer_foreground: provider_prefer_foreground_default(),
+            response_timeout: None,
+            sessions: HashMap::new(),
+        }
+    }
+}
+
 impl Default for RippleConfiguration {
     fn default() -> Self {
         Self {
@@ -685,6 +969,15 @@ impl Default for RippleConfiguration {
             metrics_logging_percentage: metrics_logging_percentage_default(),
             internet_monitoring_configuration: Default::default(),
             log_signal_log_level: log_signal_default_level(),
+            rate_limits: Default::default(),
+            dial_configuration: Default::default(),
+            provider_configuration: Default::default(),
+            session_recording_path: None,
+            storage_configuration: Default::default(),
+            metrics_exporter: Default::default(),
+            trace_export: Default::default(),
+            app_library_sync: Default::default(),
+            app_manifest_fetch: Default::default(),
         }
     }
 }
@@ -728,6 +1021,14 @@ impl DeviceManifest {
         self.configuration.internal_ws_configuration.gateway.clone()
     }
 
+    pub fn get_ws_tls_configuration(&self) -> Option<WsTlsConfiguration> {
+        self.configuration.ws_configuration.tls.clone()
+    }
+
+    pub fn get_internal_ws_tls_configuration(&self) -> Option<WsTlsConfiguration> {
+        self.configuration.internal_ws_configuration.tls.clone()
+    }
+
     pub fn get_internal_app_id(&self) -> Option<String> {
         self.configuration.internal_app_id.clone()
     }
@@ -802,6 +1103,42 @@ impl DeviceManifest {
             .internet_monitoring_configuration
             .default_monitoring_interval_seconds
     }
+
+    pub fn get_rate_limits(&self) -> RateLimitConfiguration {
+        self.configuration.rate_limits.clone()
+    }
+
+    pub fn get_dial_configuration(&self) -> DialConfiguration {
+        self.configuration.dial_configuration.clone()
+    }
+
+    pub fn get_provider_configuration(&self) -> ProviderConfiguration {
+        self.configuration.provider_configuration.clone()
+    }
+
+    pub fn get_session_recording_path(&self) -> Option<String> {
+        self.configuration.session_recording_path.clone()
+    }
+
+    pub fn get_storage_configuration(&self) -> StorageConfiguration {
+        self.configuration.storage_configuration.clone()
+    }
+
+    pub fn get_metrics_exporter_configuration(&self) -> MetricsExporterConfiguration {
+        self.configuration.metrics_exporter.clone()
+    }
+
+    pub fn get_trace_export_configuration(&self) -> TraceExportConfiguration {
+        self.configuration.trace_export.clone()
+    }
+
+    pub fn get_app_library_sync_configuration(&self) -> AppLibrarySyncConfiguration {
+        self.configuration.app_library_sync.clone()
+    }
+
+    pub fn get_app_manifest_fetch_configuration(&self) -> AppManifestFetchConfiguration {
+        self.configuration.app_manifest_fetch.clone()
+    }
 }
 
 #[cfg(test)]
@@ -822,10 +1159,12 @@ pub(crate) mod tests {
                     ws_configuration: WsConfiguration {
                         enabled: true,
                         gateway: "127.0.0.1:3473".to_string(),
+                        tls: None,
                     },
                     internal_ws_configuration: WsConfiguration {
                         enabled: true,
                         gateway: "127.0.0.1:3474".to_string(),
+                        tls: None,
                     },
                     platform_parameters: {
                         let mut params = HashMap::new();
@@ -898,6 +1237,8 @@ pub(crate) mod tests {
                         intent_validation: IntentValidation::Fail,
                         cloud_permissions: true,
                         thunder_plugin_status_check_at_broker_start_up: true,
+                        response_validation: ResponseValidation::Off,
+                        mock_gateway: false,
                     },
                     internal_app_id: Some("test".to_string()),
                     saved_dir: "/opt/persistent/ripple".to_string(),
@@ -909,6 +1250,15 @@ pub(crate) mod tests {
                     internet_monitoring_configuration: InternetMonitoringConfiguration {
                         default_monitoring_interval_seconds: 180,
                     },
+                    rate_limits: Default::default(),
+                    dial_configuration: Default::default(),
+                    provider_configuration: Default::default(),
+                    session_recording_path: None,
+                    storage_configuration: Default::default(),
+                    metrics_exporter: Default::default(),
+                    trace_export: Default::default(),
+                    app_library_sync: Default::default(),
+                    app_manifest_fetch: Default::default(),
                 },
                 capabilities: CapabilityConfiguration {
                     supported: vec!["main[manage]".to_string(), "test".to_string()],
@@ -1058,7 +1408,9 @@ pub(crate) mod tests {
                 privacy_settings_storage_type: PrivacySettingsStorageType::Local,
                 intent_validation: IntentValidation::Fail,
                 cloud_permissions: true,
-                thunder_plugin_status_check_at_broker_start_up: true
+                thunder_plugin_status_check_at_broker_start_up: true,
+                response_validation: ResponseValidation::Off,
+                mock_gateway: false,
             }
         );
     }
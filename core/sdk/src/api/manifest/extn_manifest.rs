@@ -18,7 +18,7 @@
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::{fs, path::Path};
+use std::{fs, path::Path, time::Duration};
 
 use crate::{extn::extn_id::ExtnId, utils::error::RippleError};
 
@@ -41,6 +41,17 @@ pub struct ExtnManifest {
     pub extn_sdks: Vec<String>,
     #[serde(default = "default_providers")]
     pub provider_registrations: Vec<String>,
+    /// Interval in seconds at which the files in `rules_path` are checked for changes and
+    /// reloaded. Rules are only loaded at startup when this is not set.
+    pub rules_reload_interval: Option<u64>,
+    /// Accepts services which are not declared in the manifest or do not present a valid
+    /// handshake token. Only meant for development.
+    #[serde(default)]
+    pub allow_unauthenticated_services: bool,
+    /// Interval in milliseconds at which connected services are pinged. A service which has not
+    /// answered the previous ping by the next one is disconnected. Services are not pinged when
+    /// this is not set.
+    pub service_heartbeat_interval: Option<u64>,
 }
 
 /// Some unit tests which use defaults are failing because we need default providers for unit testing
@@ -57,6 +68,9 @@ impl Default for ExtnManifest {
             rules_path: Vec::new(),
             extn_sdks: Vec::new(),
             provider_registrations: default_providers(),
+            rules_reload_interval: None,
+            allow_unauthenticated_services: false,
+            service_heartbeat_interval: None,
         }
     }
 }
@@ -91,6 +105,82 @@ pub struct ExtnManifestEntry {
     pub path: String,
     pub symbols: Vec<ExtnSymbol>,
     pub resolution: Option<Vec<ExtnResolutionEntry>>,
+    /// Hex encoded SHA-256 digest of the library, the library is not loaded when it differs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub restart: ExtnRestartPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtnRestartMode {
+    #[default]
+    Never,
+    OnFailure,
+}
+
+/// How the supervisor in main restarts an extension which exited, panicked or stalled.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExtnRestartPolicy {
+    #[serde(default)]
+    pub mode: ExtnRestartMode,
+    /// Delay in milliseconds before the first restart, doubled for every further attempt.
+    #[serde(default = "default_restart_backoff")]
+    pub backoff: u64,
+    /// Upper bound in milliseconds for the delay between restarts.
+    #[serde(default = "default_restart_max_backoff")]
+    pub max_backoff: u64,
+    /// Attempts in a row after which the extension is given up, unlimited when not set.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Time in milliseconds the extension may leave the liveness probes of main unanswered before
+    /// it is considered stalled. Stalls are not detected when not set.
+    #[serde(default)]
+    pub liveness_timeout: Option<u64>,
+    /// Time in milliseconds a run has to stay connected to main for the backoff to start over.
+    #[serde(default = "default_stable_uptime")]
+    pub stable_uptime: u64,
+}
+
+fn default_restart_backoff() -> u64 {
+    1000
+}
+
+fn default_restart_max_backoff() -> u64 {
+    60000
+}
+
+fn default_stable_uptime() -> u64 {
+    60000
+}
+
+impl Default for ExtnRestartPolicy {
+    fn default() -> Self {
+        Self {
+            mode: ExtnRestartMode::Never,
+            backoff: default_restart_backoff(),
+            max_backoff: default_restart_max_backoff(),
+            max_attempts: None,
+            liveness_timeout: None,
+            stable_uptime: default_stable_uptime(),
+        }
+    }
+}
+
+impl ExtnRestartPolicy {
+    /// Delay before the given restart attempt starting at 1, None when the extension should not
+    /// be restarted.
+    pub fn get_restart_delay(&self, attempt: u32) -> Option<Duration> {
+        if self.mode == ExtnRestartMode::Never || self.max_attempts.is_some_and(|max| attempt > max)
+        {
+            return None;
+        }
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Some(Duration::from_millis(
+            self.backoff.saturating_mul(factor).min(self.max_backoff),
+        ))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -100,6 +190,10 @@ pub struct ExtnSymbol {
     pub uses: Vec<String>,
     pub fulfills: Vec<String>,
     pub config: Option<HashMap<String, String>>,
+    /// Shared secret the service signs its handshake token with, see
+    /// [crate::service::service_auth].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
 }
 
 impl ExtnSymbol {
@@ -249,6 +343,9 @@ pub(crate) mod tests {
                 rules_path: Vec::new(),
                 extn_sdks: Vec::new(),
                 provider_registrations: Vec::new(),
+                rules_reload_interval: None,
+                allow_unauthenticated_services: false,
+                service_heartbeat_interval: None,
             }
         }
     }
@@ -259,6 +356,8 @@ pub(crate) mod tests {
             path: "/absolute/path".to_string(),
             symbols: vec![],
             resolution: None,
+            sha256: None,
+            restart: Default::default(),
         };
 
         assert_eq!(
@@ -281,11 +380,14 @@ pub(crate) mod tests {
             uses: vec![],
             fulfills: vec![],
             config: None,
+            secret: None,
         };
         let extn_manifest_entry = ExtnManifestEntry {
             path: "relative/path".to_string(),
             symbols: vec![symbol.clone()],
             resolution: None,
+            sha256: None,
+            restart: Default::default(),
         };
         assert_eq!(
             extn_manifest_entry.get_symbol(ExtnId::try_from(dist_channel).unwrap()),
@@ -333,6 +435,7 @@ pub(crate) mod tests {
             uses: vec![],
             fulfills: vec![],
             config: None,
+            secret: None,
         };
 
         let capability = symbol.get_launcher_capability();
@@ -354,6 +457,7 @@ pub(crate) mod tests {
             uses: vec![],
             fulfills: vec![],
             config: None,
+            secret: None,
         };
 
         let capability = symbol.get_distributor_capability();
@@ -376,11 +480,14 @@ pub(crate) mod tests {
             uses: vec![],
             fulfills: vec![],
             config: None,
+            secret: None,
         };
         let extn_manifest_entry = ExtnManifestEntry {
             path: "relative/path".to_string(),
             symbols: vec![symbol],
             resolution: None,
+            sha256: None,
+            restart: Default::default(),
         };
         manifest.extns = vec![extn_manifest_entry];
 
@@ -400,11 +507,14 @@ pub(crate) mod tests {
             uses: vec![],
             fulfills: vec![],
             config: None,
+            secret: None,
         };
         let extn_manifest_entry = ExtnManifestEntry {
             path: "relative/path".to_string(),
             symbols: vec![symbol],
             resolution: None,
+            sha256: None,
+            restart: Default::default(),
         };
 
         manifest.extns = vec![extn_manifest_entry];
@@ -424,11 +534,14 @@ pub(crate) mod tests {
             uses: vec!["config".to_string()],
             fulfills: vec!["test".to_string()],
             config: None,
+            secret: None,
         };
         let extn_manifest_entry = ExtnManifestEntry {
             path: "relative/path".to_string(),
             symbols: vec![symbol],
             resolution: None,
+            sha256: None,
+            restart: Default::default(),
         };
 
         manifest.extns = vec![extn_manifest_entry];
@@ -446,4 +559,26 @@ pub(crate) mod tests {
         manifest.timeout = None;
         assert_eq!(manifest.get_timeout(), 10000);
     }
+
+    #[test]
+    fn test_restart_policy() {
+        let policy: ExtnRestartPolicy = serde_json::from_str(
+            r#"{"mode": "on_failure", "backoff": 100, "max_backoff": 350, "max_attempts": 3}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy.get_restart_delay(1),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            policy.get_restart_delay(2),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            policy.get_restart_delay(3),
+            Some(Duration::from_millis(350))
+        );
+        assert_eq!(policy.get_restart_delay(4), None);
+        assert_eq!(ExtnRestartPolicy::default().get_restart_delay(1), None);
+    }
 }
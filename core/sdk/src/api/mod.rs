@@ -68,4 +68,6 @@ pub mod observability {
     pub mod log_signal;
     pub mod metrics_util;
     pub mod operational_metrics;
+    pub mod session_recording;
+    pub mod trace_context;
 }
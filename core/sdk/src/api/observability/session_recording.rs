@@ -0,0 +1,247 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::error::RippleError;
+
+/// Replaces the values of sensitive fields in a recording.
+pub const REDACTED: &str = "<redacted>";
+
+// parts of field names which carry credentials, compared in lower case
+const SENSITIVE_FIELDS: [&str; 4] = ["token", "authorization", "password", "secret"];
+
+/// True for field names carrying credentials like `token`, `xAccessToken` or `Authorization`.
+pub fn is_sensitive_field(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SENSITIVE_FIELDS.iter().any(|field| name.contains(field))
+}
+
+/// True for methods whose result is a credential, the Firebolt `authentication` module and
+/// methods ending with `Token` like `org.rdk.AuthService.1.getServiceAccessToken`.
+pub fn is_sensitive_method(method: &str) -> bool {
+    let method = method.to_lowercase();
+    method.starts_with("authentication.") || method.ends_with("token")
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_sensitive_field(key) && !value.is_null() {
+                    *value = Value::from(REDACTED);
+                } else {
+                    redact_value(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+// query parameters of a recorded HTTP uri, e.g. `?token=...`
+fn redact_query(uri: &str) -> String {
+    let Some((path, query)) = uri.split_once('?') else {
+        return uri.to_owned();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if is_sensitive_field(name) => format!("{}={}", name, REDACTED),
+            _ => param.to_owned(),
+        })
+        .collect();
+    format!("{}?{}", path, query.join("&"))
+}
+
+/// Connection a recorded message went through.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordChannel {
+    Firebolt,
+    Thunder,
+    Http,
+}
+
+/// Direction of a recorded message from the point of view of Ripple.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordDirection {
+    Inbound,
+    Outbound,
+}
+
+/// A line of a session recording. Firebolt messages carry the app and session they belong to,
+/// device traffic is recorded without them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    /// milliseconds since the epoch
+    pub ts: u64,
+    pub channel: RecordChannel,
+    pub direction: RecordDirection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub message: Value,
+}
+
+impl RecordedMessage {
+    /// Messages which are not JSON are recorded as a string.
+    pub fn new(channel: RecordChannel, direction: RecordDirection, message: &str) -> Self {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        RecordedMessage {
+            ts,
+            channel,
+            direction,
+            app_id: None,
+            session_id: None,
+            message: serde_json::from_str(message)
+                .unwrap_or_else(|_| Value::String(message.to_owned())),
+        }
+    }
+
+    pub fn with_session(mut self, app_id: &str, session_id: &str) -> Self {
+        self.app_id = Some(app_id.to_owned());
+        self.session_id = Some(session_id.to_owned());
+        self
+    }
+
+    pub fn get_id(&self) -> Option<&Value> {
+        self.message.get("id").filter(|id| !id.is_null())
+    }
+
+    pub fn get_method(&self) -> Option<&str> {
+        self.message.get("method").and_then(Value::as_str)
+    }
+
+    pub fn is(&self, channel: RecordChannel, direction: RecordDirection) -> bool {
+        self.channel == channel && self.direction == direction
+    }
+
+    /// Replaces the values of sensitive fields, including the sensitive query parameters of a
+    /// recorded HTTP uri, with [REDACTED].
+    pub fn redact(mut self) -> Self {
+        redact_value(&mut self.message);
+        if let Some(Value::String(uri)) = self.message.get_mut("uri") {
+            *uri = redact_query(uri);
+        }
+        self
+    }
+
+    /// Replaces the results of the responses for which `is_sensitive` holds with [REDACTED], it
+    /// is called for every message of a batch.
+    pub fn redact_results(mut self, mut is_sensitive: impl FnMut(&Value) -> bool) -> Self {
+        let messages = match &mut self.message {
+            Value::Array(messages) => messages.iter_mut().collect(),
+            message => vec![message],
+        };
+        for message in messages {
+            if is_sensitive(message) {
+                if let Some(result) = message.get_mut("result") {
+                    *result = Value::from(REDACTED);
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Parses the JSONL contents of a recording, empty lines are skipped.
+pub fn parse_recording(contents: &str) -> Result<Vec<RecordedMessage>, RippleError> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|_| RippleError::ParseError))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_message() {
+        let message = RecordedMessage::new(
+            RecordChannel::Firebolt,
+            RecordDirection::Inbound,
+            r#"{"jsonrpc":"2.0","id":1,"method":"device.name"}"#,
+        )
+        .with_session("app", "session");
+        assert_eq!(message.get_id(), Some(&Value::from(1)));
+        assert_eq!(message.get_method(), Some("device.name"));
+        assert!(message.is(RecordChannel::Firebolt, RecordDirection::Inbound));
+
+        let line = serde_json::to_string(&message).unwrap();
+        let other = RecordedMessage::new(RecordChannel::Http, RecordDirection::Outbound, "GET /");
+        assert_eq!(other.message, Value::from("GET /"));
+        let contents = format!("{}\n\n{}\n", line, serde_json::to_string(&other).unwrap());
+        assert_eq!(parse_recording(&contents).unwrap(), vec![message, other]);
+        assert_eq!(
+            parse_recording("not json").unwrap_err(),
+            RippleError::ParseError
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        let message = RecordedMessage::new(
+            RecordChannel::Firebolt,
+            RecordDirection::Inbound,
+            r#"{"jsonrpc":"2.0","id":1,"method":"discovery.signIn","params":{"accessToken":"abc","entitlements":[{"Authorization":"Bearer abc","id":"e"}],"refreshToken":null}}"#,
+        )
+        .redact();
+        assert_eq!(
+            message.message["params"],
+            serde_json::json!({"accessToken": REDACTED, "entitlements": [{"Authorization": REDACTED, "id": "e"}], "refreshToken": null})
+        );
+
+        let message = RecordedMessage::new(
+            RecordChannel::Http,
+            RecordDirection::Outbound,
+            r#"{"id":1,"method":"GET","uri":"https://host/path?token=abc&lang=en","body":null}"#,
+        )
+        .redact();
+        assert_eq!(
+            message.message["uri"],
+            Value::from("https://host/path?token=<redacted>&lang=en")
+        );
+
+        let message = RecordedMessage::new(
+            RecordChannel::Firebolt,
+            RecordDirection::Outbound,
+            r#"[{"jsonrpc":"2.0","id":5,"result":"abc"},{"jsonrpc":"2.0","id":6,"result":"tv"}]"#,
+        )
+        .redact_results(|response| response["id"] == 5);
+        assert_eq!(
+            message.message,
+            serde_json::json!([{"jsonrpc": "2.0", "id": 5, "result": REDACTED}, {"jsonrpc": "2.0", "id": 6, "result": "tv"}])
+        );
+
+        assert!(is_sensitive_method("authentication.token"));
+        assert!(is_sensitive_method(
+            "org.rdk.AuthService.1.getServiceAccessToken"
+        ));
+        assert!(!is_sensitive_method("device.name"));
+    }
+}
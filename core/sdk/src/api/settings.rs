@@ -189,6 +189,7 @@ mod tests {
                 cid: Some("test_cid".to_string()),
                 gateway_secure: true,
                 context: Vec::new(),
+                traceparent: None,
             },
             vec![SettingKey::VoiceGuidanceEnabled, SettingKey::ClosedCaptions],
             alias_map,
@@ -216,6 +217,7 @@ mod tests {
                 cid: Some("test_cid".to_string()),
                 gateway_secure: true,
                 context: Vec::new(),
+                traceparent: None,
             },
             keys: vec![SettingKey::VoiceGuidanceEnabled, SettingKey::ClosedCaptions],
             alias_map: Some(HashMap::new()),
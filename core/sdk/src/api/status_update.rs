@@ -16,7 +16,7 @@
 //
 
 use crate::{
-    extn::extn_client_message::{ExtnEvent, ExtnPayload, ExtnPayloadProvider},
+    extn::extn_client_message::{ExtnEvent, ExtnPayload, ExtnPayloadProvider, ExtnRequest},
     framework::ripple_contract::RippleContract,
 };
 use serde::{Deserialize, Serialize};
@@ -46,6 +46,29 @@ impl ExtnPayloadProvider for ExtnStatus {
     }
 }
 
+/// Liveness probe Main sends to an extension, the client of the extension answers it without a
+/// request processor as long as it still handles messages.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExtnProbe;
+
+impl ExtnPayloadProvider for ExtnProbe {
+    fn get_extn_payload(&self) -> ExtnPayload {
+        ExtnPayload::Request(ExtnRequest::Probe(self.clone()))
+    }
+
+    fn get_from_payload(payload: ExtnPayload) -> Option<ExtnProbe> {
+        if let ExtnPayload::Request(ExtnRequest::Probe(r)) = payload {
+            return Some(r);
+        }
+
+        None
+    }
+
+    fn contract() -> RippleContract {
+        RippleContract::ExtnStatus
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,4 +80,9 @@ mod tests {
         let contract_type: RippleContract = RippleContract::ExtnStatus;
         test_extn_payload_provider(extn_status, contract_type);
     }
+
+    #[test]
+    fn test_extn_payload_provider_for_extn_probe() {
+        test_extn_payload_provider(ExtnProbe, RippleContract::ExtnStatus);
+    }
 }
@@ -23,6 +23,7 @@ use std::{
     collections::HashMap,
     ops::ControlFlow,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 use tokio_tungstenite::tungstenite::Message;
 
@@ -40,13 +41,20 @@ use crate::{
         device::device_request::{InternetConnectionStatus, TimeZone},
         gateway::rpc_gateway_api::ApiMessage,
         manifest::extn_manifest::ExtnSymbol,
+        status_update::ExtnProbe,
     },
     extn::{
         extn_client_message::{ExtnMessage, ExtnPayloadProvider, ExtnResponse},
         extn_id::ExtnId,
     },
     framework::{ripple_contract::RippleContract, RippleResponse},
-    utils::{error::RippleError, extn_utils::ExtnStackSize, ws_utils::WebSocketUtils},
+    service::{service_auth::service_handshake_query, service_message::ServiceMessage},
+    utils::{
+        error::RippleError,
+        extn_utils::ExtnStackSize,
+        logger::{apply_log_levels, LogLevels},
+        ws_utils::WebSocketUtils,
+    },
 };
 
 use super::{
@@ -240,13 +248,46 @@ impl ExtnClient {
         }
 
         {
+            // the map is keyed like in add_sender, contracts taken over by another extension stay
             let mut contract_map = self.contract_map.write().unwrap();
             for contract in symbol.fulfills {
-                contract_map.remove(&contract);
+                if let Some(v) = RippleContract::from_manifest(&contract) {
+                    let ripple_contract_string = v.as_clear_string();
+                    if contract_map.get(&ripple_contract_string) == Some(&id) {
+                        contract_map.remove(&ripple_contract_string);
+                    }
+                }
             }
         }
     }
 
+    /// True while the extension with the id has an open channel to main.
+    pub fn has_open_sender(&self, id: &str) -> bool {
+        self.get_extn_sender_with_extn_id(id)
+            .is_some_and(|sender| !sender.is_closed())
+    }
+
+    /// Sends a [ExtnProbe] to the extension with the id, true when it answers within the timeout.
+    pub async fn probe(&self, id: &str, timeout: Duration) -> bool {
+        let Some(sender) = self.get_extn_sender_with_extn_id(id) else {
+            return false;
+        };
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        add_single_processor(
+            request_id.clone(),
+            Some(tx),
+            self.response_processors.clone(),
+        );
+        let message = self.sender.get_message(request_id.clone(), ExtnProbe);
+        let answered = sender.try_send(message.into()).is_ok()
+            && matches!(tokio::time::timeout(timeout, rx).await, Ok(Ok(_)));
+        if !answered {
+            remove_processor(request_id, self.response_processors.clone());
+        }
+        answered
+    }
+
     pub fn get_other_senders(&self) -> Vec<MSender<ApiMessage>> {
         self.extn_sender_map
             .read()
@@ -259,19 +300,30 @@ impl ExtnClient {
     }
 
     /// Called once per client initialization this is a blocking method. Use a spawned thread to call this method
-    pub async fn initialize(&self, mut tr: mpsc::Receiver<ApiMessage>) {
+    pub async fn initialize(&self, tr: mpsc::Receiver<ApiMessage>) {
         debug!("Starting initialize");
         let base_path = std::env::var("RIPPLE_SERVICE_HANDSHAKE_PATH")
             .unwrap_or_else(|_| "127.0.0.1:3474".to_string());
         let path = tokio_tungstenite::tungstenite::http::Uri::builder()
             .scheme("ws")
             .authority(base_path.as_str())
-            .path_and_query(format!("/?service_handshake={}", self.sender.get_cap()))
+            .path_and_query(format!(
+                "/?{}",
+                service_handshake_query(
+                    &self.sender.get_cap().to_string(),
+                    self.sender.secret.as_deref()
+                )
+            ))
             .build()
             .unwrap();
 
-        let path = path.to_string();
-        if let Ok((mut ws_tx, mut ws_rx)) = WebSocketUtils::get_ws_stream(&path, None).await {
+        self.connect(&path.to_string(), tr).await;
+    }
+
+    /// Relays messages between main, at the service websocket `path`, and this extension until
+    /// the connection fails. Heartbeat pings of main are answered here.
+    pub async fn connect(&self, path: &str, mut tr: mpsc::Receiver<ApiMessage>) {
+        if let Ok((mut ws_tx, mut ws_rx)) = WebSocketUtils::get_ws_stream(path, None).await {
             tokio::pin! {
                 let read_pin = ws_rx.next();
             }
@@ -281,7 +333,7 @@ impl ExtnClient {
                         match value {
                             Ok(msg) => {
                                 if let Message::Text(message) = msg.clone() {
-                                    if let Ok(extn_message) = ExtnMessage::try_from(message) {
+                                    if let Ok(extn_message) = ExtnMessage::try_from(message.clone()) {
                                         if let Some(ts) = extn_message.ts {
                                             let latency = Utc::now().timestamp_millis() - ts;
                                             if latency > 1000 {
@@ -289,6 +341,9 @@ impl ExtnClient {
                                             }
                                         }
                                         self.handle_message(extn_message);
+                                    } else if let Some(pong) = Self::get_pong(&message) {
+                                        let _feed = ws_tx.feed(Message::Text(pong.into())).await;
+                                        let _flush = ws_tx.flush().await;
                                     } else {
                                         error!("Failed to parse message: {:?}", msg);
                                     }
@@ -311,6 +366,11 @@ impl ExtnClient {
         debug!("Initialize Ended Abruptly");
     }
 
+    // answer to a heartbeat ping of main
+    fn get_pong(message: &str) -> Option<ServiceMessage> {
+        ServiceMessage::try_from(message).ok()?.get_pong()
+    }
+
     pub fn handle_message(&self, message: ExtnMessage) -> ControlFlow<()> {
         trace!("IEC recv: {:#?}", message);
         if message.payload.is_response() {
@@ -334,6 +394,17 @@ impl ExtnClient {
                 }
             } else {
                 if !is_main {
+                    if let Some(levels) = LogLevels::get_from_payload(message.payload.clone()) {
+                        debug!(
+                            "Received log levels in {}: {:?}",
+                            self.sender.get_cap(),
+                            levels
+                        );
+                        if let Err(e) = apply_log_levels(&levels) {
+                            error!("Failed to apply log levels {:?}", e);
+                        }
+                        return ControlFlow::Continue(());
+                    }
                     if let Some(context) = RippleContext::is_ripple_context(&message.payload) {
                         trace!(
                             "Received ripple context in {} message: {:?}",
@@ -393,6 +464,12 @@ impl ExtnClient {
                         self.handle_no_processor_error(message);
                     }
                 }
+            } else if ExtnProbe::get_from_payload(message.payload.clone()).is_some() {
+                if let Ok(response) = message.get_response(ExtnResponse::None(())) {
+                    if self.sender.respond(response, None).is_err() {
+                        error!("Couldnt answer the liveness probe");
+                    }
+                }
             } else if !Self::handle_stream(message.clone(), self.request_processors.clone()) {
                 self.handle_no_processor_error(message);
             }
@@ -439,6 +516,20 @@ impl ExtnClient {
         }
     }
 
+    /// Sends the log levels of Main to every connected extension so their loggers
+    /// follow the same filter.
+    pub fn propagate_log_levels(&self, levels: &LogLevels) {
+        if !self.sender.get_cap().is_main() {
+            error!("Propagating log levels is not allowed outside main");
+            return;
+        }
+        let message: ApiMessage = levels.get_event_message().into();
+        for sender in self.get_other_senders() {
+            let send_res = sender.try_send(message.clone());
+            trace!("Send log levels to other client result: {:?}", send_res);
+        }
+    }
+
     fn has_event_listener(&self, input: &str) -> bool {
         let processors = self.event_processors.read().unwrap();
         processors.contains_key(input)
@@ -1280,6 +1371,7 @@ pub mod tests {
                 uses: Vec::new(),
                 fulfills: Vec::new(),
                 config: None,
+                secret: None,
             },
             s,
         );
@@ -1308,6 +1400,7 @@ pub mod tests {
                 uses: Vec::new(),
                 fulfills: Vec::new(),
                 config: None,
+                secret: None,
             },
             s,
         );
@@ -1335,6 +1428,7 @@ pub mod tests {
                 uses: Vec::new(),
                 fulfills: vec!["account.session".to_string()],
                 config: None,
+                secret: None,
             },
             s,
         );
@@ -1349,6 +1443,81 @@ pub mod tests {
         assert!(senders.is_some(), "Expected Some, got None");
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remove_sender() {
+        let mut extn_client = ExtnClient::mock();
+        let symbol = ExtnSymbol {
+            id: "id".to_string(),
+            uses: Vec::new(),
+            fulfills: vec!["account.session".to_string()],
+            config: None,
+            secret: None,
+        };
+        let contract = RippleContract::Session(SessionAdjective::Account);
+        let (s, receiver) = mpsc::channel(2);
+        extn_client.add_sender("first".to_string(), symbol.clone(), s);
+        assert!(extn_client.has_open_sender("first"));
+        drop(receiver);
+        assert!(!extn_client.has_open_sender("first"));
+
+        // the contract was taken over, removing the first extension leaves it routed
+        let (s, _receiver) = mpsc::channel(2);
+        extn_client.add_sender("second".to_string(), symbol.clone(), s);
+        extn_client.remove_sender("first".to_string(), symbol.clone());
+        assert!(extn_client
+            .get_extn_sender_with_contract(contract.clone())
+            .is_some());
+
+        extn_client.remove_sender("second".to_string(), symbol);
+        assert!(extn_client
+            .get_extn_sender_with_contract(contract)
+            .is_none());
+        assert!(!extn_client.has_open_sender("second"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_probe() {
+        let main = ExtnClient::new_main();
+        let symbol = ExtnSymbol {
+            id: "ripple:channel:device:thunder".to_string(),
+            uses: Vec::new(),
+            fulfills: Vec::new(),
+            config: None,
+            secret: None,
+        };
+        let (extn, mut extn_rx) = ExtnClient::new_extn(symbol.clone());
+        let (s, mut main_rx) = mpsc::channel(2);
+        main.clone()
+            .add_sender(symbol.id.clone(), symbol.clone(), s);
+
+        // relays the messages between the clients like the service connection of the extension
+        let (relay_main, relay_extn) = (main.clone(), extn.clone());
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(message) = main_rx.recv() => {
+                        let message = ExtnMessage::try_from(message.jsonrpc_msg).unwrap();
+                        relay_extn.handle_message(message);
+                    }
+                    Some(message) = extn_rx.recv() => {
+                        let message = ExtnMessage::try_from(message.jsonrpc_msg).unwrap();
+                        relay_main.handle_message(message);
+                    }
+                    else => break,
+                }
+            }
+        });
+        assert!(main.probe(&symbol.id, Duration::from_secs(1)).await);
+
+        // an extension which does not handle its messages fails the probe
+        let (s, _receiver) = mpsc::channel(2);
+        main.clone()
+            .add_sender(symbol.id.clone(), symbol.clone(), s);
+        assert!(!main.probe(&symbol.id, Duration::from_millis(50)).await);
+        assert!(main.response_processors.read().unwrap().is_empty());
+        assert!(!main.probe("unknown", Duration::from_millis(50)).await);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_extn_sender_with_extn_id() {
         let extn_client = ExtnClient::mock();
@@ -1366,6 +1535,7 @@ pub mod tests {
                 uses: Vec::new(),
                 fulfills: vec![RippleContract::Session(SessionAdjective::Account).as_clear_string()],
                 config: None,
+                secret: None,
             },
             s,
         );
@@ -1529,6 +1699,7 @@ pub mod tests {
                 uses: Vec::new(),
                 fulfills: vec![RippleContract::DeviceInfo.as_clear_string()],
                 config: Some(HashMap::new()),
+                secret: None,
             },
             tx,
         );
@@ -1596,6 +1767,7 @@ pub mod tests {
                 uses: vec![RippleContract::Config.as_clear_string()],
                 fulfills: vec![RippleContract::DeviceInfo.as_clear_string()],
                 config: Some(HashMap::new()),
+                secret: None,
             },
             tx,
         );
@@ -1730,6 +1902,32 @@ pub mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_propagate_log_levels() {
+        let extn_client = ExtnClient::new_main();
+        let (s, mut receiver) = mpsc::channel(2);
+        extn_client.clone().add_sender(
+            "ripple:channel:device:thunder".into(),
+            ExtnSymbol {
+                id: "ripple:channel:device:thunder".into(),
+                uses: Vec::new(),
+                fulfills: Vec::new(),
+                config: None,
+                secret: None,
+            },
+            s,
+        );
+        let levels = LogLevels {
+            level: "debug".into(),
+            modules: HashMap::from([("thunder_ripple_sdk".into(), "trace".into())]),
+        };
+        extn_client.propagate_log_levels(&levels);
+
+        let message = ExtnMessage::try_from(receiver.recv().await.unwrap().jsonrpc_msg).unwrap();
+        assert_eq!(message.target, RippleContract::LogLevels);
+        assert_eq!(LogLevels::get_from_payload(message.payload), Some(levels));
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_handle_no_processor_error() {
         let mut extn_client = ExtnClient::new_main();
@@ -1748,6 +1946,7 @@ pub mod tests {
                 uses: vec!["account.session".to_string()],
                 fulfills: vec!["account.session".to_string()],
                 config: None,
+                secret: None,
             },
             tx,
         );
@@ -2037,6 +2236,7 @@ pub mod tests {
                 uses: vec!["config".to_string()],
                 fulfills: vec!["permissions".to_string()],
                 config: None,
+                secret: None,
             },
             tx,
         );
@@ -2080,6 +2280,7 @@ pub mod tests {
             uses: Vec::new(),
             fulfills: Vec::new(),
             config: Some(config),
+            secret: None,
         });
         let result = extn_client.get_stack_size();
 
@@ -2129,6 +2330,7 @@ pub mod tests {
             uses: Vec::new(),
             fulfills: Vec::new(),
             config,
+            secret: None,
         });
         assert_eq!(extn_client.get_bool_config("key"), expected_value);
     }
@@ -2146,6 +2348,7 @@ pub mod tests {
             uses: Vec::new(),
             fulfills: Vec::new(),
             config,
+            secret: None,
         });
         assert_eq!(extn_client.get_uint_config("key"), expected_value);
     }
@@ -2173,6 +2376,7 @@ pub mod tests {
                     uses: permitted,
                     fulfills,
                     config: None,
+                    secret: None,
                 },
                 tx,
             );
@@ -2213,6 +2417,7 @@ pub mod tests {
             uses: permitted,
             fulfills,
             config: None,
+            secret: None,
         });
         let cp = extn_client.check_contract_permitted(RippleContract::DeviceInfo);
         assert_eq!(cp, exp_resp, "{}", error_msg);
@@ -2253,6 +2458,7 @@ pub mod tests {
             uses: Vec::new(),
             fulfills,
             config: None,
+            secret: None,
         });
         let cp = extn_client.check_contract_fulfillment(RippleContract::DeviceInfo);
         assert_eq!(cp, exp_resp, "{}", error_msg);
@@ -720,6 +720,7 @@ pub mod tests {
                 uses: vec!["uses".to_string()],
                 fulfills: Vec::new(),
                 config: None,
+                secret: None,
             },
             mock_sender.tx.unwrap(),
         );
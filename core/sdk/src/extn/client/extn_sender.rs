@@ -50,6 +50,8 @@ pub struct ExtnSender {
     pub permitted: Vec<String>,
     pub fulfills: Vec<String>,
     pub config: Option<HashMap<String, String>>,
+    // shared secret the service handshake token is signed with
+    pub secret: Option<String>,
 }
 
 impl ExtnSender {
@@ -64,6 +66,7 @@ impl ExtnSender {
             permitted: Vec::default(),
             fulfills: Vec::default(),
             config: None,
+            secret: None,
         }
     }
 
@@ -74,6 +77,7 @@ impl ExtnSender {
             permitted: Vec::default(),
             fulfills: Vec::default(),
             config: None,
+            secret: None,
         }
     }
 
@@ -84,6 +88,7 @@ impl ExtnSender {
             permitted: symbol.uses.clone(),
             fulfills: symbol.fulfills.clone(),
             config: symbol.config.clone(),
+            secret: symbol.secret.clone(),
         }
     }
 
@@ -317,6 +322,7 @@ pub mod tests {
                     permitted: self.context,
                     fulfills: self.fulfills,
                     config: self.config,
+                    secret: None,
                 },
                 rx,
             )
@@ -49,11 +49,11 @@ use crate::{
         manifest::device_manifest::AppLibraryEntry,
         session::{AccountSessionRequest, AccountSessionResponse},
         settings::{SettingValue, SettingsRequest},
-        status_update::ExtnStatus,
+        status_update::{ExtnProbe, ExtnStatus},
         storage_property::StorageManagerRequest,
     },
     framework::ripple_contract::RippleContract,
-    utils::error::RippleError,
+    utils::{error::RippleError, logger::LogLevels},
 };
 
 use super::extn_id::ExtnId;
@@ -454,6 +454,7 @@ pub enum ExtnRequest {
     AuthorizedInfo(CapsRequest),
     OperationalMetricsRequest(OperationalMetricRequest),
     Context(RippleContextUpdateRequest),
+    Probe(ExtnProbe),
 }
 
 impl ExtnPayloadProvider for ExtnRequest {
@@ -526,6 +527,7 @@ pub enum ExtnEvent {
     OperationalMetrics(TelemetryPayload),
     Context(RippleContext),
     TimeZone(TimeZone),
+    LogLevels(LogLevels),
 }
 
 impl ExtnPayloadProvider for ExtnEvent {
@@ -20,6 +20,58 @@ use log::{debug, error};
 
 use crate::utils::error::RippleError;
 
+/// Version of the interface between main and the extension libraries. It has to be increased
+/// whenever [ExtnChannel] or the types exchanged through it change in an incompatible way.
+pub const EXTN_ABI_VERSION: u32 = 1;
+
+/// ABI and SDK version an extension library was built with, exported by
+/// [export_extn_channel] as `extn_abi_version`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtnAbiVersion {
+    pub abi: u32,
+    pub sdk_major: u32,
+    pub sdk_minor: u32,
+}
+
+impl ExtnAbiVersion {
+    /// The version of this build of the sdk.
+    pub fn current() -> ExtnAbiVersion {
+        let mut sdk = env!("CARGO_PKG_VERSION")
+            .split('.')
+            .map(|v| v.parse::<u32>().unwrap_or_default());
+        ExtnAbiVersion {
+            abi: EXTN_ABI_VERSION,
+            sdk_major: sdk.next().unwrap_or_default(),
+            sdk_minor: sdk.next().unwrap_or_default(),
+        }
+    }
+
+    /// Libraries are compatible when built with the same ABI version and the same minor
+    /// version of the sdk.
+    pub fn is_compatible(&self, other: &ExtnAbiVersion) -> bool {
+        self.abi == other.abi
+            && self.sdk_major == other.sdk_major
+            && self.sdk_minor == other.sdk_minor
+    }
+}
+
+/// Returns the version exported by the library, None for libraries built without it.
+///
+/// # Safety
+/// The library has to export `extn_abi_version` with the signature generated by
+/// [export_extn_channel].
+pub unsafe fn get_abi_version(lib: &Library) -> Option<ExtnAbiVersion> {
+    type AbiFfi = unsafe extern "C" fn() -> ExtnAbiVersion;
+    match lib.get::<AbiFfi>(b"extn_abi_version") {
+        Ok(f) => Some(f()),
+        Err(e) => {
+            debug!("Extn abi version symbol not found {:?}", e);
+            None
+        }
+    }
+}
+
 /// Generic Extension channel
 #[repr(C)]
 #[derive(Debug)]
@@ -43,7 +95,8 @@ pub unsafe fn load_channel_builder(lib: &Library) -> Result<Box<ExtnChannel>, Ri
     Err(RippleError::ExtnError)
 }
 
-/// Macro used by Extensions to export a channel.
+/// Macro used by Extensions to export a channel. It also exports the [ExtnAbiVersion] the
+/// extension is built with, which is checked by main before the channel is created.
 ///
 /// # Example
 /// ```
@@ -64,6 +117,11 @@ pub unsafe fn load_channel_builder(lib: &Library) -> Result<Box<ExtnChannel>, Ri
 #[macro_export]
 macro_rules! export_extn_channel {
     ($plugin_type:ty, $constructor:path) => {
+        #[no_mangle]
+        pub extern "C" fn extn_abi_version() -> $crate::extn::ffi::ffi_channel::ExtnAbiVersion {
+            $crate::extn::ffi::ffi_channel::ExtnAbiVersion::current()
+        }
+
         #[no_mangle]
         pub extern "C" fn channel_builder_create() -> *mut ExtnChannel {
             let constructor: fn() -> $plugin_type = $constructor;
@@ -73,3 +131,23 @@ macro_rules! export_extn_channel {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abi_version() {
+        let current = ExtnAbiVersion::current();
+        assert_eq!(current.abi, EXTN_ABI_VERSION);
+        assert!(current.is_compatible(&current));
+        assert!(!current.is_compatible(&ExtnAbiVersion {
+            abi: EXTN_ABI_VERSION + 1,
+            ..current
+        }));
+        assert!(!current.is_compatible(&ExtnAbiVersion {
+            sdk_minor: current.sdk_minor + 1,
+            ..current
+        }));
+    }
+}
@@ -16,19 +16,35 @@
 //
 
 use log::{debug, info, warn};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use std::{
-    fs::{self, OpenOptions},
+    fs::{self, File},
     io::Write,
     path::Path,
 };
 
 use crate::utils::error::RippleError;
 
+/// Migrates a stored value from schema version `n` to `n + 1`. The migrations of a store are
+/// registered in version order, so the current version is their count.
+pub type FileStoreMigration = fn(Value) -> Result<Value, RippleError>;
+
+/// On disk format of a store with migrations. Stores without migrations, and files written
+/// before versioning, hold the bare value which is loaded as version 0.
+#[derive(Serialize, Deserialize)]
+struct VersionedContent<T> {
+    version: usize,
+    value: T,
+}
+
+/// A value persisted as JSON. Writes go to a temporary file which is synced and renamed over the
+/// store, the previous file is kept as a backup which is loaded when the store is corrupt.
 #[derive(Debug, Clone)]
 pub struct FileStore<S> {
     pub value: S,
     path: String,
+    migrations: &'static [FileStoreMigration],
 }
 
 impl<S> FileStore<S>
@@ -39,58 +55,295 @@ where
         FileStore {
             value,
             path: Path::new(&path).to_str().unwrap().into(),
+            migrations: &[],
         }
     }
 
-    fn write_to_disk(&self, value: String) {
+    pub fn with_migrations(mut self, migrations: &'static [FileStoreMigration]) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    fn backup_path(path: &str) -> String {
+        format!("{}.bak", path)
+    }
+
+    fn temp_path(path: &str) -> String {
+        format!("{}.tmp", path)
+    }
+
+    fn write_to_disk(&self, value: String) -> Result<(), RippleError> {
         // Create the folder if it doesnt exist
         let p = Path::new(&self.path);
         if let Some(parent) = p.parent() {
             let _ = fs::create_dir_all(parent);
         }
-        match OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(self.path.clone())
-        {
-            Ok(mut file) => {
-                if let Err(e) = file.write_all(value.as_bytes()) {
-                    warn!("Failed to write file store for {:?} {}", e, self.path);
-                }
+        let temp_path = Self::temp_path(&self.path);
+        let written = File::create(&temp_path).and_then(|mut file| {
+            file.write_all(value.as_bytes())?;
+            file.sync_all()
+        });
+        if let Err(e) = written {
+            warn!("Failed to write file store for {:?} {}", e, temp_path);
+            let _ = fs::remove_file(&temp_path);
+            return Err(RippleError::InvalidOutput);
+        }
+        // between the renames the store is only found in the backup, which load falls back to
+        if p.exists() {
+            if let Err(e) = fs::rename(&self.path, Self::backup_path(&self.path)) {
+                warn!("Failed to back up file store for {} {:?}", self.path, e);
             }
-            Err(e) => {
-                warn!("Failed to open file store for {} {:?}", self.path, e);
+        }
+        if let Err(e) = fs::rename(&temp_path, &self.path) {
+            warn!("Failed to replace file store for {} {:?}", self.path, e);
+            return Err(RippleError::InvalidOutput);
+        }
+        // the renames are durable once the directory is synced
+        if let Some(parent) = p.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            if let Ok(dir) = File::open(parent) {
+                let _ = dir.sync_all();
             }
         }
+        Ok(())
+    }
+
+    /// Writes the value, returning whether it reached the disk.
+    pub fn persist(&self) -> Result<(), RippleError> {
+        let new_value_string = if self.migrations.is_empty() {
+            serde_json::to_string(&self.value)
+        } else {
+            serde_json::to_string(&VersionedContent {
+                version: self.migrations.len(),
+                value: &self.value,
+            })
+        }
+        .map_err(|_| RippleError::ParseError)?;
+        self.write_to_disk(new_value_string)
     }
 
     pub fn sync(&mut self) {
-        let new_value_string = serde_json::to_string(&self.value).unwrap();
-        self.write_to_disk(new_value_string);
+        let _ = self.persist();
     }
 
-    fn load_from_content(contents: String) -> Result<S, RippleError> {
-        match serde_json::from_str::<S>(&contents) {
-            Ok(s) => Ok(s),
-            Err(err) => {
-                warn!("{:?} could not parse file content", err);
-                Err(RippleError::ParseError)
+    fn load_from_content(
+        contents: String,
+        migrations: &[FileStoreMigration],
+    ) -> Result<S, RippleError> {
+        let mut value = serde_json::from_str::<Value>(&contents).map_err(|err| {
+            warn!("{:?} could not parse file content", err);
+            RippleError::ParseError
+        })?;
+        let mut version = 0;
+        if let Some(content) = value.as_object().filter(|c| c.len() == 2) {
+            if let (Some(v), Some(inner)) = (
+                content.get("version").and_then(Value::as_u64),
+                content.get("value"),
+            ) {
+                version = v as usize;
+                value = inner.clone();
             }
         }
+        if version > migrations.len() {
+            warn!(
+                "file content version {} is newer than {}",
+                version,
+                migrations.len()
+            );
+            return Err(RippleError::InvalidInput);
+        }
+        for (from, migration) in migrations.iter().enumerate().skip(version) {
+            info!("migrating file content from version {}", from);
+            value = migration(value)?;
+        }
+        serde_json::from_value::<S>(value).map_err(|err| {
+            warn!("{:?} could not parse file content", err);
+            RippleError::ParseError
+        })
     }
 
-    pub fn load(path: String) -> Result<FileStore<S>, RippleError> {
-        if let Ok(contents) = fs::read_to_string(&path) {
-            if let Ok(s) = Self::load_from_content(contents.clone()) {
-                debug!("valid filestore content {} from {}", contents, path);
-                Ok(FileStore { value: s, path })
-            } else {
-                Err(RippleError::InvalidAccess)
+    fn load_file(path: &str, migrations: &[FileStoreMigration]) -> Result<S, RippleError> {
+        if let Ok(contents) = fs::read_to_string(path) {
+            match Self::load_from_content(contents.clone(), migrations) {
+                Ok(s) => {
+                    debug!("valid filestore content {} from {}", contents, path);
+                    Ok(s)
+                }
+                Err(RippleError::InvalidInput) => Err(RippleError::InvalidInput),
+                Err(_) => Err(RippleError::InvalidAccess),
             }
         } else {
             info!("No file found in {}", path);
             Err(RippleError::MissingInput)
         }
     }
+
+    pub fn load(path: String) -> Result<FileStore<S>, RippleError> {
+        Self::load_with_migrations(path, &[])
+    }
+
+    /// Loads the store and migrates its value to the version of `migrations`. When the file is
+    /// missing or corrupt the backup of the previous write is loaded and restored. A file of a
+    /// newer version than `migrations` fails with [RippleError::InvalidInput], it is left as is.
+    pub fn load_with_migrations(
+        path: String,
+        migrations: &'static [FileStoreMigration],
+    ) -> Result<FileStore<S>, RippleError> {
+        let value = match Self::load_file(&path, migrations) {
+            Ok(value) => value,
+            Err(RippleError::InvalidInput) => return Err(RippleError::InvalidInput),
+            Err(e) => {
+                let backup_path = Self::backup_path(&path);
+                let value = Self::load_file(&backup_path, migrations).map_err(|_| e)?;
+                warn!("file store {} restored from {}", path, backup_path);
+                if let Err(e) = fs::copy(&backup_path, &path) {
+                    warn!("Failed to restore file store for {} {:?}", path, e);
+                }
+                value
+            }
+        };
+        Ok(FileStore {
+            value,
+            path,
+            migrations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn test_path(name: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("{}_{}", name, std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        for file in [
+            path.clone(),
+            FileStore::<Value>::backup_path(&path),
+            FileStore::<Value>::temp_path(&path),
+        ] {
+            let _ = fs::remove_file(file);
+        }
+        path
+    }
+
+    #[test]
+    fn test_file_store_backup() {
+        let path = test_path("ripple_file_store_backup_test");
+        let mut store = FileStore::new(path.clone(), HashMap::from([("a".to_owned(), 1)]));
+        store.sync();
+        store.value.insert("b".to_owned(), 2);
+        store.sync();
+        assert!(!Path::new(&FileStore::<Value>::temp_path(&path)).exists());
+        // stores without migrations keep the bare value
+        let contents: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(contents, json!({"a": 1, "b": 2}));
+        assert_eq!(
+            FileStore::<HashMap<String, i32>>::load(path.clone())
+                .unwrap()
+                .value
+                .len(),
+            2
+        );
+
+        // a write cut short leaves a partial file behind, the previous write is loaded
+        fs::write(&path, "{\"version\":0,\"val").unwrap();
+        let loaded = FileStore::<HashMap<String, i32>>::load(path.clone()).unwrap();
+        assert_eq!(loaded.value, HashMap::from([("a".to_owned(), 1)]));
+        // and restored
+        assert!(FileStore::<HashMap<String, i32>>::load_file(&path, &[]).is_ok());
+
+        fs::remove_file(FileStore::<Value>::backup_path(&path)).unwrap();
+        fs::write(&path, "").unwrap();
+        assert_eq!(
+            FileStore::<HashMap<String, i32>>::load(path.clone()).unwrap_err(),
+            RippleError::InvalidAccess
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            FileStore::<HashMap<String, i32>>::load(path).unwrap_err(),
+            RippleError::MissingInput
+        );
+    }
+
+    fn titles_to_entries(value: Value) -> Result<Value, RippleError> {
+        let titles = value.as_object().ok_or(RippleError::ParseError)?;
+        Ok(Value::Object(
+            titles
+                .iter()
+                .map(|(id, title)| (id.clone(), json!({ "title": title })))
+                .collect(),
+        ))
+    }
+
+    fn add_visible(mut value: Value) -> Result<Value, RippleError> {
+        for entry in value
+            .as_object_mut()
+            .ok_or(RippleError::ParseError)?
+            .values_mut()
+        {
+            entry["visible"] = json!(true);
+        }
+        Ok(value)
+    }
+
+    static MIGRATIONS: [FileStoreMigration; 2] = [titles_to_entries, add_visible];
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Entry {
+        title: String,
+        visible: bool,
+    }
+
+    #[test]
+    fn test_file_store_migrations() {
+        let path = test_path("ripple_file_store_migrations_test");
+        // written before versioning
+        fs::write(&path, r#"{"app":"App"}"#).unwrap();
+        let mut store =
+            FileStore::<HashMap<String, Entry>>::load_with_migrations(path.clone(), &MIGRATIONS)
+                .unwrap();
+        let entry = Entry {
+            title: "App".to_owned(),
+            visible: true,
+        };
+        assert_eq!(store.value.get("app"), Some(&entry));
+        store.sync();
+        let contents: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(contents["version"], json!(2));
+
+        fs::write(
+            &path,
+            r#"{"version":1,"value":{"app":{"title":"App","visible":false}}}"#,
+        )
+        .unwrap();
+        let mut store =
+            FileStore::<HashMap<String, Entry>>::load_with_migrations(path.clone(), &MIGRATIONS)
+                .unwrap();
+        assert_eq!(store.value.get("app"), Some(&entry));
+
+        // content of a newer version is not loaded, nor replaced by the backup of version 1
+        store.sync();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(Path::new(&FileStore::<Value>::backup_path(&path)).exists());
+        assert_eq!(
+            FileStore::<HashMap<String, Entry>>::load_with_migrations(
+                path.clone(),
+                &MIGRATIONS[..1]
+            )
+            .unwrap_err(),
+            RippleError::InvalidInput
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(
+            FileStore::<Value>::load(path.clone()).unwrap_err(),
+            RippleError::InvalidInput
+        );
+        for file in [path.clone(), FileStore::<Value>::backup_path(&path)] {
+            let _ = fs::remove_file(file);
+        }
+    }
 }
@@ -94,6 +94,9 @@ pub enum RippleContract {
     /// the Session information based on their policies. Used by [crate::api::session::AccountSession]
     Session(SessionAdjective),
     RippleContext,
+    /// Used by Main to propagate runtime log level changes to extensions.
+    /// Used as a Event contract doesnt map to a request.
+    LogLevels,
     ExtnProvider(ExtnProviderAdjective),
     // Runtime ability for a given distributor to turn off a certian feature
     RemoteFeatureControl,
@@ -14,6 +14,7 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 //
+pub mod service_auth;
 pub mod service_client;
 pub mod service_event_state;
 pub mod service_message;
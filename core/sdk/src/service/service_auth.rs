@@ -0,0 +1,255 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Authentication of services connecting to the gateway. A service presents a token derived from
+//! the shared secret declared for its symbol in the extn manifest as query parameters of the
+//! `service_handshake` connection.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ring::hmac;
+use uuid::Uuid;
+
+use crate::utils::digest_utils::to_hex;
+
+pub const SERVICE_HANDSHAKE_QUERY: &str = "service_handshake";
+pub const SERVICE_TIMESTAMP_QUERY: &str = "timestamp";
+pub const SERVICE_NONCE_QUERY: &str = "nonce";
+pub const SERVICE_TOKEN_QUERY: &str = "token";
+/// Maximum difference in seconds between the timestamp of a token and the gateway clock.
+pub const SERVICE_TOKEN_VALIDITY: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServiceAuthError {
+    MissingToken,
+    Expired,
+    InvalidToken,
+    /// the token was already used for a connection
+    Replayed,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn signed_message(service_id: &str, timestamp: u64, nonce: &str) -> String {
+    format!("{}:{}:{}", service_id, timestamp, nonce)
+}
+
+/// Returns the hex encoded HMAC-SHA256 of `<service_id>:<timestamp>:<nonce>` keyed with the
+/// secret.
+pub fn service_token(service_id: &str, secret: &str, timestamp: u64, nonce: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    to_hex(
+        hmac::sign(
+            &key,
+            signed_message(service_id, timestamp, nonce).as_bytes(),
+        )
+        .as_ref(),
+    )
+}
+
+/// Returns the query of a `service_handshake` connection, with a token when a secret is given.
+pub fn service_handshake_query(service_id: &str, secret: Option<&str>) -> String {
+    match secret {
+        Some(secret) => {
+            let timestamp = now_secs();
+            let nonce = Uuid::new_v4().simple().to_string();
+            format!(
+                "{}={}&{}={}&{}={}&{}={}",
+                SERVICE_HANDSHAKE_QUERY,
+                service_id,
+                SERVICE_TIMESTAMP_QUERY,
+                timestamp,
+                SERVICE_NONCE_QUERY,
+                nonce,
+                SERVICE_TOKEN_QUERY,
+                service_token(service_id, secret, timestamp, &nonce)
+            )
+        }
+        None => format!("{}={}", SERVICE_HANDSHAKE_QUERY, service_id),
+    }
+}
+
+fn decode_hex(token: &str) -> Option<Vec<u8>> {
+    if token.len() % 2 != 0 {
+        return None;
+    }
+    (0..token.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(token.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Verifies the token presented by a service against its secret. The comparison is done in
+/// constant time.
+pub fn verify_service_token(
+    service_id: &str,
+    secret: &str,
+    timestamp: Option<&str>,
+    nonce: Option<&str>,
+    token: Option<&str>,
+    now: u64,
+) -> Result<(), ServiceAuthError> {
+    let (Some(timestamp), Some(nonce), Some(token)) = (timestamp, nonce, token) else {
+        return Err(ServiceAuthError::MissingToken);
+    };
+    let timestamp = timestamp
+        .parse::<u64>()
+        .map_err(|_| ServiceAuthError::InvalidToken)?;
+    if now.abs_diff(timestamp) > SERVICE_TOKEN_VALIDITY {
+        return Err(ServiceAuthError::Expired);
+    }
+    let token = decode_hex(token).ok_or(ServiceAuthError::InvalidToken)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(
+        &key,
+        signed_message(service_id, timestamp, nonce).as_bytes(),
+        &token,
+    )
+    .map_err(|_| ServiceAuthError::InvalidToken)
+}
+
+/// Tokens accepted within the validity window, so each token authenticates one connection.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceTokenCache {
+    // timestamps of the accepted tokens by token
+    used: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl ServiceTokenCache {
+    /// Verifies the token like [verify_service_token] and refuses a token which was already
+    /// accepted.
+    pub fn verify(
+        &self,
+        service_id: &str,
+        secret: &str,
+        timestamp: Option<&str>,
+        nonce: Option<&str>,
+        token: Option<&str>,
+        now: u64,
+    ) -> Result<(), ServiceAuthError> {
+        verify_service_token(service_id, secret, timestamp, nonce, token, now)?;
+        let mut used = self.used.lock().unwrap();
+        // expired tokens are refused anyway
+        used.retain(|_, timestamp| now.abs_diff(*timestamp) <= SERVICE_TOKEN_VALIDITY);
+        let token = token.unwrap_or_default().to_ascii_lowercase();
+        if used.contains_key(&token) {
+            return Err(ServiceAuthError::Replayed);
+        }
+        let timestamp = timestamp.and_then(|t| t.parse().ok()).unwrap_or(now);
+        used.insert(token, timestamp);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_service_token() {
+        let id = "ripple:channel:gateway:badger";
+        let nonce = Some("n1");
+        let token = service_token(id, "secret", 1000, "n1");
+        assert_eq!(token.len(), 64);
+        assert!(
+            verify_service_token(id, "secret", Some("1000"), nonce, Some(&token), 1005).is_ok()
+        );
+        assert_eq!(
+            verify_service_token(id, "other", Some("1000"), nonce, Some(&token), 1000),
+            Err(ServiceAuthError::InvalidToken)
+        );
+        assert_eq!(
+            verify_service_token(
+                "ripple:channel:gateway:other",
+                "secret",
+                Some("1000"),
+                nonce,
+                Some(&token),
+                1000
+            ),
+            Err(ServiceAuthError::InvalidToken)
+        );
+        assert_eq!(
+            verify_service_token(id, "secret", Some("1000"), Some("n2"), Some(&token), 1000),
+            Err(ServiceAuthError::InvalidToken)
+        );
+        assert_eq!(
+            verify_service_token(id, "secret", Some("1000"), nonce, Some(&token), 1006),
+            Err(ServiceAuthError::Expired)
+        );
+        assert_eq!(
+            verify_service_token(id, "secret", Some("1000"), nonce, Some("zz"), 1000),
+            Err(ServiceAuthError::InvalidToken)
+        );
+        assert_eq!(
+            verify_service_token(id, "secret", None, nonce, Some(&token), 1000),
+            Err(ServiceAuthError::MissingToken)
+        );
+        assert_eq!(
+            verify_service_token(id, "secret", Some("1000"), None, Some(&token), 1000),
+            Err(ServiceAuthError::MissingToken)
+        );
+    }
+
+    #[test]
+    fn test_service_token_cache() {
+        let id = "ripple:channel:gateway:badger";
+        let cache = ServiceTokenCache::default();
+        let first = service_token(id, "secret", 1000, "n1");
+        let second = service_token(id, "secret", 1000, "n2");
+        let verify = |nonce, token: &str, now| {
+            cache.verify(id, "secret", Some("1000"), Some(nonce), Some(token), now)
+        };
+        assert!(verify("n1", &first, 1000).is_ok());
+        assert_eq!(verify("n1", &first, 1001), Err(ServiceAuthError::Replayed));
+        // another nonce in the same second
+        assert!(verify("n2", &second, 1001).is_ok());
+        // a failed verification is not remembered
+        assert_eq!(
+            cache.verify(id, "other", Some("1000"), Some("n3"), Some(&first), 1000),
+            Err(ServiceAuthError::InvalidToken)
+        );
+        assert_eq!(verify("n1", &first, 1006), Err(ServiceAuthError::Expired));
+        let later = service_token(id, "secret", 1006, "n4");
+        assert!(cache
+            .verify(id, "secret", Some("1006"), Some("n4"), Some(&later), 1006)
+            .is_ok());
+        // the expired tokens are forgotten
+        assert_eq!(cache.used.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_service_handshake_query() {
+        assert_eq!(
+            service_handshake_query("id", None),
+            "service_handshake=id".to_owned()
+        );
+        let query = service_handshake_query("id", Some("secret"));
+        assert!(query.starts_with("service_handshake=id&timestamp="));
+        assert!(query.contains("&nonce="));
+        assert!(query.contains("&token="));
+    }
+}
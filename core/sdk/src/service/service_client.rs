@@ -32,6 +32,7 @@ use crate::processor::rpc_router::RouterState;
 use crate::service::service_message::{Id, JsonRpcMessage};
 use crate::service::service_rpc_router::route_service_message;
 use crate::utils::extn_utils::ExtnStackSize;
+use crate::utils::logger::{apply_log_levels, LogLevels, LOG_LEVELS_CHANGED_NOTIFICATION};
 #[cfg(any(test, feature = "mock"))]
 use crate::utils::mock_utils::get_next_mock_service_response;
 use crate::utils::{error::RippleError, ws_utils::WebSocketUtils};
@@ -46,6 +47,7 @@ use tokio::sync::{mpsc::Sender as MSender, oneshot::Sender as OSender};
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
+use super::service_auth::service_handshake_query;
 use super::service_message::{JsonRpcSuccess, ServiceMessage};
 #[derive(Debug, Clone, Default)]
 pub struct ServiceClient {
@@ -56,6 +58,8 @@ pub struct ServiceClient {
     pub extn_client: Option<ExtnClient>,
     // TBD: Remove this field after implementing service.register API call.
     pub service_id: Option<ExtnId>,
+    // shared secret the handshake token is signed with
+    service_secret: Option<String>,
     pub outbound_extn_rx: Arc<RwLock<Option<mpsc::Receiver<ApiMessage>>>>,
     pub outbound_service_rx: Arc<RwLock<Option<mpsc::Receiver<ServiceMessage>>>>,
     extn_manifest: ExtnManifest,
@@ -128,6 +132,10 @@ impl ServiceClientBuilder {
                 service_router,
                 extn_client: Some(extn_client),
                 service_id: Some(ExtnId::try_from(symbol.id.clone()).unwrap()),
+                service_secret: symbol
+                    .secret
+                    .clone()
+                    .or_else(|| std::env::var("RIPPLE_SERVICE_SECRET").ok()),
                 response_processors: Arc::new(RwLock::new(HashMap::new())),
                 event_processors: Arc::new(RwLock::new(HashMap::new())),
                 outbound_extn_rx: Arc::new(RwLock::new(Some(ext_tr))),
@@ -141,6 +149,7 @@ impl ServiceClientBuilder {
                 service_router,
                 extn_client: None,
                 service_id: None,
+                service_secret: None,
                 response_processors: Arc::new(RwLock::new(HashMap::new())),
                 event_processors: Arc::new(RwLock::new(HashMap::new())),
                 outbound_extn_rx: Arc::new(RwLock::new(None)),
@@ -198,13 +207,6 @@ impl ServiceClient {
 
         let base_path = std::env::var("RIPPLE_SERVICE_HANDSHAKE_PATH")
             .unwrap_or_else(|_| "127.0.0.1:3474".to_string());
-        let path = tokio_tungstenite::tungstenite::http::Uri::builder()
-            .scheme("ws")
-            .authority(base_path.as_str())
-            .path_and_query(format!("/?service_handshake={}", service_id))
-            .build()
-            .unwrap()
-            .to_string();
 
         let outbound_service_rx = self.get_outbound_service_rx();
         let mut outbound_service_rx = match outbound_service_rx {
@@ -225,7 +227,17 @@ impl ServiceClient {
 
         let mut retry_count = 0u32;
         loop {
-            debug!("Connecting to WebSocket at {}", path);
+            // the token is signed with the current time, so it is created for every connection
+            let query =
+                service_handshake_query(&service_id.to_string(), self.service_secret.as_deref());
+            let path = tokio_tungstenite::tungstenite::http::Uri::builder()
+                .scheme("ws")
+                .authority(base_path.as_str())
+                .path_and_query(format!("/?{}", query))
+                .build()
+                .unwrap()
+                .to_string();
+            debug!("Connecting to WebSocket at {}", base_path);
             Self::connect_websocket(self, &path, &mut outbound_service_rx, &mut outbound_extn_rx)
                 .await;
 
@@ -262,6 +274,12 @@ impl ServiceClient {
                                     error!("Service sender is not available");
                                 }
                             }
+                            JsonRpcMessage::Notification(ref json_rpc_notification)
+                                if json_rpc_notification.method
+                                    == LOG_LEVELS_CHANGED_NOTIFICATION =>
+                            {
+                                on_log_levels_changed(json_rpc_notification.params.clone());
+                            }
                             JsonRpcMessage::Notification(ref json_rpc_notification) => {
                                 debug!(
                                     "Received Service Notification: {:?}",
@@ -734,6 +752,18 @@ impl ServiceClient {
     }
 }
 
+fn on_log_levels_changed(params: Option<Value>) {
+    match params.map(serde_json::from_value::<LogLevels>) {
+        Some(Ok(levels)) => {
+            debug!("Received log levels {:?}", levels);
+            if let Err(e) = apply_log_levels(&levels) {
+                error!("Failed to apply log levels {:?}", e);
+            }
+        }
+        _ => error!("Invalid log levels notification"),
+    }
+}
+
 fn add_response_processor<P>(
     id: String,
     processor: Option<P>,
@@ -785,6 +815,7 @@ pub mod tests {
                 service_id: Some(
                     ExtnId::try_from("ripple:channel:gateway:service1".to_string()).unwrap(),
                 ),
+                service_secret: None,
                 response_processors: Arc::new(RwLock::new(HashMap::new())),
                 outbound_extn_rx: Arc::new(RwLock::new(Some(extn_tr))),
                 outbound_service_rx: Arc::new(RwLock::new(Some(service_tr))),
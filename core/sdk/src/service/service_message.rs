@@ -18,9 +18,13 @@ use crate::utils::error::RippleError;
 use jsonrpsee::core::RpcResult;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::fmt::Debug;
 
+/// Heartbeat request Ripple Main sends to connected services, any response counts as alive.
+pub const SERVICE_PING_METHOD: &str = "ripple.ping";
+const SERVICE_PING_ID_PREFIX: &str = "ping-";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Id {
@@ -233,6 +237,42 @@ impl ServiceMessage {
         }
     }
 
+    // ping ids are strings so they never collide with the numeric ids of brokered requests
+    pub fn new_ping(sequence: u64) -> Self {
+        Self::new_request(
+            SERVICE_PING_METHOD.to_string(),
+            None,
+            Id::String(format!("{}{}", SERVICE_PING_ID_PREFIX, sequence)),
+        )
+    }
+
+    pub fn is_ping(&self) -> bool {
+        matches!(&self.message, JsonRpcMessage::Request(req) if req.method == SERVICE_PING_METHOD)
+    }
+
+    // answer to this message if it is a ping
+    pub fn get_pong(&self) -> Option<Self> {
+        match &self.message {
+            JsonRpcMessage::Request(req) if self.is_ping() => {
+                Some(Self::new_success(json!("pong"), req.id.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    // sequence of the ping this message responds to
+    pub fn get_pong_sequence(&self) -> Option<u64> {
+        let id = match &self.message {
+            JsonRpcMessage::Success(success) => &success.id,
+            JsonRpcMessage::Error(err) => &err.id,
+            _ => return None,
+        };
+        match id {
+            Id::String(id) => id.strip_prefix(SERVICE_PING_ID_PREFIX)?.parse().ok(),
+            _ => None,
+        }
+    }
+
     pub fn set_context(&mut self, context: Option<Value>) {
         self.context = context;
     }
@@ -319,6 +359,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ping_pong() {
+        let ping = ServiceMessage::new_ping(7);
+        assert!(ping.is_ping());
+        assert_eq!(ping.get_pong_sequence(), None);
+        assert_eq!(ping.get_pong().unwrap().get_pong_sequence(), Some(7));
+        let id = match ping.message {
+            JsonRpcMessage::Request(req) => req.id,
+            _ => panic!("Expected Request variant"),
+        };
+        let pong = ServiceMessage::new_success(json!("pong"), id.clone());
+        assert_eq!(pong.get_pong_sequence(), Some(7));
+        let error = ServiceMessage::new_error(-32601, "unknown".to_string(), None, id);
+        assert_eq!(error.get_pong_sequence(), Some(7));
+        let response = ServiceMessage::new_success(json!(true), Id::Number(7));
+        assert_eq!(response.get_pong_sequence(), None);
+        assert!(response.get_pong().is_none());
+    }
+
     #[test]
     fn test_new_notification() {
         let msg = ServiceMessage::new_notification("notify".to_string(), Some(json!({"a": 1})));
@@ -31,6 +31,11 @@ pub fn route_service_message(
 ) -> Result<(), RippleError> {
     trace!("Received Service Message: {:#?}", sm);
     match sm.message {
+        JsonRpcMessage::Request(_) if sm.is_ping() => {
+            if let Err(e) = sender.try_send(sm.get_pong().unwrap()) {
+                error!("Error sending pong: {:?}", e);
+            }
+        }
         JsonRpcMessage::Request(ref json_rpc_request) => {
             let ctx = sm.context.as_ref().map_or_else(CallContext::default, |v| {
                 serde_json::from_value(v.clone()).unwrap_or_default()
@@ -239,6 +244,15 @@ mod tests {
         let _ = route_service_message(&tx, &state, sm);
     }
 
+    #[tokio::test]
+    async fn test_route_service_message_ping() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let state = dummy_router_state();
+        route_service_message(&tx, &state, ServiceMessage::new_ping(3)).unwrap();
+        let pong = rx.recv().await.unwrap();
+        assert_eq!(pong.get_pong_sequence(), Some(3));
+    }
+
     #[tokio::test]
     async fn test_route_service_message_notification() {
         let (tx, mut rx) = mpsc::channel(1);
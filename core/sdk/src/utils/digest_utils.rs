@@ -0,0 +1,125 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    fmt::Write,
+    fs::File,
+    io::{BufReader, Read},
+};
+
+use log::error;
+use ring::{
+    digest::{Context, SHA256},
+    signature::{UnparsedPublicKey, ED25519},
+};
+
+use super::error::RippleError;
+
+/// Returns the lower case hex encoding of the bytes.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02x}", b);
+        hex
+    })
+}
+
+/// Returns the hex encoded SHA-256 digest of the file at the given path.
+pub fn sha256_file(path: &str) -> Result<String, RippleError> {
+    let file = File::open(path).map_err(|e| {
+        error!("Unable to open {} {:?}", path, e);
+        RippleError::MissingInput
+    })?;
+    sha256_reader(file)
+}
+
+/// Returns the hex encoded SHA-256 digest of everything left in the reader.
+pub fn sha256_reader(reader: impl Read) -> Result<String, RippleError> {
+    let mut reader = BufReader::new(reader);
+    let mut context = Context::new(&SHA256);
+    let mut buf = [0u8; 8192];
+    loop {
+        let len = reader.read(&mut buf).map_err(|e| {
+            error!("Unable to read {:?}", e);
+            RippleError::InvalidInput
+        })?;
+        if len == 0 {
+            break;
+        }
+        context.update(&buf[..len]);
+    }
+    Ok(to_hex(context.finish().as_ref()))
+}
+
+/// Verifies the detached Ed25519 `signature` of `message` with the raw 32 byte `public_key`.
+pub fn verify_ed25519(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), RippleError> {
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(message, signature)
+        .map_err(|_| {
+            error!("Ed25519 signature verification failed");
+            RippleError::InvalidInput
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+
+    #[test]
+    fn test_sha256_file() {
+        let path =
+            std::env::temp_dir().join(format!("ripple_digest_utils_test_{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(path.to_str().unwrap()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            sha256_reader(&b"abc"[..]).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_file("/nonexistent/ripple"),
+            Err(RippleError::MissingInput)
+        );
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = key_pair.public_key().as_ref();
+        let signature = key_pair.sign(b"manifest");
+        assert_eq!(
+            verify_ed25519(public_key, b"manifest", signature.as_ref()),
+            Ok(())
+        );
+        assert_eq!(
+            verify_ed25519(public_key, b"tampered", signature.as_ref()),
+            Err(RippleError::InvalidInput)
+        );
+    }
+}
@@ -15,21 +15,165 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use crate::{
+    extn::{
+        extn_client_message::{ExtnEvent, ExtnMessage, ExtnPayload, ExtnPayloadProvider},
+        extn_id::ExtnId,
+    },
+    framework::ripple_contract::RippleContract,
+    utils::error::RippleError,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::RwLock;
 use std::{str::FromStr, sync::atomic::AtomicU32};
 
 pub static LOG_COUNTER: AtomicU32 = AtomicU32::new(1);
 
+/// Notification method used to push [LogLevels] to connected services.
+pub const LOG_LEVELS_CHANGED_NOTIFICATION: &str = "ripple.onLogLevelsChanged";
+
 lazy_static::lazy_static! {
     pub static ref MODULE_LOG_LEVELS: RwLock<HashMap<String, log::LevelFilter>> = RwLock::new(HashMap::new());
+    static ref GLOBAL_LOG_LEVEL: RwLock<log::LevelFilter> = RwLock::new(log::LevelFilter::Info);
+}
+
+/// Snapshot of the runtime log filter. Main shares it with extensions and services
+/// so that their dispatchers follow the same levels.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogLevels {
+    pub level: String,
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+}
+
+impl LogLevels {
+    fn parse(&self) -> Result<(log::LevelFilter, HashMap<String, log::LevelFilter>), RippleError> {
+        let level = parse_level(&self.level)?;
+        let mut modules = HashMap::new();
+        for (module, level) in &self.modules {
+            modules.insert(module.clone(), parse_level(level)?);
+        }
+        Ok((level, modules))
+    }
+
+    pub fn get_event_message(&self) -> ExtnMessage {
+        ExtnMessage {
+            id: "log_levels_update".to_owned(),
+            requestor: ExtnId::get_main_target("log_levels".to_owned()),
+            target: RippleContract::LogLevels,
+            target_id: None,
+            payload: self.get_extn_payload(),
+            ts: None,
+        }
+    }
+}
+
+impl ExtnPayloadProvider for LogLevels {
+    fn get_extn_payload(&self) -> ExtnPayload {
+        ExtnPayload::Event(ExtnEvent::LogLevels(self.clone()))
+    }
+
+    fn get_from_payload(payload: ExtnPayload) -> Option<LogLevels> {
+        if let ExtnPayload::Event(ExtnEvent::LogLevels(r)) = payload {
+            return Some(r);
+        }
+
+        None
+    }
+
+    fn contract() -> RippleContract {
+        RippleContract::LogLevels
+    }
+}
+
+pub fn parse_level(level: &str) -> Result<log::LevelFilter, RippleError> {
+    log::LevelFilter::from_str(level).map_err(|_| RippleError::InvalidInput)
+}
+
+/// Level of the most specific module entry covering the target, falling back to the
+/// global level. A module covers its own target and every `module::` path below it.
+fn resolve_level(
+    target: &str,
+    global: log::LevelFilter,
+    modules: &HashMap<String, log::LevelFilter>,
+) -> log::LevelFilter {
+    modules
+        .iter()
+        .filter(|(module, _)| {
+            target == module.as_str()
+                || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map_or(global, |(_, level)| *level)
+}
+
+fn is_enabled(metadata: &log::Metadata) -> bool {
+    let global = *GLOBAL_LOG_LEVEL.read().unwrap();
+    let level = resolve_level(
+        metadata.target(),
+        global,
+        &MODULE_LOG_LEVELS.read().unwrap(),
+    );
+    metadata.level() <= level
+}
+
+/// Raises or lowers the `log` crate's static ceiling so that disabled records are
+/// dropped before reaching the dispatcher.
+fn update_max_level() {
+    let global = *GLOBAL_LOG_LEVEL.read().unwrap();
+    let max = MODULE_LOG_LEVELS
+        .read()
+        .unwrap()
+        .values()
+        .fold(global, |max, level| max.max(*level));
+    log::set_max_level(max);
+}
+
+pub fn get_log_levels() -> LogLevels {
+    LogLevels {
+        level: GLOBAL_LOG_LEVEL.read().unwrap().to_string(),
+        modules: MODULE_LOG_LEVELS
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(module, level)| (module.clone(), level.to_string()))
+            .collect(),
+    }
+}
+
+pub fn set_log_level(level: log::LevelFilter) {
+    *GLOBAL_LOG_LEVEL.write().unwrap() = level;
+    update_max_level();
+}
+
+/// Sets the level for a module, or removes its override when `level` is `None`.
+pub fn set_module_log_level(module: &str, level: Option<log::LevelFilter>) {
+    {
+        let mut modules = MODULE_LOG_LEVELS.write().unwrap();
+        match level {
+            Some(level) => modules.insert(module.to_owned(), level),
+            None => modules.remove(module),
+        };
+    }
+    update_max_level();
+}
+
+/// Replaces the global level and every module override with the given snapshot.
+pub fn apply_log_levels(levels: &LogLevels) -> Result<(), RippleError> {
+    let (level, modules) = levels.parse()?;
+    *GLOBAL_LOG_LEVEL.write().unwrap() = level;
+    *MODULE_LOG_LEVELS.write().unwrap() = modules;
+    update_max_level();
+    Ok(())
 }
 
 pub fn init_logger(name: String) -> Result<(), fern::InitError> {
     let log_string: String = std::env::var("RUST_LOG").unwrap_or_else(|_| "debug".into());
     println!("log level {}", log_string);
     let filter = log::LevelFilter::from_str(&log_string).unwrap_or(log::LevelFilter::Info);
-    fern::Dispatch::new()
+    *GLOBAL_LOG_LEVEL.write().unwrap() = filter;
+    let result = fern::Dispatch::new()
         .format(move |out, message, record| {
             #[cfg(not(feature = "sysd"))]
             return out.finish(format_args!(
@@ -49,7 +193,9 @@ pub fn init_logger(name: String) -> Result<(), fern::InitError> {
                 message
             ));
         })
-        .level(filter)
+        // levels are resolved per record so they can be changed at runtime
+        .level(log::LevelFilter::Trace)
+        .filter(is_enabled)
         //log filter applied here, making the log level to OFF for the below mentioned crates
         .level_for("h2", log::LevelFilter::Off)
         .level_for("hyper", log::LevelFilter::Off)
@@ -59,7 +205,9 @@ pub fn init_logger(name: String) -> Result<(), fern::InitError> {
         .level_for("jsonrpsee_client_transport", log::LevelFilter::Off)
         .level_for("jsonrpsee_core", log::LevelFilter::Off)
         .chain(std::io::stdout())
-        .apply()?;
+        .apply();
+    update_max_level();
+    result?;
     Ok(())
 }
 
@@ -72,6 +220,7 @@ pub fn init_and_configure_logger(
     println!("log level {}", log_string);
     let _version_string = version.to_string();
     let filter = log::LevelFilter::from_str(&log_string).unwrap_or(log::LevelFilter::Info);
+    *GLOBAL_LOG_LEVEL.write().unwrap() = filter;
     let (extracted_module_name, extracted_level_filter) = additional_modules
         .clone()
         .and_then(|modules| modules.into_iter().last())
@@ -89,7 +238,7 @@ pub fn init_and_configure_logger(
         "additional module: {}, Level filter : {}",
         extracted_module_name, extracted_level_filter
     );
-    fern::Dispatch::new()
+    let result = fern::Dispatch::new()
         .format(move |out, message, record| {
             let _v = LOG_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             #[cfg(not(feature = "sysd"))]
@@ -135,8 +284,9 @@ pub fn init_and_configure_logger(
                 ));
             }
         })
-        .level(filter)
-        .level_for(extracted_module_name, extracted_level_filter)
+        // levels are resolved per record so they can be changed at runtime
+        .level(log::LevelFilter::Trace)
+        .filter(is_enabled)
         //log filter applied here, making the log level to OFF for the below mentioned crates
         .level_for("h2", log::LevelFilter::Off)
         .level_for("hyper", log::LevelFilter::Off)
@@ -150,7 +300,65 @@ pub fn init_and_configure_logger(
         .level_for("soketto", log::LevelFilter::Off)
         .level_for("tracing", log::LevelFilter::Off)
         .chain(std::io::stdout())
-        .apply()?;
+        .apply();
+    update_max_level();
+    result?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_resolve_level() {
+        let modules = HashMap::from([
+            ("ripple_sdk".to_owned(), LevelFilter::Warn),
+            ("ripple_sdk::extn".to_owned(), LevelFilter::Trace),
+        ]);
+        let global = LevelFilter::Info;
+        assert_eq!(resolve_level("ripple", global, &modules), LevelFilter::Info);
+        assert_eq!(
+            resolve_level("ripple_sdk_ext", global, &modules),
+            LevelFilter::Info
+        );
+        assert_eq!(
+            resolve_level("ripple_sdk::api", global, &modules),
+            LevelFilter::Warn
+        );
+        assert_eq!(
+            resolve_level("ripple_sdk::extn::client", global, &modules),
+            LevelFilter::Trace
+        );
+    }
+
+    #[test]
+    fn test_log_levels_parse() {
+        let levels = LogLevels {
+            level: "debug".to_owned(),
+            modules: HashMap::from([("ripple::broker".to_owned(), "trace".to_owned())]),
+        };
+        let (level, modules) = levels.parse().unwrap();
+        assert_eq!(level, LevelFilter::Debug);
+        assert_eq!(modules.get("ripple::broker"), Some(&LevelFilter::Trace));
+
+        let invalid = LogLevels {
+            level: "loud".to_owned(),
+            modules: HashMap::new(),
+        };
+        assert_eq!(invalid.parse(), Err(RippleError::InvalidInput));
+    }
+
+    #[test]
+    fn test_log_levels_payload() {
+        let levels = LogLevels {
+            level: "warn".to_owned(),
+            modules: HashMap::new(),
+        };
+        let message = levels.get_event_message();
+        assert_eq!(message.target, RippleContract::LogLevels);
+        assert_eq!(LogLevels::get_from_payload(message.payload), Some(levels));
+    }
+}
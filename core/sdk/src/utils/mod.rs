@@ -16,6 +16,7 @@
 //
 
 pub mod channel_utils;
+pub mod digest_utils;
 pub mod error;
 pub mod extn_utils;
 pub mod logger;
@@ -24,4 +25,5 @@ pub mod rpc_utils;
 pub mod serde_utils;
 pub mod test_utils;
 pub mod time_utils;
+pub mod tls_utils;
 pub mod ws_utils;
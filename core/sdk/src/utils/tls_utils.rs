@@ -0,0 +1,370 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerConfig,
+        ServerName,
+    },
+};
+
+use super::error::RippleError;
+
+/// TLS settings of a client connection.
+///
+/// All paths point to PEM files. Without a `ca_bundle` the server certificate is verified
+/// against the bundled web PKI roots. `client_cert` and `client_key` enable mutual TLS and
+/// have to be given together. `server_name` overrides the name used for SNI and certificate
+/// verification, which otherwise is the host of the url.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn client_config(&self) -> Result<ClientConfig, RippleError> {
+        let mut roots = RootCertStore::empty();
+        if let Some(ca_bundle) = &self.ca_bundle {
+            for cert in read_certs(ca_bundle)? {
+                roots.add(&cert).map_err(|e| {
+                    error!("Invalid CA certificate in {}: {:?}", ca_bundle, e);
+                    RippleError::InvalidInput
+                })?;
+            }
+        } else {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+        }
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(read_certs(cert)?, read_key(key)?)
+                .map_err(|e| {
+                    error!("Invalid client certificate {}: {:?}", cert, e);
+                    RippleError::InvalidInput
+                }),
+            (None, None) => Ok(builder.with_no_client_auth()),
+            _ => {
+                error!("client_cert and client_key have to be configured together");
+                Err(RippleError::InvalidInput)
+            }
+        }
+    }
+
+    pub fn connector(&self) -> Result<TlsConnector, RippleError> {
+        Ok(TlsConnector {
+            config: Arc::new(self.client_config()?),
+            server_name: self.server_name.clone(),
+        })
+    }
+}
+
+/// Establishes TLS sessions on top of TCP streams using a [TlsConfig].
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    server_name: Option<String>,
+}
+
+impl TlsConnector {
+    pub fn server_name(&self) -> Option<String> {
+        self.server_name.clone()
+    }
+
+    pub fn client_config(&self) -> ClientConfig {
+        self.config.as_ref().clone()
+    }
+
+    pub async fn connect(
+        &self,
+        host: &str,
+        stream: TcpStream,
+    ) -> Result<TlsStream<TcpStream>, RippleError> {
+        let name = self.server_name.as_deref().unwrap_or(host);
+        let server_name = ServerName::try_from(name).map_err(|_| {
+            error!("Invalid TLS server name {}", name);
+            RippleError::InvalidInput
+        })?;
+        tokio_rustls::TlsConnector::from(self.config.clone())
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| {
+                error!("TLS handshake with {} failed: {:?}", name, e);
+                RippleError::NotAvailable
+            })
+    }
+}
+
+/// Loads the certificate chain and private key of a server from PEM files.
+pub fn server_config(cert: &str, key: &str) -> Result<ServerConfig, RippleError> {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(read_certs(cert)?, read_key(key)?)
+        .map_err(|e| {
+            error!("Invalid server certificate {}: {:?}", cert, e);
+            RippleError::InvalidInput
+        })
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>, RippleError> {
+    let file = File::open(path).map_err(|e| {
+        error!("Unable to open certificate file {}: {:?}", path, e);
+        RippleError::InvalidInput
+    })?;
+    let certs =
+        rustls_pemfile::certs(&mut BufReader::new(file)).map_err(|_| RippleError::ParseError)?;
+    if certs.is_empty() {
+        error!("No certificate found in {}", path);
+        return Err(RippleError::InvalidInput);
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &str) -> Result<PrivateKey, RippleError> {
+    let file = File::open(path).map_err(|e| {
+        error!("Unable to open key file {}: {:?}", path, e);
+        RippleError::InvalidInput
+    })?;
+    let items =
+        rustls_pemfile::read_all(&mut BufReader::new(file)).map_err(|_| RippleError::ParseError)?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            error!("No private key found in {}", path);
+            RippleError::InvalidInput
+        })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{rustls::server::AllowAnyAuthenticatedClient, TlsAcceptor};
+
+    pub struct TestPki {
+        pub ca_path: String,
+        pub cert: rcgen::Certificate,
+        pub server_cert: Vec<u8>,
+        pub server_key: Vec<u8>,
+    }
+
+    fn write_file(name: &str, contents: &str) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "ripple_tls_{}_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    /// Self signed certificate for the given name acting as its own CA.
+    pub fn test_pki(name: &str) -> TestPki {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_owned()]).unwrap();
+        // every serialization signs again, so derive the DER from the written PEM
+        let pem = cert.serialize_pem().unwrap();
+        let server_cert = rustls_pemfile::certs(&mut pem.as_bytes())
+            .unwrap()
+            .remove(0);
+        TestPki {
+            ca_path: write_file("ca.pem", &pem),
+            server_cert,
+            server_key: cert.serialize_private_key_der(),
+            cert,
+        }
+    }
+
+    async fn start_server(pki: &TestPki, client_ca: Option<&TestPki>) -> u16 {
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match client_ca {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                roots.add(&Certificate(ca.server_cert.clone())).unwrap();
+                builder.with_client_cert_verifier(Arc::new(AllowAnyAuthenticatedClient::new(roots)))
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder
+            .with_single_cert(
+                vec![Certificate(pki.server_cert.clone())],
+                PrivateKey(pki.server_key.clone()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(mut tls) = acceptor.accept(stream).await {
+                    let mut buf = [0u8; 4];
+                    if tls.read_exact(&mut buf).await.is_ok() {
+                        let _ = tls.write_all(&buf).await;
+                    }
+                }
+            }
+        });
+        port
+    }
+
+    async fn echo(connector: &TlsConnector, port: u16) -> Result<Vec<u8>, RippleError> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut tls = connector.connect("127.0.0.1", stream).await?;
+        let mut buf = vec![0u8; 4];
+        // with TLS 1.3 a rejected client certificate only surfaces on the first read
+        tls.write_all(b"ping")
+            .await
+            .map_err(|_| RippleError::NotAvailable)?;
+        tls.read_exact(&mut buf)
+            .await
+            .map_err(|_| RippleError::NotAvailable)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_client_config_missing_files() {
+        let config = TlsConfig {
+            ca_bundle: Some("/does/not/exist.pem".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.client_config(),
+            Err(RippleError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn test_client_config_requires_cert_and_key() {
+        let pki = test_pki("localhost");
+        let config = TlsConfig {
+            client_cert: Some(pki.ca_path),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.client_config(),
+            Err(RippleError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn test_server_config() {
+        let pki = test_pki("ripple.test");
+        let key = write_file("key.pem", &pki.cert.serialize_private_key_pem());
+        assert!(server_config(&pki.ca_path, &key).is_ok());
+        assert!(matches!(
+            server_config(&pki.ca_path, &pki.ca_path),
+            Err(RippleError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn test_tls_config_deserialize() {
+        let config: TlsConfig =
+            serde_json::from_str(r#"{"ca_bundle": "/etc/ca.pem", "server_name": "ripple.test"}"#)
+                .unwrap();
+        assert_eq!(config.ca_bundle, Some("/etc/ca.pem".to_owned()));
+        assert_eq!(config.server_name, Some("ripple.test".to_owned()));
+        assert!(config.client_cert.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connect_self_signed_with_server_name() {
+        let pki = test_pki("ripple.test");
+        let port = start_server(&pki, None).await;
+
+        // the certificate is not valid for 127.0.0.1
+        let connector = TlsConfig {
+            ca_bundle: Some(pki.ca_path.clone()),
+            ..Default::default()
+        }
+        .connector()
+        .unwrap();
+        assert!(echo(&connector, port).await.is_err());
+
+        let connector = TlsConfig {
+            ca_bundle: Some(pki.ca_path.clone()),
+            server_name: Some("ripple.test".to_owned()),
+            ..Default::default()
+        }
+        .connector()
+        .unwrap();
+        assert_eq!(echo(&connector, port).await.unwrap(), b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_connect_mutual_tls() {
+        let server = test_pki("ripple.test");
+        let client = test_pki("client.ripple.test");
+        let port = start_server(&server, Some(&client)).await;
+
+        let config = TlsConfig {
+            ca_bundle: Some(server.ca_path.clone()),
+            server_name: Some("ripple.test".to_owned()),
+            ..Default::default()
+        };
+        let connector = config.connector().unwrap();
+        assert!(echo(&connector, port).await.is_err());
+
+        let connector = TlsConfig {
+            client_cert: Some(client.ca_path.clone()),
+            client_key: Some(write_file(
+                "client_key.pem",
+                &client.cert.serialize_private_key_pem(),
+            )),
+            ..config
+        }
+        .connector()
+        .unwrap();
+        assert_eq!(echo(&connector, port).await.unwrap(), b"ping");
+    }
+}
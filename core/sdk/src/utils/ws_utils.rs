@@ -15,15 +15,21 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::time::Duration;
+use std::{future::Future, time::Duration};
 
 use futures::stream::{SplitSink, SplitStream};
 use futures_util::StreamExt;
 use log::{error, info, warn};
-use tokio::net::TcpStream;
-use tokio_tungstenite::{client_async, tungstenite::Message, WebSocketStream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_tungstenite::{client_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
-use super::error::RippleError;
+use super::{
+    error::RippleError,
+    tls_utils::{TlsConfig, TlsConnector},
+};
 
 const DEFAULT_RETRY_INTERVAL: u64 = 50;
 
@@ -139,15 +145,155 @@ impl WebSocketUtils {
 
         info!("Url host str {}", url.host_str().unwrap());
 
+        let connect = {
+            let (tcp_port, url_path) = (tcp_port.clone(), url_path.clone());
+            move || {
+                let (tcp_port, url_path) = (tcp_port.clone(), url_path.clone());
+                async move { Self::connect_tcp_port(&tcp_port, &url_path).await }
+            }
+        };
+        Self::handshake_with_timeout(config, retry_every, url_path, tcp_port, connect).await
+    }
+
+    /// Same as [WebSocketUtils::get_ws_stream] but also accepts `wss://` endpoints.
+    ///
+    /// A `wss://` endpoint is connected using the given [TlsConfig], or verifying against the
+    /// web PKI roots when none is given. Unlike plain `ws://` endpoints, secure
+    /// endpoints are not restricted to the local host.
+    ///
+    /// # Errors
+    /// - `RippleError::InvalidInput` if the URL or the TLS settings are invalid, or TLS settings are given for a `ws://` endpoint.
+    /// - `RippleError::NotAvailable` if the connection or the TLS handshake fails after the specified retries.
+    pub async fn get_ws_stream_with_tls(
+        endpoint: &str,
+        inital_config: Option<WebSocketConfig>,
+        tls: Option<&TlsConfig>,
+    ) -> Result<
+        (
+            SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+            SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        ),
+        RippleError,
+    > {
+        info!("Broker Endpoint url {}", endpoint);
+        let config = inital_config.unwrap_or_else(|| {
+            WebSocketConfigBuilder::default()
+                .retry(DEFAULT_RETRY_INTERVAL)
+                .build()
+        });
+        let retry_every = config.retry.unwrap_or(DEFAULT_RETRY_INTERVAL);
+        let url_path = if let Some(ref a) = config.alias {
+            format!("{}{}", endpoint, a)
+        } else {
+            endpoint.to_owned()
+        };
+        let url = url::Url::parse(&url_path).map_err(|_| RippleError::InvalidInput)?;
+        let tls = match (url.scheme(), tls) {
+            ("wss", tls) => Some(tls.cloned().unwrap_or_default().connector()?),
+            ("ws", None) => {
+                if cfg!(not(feature = "local_dev"))
+                    && !url_path.starts_with("ws://127.0.0.1")
+                    && !url_path.starts_with("ws://localhost")
+                {
+                    // Only support local plain ws connections
+                    return Err(RippleError::InvalidInput);
+                }
+                None
+            }
+            (scheme, _) => {
+                error!("Unsupported websocket scheme {} with TLS", scheme);
+                return Err(RippleError::InvalidInput);
+            }
+        };
+        let host = url
+            .host_str()
+            .ok_or(RippleError::InvalidInput)?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_owned();
+        let tcp_port = Self::extract_tcp_port(endpoint)?;
+
+        let connect = {
+            let (tcp_port, url_path) = (tcp_port.clone(), url_path.clone());
+            move || {
+                let (tcp_port, url_path, host, tls) = (
+                    tcp_port.clone(),
+                    url_path.clone(),
+                    host.clone(),
+                    tls.clone(),
+                );
+                async move { Self::connect_tls_port(&tcp_port, &url_path, &host, tls).await }
+            }
+        };
+        Self::handshake_with_timeout(config, retry_every, url_path, tcp_port, connect).await
+    }
+
+    async fn handshake_with_timeout<S, F, Fut>(
+        config: WebSocketConfig,
+        retry_every: u64,
+        url_path: String,
+        tcp_port: String,
+        connect: F,
+    ) -> Result<
+        (
+            SplitSink<WebSocketStream<S>, Message>,
+            SplitStream<WebSocketStream<S>>,
+        ),
+        RippleError,
+    >
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: Fn() -> Fut,
+        Fut: Future<
+            Output = Result<
+                (
+                    SplitSink<WebSocketStream<S>, Message>,
+                    SplitStream<WebSocketStream<S>>,
+                ),
+                RippleError,
+            >,
+        >,
+    {
         let timeout_duration = config.fail_after.map(|f| Duration::from_secs(f as u64));
         if let Some(duration) = timeout_duration {
             tokio::time::timeout(duration, async {
-                Self::handshake(config, retry_every, url_path, tcp_port).await
+                Self::handshake(config, retry_every, url_path, tcp_port, connect).await
             })
             .await
             .map_err(|_| RippleError::NotAvailable)?
         } else {
-            Self::handshake(config, retry_every, url_path, tcp_port).await
+            Self::handshake(config, retry_every, url_path, tcp_port, connect).await
+        }
+    }
+
+    async fn connect_tls_port(
+        tcp_port: &str,
+        url_path: &str,
+        host: &str,
+        tls: Option<TlsConnector>,
+    ) -> Result<
+        (
+            SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+            SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        ),
+        RippleError,
+    > {
+        let stream = match TcpStream::connect(&tcp_port).await {
+            Ok(v) => v,
+            Err(e) => {
+                if !e.to_string().to_lowercase().contains("connection refused") {
+                    error!("Failed to connect to TCP port {}: {}", tcp_port, e);
+                }
+                return Err(RippleError::NotAvailable);
+            }
+        };
+        let stream = match tls {
+            Some(tls) => MaybeTlsStream::Rustls(tls.connect(host, stream).await?),
+            None => MaybeTlsStream::Plain(stream),
+        };
+        match client_async(url_path, stream).await {
+            Ok((stream, _)) => Ok(stream.split()),
+            Err(_) => Err(RippleError::NotAvailable),
         }
     }
 
@@ -189,24 +335,38 @@ impl WebSocketUtils {
         }
     }
 
-    async fn handshake(
+    async fn handshake<S, F, Fut>(
         config: WebSocketConfig,
         retry_every: u64,
         url_path: String,
         tcp_port: String,
+        connect: F,
     ) -> Result<
         (
-            SplitSink<WebSocketStream<TcpStream>, Message>,
-            SplitStream<WebSocketStream<TcpStream>>,
+            SplitSink<WebSocketStream<S>, Message>,
+            SplitStream<WebSocketStream<S>>,
         ),
         RippleError,
-    > {
+    >
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: Fn() -> Fut,
+        Fut: Future<
+            Output = Result<
+                (
+                    SplitSink<WebSocketStream<S>, Message>,
+                    SplitStream<WebSocketStream<S>>,
+                ),
+                RippleError,
+            >,
+        >,
+    {
         let mut index: i32 = 0;
         let mut retry_count: u32 = 0;
         let mut delay_duration = tokio::time::Duration::from_millis(retry_every);
 
         loop {
-            match Self::connect_tcp_port(&tcp_port, &url_path).await {
+            match connect().await {
                 Ok(v) => {
                     if retry_count > 0 {
                         info!(
@@ -298,4 +458,66 @@ mod tests {
         let result = WebSocketUtils::get_ws_stream("ws://127.0.0.1:0", Some(config)).await;
         assert!(matches!(result, Err(RippleError::NotAvailable)));
     }
+
+    #[tokio::test]
+    async fn test_get_ws_stream_with_tls_self_signed() {
+        use crate::utils::tls_utils::{tests::test_pki, TlsConfig};
+        use futures_util::SinkExt;
+        use std::sync::Arc;
+        use tokio_rustls::{
+            rustls::{Certificate, PrivateKey, ServerConfig},
+            TlsAcceptor,
+        };
+
+        let pki = test_pki("ripple.test");
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(pki.server_cert.clone())],
+                PrivateKey(pki.server_key.clone()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tls = acceptor.accept(stream).await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tls).await.unwrap();
+            if let Some(Ok(msg)) = ws.next().await {
+                ws.send(msg).await.unwrap();
+            }
+        });
+
+        let tls = TlsConfig {
+            ca_bundle: Some(pki.ca_path.clone()),
+            server_name: Some("ripple.test".to_owned()),
+            ..Default::default()
+        };
+        let config = WebSocketConfigBuilder::default().fail_after(3).build();
+        let (mut tx, mut rx) = WebSocketUtils::get_ws_stream_with_tls(
+            &format!("wss://127.0.0.1:{}", port),
+            Some(config),
+            Some(&tls),
+        )
+        .await
+        .unwrap();
+        tx.send(Message::Text("ping".to_owned())).await.unwrap();
+        assert_eq!(
+            rx.next().await.unwrap().unwrap(),
+            Message::Text("ping".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_ws_stream_with_tls_rejects_tls_for_ws() {
+        let result = WebSocketUtils::get_ws_stream_with_tls(
+            "ws://127.0.0.1:0",
+            None,
+            Some(&TlsConfig::default()),
+        )
+        .await;
+        assert!(matches!(result, Err(RippleError::InvalidInput)));
+    }
 }
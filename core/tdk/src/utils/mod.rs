@@ -15,4 +15,5 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+pub mod session_replay;
 pub mod test_utils;
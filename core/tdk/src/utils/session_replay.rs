@@ -0,0 +1,370 @@
+// Copyright 2023 Comcast Cable Communications Management, LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+use std::{collections::HashMap, time::Duration};
+
+use ripple_sdk::{
+    api::observability::session_recording::{RecordChannel, RecordDirection, RecordedMessage},
+    futures::{SinkExt, StreamExt},
+    log::{debug, error},
+    tokio::time::timeout,
+    tokio_tungstenite::{connect_async, tungstenite::Message},
+    utils::error::RippleError,
+};
+use serde_json::{json, Map, Value};
+
+const REPLAY_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A recorded Firebolt request whose replayed response differs from the recorded one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMismatch {
+    pub app_id: String,
+    pub session_id: String,
+    pub request: Value,
+    pub expected: Option<Value>,
+    /// `None` when Ripple did not respond in time.
+    pub actual: Option<Value>,
+}
+
+/// Builds the mock device data which answers the recorded Thunder requests the way the device
+/// did. The result uses the format of the mock device `mock_data` files.
+pub fn to_mock_data(recording: &[RecordedMessage]) -> Value {
+    let mut pending: HashMap<String, (String, Option<Value>)> = HashMap::new();
+    let mut data: Map<String, Value> = Map::new();
+    for message in recording {
+        let Some(id) = message.get_id().map(Value::to_string) else {
+            continue;
+        };
+        if message.is(RecordChannel::Thunder, RecordDirection::Outbound) {
+            if let Some(method) = message.get_method() {
+                let params = message.message.get("params").cloned();
+                pending.insert(id, (method.to_owned(), params));
+            }
+        } else if message.is(RecordChannel::Thunder, RecordDirection::Inbound) {
+            let Some((method, params)) = pending.remove(&id) else {
+                continue;
+            };
+            let mut entry = Map::new();
+            if let Some(params) = params {
+                entry.insert("params".into(), params);
+            }
+            for key in ["result", "error"] {
+                if let Some(value) = message.message.get(key) {
+                    entry.insert(key.into(), value.clone());
+                }
+            }
+            let entries = data.entry(method).or_insert_with(|| json!([]));
+            if let Some(entries) = entries.as_array_mut() {
+                let entry = Value::Object(entry);
+                if !entries.contains(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+    Value::Object(data)
+}
+
+/// Replays the recorded Firebolt requests of every session against the insecure gateway of a
+/// Ripple instance at `ws_url`, e.g. `ws://127.0.0.1:3474`, and returns the requests whose
+/// responses differ from the recording. Requests are sent one at a time in recorded order, each
+/// waiting for its response, events and requests without an id are not compared.
+pub async fn replay(
+    ws_url: &str,
+    recording: &[RecordedMessage],
+) -> Result<Vec<ReplayMismatch>, RippleError> {
+    let mut sessions: Vec<(String, String)> = Vec::new();
+    for message in recording {
+        if let (Some(app_id), Some(session_id)) = (&message.app_id, &message.session_id) {
+            let session = (app_id.clone(), session_id.clone());
+            if !sessions.contains(&session) {
+                sessions.push(session);
+            }
+        }
+    }
+
+    let mut mismatches = Vec::new();
+    for (app_id, session_id) in sessions {
+        let messages: Vec<&RecordedMessage> = recording
+            .iter()
+            .filter(|m| m.session_id.as_deref() == Some(session_id.as_str()))
+            .collect();
+        mismatches.extend(replay_session(ws_url, &app_id, &session_id, &messages).await?);
+    }
+    Ok(mismatches)
+}
+
+async fn replay_session(
+    ws_url: &str,
+    app_id: &str,
+    session_id: &str,
+    messages: &[&RecordedMessage],
+) -> Result<Vec<ReplayMismatch>, RippleError> {
+    // the request target of a url without a path would start with the query
+    let has_path = ws_url
+        .split_once("://")
+        .is_some_and(|(_, rest)| rest.contains('/'));
+    let separator = if has_path { "" } else { "/" };
+    let url = format!(
+        "{}{}?appId={}&session={}",
+        ws_url, separator, app_id, session_id
+    );
+    let (stream, _) = connect_async(url.as_str()).await.map_err(|e| {
+        error!("replay could not connect to {} {:?}", url, e);
+        RippleError::NotAvailable
+    })?;
+    let (mut sender, mut receiver) = stream.split();
+
+    let mut mismatches = Vec::new();
+    for request in messages
+        .iter()
+        .filter(|m| m.is(RecordChannel::Firebolt, RecordDirection::Inbound))
+    {
+        sender
+            .send(Message::Text(request.message.to_string()))
+            .await
+            .map_err(|_| RippleError::SendFailure)?;
+        let Some(id) = request.get_id() else {
+            continue;
+        };
+        let expected = messages
+            .iter()
+            .find(|m| {
+                m.is(RecordChannel::Firebolt, RecordDirection::Outbound)
+                    && m.get_id() == Some(id)
+                    && m.get_method().is_none()
+            })
+            .map(|m| m.message.clone());
+
+        let wait_response = async {
+            while let Some(Ok(message)) = receiver.next().await {
+                let Ok(text) = message.to_text() else {
+                    continue;
+                };
+                match serde_json::from_str::<Value>(text) {
+                    Ok(response)
+                        if response.get("id") == Some(id) && response.get("method").is_none() =>
+                    {
+                        return Some(response)
+                    }
+                    _ => debug!("replay skipping {}", text),
+                }
+            }
+            None
+        };
+        let actual = timeout(REPLAY_RESPONSE_TIMEOUT, wait_response)
+            .await
+            .ok()
+            .flatten();
+        if actual.is_none() || actual != expected {
+            mismatches.push(ReplayMismatch {
+                app_id: app_id.to_owned(),
+                session_id: session_id.to_owned(),
+                request: request.message.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ripple_sdk::tokio_tungstenite::{
+        accept_hdr_async,
+        tungstenite::handshake::server::{Request, Response},
+    };
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// Gateway answering `device.name` with "Kitchen" and every other request with "tv", each
+    /// response preceded by an event. Returns its url and the paths of the connections.
+    async fn start_gateway() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let connections = paths.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let connections = connections.clone();
+                let callback = move |request: &Request, response: Response| {
+                    connections.lock().unwrap().push(request.uri().to_string());
+                    Ok(response)
+                };
+                let mut ws = accept_hdr_async(stream, callback).await.unwrap();
+                tokio::spawn(async move {
+                    while let Some(Ok(Message::Text(text))) = ws.next().await {
+                        let request: Value = serde_json::from_str(&text).unwrap();
+                        let Some(id) = request.get("id") else {
+                            continue;
+                        };
+                        let result = match request["method"].as_str() {
+                            Some("device.name") => "Kitchen",
+                            _ => "tv",
+                        };
+                        let event = json!({"jsonrpc": "2.0", "method": "device.onNameChanged", "params": "tv"});
+                        let response = json!({"jsonrpc": "2.0", "id": id, "result": result});
+                        let _ = ws.send(Message::Text(event.to_string())).await;
+                        let _ = ws.send(Message::Text(response.to_string())).await;
+                    }
+                });
+            }
+        });
+        (url, paths)
+    }
+
+    fn firebolt(session_id: &str, direction: RecordDirection, message: Value) -> RecordedMessage {
+        recorded(RecordChannel::Firebolt, direction, message).with_session("app", session_id)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_replay() {
+        let (url, paths) = start_gateway().await;
+        let name = json!({"jsonrpc": "2.0", "id": 1, "method": "device.name"});
+        let recording = vec![
+            firebolt("first", RecordDirection::Inbound, name.clone()),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "org.rdk.System.1.getFriendlyName"}),
+            ),
+            firebolt(
+                "first",
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 1, "result": "Living Room"}),
+            ),
+            firebolt(
+                "second",
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "device.model"}),
+            ),
+            firebolt(
+                "first",
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "method": "lifecycle.ready"}),
+            ),
+            firebolt(
+                "first",
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 2, "method": "device.model"}),
+            ),
+            firebolt(
+                "first",
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 2, "result": "tv"}),
+            ),
+            firebolt(
+                "second",
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 1, "result": "tv"}),
+            ),
+        ];
+
+        let mismatches = replay(&url, &recording).await.unwrap();
+        assert_eq!(
+            mismatches,
+            vec![ReplayMismatch {
+                app_id: "app".to_owned(),
+                session_id: "first".to_owned(),
+                request: name,
+                expected: Some(json!({"jsonrpc": "2.0", "id": 1, "result": "Living Room"})),
+                actual: Some(json!({"jsonrpc": "2.0", "id": 1, "result": "Kitchen"})),
+            }]
+        );
+        assert_eq!(
+            *paths.lock().unwrap(),
+            vec!["/?appId=app&session=first", "/?appId=app&session=second"]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_replay_without_gateway() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let recording = vec![firebolt(
+            "first",
+            RecordDirection::Inbound,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "device.name"}),
+        )];
+        assert_eq!(
+            replay(&url, &recording).await.unwrap_err(),
+            RippleError::NotAvailable
+        );
+    }
+
+    fn recorded(
+        channel: RecordChannel,
+        direction: RecordDirection,
+        message: Value,
+    ) -> RecordedMessage {
+        RecordedMessage::new(channel, direction, &message.to_string())
+    }
+
+    #[test]
+    fn test_to_mock_data() {
+        let recording = vec![
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 3, "method": "org.rdk.System.1.getFriendlyName"}),
+            ),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 4, "method": "org.rdk.System.1.setFriendlyName", "params": {"friendlyName": "tv"}}),
+            ),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 4, "error": {"code": -32601, "message": "Unknown method"}}),
+            ),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 3, "result": {"friendlyName": "Living Room"}}),
+            ),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Outbound,
+                json!({"jsonrpc": "2.0", "id": 5, "method": "org.rdk.System.1.getFriendlyName"}),
+            ),
+            recorded(
+                RecordChannel::Thunder,
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 5, "result": {"friendlyName": "Living Room"}}),
+            ),
+            recorded(
+                RecordChannel::Firebolt,
+                RecordDirection::Inbound,
+                json!({"jsonrpc": "2.0", "id": 5, "method": "device.name"}),
+            ),
+        ];
+        assert_eq!(
+            to_mock_data(&recording),
+            json!({
+                "org.rdk.System.1.getFriendlyName": [
+                    {"result": {"friendlyName": "Living Room"}}
+                ],
+                "org.rdk.System.1.setFriendlyName": [
+                    {"params": {"friendlyName": "tv"}, "error": {"code": -32601, "message": "Unknown method"}}
+                ]
+            })
+        );
+    }
+}
@@ -20,16 +20,25 @@ use crate::{
     thunder_state::ThunderBootstrapStateWithClient,
 };
 use ripple_sdk::{
+    api::{
+        apps::{AppEvent, AppEventRequest},
+        device::device_events::{DeviceConnectionStatus, DEVICE_CONNECTION_CHANGED_EVENT},
+    },
     extn::client::extn_client::ExtnClient,
     log::{debug, error, info, warn},
     serde_json,
+    tokio::{self, sync::broadcast},
 };
 use serde_json::Value;
 
+use crate::client::thunder_async_client::{
+    ReconnectBackoff, ReplayPolicy, DEFAULT_RECONNECT_INITIAL_DELAY, DEFAULT_RECONNECT_MAX_DELAY,
+};
 use crate::client::thunder_client::ThunderClientBuilder;
 use crate::thunder_state::ThunderBootstrapStateWithConfig;
 use crate::thunder_state::ThunderState;
 use serde::Deserialize;
+use std::time::Duration;
 
 const GATEWAY_DEFAULT: &str = "ws://127.0.0.1:9998/jsonrpc";
 
@@ -43,6 +52,63 @@ fn gateway_default() -> String {
     String::from(GATEWAY_DEFAULT)
 }
 
+// Lets apps know when Ripple loses or regains its connection to Thunder.
+fn forward_connection_status(
+    client: ExtnClient,
+    mut connection_status: broadcast::Receiver<DeviceConnectionStatus>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let status = match connection_status.recv().await {
+                Ok(status) => status,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            info!("Thunder connection status {:?}", status);
+            let event = AppEventRequest::Emit(AppEvent {
+                event_name: DEVICE_CONNECTION_CHANGED_EVENT.to_owned(),
+                context: None,
+                result: serde_json::to_value(status).unwrap_or_default(),
+                app_id: None,
+            });
+            if let Err(e) = client.request_transient(event) {
+                error!("Error sending thunder connection status {:?}", e);
+            }
+        }
+    });
+}
+
+// Reconnect settings from the extension config, e.g. "reconnect_max_delay_ms": "5000" and
+// "replay_idempotent": "[\"org.rdk.System.1.setMode\"]".
+fn get_reconnect_config(ext_client: &ExtnClient) -> (ReconnectBackoff, ReplayPolicy) {
+    let delay = |key: &str, default: Duration| {
+        ext_client
+            .get_uint_config(key)
+            .map_or(default, Duration::from_millis)
+    };
+    let backoff = ReconnectBackoff::new(
+        delay(
+            "reconnect_initial_delay_ms",
+            DEFAULT_RECONNECT_INITIAL_DELAY,
+        ),
+        delay("reconnect_max_delay_ms", DEFAULT_RECONNECT_MAX_DELAY),
+    );
+    let mut replay_policy = ReplayPolicy::default();
+    for method in ext_client
+        .get_string_array_config("replay_idempotent")
+        .unwrap_or_default()
+    {
+        replay_policy = replay_policy.with_idempotent(&method);
+    }
+    for method in ext_client
+        .get_string_array_config("replay_not_idempotent")
+        .unwrap_or_default()
+    {
+        replay_policy = replay_policy.with_not_idempotent(&method);
+    }
+    (backoff, replay_policy)
+}
+
 pub async fn boot_thunder(
     ext_client: ExtnClient,
     thunder_parameters: &Value,
@@ -92,9 +158,18 @@ pub async fn boot_thunder(
         gateway_url.set_host(Some(&host_override)).ok();
     }
 
-    let state = if let Ok(thndr_client) =
-        ThunderClientBuilder::start_thunder_client(gateway_url.clone(), status_check).await
+    let (backoff, replay_policy) = get_reconnect_config(&ext_client);
+    let state = if let Ok(thndr_client) = ThunderClientBuilder::start_thunder_client(
+        gateway_url.clone(),
+        status_check,
+        backoff,
+        replay_policy,
+    )
+    .await
     {
+        if let Some(connection_status) = thndr_client.subscribe_connection_status() {
+            forward_connection_status(ext_client.clone(), connection_status);
+        }
         let thunder_state = ThunderState::new(ext_client.clone(), thndr_client);
 
         let thndr_boot_statecfg = ThunderBootstrapStateWithConfig {
@@ -121,3 +196,47 @@ pub async fn boot_thunder(
     }
     state
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::device_operator::{DeviceCallRequest, DeviceChannelRequest};
+    use ripple_sdk::api::manifest::extn_manifest::ExtnSymbol;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_get_reconnect_config() {
+        let config = HashMap::from([
+            ("reconnect_initial_delay_ms".to_owned(), "400".to_owned()),
+            ("reconnect_max_delay_ms".to_owned(), "800".to_owned()),
+            (
+                "replay_idempotent".to_owned(),
+                r#"["org.rdk.System.1.setMode"]"#.to_owned(),
+            ),
+            (
+                "replay_not_idempotent".to_owned(),
+                r#"["org.rdk.Wifi"]"#.to_owned(),
+            ),
+        ]);
+        let (ext_client, _rx) = ExtnClient::new_extn(ExtnSymbol {
+            id: "ripple:channel:device:thunder".to_owned(),
+            uses: vec![],
+            fulfills: vec![],
+            config: Some(config),
+            secret: None,
+        });
+        let (mut backoff, replay_policy) = get_reconnect_config(&ext_client);
+        let delays: Vec<Duration> = (0..3).map(|_| backoff.next_delay()).collect();
+        assert!(delays[0] >= Duration::from_millis(200) && delays[0] <= Duration::from_millis(400));
+        assert!(delays[2] >= Duration::from_millis(400) && delays[2] <= Duration::from_millis(800));
+
+        let call = |method: &str| {
+            DeviceChannelRequest::Call(DeviceCallRequest {
+                method: method.to_owned(),
+                params: None,
+            })
+        };
+        assert!(replay_policy.is_idempotent(&call("org.rdk.System.1.setMode")));
+        assert!(!replay_policy.is_idempotent(&call("org.rdk.Wifi.1.getConnectedSSID")));
+    }
+}
@@ -24,13 +24,118 @@ use futures::{stream::SplitSink, SinkExt, StreamExt};
 //use futures_util::{SinkExt, StreamExt};
 use ripple_sdk::tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use ripple_sdk::{
-    api::gateway::rpc_gateway_api::{JsonRpcApiRequest, JsonRpcApiResponse},
-    log::{debug, error, info},
-    tokio::{self, net::TcpStream, sync::mpsc::Receiver},
-    utils::{error::RippleError, ws_utils::WebSocketUtils},
+    api::{
+        device::device_events::DeviceConnectionStatus,
+        gateway::rpc_gateway_api::{JsonRpcApiRequest, JsonRpcApiResponse},
+    },
+    log::{debug, error, info, warn},
+    tokio::{
+        self,
+        net::TcpStream,
+        sync::{broadcast, mpsc::Receiver},
+    },
+    utils::{
+        error::RippleError,
+        ws_utils::{WebSocketConfigBuilder, WebSocketUtils},
+    },
 };
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet, VecDeque},
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+// requests arriving while Thunder is not connected are kept up to this limit
+const MAX_QUEUED_REQUESTS: usize = 64;
+pub const DEFAULT_RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(100);
+pub const DEFAULT_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Exponential backoff with jitter between attempts to reconnect to Thunder.
+#[derive(Clone, Debug)]
+pub struct ReconnectBackoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECONNECT_INITIAL_DELAY, DEFAULT_RECONNECT_MAX_DELAY)
+    }
+}
+
+impl ReconnectBackoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            attempt: 0,
+        }
+    }
+
+    /// Returns the delay before the next attempt, a random value between half and all of the
+    /// current backoff, which doubles with every attempt up to the maximum.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+        let half = delay / 2;
+        let jitter = (delay - half).as_millis() as u64;
+        if jitter == 0 {
+            return delay;
+        }
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(self.attempt);
+        half + Duration::from_millis(hasher.finish() % (jitter + 1))
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Decides which Thunder calls are sent again when the connection drops before they are
+/// answered. Calls which are not idempotent are failed instead, as Thunder may have handled
+/// them already. Getters are idempotent unless configured otherwise.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayPolicy {
+    idempotent: HashSet<String>,
+    not_idempotent: HashSet<String>,
+}
+
+impl ReplayPolicy {
+    /// Marks a method, e.g. `org.rdk.System.1.setMode`, or all methods of a callsign, e.g.
+    /// `org.rdk.System`, as safe to send again.
+    pub fn with_idempotent(mut self, method: &str) -> Self {
+        self.idempotent.insert(method.to_owned());
+        self
+    }
+
+    pub fn with_not_idempotent(mut self, method: &str) -> Self {
+        self.not_idempotent.insert(method.to_owned());
+        self
+    }
+
+    pub fn is_idempotent(&self, request: &DeviceChannelRequest) -> bool {
+        let DeviceChannelRequest::Call(call) = request else {
+            // subscriptions can always be registered again
+            return true;
+        };
+        let (callsign, method) = request.get_callsign_method();
+        for key in [&call.method, &callsign] {
+            if self.not_idempotent.contains(key) {
+                return false;
+            }
+            if self.idempotent.contains(key) {
+                return true;
+            }
+        }
+        method.starts_with("get")
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ThunderAsyncClient {
@@ -38,6 +143,11 @@ pub struct ThunderAsyncClient {
     sender: AsyncSender,
     callback: AsyncCallback,
     subscriptions: HashMap<String, JsonRpcApiRequest>,
+    // requests sent to Thunder which are not answered yet
+    in_flight: HashMap<u64, ThunderAsyncRequest>,
+    backoff: ReconnectBackoff,
+    replay_policy: ReplayPolicy,
+    connection_status: broadcast::Sender<DeviceConnectionStatus>,
 }
 
 #[derive(Clone, Debug)]
@@ -219,14 +329,34 @@ impl ThunderAsyncClient {
     }
 
     pub fn new(callback: AsyncCallback, sender: AsyncSender) -> Self {
+        let (connection_status, _) = broadcast::channel(16);
         Self {
             status_manager: StatusManager::new(),
             sender,
             callback,
             subscriptions: HashMap::new(),
+            in_flight: HashMap::new(),
+            backoff: ReconnectBackoff::default(),
+            replay_policy: ReplayPolicy::default(),
+            connection_status,
         }
     }
 
+    pub fn with_backoff(mut self, backoff: ReconnectBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_replay_policy(mut self, replay_policy: ReplayPolicy) -> Self {
+        self.replay_policy = replay_policy;
+        self
+    }
+
+    /// Receives a status every time the websocket to Thunder is (re)established or lost.
+    pub fn subscribe_connection_status(&self) -> broadcast::Receiver<DeviceConnectionStatus> {
+        self.connection_status.subscribe()
+    }
+
     async fn handle_response(&mut self, message: Message) {
         if let Message::Text(t) = message {
             debug!("thunder_async_response: {}", t);
@@ -275,14 +405,33 @@ impl ThunderAsyncClient {
         mut thunder_async_request_rx: Receiver<ThunderAsyncRequest>,
         status_check: bool,
     ) {
+        let mut queue = VecDeque::new();
         loop {
             info!("start: (re)establishing websocket connection: url={}", url);
-            let resp = WebSocketUtils::get_ws_stream(url, None).await;
-            if resp.is_err() {
-                error!("FATAL ERROR Thunder URL badly configured.");
-                break;
-            }
-            let (mut thunder_tx, mut thunder_rx) = resp.unwrap();
+            let config = WebSocketConfigBuilder::default().fail_after(1).build();
+            let (mut thunder_tx, mut thunder_rx) =
+                match WebSocketUtils::get_ws_stream(url, Some(config)).await {
+                    Ok(stream) => stream,
+                    Err(RippleError::InvalidInput) => {
+                        error!("FATAL ERROR Thunder URL badly configured.");
+                        break;
+                    }
+                    Err(_) => {
+                        let delay = self.backoff.next_delay();
+                        warn!(
+                            "Thunder not available, reconnecting in {} ms",
+                            delay.as_millis()
+                        );
+                        if !self
+                            .wait_for_reconnect(delay, &mut thunder_async_request_rx, &mut queue)
+                            .await
+                        {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+            self.backoff.reset();
 
             // send the controller statechange subscription request
             let status_request = self
@@ -302,86 +451,190 @@ impl ThunderAsyncClient {
                 debug!("thunder plugin status check at thunder async client startup");
                 //send thunder plugin status check request for all plugins
                 let status_check_request = self.status_manager.generate_plugin_status_request(None);
-                {
-                    // let mut ws_tx = ws_tx_wrap.lock().await;
-                    let _feed = thunder_tx
-                        .feed(Message::Text(status_check_request.to_string()))
-                        .await;
-                    let _flush = thunder_tx.flush().await;
-                }
+                let _feed = thunder_tx
+                    .feed(Message::Text(status_check_request.to_string()))
+                    .await;
+                let _flush = thunder_tx.flush().await;
             } else {
                 debug!("thunder plugin status check at thunder async client startup is disabled");
             }
 
-            tokio::pin! {
-                let subscriptions_socket = thunder_rx.next();
+            let _ = self
+                .connection_status
+                .send(DeviceConnectionStatus::Connected);
+            if !queue.is_empty() {
+                info!("Sending {} requests queued while disconnected", queue.len());
+            }
+            while let Some(request) = queue.pop_front() {
+                self.send_request(&mut thunder_tx, request).await;
             }
 
             loop {
                 tokio::select! {
-                    Some(value) = &mut subscriptions_socket => {
+                    value = thunder_rx.next() => {
                         match value {
-                            Ok(message) => {
+                            Some(Ok(message)) => {
                                 self.handle_response(message).await;
                             },
-                            Err(e) => {
+                            Some(Err(e)) => {
                                 error!("Thunder_async_client Websocket error on read {:?}", e);
                                 break;
                             }
+                            None => {
+                                error!("Thunder_async_client Websocket closed");
+                                break;
+                            }
                         }
                     },
                     Some(request) = thunder_async_request_rx.recv() => {
-                        match self.check_plugin_status_n_prepare_request(&request) {
-                            Ok(updated_request) => {
-                                if let Ok(jsonrpc_request) = serde_json::from_str::<JsonRpcApiRequest>(&updated_request) {
-                                    if jsonrpc_request.method.ends_with(".register") {
-                                        if let Some(Value::Object(ref params)) = jsonrpc_request.params {
-                                            if let Some(Value::String(event)) = params.get("event") {
-                                                debug!("thunder_async_request_rx: Rerouting subscription request for {}", event);
-
-                                                // Store the subscription request in the subscriptions list in case we need to
-                                                // resubscribe later due to a socket disconnect.
-                                                self.subscriptions.insert(event.to_string(), jsonrpc_request.clone());
-                                                debug!("thunder_async_request_rx: subscription request={}", updated_request);
-                                                // Reroute subsubscription requests through the persistent websocket so all notifications
-                                                // are sent to the same websocket connection.
-                                                let _feed = thunder_tx.feed(Message::Text(updated_request)).await;
-                                                let _flush = thunder_tx.flush().await;
-                                            } else {
-                                                error!("thunder_async_request_rx: Missing 'event' parameter");
-                                            }
-                                        } else {
-                                            error!("thunder_async_request_rx: Missing 'params' object");
-                                        }
-                                    }
-                                    else {
-                                        debug!("thunder_async_request_rx: call request={}", updated_request);
-                                        let _feed = thunder_tx.feed(Message::Text(updated_request)).await;
-                                        let _flush = thunder_tx.flush().await;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                match e {
-                                    RippleError::ServiceNotReady => {
-                                        info!("Thunder Service not ready, request is now in pending list {:?}", request);
-                                    },
-                                    _ => {
-                                        error!("error preparing request {:?}", e);
-                                        let response = ThunderAsyncResponse::new_error(request.id,e.clone());
-                                        self.callback.send(response).await;
-                                    }
-                                }
+                        self.send_request(&mut thunder_tx, request).await;
+                    }
+                }
+            }
+            self.on_disconnected(&mut queue).await;
+        }
+    }
+
+    async fn send_request(
+        &mut self,
+        thunder_tx: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+        request: ThunderAsyncRequest,
+    ) {
+        match self.check_plugin_status_n_prepare_request(&request) {
+            Ok(updated_request) => {
+                if let Ok(jsonrpc_request) =
+                    serde_json::from_str::<JsonRpcApiRequest>(&updated_request)
+                {
+                    // Controller requests checking the plugin carry their own id, the request
+                    // itself waits in the pending list of the status manager.
+                    if jsonrpc_request.id == Some(request.id) {
+                        self.in_flight.insert(request.id, request.clone());
+                    }
+                    if jsonrpc_request.method.ends_with(".register") {
+                        if let Some(Value::Object(ref params)) = jsonrpc_request.params {
+                            if let Some(Value::String(event)) = params.get("event") {
+                                debug!(
+                                    "thunder_async_request_rx: Rerouting subscription request for {}",
+                                    event
+                                );
+
+                                // Store the subscription request in the subscriptions list in case we need to
+                                // resubscribe later due to a socket disconnect.
+                                self.subscriptions
+                                    .insert(event.to_string(), jsonrpc_request.clone());
+                                debug!(
+                                    "thunder_async_request_rx: subscription request={}",
+                                    updated_request
+                                );
+                                // Reroute subsubscription requests through the persistent websocket so all notifications
+                                // are sent to the same websocket connection.
+                                let _feed = thunder_tx.feed(Message::Text(updated_request)).await;
+                                let _flush = thunder_tx.flush().await;
+                            } else {
+                                error!("thunder_async_request_rx: Missing 'event' parameter");
                             }
+                        } else {
+                            error!("thunder_async_request_rx: Missing 'params' object");
                         }
+                    } else {
+                        debug!("thunder_async_request_rx: call request={}", updated_request);
+                        let _feed = thunder_tx.feed(Message::Text(updated_request)).await;
+                        let _flush = thunder_tx.flush().await;
                     }
                 }
             }
+            Err(e) => match e {
+                RippleError::ServiceNotReady => {
+                    info!(
+                        "Thunder Service not ready, request is now in pending list {:?}",
+                        request
+                    );
+                }
+                _ => {
+                    error!("error preparing request {:?}", e);
+                    let response = ThunderAsyncResponse::new_error(request.id, e.clone());
+                    self.callback.send(response).await;
+                }
+            },
+        }
+    }
+
+    async fn queue_request(
+        &self,
+        queue: &mut VecDeque<ThunderAsyncRequest>,
+        request: ThunderAsyncRequest,
+    ) {
+        if queue.len() < MAX_QUEUED_REQUESTS {
+            queue.push_back(request);
+        } else {
+            error!("Thunder not connected and queue full, failing {}", request);
+            self.callback
+                .send(ThunderAsyncResponse::new_error(
+                    request.id,
+                    RippleError::NotAvailable,
+                ))
+                .await;
+        }
+    }
+
+    // Queues the requests arriving during the delay. Returns false when no more requests can
+    // arrive.
+    async fn wait_for_reconnect(
+        &self,
+        delay: Duration,
+        thunder_async_request_rx: &mut Receiver<ThunderAsyncRequest>,
+        queue: &mut VecDeque<ThunderAsyncRequest>,
+    ) -> bool {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return true,
+                request = thunder_async_request_rx.recv() => match request {
+                    Some(request) => self.queue_request(queue, request).await,
+                    None => return false,
+                }
+            }
+        }
+    }
+
+    async fn on_disconnected(&mut self, queue: &mut VecDeque<ThunderAsyncRequest>) {
+        let _ = self
+            .connection_status
+            .send(DeviceConnectionStatus::Disconnected);
+        // Thunder may have restarted, plugin states are checked again after reconnecting
+        let mut requests = self.status_manager.reset();
+        let mut in_flight: Vec<ThunderAsyncRequest> =
+            self.in_flight.drain().map(|(_, request)| request).collect();
+        in_flight.sort_by_key(|request| request.id);
+        for request in in_flight {
+            if self.replay_policy.is_idempotent(&request.request) {
+                if let DeviceChannelRequest::Subscribe(_) = request.request {
+                    // sent again with its own id, so the subscriber gets the response
+                    let (_, event) = request.request.get_callsign_method();
+                    self.subscriptions.remove(&event);
+                }
+                requests.push(request);
+            } else {
+                warn!("Thunder disconnected, failing unanswered {}", request);
+                self.callback
+                    .send(ThunderAsyncResponse::new_error(
+                        request.id,
+                        RippleError::NotAvailable,
+                    ))
+                    .await;
+            }
+        }
+        for request in requests {
+            self.queue_request(queue, request).await;
         }
     }
 
     async fn handle_jsonrpc_response(&mut self, result: &[u8]) {
         if let Ok(message) = serde_json::from_slice::<JsonRpcApiResponse>(result) {
+            if let Some(id) = message.id {
+                self.in_flight.remove(&id);
+            }
             self.callback
                 .send(ThunderAsyncResponse::new_response(message))
                 .await
@@ -552,6 +805,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reconnect_backoff() {
+        let mut backoff =
+            ReconnectBackoff::new(Duration::from_millis(100), Duration::from_millis(400));
+        for max in [100, 200, 400, 400] {
+            let delay = backoff.next_delay();
+            assert!(delay >= Duration::from_millis(max / 2));
+            assert!(delay <= Duration::from_millis(max));
+        }
+        backoff.reset();
+        assert!(backoff.next_delay() <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_replay_policy() {
+        let call = |method: &str| {
+            DeviceChannelRequest::Call(DeviceCallRequest {
+                method: method.to_owned(),
+                params: None,
+            })
+        };
+        let policy = ReplayPolicy::default()
+            .with_idempotent("org.rdk.System.1.setMode")
+            .with_not_idempotent("org.rdk.Wifi");
+        assert!(policy.is_idempotent(&call("org.rdk.System.1.getSerialNumber")));
+        assert!(policy.is_idempotent(&call("org.rdk.System.1.setMode")));
+        assert!(!policy.is_idempotent(&call("org.rdk.System.1.reboot")));
+        assert!(!policy.is_idempotent(&call("org.rdk.Wifi.1.getConnectedSSID")));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_replays_idempotent_requests() {
+        use ripple_sdk::tokio_tungstenite::accept_async;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/jsonrpc", listener.local_addr().unwrap());
+
+        // a Thunder answering plugin status requests, the first connection is dropped once
+        // both calls arrived
+        tokio::spawn(async move {
+            for connection in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = accept_async(stream).await.unwrap();
+                let mut calls = 0;
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    let method = request["method"].as_str().unwrap_or_default();
+                    let result = if let Some(callsign) = method.strip_prefix("Controller.1.status@")
+                    {
+                        json!([{"callsign": callsign, "state": "activated"}])
+                    } else if method.starts_with("org.rdk.System") {
+                        calls += 1;
+                        if connection == 0 {
+                            if calls == 2 {
+                                break;
+                            }
+                            continue;
+                        }
+                        json!("SN")
+                    } else {
+                        json!(0)
+                    };
+                    let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+                    let _ = ws.send(Message::Text(response.to_string())).await;
+                }
+            }
+        });
+
+        let (resp_tx, mut resp_rx) = mpsc::channel(10);
+        let callback = AsyncCallback { sender: resp_tx };
+        let (async_tx, async_rx) = mpsc::channel(10);
+        let async_sender = AsyncSender { sender: async_tx };
+        let mut client = ThunderAsyncClient::new(callback, async_sender.clone()).with_backoff(
+            ReconnectBackoff::new(Duration::from_millis(10), Duration::from_millis(50)),
+        );
+        let mut connection_status = client.subscribe_connection_status();
+        tokio::spawn(async move { client.start(&url, async_rx, false).await });
+
+        let call = |method: &str| {
+            ThunderAsyncRequest::new(DeviceChannelRequest::Call(DeviceCallRequest {
+                method: method.to_owned(),
+                params: None,
+            }))
+        };
+        let getter = call("org.rdk.System.1.getSerialNumber");
+        let setter = call("org.rdk.System.1.setMode");
+        async_sender.send(getter.clone()).await.unwrap();
+        async_sender.send(setter.clone()).await.unwrap();
+
+        let mut responses = HashMap::new();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while responses.len() < 2 {
+                let response = resp_rx.recv().await.unwrap();
+                if let Some(id) = response
+                    .get_id()
+                    .filter(|id| *id == getter.id || *id == setter.id)
+                {
+                    responses.insert(id, response.result.unwrap());
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(responses[&getter.id].result, Some(json!("SN")));
+        assert!(responses[&setter.id].error.is_some());
+
+        for expected in [
+            DeviceConnectionStatus::Connected,
+            DeviceConnectionStatus::Disconnected,
+            DeviceConnectionStatus::Connected,
+        ] {
+            assert_eq!(connection_status.recv().await.unwrap(), expected);
+        }
+    }
+
     #[tokio::test]
     async fn test_thunder_async_client_start() {
         let (resp_tx, mut resp_rx) = mpsc::channel(10);
@@ -263,6 +263,17 @@ impl StatusManager {
         }
     }
 
+    // Forgets the plugin states after the connection to Thunder was lost, as Thunder may have
+    // restarted. Returns the requests which were waiting for a plugin, so they can be sent again.
+    pub fn reset(&self) -> Vec<ThunderAsyncRequest> {
+        self.inprogress_plugins_request.write().unwrap().clear();
+        let mut status = self.status.write().unwrap();
+        status
+            .drain()
+            .flat_map(|(_, plugin_state)| plugin_state.pending_requests)
+            .collect()
+    }
+
     pub fn get_status(&self, plugin_name: String) -> Option<ThunderPluginState> {
         let status = self.status.read().unwrap();
         status.get(&plugin_name).cloned()
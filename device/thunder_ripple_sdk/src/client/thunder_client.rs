@@ -19,14 +19,18 @@ use super::device_operator::{
     DeviceCallRequest, DeviceChannelRequest, DeviceOperator, DeviceResponseMessage,
     DeviceResponseSubscription, DeviceSubscribeRequest, DeviceUnsubscribeRequest,
 };
-use super::thunder_async_client::{ThunderAsyncClient, ThunderAsyncRequest, ThunderAsyncResponse};
+use super::thunder_async_client::{
+    ReconnectBackoff, ReplayPolicy, ThunderAsyncClient, ThunderAsyncRequest, ThunderAsyncResponse,
+};
 use super::thunder_async_client_plugins_status_mgr::{AsyncCallback, AsyncSender};
 use jsonrpsee::core::async_trait;
 
 use ripple_sdk::{
+    api::device::device_events::DeviceConnectionStatus,
     log::error,
     serde_json::Value,
     tokio,
+    tokio::sync::broadcast,
     tokio::sync::mpsc::{self, Receiver, Sender as MpscSender},
     tokio::sync::oneshot::{self, error::RecvError, Sender as OneShotSender},
     utils::channel_utils::{mpsc_send_and_log, oneshot_send_and_log},
@@ -192,6 +196,14 @@ impl DeviceOperator for ThunderClient {
 }
 
 impl ThunderClient {
+    pub fn subscribe_connection_status(
+        &self,
+    ) -> Option<broadcast::Receiver<DeviceConnectionStatus>> {
+        self.thunder_async_client
+            .as_ref()
+            .map(|client| client.subscribe_connection_status())
+    }
+
     fn add_callback(
         &self,
         request: &ThunderAsyncRequest,
@@ -310,12 +322,16 @@ impl ThunderClientBuilder {
     pub async fn start_thunder_client(
         url: Url,
         status_check: bool,
+        backoff: ReconnectBackoff,
+        replay_policy: ReplayPolicy,
     ) -> Result<ThunderClient, RippleError> {
         let (resp_tx, resp_rx) = mpsc::channel(32);
         let callback = AsyncCallback { sender: resp_tx };
         let (broker_tx, broker_rx) = mpsc::channel(32);
         let broker_sender = AsyncSender { sender: broker_tx };
-        let client = ThunderAsyncClient::new(callback, broker_sender);
+        let client = ThunderAsyncClient::new(callback, broker_sender)
+            .with_backoff(backoff)
+            .with_replay_policy(replay_policy);
 
         let thunder_client = ThunderClient {
             id: Uuid::new_v4(),
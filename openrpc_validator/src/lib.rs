@@ -74,6 +74,20 @@ impl RpcMethodValidator {
         }
         Err(ValidationError::SpecVersionNotFound)
     }
+
+    pub fn result_validator(
+        &self,
+        version: String,
+        method: &str,
+    ) -> Result<JSONSchema, ValidationError> {
+        for validator in &self.validators {
+            let validator = validator.result_validator(version.clone(), method.to_owned());
+            if validator.is_ok() {
+                return validator;
+            }
+        }
+        Err(ValidationError::SpecVersionNotFound)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]